@@ -2,29 +2,41 @@
 mod error;
 mod graphql;
 
-use asdb::Asdb;
-use asdb_models::{As, AsrankAsn, AsrankDegree, Coord};
+use asdb::Storage;
+use asdb_models::{As, AsSource, AsrankAsn, AsrankDegree, Coord};
 pub use error::{Error, Result};
 
 use indicatif::ProgressIterator;
 use serde_json::Value;
 use std::io::BufReader;
 use std::io::prelude::*;
-use std::{fs::File, path::Path};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
 
 use crate::asrank::graphql::asns_query;
 
 const API_URL: &str = "https:///api.asrank.caida.org/v2/graphql";
 const PAGE_SIZE: i64 = 10000;
 
-/// load asns either from
-pub async fn load(asdb: &Asdb, file: Option<impl AsRef<Path>>) -> Result<()> {
+/// The file asns are loaded from or the API if there is none
+pub fn files(file: Option<&Path>) -> Vec<(String, Option<PathBuf>)> {
+    match file {
+        Some(f) => vec![(f.display().to_string(), Some(f.to_path_buf()))],
+        None => vec![(API_URL.to_string(), None)],
+    }
+}
+
+/// load asns either from file or the API, returns the imported asns
+pub async fn load(asdb: &impl Storage, file: Option<impl AsRef<Path>>) -> Result<Vec<u32>> {
     let ases = if let Some(f) = file {
         import_asns(f).await?
     } else {
         download_asns().await?
     };
-    write_to_db(&ases, asdb).await
+    write_to_db(&ases, asdb).await?;
+    Ok(ases.iter().map(|x| x.asn).collect())
 }
 
 /// Imports asns from "asns.jsonl" file which can be obtained from asrank API using
@@ -47,6 +59,9 @@ pub async fn import_asns(file: impl AsRef<Path>) -> Result<Vec<As>> {
                     organization: line["organization"]["orgName"]
                         .as_str()
                         .map(|x| x.to_string()),
+                    organization_id: line["organization"]["orgId"]
+                        .as_str()
+                        .map(|x| x.to_string()),
                     country_iso: line["country"]["iso"].as_str().unwrap().to_string(),
                     country_name: line["country"]["name"].as_str().unwrap().to_string(),
                     coordinates: Coord {
@@ -72,20 +87,12 @@ pub async fn import_asns(file: impl AsRef<Path>) -> Result<Vec<As>> {
     Ok(json)
 }
 
-pub async fn write_to_db(ases: &[As], asdb: &Asdb) -> Result<()> {
+pub async fn write_to_db(ases: &[As], asdb: &impl Storage) -> Result<()> {
     println!("Inserting asrank data into the database");
-    let insert_result = asdb.insert_ases(ases).await;
-    if let Err(e) = insert_result {
-        match e {
-            asdb::Error::DuplicatesFound(c) => {
-                println!("Inserted asns but skipped {c} duplicated entries");
-                return Ok(());
-            }
-            _ => {
-                Err(e)?;
-            }
-        }
-    }
+    let stats = asdb.upsert_ases(ases, AsSource::Asrank).await?;
+    println!("Asrank data written, {stats}");
+    println!("Updating organisations from asrank data");
+    asdb.update_asrank_organisations(ases).await?;
     Ok(())
 }
 
@@ -21,7 +21,8 @@ impl From<asns_query::AsnsQueryAsnsEdges> for asdb_models::As {
             asn: node.asn.parse().unwrap(),
             asrank_data: Some(AsrankAsn {
                 rank: node.rank.unwrap() as u32,
-                organization: node.organization.and_then(|x| x.org_name),
+                organization: node.organization.as_ref().and_then(|x| x.org_name.clone()),
+                organization_id: node.organization.map(|x| x.org_id),
                 country_iso: country.iso.unwrap(),
                 country_name: country.name.unwrap(),
                 coordinates: Coord {
@@ -0,0 +1,84 @@
+//! Loads CAIDA AS relationship files, as-rel and as-rel2 serials from
+//! https://publicdata.caida.org/datasets/as-relationships/
+//!
+//! Each line is `<provider>|<customer>|-1` or `<peer>|<peer>|0`, as-rel2 files add the source
+//! the link was inferred from as the fourth field. Lines starting with `#` are comments.
+mod error;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use asdb::Storage;
+use asdb_models::{AsRelationship, Relationship};
+use bzip2::read::MultiBzDecoder;
+pub use error::{Error, Result};
+
+/// The local file relationships are read from
+pub fn files(file: &Path) -> Vec<(String, Option<PathBuf>)> {
+    vec![(file.display().to_string(), Some(file.to_path_buf()))]
+}
+
+/// Replaces the stored relationships with the ones from the file, either plain text or bzip2
+/// compressed. Returns the asns which have any of them.
+pub async fn load(asdb: &impl Storage, file: impl AsRef<Path>) -> Result<Vec<u32>> {
+    println!(
+        "loading caida relationships from {}",
+        file.as_ref().display()
+    );
+    let relationships = read(file)?;
+    asdb.replace_relationships(&relationships).await?;
+    let asns: BTreeSet<u32> = relationships.iter().flat_map(|x| [x.a, x.b]).collect();
+    println!(
+        "loaded {} relationships of {} ases",
+        relationships.len(),
+        asns.len()
+    );
+    Ok(asns.into_iter().collect())
+}
+
+/// Reads relationships from the file, a link listed twice is kept once with its last values
+fn read(file: impl AsRef<Path>) -> Result<Vec<AsRelationship>> {
+    let f = File::open(&file)?;
+    let reader: Box<dyn BufRead> = match file.as_ref().extension() {
+        Some(x) if x == "bz2" => Box::new(BufReader::new(MultiBzDecoder::new(f))),
+        _ => Box::new(BufReader::new(f)),
+    };
+    let mut links = BTreeMap::new();
+    for (i, line) in reader.lines().enumerate() {
+        if let Some(x) = parse_line(&line?).map_err(|x| Error::InvalidLine(i + 1, x))? {
+            links.insert((x.a, x.b), x);
+        }
+    }
+    Ok(links.into_values().collect())
+}
+
+/// None for comments and empty lines, the line itself if it's not a relationship
+fn parse_line(line: &str) -> std::result::Result<Option<AsRelationship>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split('|').collect();
+    let (a, b, kind) = match fields[..] {
+        [a, b, kind] | [a, b, kind, _] => (a.parse(), b.parse(), kind),
+        _ => return Err(line.to_string()),
+    };
+    let kind = match kind {
+        "-1" => Relationship::ProviderCustomer,
+        "0" => Relationship::PeerPeer,
+        _ => return Err(line.to_string()),
+    };
+    let (Ok(a), Ok(b)) = (a, b) else {
+        return Err(line.to_string());
+    };
+    Ok(Some(AsRelationship {
+        a,
+        b,
+        kind,
+        source: fields.get(3).map(|x| x.to_string()),
+    }))
+}
@@ -1,4 +1,4 @@
-use crate::{asrank, ipnetdb, stanford_asdb};
+use crate::{asrank, caida, ipnetdb, peeringdb, ripe_db, rir_stats, stanford_asdb};
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -18,6 +18,14 @@ pub enum Error {
     IpnetDB(#[from] ipnetdb::Error),
     #[error("stanford asdb error")]
     StanfordASdb(#[from] stanford_asdb::Error),
+    #[error("caida error")]
+    Caida(#[from] caida::Error),
+    #[error("peeringdb error")]
+    Peeringdb(#[from] peeringdb::Error),
+    #[error("rir statistics error")]
+    RirStats(#[from] rir_stats::Error),
+    #[error("ripe database dump error")]
+    RipeDb(#[from] ripe_db::Error),
 }
 
 impl From<asdb::Error> for Error {
@@ -1,8 +1,11 @@
-use asdb::Asdb;
+use asdb::Storage;
 
 use ipnetwork::IpNetwork;
 
-use std::{ffi::OsStr, path::Path};
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
 use trauma::{download::Download, downloader::DownloaderBuilder};
 
 pub use error::{Error, Result};
@@ -13,16 +16,24 @@ mod read_models;
 const LATEST_PREFIX_MMDB: &str = "https://cdn.ipnetdb.net/ipnetdb_prefix_latest.mmdb";
 const LATEST_ASN_MMDB: &str = "https://cdn.ipnetdb.net/ipnetdb_asn_latest.mmdb";
 
-pub async fn load(asdb: &Asdb) -> Result<()> {
+const ASN_MMDB_PATH: &str = "inputs/ipnetdb_asn_latest.mmdb";
+const PREFIX_MMDB_PATH: &str = "inputs/ipnetdb_prefix_latest.mmdb";
+
+/// URLs of the downloaded databases along with the paths they're read from
+pub fn files() -> Vec<(String, Option<PathBuf>)> {
+    vec![
+        (LATEST_ASN_MMDB.to_string(), Some(ASN_MMDB_PATH.into())),
+        (
+            LATEST_PREFIX_MMDB.to_string(),
+            Some(PREFIX_MMDB_PATH.into()),
+        ),
+    ]
+}
+
+/// Returns asns which got ipnetdb data
+pub async fn load(asdb: &impl Storage) -> Result<Vec<u32>> {
     download(&"inputs").await?;
-    read_asns(
-        &"inputs/ipnetdb_asn_latest.mmdb",
-        &"inputs/ipnetdb_prefix_latest.mmdb",
-        asdb,
-    )
-    .await
-    .unwrap();
-    Ok(())
+    read_asns(&ASN_MMDB_PATH, &PREFIX_MMDB_PATH, asdb).await
 }
 
 async fn download<T: AsRef<Path> + AsRef<OsStr>>(dest: &T) -> Result<()> {
@@ -39,8 +50,8 @@ async fn download<T: AsRef<Path> + AsRef<OsStr>>(dest: &T) -> Result<()> {
 async fn read_asns(
     asn_mmdb: &impl AsRef<Path>,
     prefix_mmdb: &impl AsRef<Path>,
-    asdb: &Asdb,
-) -> Result<()> {
+    asdb: &impl Storage,
+) -> Result<Vec<u32>> {
     println!("importing ipnetdb asns from mmdb file to the database");
     let every_ip = IpNetwork::V4("0.0.0.0/0".parse()?);
     let asn_reader = maxminddb::Reader::open_readfile(asn_mmdb)?;
@@ -50,6 +61,7 @@ async fn read_asns(
     let total_asns = asn_reader.within(every_ip, Default::default())?.count() as u64;
     let bar = indicatif::ProgressBar::new(total_asns);
 
+    let mut asns = vec![];
     for asn_lookup in asn_iter.flatten() {
         let Some(decoded) = asn_lookup.decode::<read_models::IPNetDBAsn>()? else {
             continue;
@@ -60,21 +72,30 @@ async fn read_asns(
             continue;
         };
 
-        for prefix in &mut asn_model.ipv4_prefixes {
+        for prefix in asn_model
+            .ipv4_prefixes
+            .iter_mut()
+            .chain(asn_model.ipv6_prefixes.iter_mut())
+        {
             prefix.details = prefix_reader
                 .lookup(prefix.range.network())
                 .ok()
                 .and_then(|l| l.decode::<read_models::IPNetDBPrefix>().ok())
                 .flatten()
-                .and_then(|p| asdb_models::IPNetDBPrefixDetails::try_from(p).ok());
+                .map(asdb_models::IPNetDBPrefixDetails::from);
         }
 
         asdb.insert_ipnetdb_asn(decoded.as_, &asn_model).await?;
+        asdb.insert_prefixes(decoded.as_, &asn_model.ipv4_prefixes)
+            .await?;
+        asdb.insert_prefixes(decoded.as_, &asn_model.ipv6_prefixes)
+            .await?;
+        asns.push(decoded.as_);
         bar.inc(1);
     }
 
     bar.finish();
-    Ok(())
+    Ok(asns)
 }
 
 #[cfg(test)]
@@ -144,10 +144,9 @@ impl From<IPNetDBPrefix> for asdb_models::IPNetDBPrefixDetails {
             } else {
                 None
             },
-            allocation_registry: asdb_models::InternetRegistry::try_from(
+            allocation_registry: Some(asdb_models::InternetRegistry::from(
                 value.allocation_registry.as_str(),
-            )
-            .ok(),
+            )),
             prefix_entity: value.prefix_entity,
             prefix_name: value.prefix_name,
             prefix_origins: value.prefix_origins.unwrap_or_default(),
@@ -1,24 +1,36 @@
 //! Builds and populates an AS database from multiple data sources.
 //!
-//! Downloads and imports data from ASRank, IPNetDB, and Stanford ASDB into MongoDB.
+//! Downloads and imports data from ASRank, IPNetDB, Stanford ASDB, CAIDA AS relationships,
+//! PeeringDB, RIR statistics and RIPE database dumps into MongoDB or any other [`Storage`]
+//! backend.
 
 mod asrank;
+mod caida;
 mod error;
 mod ipnetdb;
+mod peeringdb;
+mod ripe_db;
+mod rir_stats;
 mod stanford_asdb;
 pub mod whois;
 
-use std::path::{Path, PathBuf};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
 
-use asdb::Asdb;
-use error::Result;
+use asdb::{Asdb, Storage};
+use asdb_models::{Dataset, DatasetFile};
+use error::{Error, Result};
+use sha2::{Digest, Sha256};
 
 /// Main builder for populating the AS database.
 ///
-/// Interfaces with MongoDB through the `asdb` crate to import AS data
-/// from ASRank API, IPNetDB MaxMind files, and Stanford classifications.
-pub struct AsdbBuilder {
-    a: Asdb,
+/// Interfaces with the storage backend from the `asdb` crate (MongoDB by default)
+/// to import AS data from ASRank API, IPNetDB MaxMind files, and Stanford classifications.
+pub struct AsdbBuilder<S: Storage = Asdb> {
+    a: S,
     inputs: PathBuf,
 }
 
@@ -31,10 +43,44 @@ impl AsdbBuilder {
     /// * `inputs_path` - Directory for downloaded files
     pub async fn new(conn_str: &str, database: &str, inputs_path: &str) -> Result<Self> {
         let a = Asdb::new(conn_str, database).await?;
-        Ok(Self {
-            a,
+        Ok(Self::with_storage(a, inputs_path))
+    }
+
+    /// Creates a new builder loading into a named dataset of the database, see
+    /// [`asdb::dataset_database`].
+    ///
+    /// # Arguments
+    /// * `conn_str` - MongoDB connection string
+    /// * `database` - Database name
+    /// * `dataset` - Dataset name, [`asdb::DEFAULT_DATASET`] is the database itself
+    /// * `inputs_path` - Directory for downloaded files
+    pub async fn new_dataset(
+        conn_str: &str,
+        database: &str,
+        dataset: &str,
+        inputs_path: &str,
+    ) -> Result<Self> {
+        let a = Asdb::new_dataset(conn_str, database, dataset).await?;
+        Ok(Self::with_storage(a, inputs_path))
+    }
+}
+
+impl<S: Storage> AsdbBuilder<S> {
+    /// Creates a new builder on top of an already initialized storage backend.
+    ///
+    /// # Arguments
+    /// * `storage` - Storage backend, e.g. [`asdb::InMemoryAsdb`]
+    /// * `inputs_path` - Directory for downloaded files
+    pub fn with_storage(storage: S, inputs_path: &str) -> Self {
+        Self {
+            a: storage,
             inputs: PathBuf::from(inputs_path),
-        })
+        }
+    }
+
+    /// Returns the underlying storage backend.
+    pub fn storage(&self) -> &S {
+        &self.a
     }
 
     /// Drops all collections and recreates indexes.
@@ -44,24 +90,144 @@ impl AsdbBuilder {
         Ok(())
     }
 
-    /// Downloads ASRank data via GraphQL and imports to MongoDB.
+    /// Returns a builder loading into empty staging storage, the data being served stays as it
+    /// is until the staged one is promoted with [`AsdbBuilder::promote`].
+    pub async fn staging(&self) -> Result<AsdbBuilder<S>> {
+        Ok(AsdbBuilder {
+            a: self.a.staging().await?,
+            inputs: self.inputs.clone(),
+        })
+    }
+
+    /// Validates the data loaded by the `staging` builder and swaps it in place of the current
+    /// one, keeping the user and WHOIS data of the current ases. Returns the count of ases now in
+    /// place.
+    pub async fn promote(&self, staging: AsdbBuilder<S>) -> Result<u64> {
+        Ok(self.a.promote(staging.a, true).await?)
+    }
+
+    /// Puts the data replaced by the last [`AsdbBuilder::promote`] back into place.
+    pub async fn rollback(&self) -> Result<()> {
+        self.a.rollback().await?;
+        Ok(())
+    }
+
+    /// Downloads ASRank data via GraphQL and imports to the database.
     ///
     /// If `asns_jsonl` is provided, reads from that file instead of downloading.
-    pub async fn load_asrank_asns(&self, asns_jsonl: Option<impl AsRef<Path>>) -> Result<()> {
-        asrank::load(&self.a, asns_jsonl.map(|x| self.inputs.join(x))).await?;
-        Ok(())
+    /// Returns the import run id the imported ases got snapshotted under.
+    pub async fn load_asrank_asns(&self, asns_jsonl: Option<impl AsRef<Path>>) -> Result<String> {
+        let file = asns_jsonl.map(|x| self.inputs.join(x));
+        let files = asrank::files(file.as_deref());
+        self.run_import("asrank", files, asrank::load(&self.a, file))
+            .await
     }
 
     /// Downloads IPNetDB MaxMind databases and imports IP prefix data.
-    pub async fn load_ipnetdb(&self) -> Result<()> {
-        ipnetdb::load(&self.a).await?;
-        Ok(())
+    ///
+    /// Returns the import run id the updated ases got snapshotted under.
+    pub async fn load_ipnetdb(&self) -> Result<String> {
+        self.run_import("ipnetdb", ipnetdb::files(), ipnetdb::load(&self.a))
+            .await
     }
 
     /// Downloads Stanford ASDB classifications and imports AS categories.
-    pub async fn load_stanford_asdb(&self) -> Result<()> {
-        stanford_asdb::load(&self.a).await?;
-        Ok(())
+    ///
+    /// Returns the import run id the updated ases got snapshotted under.
+    pub async fn load_stanford_asdb(&self) -> Result<String> {
+        let files = stanford_asdb::files();
+        self.run_import("stanford-asdb", files, stanford_asdb::load(&self.a))
+            .await
+    }
+
+    /// Loads provider-customer and peer-peer links from a CAIDA as-rel or as-rel2 file,
+    /// `.txt` or `.bz2`, replacing the stored ones.
+    ///
+    /// Returns the import run id the linked ases got snapshotted under.
+    pub async fn load_caida_relationships(&self, file: impl AsRef<Path>) -> Result<String> {
+        let file = self.inputs.join(file);
+        let files = caida::files(&file);
+        self.run_import("caida-as-rel", files, caida::load(&self.a, &file))
+            .await
+    }
+
+    /// Loads networks, IXPs and facilities from a PeeringDB JSON dump. The networks are attached
+    /// to the stored ases, IXPs and facilities replace the stored ones.
+    ///
+    /// Returns the import run id the updated ases got snapshotted under.
+    pub async fn load_peeringdb(&self, file: impl AsRef<Path>) -> Result<String> {
+        let file = self.inputs.join(file);
+        let files = peeringdb::files(&file);
+        self.run_import("peeringdb", files, peeringdb::load(&self.a, &file))
+            .await
+    }
+
+    /// Loads registry, country, allocation date, status and holder id of asns and IP blocks
+    /// from the delegated-extended statistics files of the RIRs. Asn delegations are attached to
+    /// the stored ases, IP blocks replace the stored ones.
+    ///
+    /// Returns the import run id the updated ases got snapshotted under.
+    pub async fn load_rir_stats(&self, files: &[impl AsRef<Path>]) -> Result<String> {
+        let files: Vec<PathBuf> = files.iter().map(|x| self.inputs.join(x)).collect();
+        let inputs = rir_stats::files(&files);
+        self.run_import("rir-stats", inputs, rir_stats::load(&self.a, &files))
+            .await
+    }
+
+    /// Fills WHOIS data of the stored ases, their organisations and contacts from the split
+    /// dumps of the RIPE database, `.gz` or plain text, in one pass instead of querying the RIPE
+    /// API per AS.
+    ///
+    /// Returns the import run id the updated ases got snapshotted under.
+    pub async fn load_ripe_db(&self, files: &[impl AsRef<Path>]) -> Result<String> {
+        let files: Vec<PathBuf> = files.iter().map(|x| self.inputs.join(x)).collect();
+        let inputs = ripe_db::files(&files);
+        self.run_import("ripe-db", inputs, ripe_db::load(&self.a, &files))
+            .await
+    }
+
+    /// Runs the import of `source` and snapshots the ases it returns. The run is recorded in
+    /// the datasets collection along with the `files` it read, (source URL or path, local path),
+    /// which are hashed once the import is done and they're downloaded.
+    async fn run_import<E: Into<Error>>(
+        &self,
+        source: &str,
+        files: Vec<(String, Option<PathBuf>)>,
+        import: impl Future<Output = std::result::Result<Vec<u32>, E>>,
+    ) -> Result<String> {
+        let run_id = run_id(source);
+        let mut dataset = Dataset {
+            run_id: run_id.clone(),
+            source: source.to_string(),
+            started_at: chrono::Utc::now().to_rfc3339(),
+            ..Default::default()
+        };
+        self.a.save_dataset(&dataset).await?;
+
+        let result = match import.await.map_err(Into::into) {
+            Ok(asns) => self
+                .a
+                .snapshot_ases(&run_id, &asns)
+                .await
+                .map(|_| asns.len() as u64)
+                .map_err(Error::from),
+            Err(e) => Err(e),
+        };
+        dataset.files = files
+            .into_iter()
+            .map(|(source, path)| DatasetFile {
+                source,
+                sha256: path.as_deref().and_then(|x| sha256(x).ok()),
+                path: path.map(|x| x.display().to_string()),
+            })
+            .collect();
+        match &result {
+            Ok(records) => dataset.records = *records,
+            Err(e) => dataset.errors.push(format!("{e:?}")),
+        }
+        dataset.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        self.a.save_dataset(&dataset).await?;
+        result.map(|_| run_id)
     }
 
     /// Generates normalized AS categories from imported data.
@@ -71,13 +237,30 @@ impl AsdbBuilder {
     }
 }
 
+/// Hex encoded SHA-256 of the file
+fn sha256(path: &Path) -> std::io::Result<String> {
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Id of an import run, name of the source followed by the start time in unix nanoseconds
+fn run_id(source: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time is after the unix epoch")
+        .as_nanos();
+    format!("{source}-{nanos}")
+}
+
 #[cfg(test)]
 mod tests {
     use futures::stream::TryStreamExt;
     use mongodb::{Client, Collection, bson::doc, options::ClientOptions};
 
     use super::*;
-    use asdb_models::As;
+    use asdb::InMemoryAsdb;
+    use asdb_models::{As, AsFilters, AsNeighbor, InternetRegistry, NeighborKind};
     use std::fs::read_to_string;
     use test_context::TestContext;
 
@@ -85,6 +268,14 @@ mod tests {
     const ASNS_COLLECTION: &str = "asns";
     const ASNS: &str = "asns.jsonl";
     const ASNS2: &str = "asns2.jsonl";
+    const AS_REL: &str = "as-rel.txt";
+    const AS_REL2: &str = "as-rel2.txt.bz2";
+    const PEERINGDB: &str = "peeringdb.json";
+    const RIPE_DB: [&str; 3] = ["ripe.db.aut-num.gz", "ripe.db.organisation", "ripe.db.role"];
+    const RIR_STATS: [&str; 2] = [
+        "delegated-ripencc-extended.txt",
+        "delegated-arin-extended.txt",
+    ];
     const INPUTS_PATH: &str = "test-data";
 
     #[tokio::test(flavor = "multi_thread")]
@@ -152,6 +343,321 @@ mod tests {
         assert_eq!(ases.len(), 1);
     }
 
+    #[tokio::test]
+    async fn import_asrank_asns_into_memory_appends_only_new_ones() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let (_, first_docs) = m.storage().get_ases_page(0, 0).await.unwrap();
+        assert_eq!(
+            count_lines(&PathBuf::from(INPUTS_PATH).join(ASNS)),
+            first_docs
+        );
+
+        m.load_asrank_asns(Some(&ASNS2)).await.unwrap();
+        let (ases, second_docs) = m.storage().get_ases_page(0, 0).await.unwrap();
+
+        assert!(second_docs > first_docs);
+        assert_eq!(ases.iter().filter(|x| x.asn == 1299).count(), 1);
+    }
+
+    #[tokio::test]
+    async fn import_asrank_asns_into_memory_creates_organisations() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+
+        // 1299 is in test-data/asns.jsonl with organization "7cf5c4e5ce"
+        let org = m
+            .storage()
+            .get_as_organisation(1299)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(org.org_id, "7cf5c4e5ce");
+        assert_eq!(org.name, "Arelion");
+        assert_eq!(org.asns, [1299]);
+    }
+
+    #[tokio::test]
+    async fn import_asrank_asns_into_memory_snapshots_each_run() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        let first = m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let second = m.load_asrank_asns(Some(&ASNS2)).await.unwrap();
+        assert_ne!(first, second);
+
+        let snapshots = m.storage().get_run_snapshots(&first).await.unwrap();
+        assert_eq!(
+            count_lines(&PathBuf::from(INPUTS_PATH).join(ASNS)),
+            snapshots.len() as u64
+        );
+        // shared asns are identical in both files, the rest is in only one of them
+        let changed = m.storage().get_changed_ases(&first, &second).await.unwrap();
+        assert_eq!(changed, [174, 6762, 10429, 33891]);
+        let diff = m.storage().diff_as_snapshots(1299, &first, &second).await;
+        assert!(diff.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn import_asrank_asns_into_memory_records_datasets() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        let run = m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        assert!(m.load_asrank_asns(Some(&"missing.jsonl")).await.is_err());
+
+        let datasets = m.storage().get_datasets().await.unwrap();
+        assert_eq!(datasets.len(), 2);
+        let (failed, loaded) = (&datasets[0], &datasets[1]);
+        assert_eq!(loaded.run_id, run);
+        assert_eq!(loaded.source, "asrank");
+        assert_eq!(
+            loaded.records,
+            count_lines(&PathBuf::from(INPUTS_PATH).join(ASNS))
+        );
+        assert!(loaded.errors.is_empty() && loaded.finished_at.is_some());
+        let path = PathBuf::from(INPUTS_PATH).join(ASNS);
+        assert_eq!(loaded.files[0].sha256, Some(sha256(&path).unwrap()));
+        assert_eq!(loaded.files[0].sha256.as_ref().unwrap().len(), 64);
+        assert_eq!(failed.errors.len(), 1);
+        assert_eq!(failed.files[0].sha256, None);
+    }
+
+    #[tokio::test]
+    async fn staged_import_into_memory_replaces_ases_once_promoted() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+
+        let staging = m.staging().await.unwrap();
+        let run = staging.load_asrank_asns(Some(&ASNS2)).await.unwrap();
+        // 10429 is only in asns2.jsonl, 174 only in asns.jsonl
+        assert!(m.storage().get_as(10429).await.is_err());
+        let count = m.promote(staging).await.unwrap();
+
+        assert_eq!(count_lines(&PathBuf::from(INPUTS_PATH).join(ASNS2)), count);
+        assert!(m.storage().get_as(10429).await.is_ok());
+        assert!(m.storage().get_as(174).await.is_err());
+        // the import history is kept along with the promoted data
+        let datasets = m.storage().get_datasets().await.unwrap();
+        assert_eq!(datasets[0].run_id, run);
+        m.rollback().await.unwrap();
+        assert!(m.storage().get_as(10429).await.is_err());
+        assert!(m.storage().get_as(174).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn import_caida_relationships_into_memory_stores_neighbors() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let run = m.load_caida_relationships(AS_REL).await.unwrap();
+
+        let customers = m
+            .storage()
+            .get_neighbors(1299, Some(NeighborKind::Customer))
+            .await
+            .unwrap();
+        assert_eq!(customers.iter().map(|x| x.asn).collect::<Vec<_>>(), [5550]);
+        let peers = m
+            .storage()
+            .get_neighbors(1299, Some(NeighborKind::Peer))
+            .await
+            .unwrap();
+        assert_eq!(peers.iter().map(|x| x.asn).collect::<Vec<_>>(), [174, 3356]);
+        // only the linked ases present in the database are snapshotted
+        let snapshots = m.storage().get_run_snapshots(&run).await.unwrap();
+        assert_eq!(snapshots.len(), 4);
+        assert_eq!(m.storage().get_datasets().await.unwrap()[0].records, 5);
+
+        // a newer file replaces all links, 174 is no longer a peer of 3356
+        m.load_caida_relationships(AS_REL2).await.unwrap();
+        let neighbors = m.storage().get_neighbors(3356, None).await.unwrap();
+        assert_eq!(
+            neighbors,
+            [
+                AsNeighbor {
+                    asn: 1299,
+                    kind: NeighborKind::Peer
+                },
+                AsNeighbor {
+                    asn: 5550,
+                    kind: NeighborKind::Customer
+                },
+            ]
+        );
+        assert!(m.load_caida_relationships(ASNS).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn import_peeringdb_into_memory_attaches_networks() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let run = m.load_peeringdb(PEERINGDB).await.unwrap();
+
+        let net = m.storage().get_as(1299).await.unwrap().peeringdb_data;
+        let net = net.unwrap();
+        assert_eq!(net.policy_general.as_deref(), Some("Restrictive"));
+        assert_eq!(net.info_traffic, None);
+        assert_eq!(net.ixps.len(), 2);
+        assert_eq!(net.ixps[0].name, "DE-CIX Frankfurt");
+        let facilities: Vec<_> = net.facilities.iter().map(|x| x.fac_id).collect();
+        assert_eq!(facilities, [20, 21]);
+        // the port on an unknown peering LAN is skipped, info_type falls back to info_types
+        let net = m.storage().get_as(174).await.unwrap().peeringdb_data;
+        let net = net.unwrap();
+        assert_eq!(net.ixps.len(), 1);
+        assert!(net.ixps[0].is_rs_peer);
+        assert_eq!(net.info_type.as_deref(), Some("NSP"));
+        // 64512 isn't in asns.jsonl so it's neither added nor snapshotted
+        assert!(m.storage().get_as(64512).await.is_err());
+        assert_eq!(m.storage().get_run_snapshots(&run).await.unwrap().len(), 2);
+
+        let ixps = m.storage().get_ixps().await.unwrap();
+        assert_eq!(ixps.len(), 2);
+        assert_eq!(ixps[0].networks, 2);
+        let center = ixps[0].coordinates.as_ref().unwrap();
+        assert!((center.lat - 50.15).abs() < 1e-9 && (center.lon - 8.7).abs() < 1e-9);
+        assert_eq!(ixps[1].coordinates, None);
+        assert_eq!(ixps[1].name_long, None);
+        let facilities = m.storage().get_facilities().await.unwrap();
+        assert_eq!(
+            facilities.iter().map(|x| x.networks).collect::<Vec<_>>(),
+            [2, 1, 0]
+        );
+        assert_eq!(facilities[2].coordinates, None);
+    }
+
+    #[tokio::test]
+    async fn reloading_peeringdb_clears_networks_missing_from_the_dump() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        m.load_peeringdb(PEERINGDB).await.unwrap();
+        // a network from an earlier dump which is gone from this one
+        let gone = m.storage().get_as(1299).await.unwrap().peeringdb_data;
+        m.storage()
+            .insert_peeringdb_net(3356, &gone.unwrap())
+            .await
+            .unwrap();
+
+        let run = m.load_peeringdb(PEERINGDB).await.unwrap();
+
+        let cleared = m.storage().get_as(3356).await.unwrap();
+        assert!(cleared.peeringdb_data.is_none());
+        let kept = m.storage().get_as(1299).await.unwrap();
+        assert!(kept.peeringdb_data.is_some());
+        assert_eq!(m.storage().get_run_snapshots(&run).await.unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn import_rir_stats_into_memory_attaches_delegations() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let run = m.load_rir_stats(&RIR_STATS).await.unwrap();
+
+        // 1299 is the last of the 1297-1299 range
+        let rir = m.storage().get_as(1299).await.unwrap().rir_data.unwrap();
+        assert_eq!(rir.registry, InternetRegistry::RIPE);
+        assert_eq!(rir.country.as_deref(), Some("SE"));
+        assert_eq!(rir.date.as_deref(), Some("1993-09-01"));
+        assert_eq!(rir.status, "allocated");
+        assert!(rir.opaque_id.is_some());
+        let rir = m.storage().get_as(3356).await.unwrap().rir_data.unwrap();
+        assert_eq!(rir.registry, InternetRegistry::ARIN);
+        assert_eq!(m.storage().get_run_snapshots(&run).await.unwrap().len(), 5);
+
+        let filters = AsFilters {
+            registry: Some(InternetRegistry::ARIN),
+            allocated_after: Some("1995-01-01".to_string()),
+            ..Default::default()
+        };
+        let asns: Vec<u32> = m
+            .storage()
+            .get_ases_filtered(&filters)
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.asn)
+            .collect();
+        assert_eq!(asns, [3356, 6939]);
+        let filters = AsFilters {
+            allocated_before: Some("1996-08-13".to_string()),
+            ..Default::default()
+        };
+        let count = m.storage().count_ases_filtered(&filters).await.unwrap();
+        assert_eq!(count, 3);
+
+        let lookup = |ip: &str| m.storage().lookup_rir_block(ip.parse().unwrap());
+        // 768 addresses from 192.0.2.0 are split into a /23 and a /24
+        let block = lookup("192.0.4.10").await.unwrap().unwrap();
+        assert_eq!(block.range.to_string(), "192.0.4.0/24");
+        assert_eq!(block.delegation.date.as_deref(), Some("2010-05-03"));
+        let block = lookup("2001:2000:ffff::1").await.unwrap().unwrap();
+        assert_eq!(block.range.to_string(), "2001:2000::/20");
+        let block = lookup("185.0.1.1").await.unwrap().unwrap();
+        assert_eq!(block.delegation.country, None);
+        assert_eq!(block.delegation.date, None);
+        // available resources are skipped
+        assert!(lookup("23.128.0.1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn reloading_rir_stats_clears_delegations_dropped_from_them() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        m.load_rir_stats(&RIR_STATS).await.unwrap();
+        // AS6762 got returned to RIPE NCC since
+        let ripe = read_to_string(Path::new(INPUTS_PATH).join(RIR_STATS[0])).unwrap();
+        let newer = std::env::temp_dir().join("delegated-ripencc-extended-newer.txt");
+        std::fs::write(
+            &newer,
+            ripe.lines()
+                .filter(|x| !x.contains("|6762|"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+        .unwrap();
+
+        let asns = rir_stats::load(m.storage(), &[newer]).await.unwrap();
+
+        assert!(asns.contains(&6762));
+        assert!(m.storage().get_as(6762).await.unwrap().rir_data.is_none());
+        assert!(m.storage().get_as(1299).await.unwrap().rir_data.is_some());
+        // delegations of the other registries stay
+        let rir = m.storage().get_as(3356).await.unwrap().rir_data.unwrap();
+        assert_eq!(rir.registry, InternetRegistry::ARIN);
+    }
+
+    #[tokio::test]
+    async fn import_ripe_db_into_memory_fills_whois_data() {
+        let m = AsdbBuilder::with_storage(InMemoryAsdb::new(), INPUTS_PATH);
+        m.load_asrank_asns(Some(&ASNS)).await.unwrap();
+        let run = m.load_ripe_db(&RIPE_DB).await.unwrap();
+
+        let whois = m.storage().get_whois_data(1299).await.unwrap().unwrap();
+        assert_eq!(whois.as_name.as_deref(), Some("TWELVE99"));
+        assert_eq!(whois.descr.len(), 2);
+        assert!(whois.fetched_at.is_some());
+        let org = whois.organisation.unwrap();
+        assert_eq!(org.org_name, "Arelion Sweden AB");
+        assert_eq!(org.address.len(), 4);
+        let contacts: Vec<_> = whois.contacts.iter().map(|x| x.nic_hdl.as_str()).collect();
+        assert_eq!(contacts, ["AR17615-RIPE", "TR7815-RIPE"]);
+        let org = m
+            .storage()
+            .get_as_organisation(1299)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(org.whois_org_id.as_deref(), Some("ORG-TCA23-RIPE"));
+
+        // continuation lines are joined, contacts missing from the dumps are kept as handles
+        let whois = m.storage().get_whois_data(6762).await.unwrap().unwrap();
+        assert_eq!(
+            whois.organisation.unwrap().address,
+            ["Via di Macchia Palocco, 223 00125 Roma"]
+        );
+        let names: Vec<_> = whois.contacts.iter().map(|x| x.name.as_str()).collect();
+        assert_eq!(names, ["", "Sparkle NOC"]);
+        // 5550 isn't in asns.jsonl
+        assert!(m.storage().get_as(5550).await.is_err());
+        assert_eq!(m.storage().get_run_snapshots(&run).await.unwrap().len(), 2);
+    }
+
     fn count_lines(path: &impl AsRef<Path>) -> u64 {
         read_to_string(path).unwrap().lines().map(|_| 1).sum()
     }
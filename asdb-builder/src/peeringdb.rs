@@ -0,0 +1,157 @@
+//! Loads networks, IXPs and facilities from a local PeeringDB JSON dump, e.g. the daily ones
+//! archived at https://publicdata.caida.org/datasets/peeringdb/
+//!
+//! The dump holds one `{"data": [...]}` table per object type. Networks are attached to the
+//! ases of their asn in place of the ones of earlier dumps, IXPs and facilities replace the
+//! stored ones.
+mod error;
+mod read_models;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use asdb::Storage;
+use asdb_models::{Coord, Facility, FacilityPresence, Ixp, IxpPresence, PeeringdbNet};
+pub use error::{Error, Result};
+use read_models::{Dump, text};
+
+/// The local dump the data is read from
+pub fn files(file: &Path) -> Vec<(String, Option<PathBuf>)> {
+    vec![(file.display().to_string(), Some(file.to_path_buf()))]
+}
+
+/// Returns asns of the networks in the dump along with the ones whose networks are gone
+pub async fn load(asdb: &impl Storage, file: impl AsRef<Path>) -> Result<Vec<u32>> {
+    println!("reading peeringdb dump {}", file.as_ref().display());
+    let dump: Dump = serde_json::from_reader(BufReader::new(File::open(&file)?))?;
+    let (nets, ixps, facilities) = convert(&dump);
+
+    let mut asns: BTreeSet<u32> = asdb.clear_peeringdb_nets().await?.into_iter().collect();
+    println!("writing {} peeringdb networks to the database", nets.len());
+    let bar = indicatif::ProgressBar::new(nets.len() as u64);
+    for (asn, net) in &nets {
+        asdb.insert_peeringdb_net(*asn, net).await?;
+        bar.inc(1);
+    }
+    bar.finish();
+    asdb.replace_ixps(&ixps).await?;
+    asdb.replace_facilities(&facilities).await?;
+    println!("{} ixps and {} facilities", ixps.len(), facilities.len());
+    asns.extend(nets.into_iter().map(|(asn, _)| asn));
+    Ok(asns.into_iter().collect())
+}
+
+/// Networks along with their asns, IXPs and facilities of the dump
+fn convert(dump: &Dump) -> (Vec<(u32, PeeringdbNet)>, Vec<Ixp>, Vec<Facility>) {
+    let ixs: BTreeMap<u32, _> = dump.ix.data.iter().map(|x| (x.id, x)).collect();
+    let facs: BTreeMap<u32, _> = dump.fac.data.iter().map(|x| (x.id, x)).collect();
+    let ixlan_ix: BTreeMap<u32, u32> = dump.ixlan.data.iter().map(|x| (x.id, x.ix_id)).collect();
+
+    let mut ix_presence: BTreeMap<u32, Vec<IxpPresence>> = BTreeMap::new();
+    let mut ix_networks: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+    for x in &dump.netixlan.data {
+        let Some(ix) = ixlan_ix.get(&x.ixlan_id).and_then(|id| ixs.get(id)) else {
+            continue;
+        };
+        ix_presence.entry(x.net_id).or_default().push(IxpPresence {
+            ix_id: ix.id,
+            name: ix.name.clone(),
+            speed: x.speed,
+            ipaddr4: text(&x.ipaddr4),
+            ipaddr6: text(&x.ipaddr6),
+            is_rs_peer: x.is_rs_peer,
+        });
+        ix_networks.entry(ix.id).or_default().insert(x.net_id);
+    }
+    let mut fac_presence: BTreeMap<u32, Vec<FacilityPresence>> = BTreeMap::new();
+    let mut fac_networks: BTreeMap<u32, BTreeSet<u32>> = BTreeMap::new();
+    for x in &dump.netfac.data {
+        let Some(fac) = facs.get(&x.fac_id) else {
+            continue;
+        };
+        fac_presence
+            .entry(x.net_id)
+            .or_default()
+            .push(FacilityPresence {
+                fac_id: fac.id,
+                name: fac.name.clone(),
+            });
+        fac_networks.entry(fac.id).or_default().insert(x.net_id);
+    }
+
+    let nets = dump
+        .net
+        .data
+        .iter()
+        .map(|x| {
+            let mut ixps = ix_presence.remove(&x.id).unwrap_or_default();
+            ixps.sort_by(|a, b| (&a.name, a.ix_id).cmp(&(&b.name, b.ix_id)));
+            let mut facilities = fac_presence.remove(&x.id).unwrap_or_default();
+            facilities.sort_by(|a, b| (&a.name, a.fac_id).cmp(&(&b.name, b.fac_id)));
+            let net = PeeringdbNet {
+                id: x.id,
+                name: x.name.clone(),
+                policy_general: text(&x.policy_general),
+                info_traffic: text(&x.info_traffic),
+                info_type: text(&x.info_type).or_else(|| x.info_types.first().cloned()),
+                ixps,
+                facilities,
+            };
+            (x.asn, net)
+        })
+        .collect();
+
+    let coordinates = |fac: &read_models::Fac| match (fac.latitude, fac.longitude) {
+        (Some(lat), Some(lon)) => Some(Coord { lat, lon }),
+        _ => None,
+    };
+    let mut ix_coordinates: BTreeMap<u32, Vec<Coord>> = BTreeMap::new();
+    for x in &dump.ixfac.data {
+        if let Some(c) = facs.get(&x.fac_id).and_then(|f| coordinates(f)) {
+            ix_coordinates.entry(x.ix_id).or_default().push(c);
+        }
+    }
+    let ixps = dump
+        .ix
+        .data
+        .iter()
+        .map(|x| Ixp {
+            id: x.id,
+            name: x.name.clone(),
+            name_long: text(&x.name_long),
+            city: text(&x.city),
+            country: text(&x.country),
+            website: text(&x.website),
+            coordinates: ix_coordinates.get(&x.id).map(|c| center(c)),
+            networks: ix_networks.get(&x.id).map_or(0, |n| n.len() as u32),
+        })
+        .collect();
+    let facilities = dump
+        .fac
+        .data
+        .iter()
+        .map(|x| Facility {
+            id: x.id,
+            name: x.name.clone(),
+            city: text(&x.city),
+            country: text(&x.country),
+            website: text(&x.website),
+            coordinates: coordinates(x),
+            networks: fac_networks.get(&x.id).map_or(0, |n| n.len() as u32),
+        })
+        .collect();
+    (nets, ixps, facilities)
+}
+
+/// Mean of the coordinates, the facilities of one IXP are close enough for it to be on land
+fn center(coordinates: &[Coord]) -> Coord {
+    let n = coordinates.len() as f64;
+    Coord {
+        lat: coordinates.iter().map(|c| c.lat).sum::<f64>() / n,
+        lon: coordinates.iter().map(|c| c.lon).sum::<f64>() / n,
+    }
+}
@@ -0,0 +1,100 @@
+//! Objects of a PeeringDB dump, only the fields which are imported. PeeringDB leaves unset
+//! text fields as empty strings, [`text`] turns them into None.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct Dump {
+    pub net: Table<Net>,
+    pub ix: Table<Ix>,
+    pub ixlan: Table<IxLan>,
+    pub netixlan: Table<NetIxLan>,
+    pub fac: Table<Fac>,
+    pub netfac: Table<NetFac>,
+    /// only used to place IXPs on the map, dumps without it leave them without coordinates
+    #[serde(default)]
+    pub ixfac: Table<IxFac>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Table<T> {
+    pub data: Vec<T>,
+}
+
+impl<T> Default for Table<T> {
+    fn default() -> Self {
+        Self { data: vec![] }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Net {
+    pub id: u32,
+    pub asn: u32,
+    pub name: String,
+    pub policy_general: Option<String>,
+    pub info_traffic: Option<String>,
+    /// replaced by info_types in newer dumps
+    pub info_type: Option<String>,
+    #[serde(default)]
+    pub info_types: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Ix {
+    pub id: u32,
+    pub name: String,
+    pub name_long: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub website: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IxLan {
+    pub id: u32,
+    pub ix_id: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NetIxLan {
+    pub net_id: u32,
+    pub ixlan_id: u32,
+    pub speed: u32,
+    pub ipaddr4: Option<String>,
+    pub ipaddr6: Option<String>,
+    #[serde(default)]
+    pub is_rs_peer: bool,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Fac {
+    pub id: u32,
+    pub name: String,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub website: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct NetFac {
+    pub net_id: u32,
+    pub fac_id: u32,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct IxFac {
+    pub ix_id: u32,
+    pub fac_id: u32,
+}
+
+/// None for a missing or blank value
+pub fn text(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(str::to_string)
+}
@@ -0,0 +1,87 @@
+//! Fills WHOIS data of the stored ases from the split dumps of the RIPE database, e.g.
+//! `ripe.db.aut-num.gz`, `ripe.db.organisation.gz` and `ripe.db.role.gz` from
+//! https://ftp.ripe.net/ripe/dbase/split/
+//!
+//! All the files are read before anything is stored, so they can come in any order. The data
+//! ends up the same as fetched per AS with [`crate::whois::RipeClient`], contacts missing from
+//! the dumps are kept as bare handles the same way as the ones the API doesn't return.
+mod error;
+
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use asdb::Storage;
+use asdb_models::WhoIsAsn;
+pub use error::{Error, Result};
+use flate2::read::MultiGzDecoder;
+
+use crate::whois::{RipeDump, RpslReader};
+
+/// Ases looked up in the database at once
+const LOOKUP_CHUNK: usize = 1000;
+
+/// The local dump files
+pub fn files(files: &[PathBuf]) -> Vec<(String, Option<PathBuf>)> {
+    files
+        .iter()
+        .map(|x| (x.display().to_string(), Some(x.clone())))
+        .collect()
+}
+
+/// Stores WHOIS data of the aut-nums in the dumps whose ases are in the database, links their
+/// organisations and stores their contacts. Returns the asns of the updated ases.
+pub async fn load(asdb: &impl Storage, files: &[PathBuf]) -> Result<Vec<u32>> {
+    let mut dump = RipeDump::default();
+    for file in files {
+        println!("reading ripe database dump {}", file.display());
+        for object in RpslReader::new(reader(file)?) {
+            dump.add(&object?);
+        }
+    }
+    println!(
+        "{} aut-nums, {} organisations and {} contacts read",
+        dump.aut_nums.len(),
+        dump.organisations.len(),
+        dump.persons.len()
+    );
+
+    let asns: Vec<u32> = dump.aut_nums.iter().map(|x| x.asn).collect();
+    let mut stored = BTreeSet::new();
+    for chunk in asns.chunks(LOOKUP_CHUNK) {
+        let (ases, _) = asdb.get_ases(chunk).await?;
+        stored.extend(ases.iter().map(|x| x.asn));
+    }
+
+    println!(
+        "writing whois data of {} ases to the database",
+        stored.len()
+    );
+    let fetched_at = chrono::Utc::now().to_rfc3339();
+    let bar = indicatif::ProgressBar::new(stored.len() as u64);
+    for aut_num in dump.aut_nums.iter().filter(|x| stored.contains(&x.asn)) {
+        let whois = WhoIsAsn {
+            fetched_at: Some(fetched_at.clone()),
+            ..dump.as_whois_data(aut_num).into()
+        };
+        asdb.update_whois_data(aut_num.asn, &whois).await?;
+        if let Some(org) = &whois.organisation {
+            asdb.link_whois_organisation(aut_num.asn, org).await?;
+        }
+        bar.inc(1);
+    }
+    bar.finish();
+    Ok(stored.into_iter().collect())
+}
+
+/// Reads the file, either plain text or gzip compressed
+fn reader(file: &Path) -> Result<Box<dyn BufRead>> {
+    let f = File::open(file)?;
+    Ok(match file.extension() {
+        Some(x) if x == "gz" => Box::new(BufReader::new(MultiGzDecoder::new(f))),
+        _ => Box::new(BufReader::new(f)),
+    })
+}
@@ -0,0 +1,212 @@
+//! Loads the delegated-extended statistics files of the five RIRs, e.g.
+//! https://ftp.ripe.net/pub/stats/ripencc/delegated-ripencc-extended-latest
+//!
+//! After the version and summary lines each record is
+//! `registry|cc|type|start|value|date|status|opaque-id`, where `value` is the count of asns or
+//! IPv4 addresses, or the prefix length of an IPv6 block. Lines starting with `#` are comments.
+//! Resources still `available` at the RIR are skipped. Delegations of asns by the registries of
+//! the files are cleared first, so asns which dropped out of the statistics don't keep them.
+mod error;
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{BufRead, BufReader},
+    net::{Ipv4Addr, Ipv6Addr},
+    path::{Path, PathBuf},
+};
+
+use asdb::Storage;
+use asdb_models::{InternetRegistry, RirBlock, RirDelegation};
+pub use error::{Error, Result};
+use ipnetwork::{IpNetwork, Ipv4Network, Ipv6Network};
+
+/// Resources delegated by a single record
+#[derive(Debug)]
+enum Record {
+    Asns {
+        first: u32,
+        count: u32,
+        delegation: RirDelegation,
+    },
+    Blocks(Vec<RirBlock>),
+    /// registry of the file, from the version line
+    Header(InternetRegistry),
+}
+
+/// The local files the statistics are read from
+pub fn files(files: &[PathBuf]) -> Vec<(String, Option<PathBuf>)> {
+    files
+        .iter()
+        .map(|x| (x.display().to_string(), Some(x.clone())))
+        .collect()
+}
+
+/// Attaches the delegations of asns to the stored ases and replaces the stored RIR blocks with
+/// the ones from all the files. Returns the asns of the updated ases.
+pub async fn load(asdb: &impl Storage, files: &[PathBuf]) -> Result<Vec<u32>> {
+    let mut registries = vec![];
+    let mut asn_delegations = vec![];
+    let mut blocks = BTreeMap::new();
+    for file in files {
+        println!("reading rir statistics {}", file.display());
+        for record in read(file)? {
+            match record {
+                Record::Asns {
+                    first,
+                    count,
+                    delegation,
+                } => asn_delegations.push((first, count, delegation)),
+                Record::Blocks(x) => blocks.extend(x.into_iter().map(|b| (b.range, b))),
+                Record::Header(x) => {
+                    if !registries.contains(&x) {
+                        registries.push(x);
+                    }
+                }
+            }
+        }
+    }
+
+    println!(
+        "writing {} asn delegations to the database",
+        asn_delegations.len()
+    );
+    let mut asns: BTreeSet<u32> = asdb
+        .clear_rir_asn_delegations(&registries)
+        .await?
+        .into_iter()
+        .collect();
+    let bar = indicatif::ProgressBar::new(asn_delegations.len() as u64);
+    for (first, count, delegation) in &asn_delegations {
+        asns.extend(
+            asdb.insert_rir_asn_delegation(*first, *count, delegation)
+                .await?,
+        );
+        bar.inc(1);
+    }
+    bar.finish();
+    let blocks: Vec<RirBlock> = blocks.into_values().collect();
+    asdb.replace_rir_blocks(&blocks).await?;
+    println!("{} ases updated, {} ip blocks", asns.len(), blocks.len());
+    Ok(asns.into_iter().collect())
+}
+
+/// Reads the records of the file
+fn read(file: &Path) -> Result<Vec<Record>> {
+    let reader = BufReader::new(File::open(file)?);
+    let mut records = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let record = parse_line(&line?)
+            .map_err(|x| Error::InvalidLine(file.display().to_string(), i + 1, x))?;
+        records.extend(record);
+    }
+    Ok(records)
+}
+
+/// None for the summary lines, comments and available resources, the line itself if it's not a
+/// record
+fn parse_line(line: &str) -> std::result::Result<Option<Record>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let fields: Vec<&str> = line.split('|').collect();
+    // the version line starts with the version of the format, e.g. `2.3|ripencc|...`
+    if fields[0].parse::<f64>().is_ok() {
+        return match fields.get(1) {
+            Some(registry) => Ok(Some(Record::Header(InternetRegistry::from(*registry)))),
+            None => Err(line.to_string()),
+        };
+    }
+    if fields.get(5) == Some(&"summary") {
+        return Ok(None);
+    }
+    let [registry, country, kind, start, value, date, status, ..] = fields[..] else {
+        return Err(line.to_string());
+    };
+    if status == "available" {
+        return Ok(None);
+    }
+    let delegation = RirDelegation {
+        registry: InternetRegistry::from(registry),
+        country: Some(country)
+            .filter(|x| !x.is_empty() && *x != "ZZ")
+            .map(str::to_string),
+        date: chrono::NaiveDate::parse_from_str(date, "%Y%m%d")
+            .ok()
+            .map(|x| x.format("%Y-%m-%d").to_string()),
+        status: status.to_string(),
+        opaque_id: fields
+            .get(7)
+            .filter(|x| !x.is_empty())
+            .map(|x| x.to_string()),
+    };
+    let record = match kind {
+        "asn" => {
+            let (Ok(first), Ok(count)) = (start.parse(), value.parse()) else {
+                return Err(line.to_string());
+            };
+            Record::Asns {
+                first,
+                count,
+                delegation,
+            }
+        }
+        "ipv4" => {
+            let (Ok(start), Ok(count)) = (start.parse(), value.parse()) else {
+                return Err(line.to_string());
+            };
+            let ranges = ipv4_ranges(start, count).ok_or_else(|| line.to_string())?;
+            blocks(ranges, &delegation)
+        }
+        "ipv6" => {
+            let (Ok(start), Ok(prefix)) = (start.parse::<Ipv6Addr>(), value.parse()) else {
+                return Err(line.to_string());
+            };
+            let range = Ipv6Network::new(start, prefix)
+                .and_then(|x| Ipv6Network::new(x.network(), prefix))
+                .map_err(|_| line.to_string())?;
+            blocks(vec![IpNetwork::V6(range)], &delegation)
+        }
+        _ => return Err(line.to_string()),
+    };
+    Ok(Some(record))
+}
+
+fn blocks(ranges: Vec<IpNetwork>, delegation: &RirDelegation) -> Record {
+    Record::Blocks(
+        ranges
+            .into_iter()
+            .map(|range| RirBlock {
+                range,
+                delegation: delegation.clone(),
+            })
+            .collect(),
+    )
+}
+
+/// Smallest set of CIDR networks covering `count` addresses from `start`, None if they run past
+/// the end of the address space or there are none
+fn ipv4_ranges(start: Ipv4Addr, count: u64) -> Option<Vec<IpNetwork>> {
+    let mut start = u64::from(u32::from(start));
+    let end = start
+        .checked_add(count)
+        .filter(|x| count > 0 && *x <= 1 << 32)?;
+    let mut ranges = vec![];
+    while start < end {
+        // the largest block aligned at start which still fits
+        let mut size = if start == 0 {
+            1 << 32
+        } else {
+            1 << start.trailing_zeros()
+        };
+        while start + size > end {
+            size >>= 1;
+        }
+        let prefix = 32 - size.trailing_zeros() as u8;
+        let network = Ipv4Network::new(Ipv4Addr::from(start as u32), prefix).ok()?;
+        ranges.push(IpNetwork::V4(network));
+        start += size;
+    }
+    Some(ranges)
+}
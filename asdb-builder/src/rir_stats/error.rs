@@ -0,0 +1,11 @@
+pub type Result<T> = std::result::Result<T, Error>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("invalid record in {0} on line {1}: {2}")]
+    InvalidLine(String, usize, String),
+    #[error("io")]
+    Io(#[from] std::io::Error),
+    #[error("asdb internal problem")]
+    Asdb(#[from] asdb::Error),
+}
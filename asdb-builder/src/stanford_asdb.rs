@@ -7,7 +7,7 @@ use std::{
 };
 use trauma::{download::Download, downloader::DownloaderBuilder};
 
-use asdb::Asdb;
+use asdb::Storage;
 pub use error::{Error, Result};
 
 pub mod categories;
@@ -16,14 +16,21 @@ mod error;
 const LATEST_ASDB_CSV: &str = "https://asdb.stanford.edu/data/2023-05_categorized_ases.csv";
 const ASDB_DST_FILENAME: &str = "stanford-asdb.csv";
 
-pub async fn load(asdb: &Asdb) -> Result<()> {
+/// URL of the downloaded csv along with the path it's read from
+pub fn files() -> Vec<(String, Option<PathBuf>)> {
+    let path = ["inputs", ASDB_DST_FILENAME].iter().collect();
+    vec![(LATEST_ASDB_CSV.to_string(), Some(path))]
+}
+
+/// Returns asns which got categories
+pub async fn load(asdb: &impl Storage) -> Result<Vec<u32>> {
     download(&"inputs").await?;
-    write_to_db(
+    let asns = write_to_db(
         asdb,
         &["inputs", ASDB_DST_FILENAME].iter().collect::<PathBuf>(),
     )
     .await?;
-    Ok(())
+    Ok(asns)
 }
 
 /// Takes in path to a directory where the file will be saved
@@ -41,10 +48,11 @@ async fn download<T: AsRef<Path> + AsRef<OsStr>>(dest: &T) -> Result<()> {
     Ok(())
 }
 
-async fn write_to_db(asdb: &Asdb, csv: &impl AsRef<Path>) -> Result<()> {
+async fn write_to_db(asdb: &impl Storage, csv: &impl AsRef<Path>) -> Result<Vec<u32>> {
     println!("Writing stanford asdb categories to the database");
     let bar = indicatif::ProgressBar::new(BufReader::new(File::open(csv)?).lines().count() as u64);
     let mut rdr = csv::ReaderBuilder::new().flexible(true).from_path(csv)?;
+    let mut asns = vec![];
     for result in rdr.records() {
         let record = result?;
         let asn = record.get(0).unwrap();
@@ -70,8 +78,9 @@ async fn write_to_db(asdb: &Asdb, csv: &impl AsRef<Path>) -> Result<()> {
         asdb.insert_stanford_asdb_categories(asn, categories.as_slice())
             .await
             .unwrap();
+        asns.push(asn);
         bar.inc(1);
     }
     bar.finish();
-    Ok(())
+    Ok(asns)
 }
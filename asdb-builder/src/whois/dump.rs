@@ -0,0 +1,180 @@
+//! Reader for the RIPE database split dumps, e.g.
+//! https://ftp.ripe.net/ripe/dbase/split/ripe.db.aut-num.gz
+//!
+//! The dumps hold RPSL objects separated by empty lines, each attribute on a line of its own as
+//! `name: value`. Lines starting with a space, a tab or `+` continue the value of the previous
+//! attribute, `#` starts a comment and lines starting with `%` or `#` are comments as a whole.
+//! Objects are parsed into the same models the REST API responses are.
+
+use std::{collections::HashMap, io::BufRead};
+
+use super::models::*;
+use super::ripe::{contact_refs, parse_aut_num, parse_organisation, parse_person};
+
+/// Iterator over the RPSL objects read from a dump
+pub struct RpslReader<R> {
+    reader: R,
+    line: Vec<u8>,
+}
+
+impl<R: BufRead> RpslReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for RpslReader<R> {
+    type Item = std::io::Result<RipeObject>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut attributes: Vec<Attribute> = Vec::new();
+        loop {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            // older objects are in latin-1, they only lose their accents
+            let line = String::from_utf8_lossy(&self.line);
+            let line = line.trim_end();
+            if line.is_empty() {
+                if attributes.is_empty() {
+                    continue;
+                }
+                break;
+            }
+            if line.starts_with('%') || line.starts_with('#') {
+                continue;
+            }
+            let value = |x: &str| x.split('#').next().unwrap_or_default().trim().to_string();
+            if line.starts_with([' ', '\t', '+']) {
+                if let Some(last) = attributes.last_mut() {
+                    let continued = value(&line[1..]);
+                    if !continued.is_empty() {
+                        if !last.value.is_empty() {
+                            last.value.push(' ');
+                        }
+                        last.value.push_str(&continued);
+                    }
+                }
+                continue;
+            }
+            let Some((name, rest)) = line.split_once(':') else {
+                continue;
+            };
+            attributes.push(Attribute {
+                name: name.trim().to_ascii_lowercase(),
+                value: value(rest),
+                referenced_type: None,
+            });
+        }
+        let object_type = attributes.first()?.name.clone();
+        Some(Ok(RipeObject {
+            object_type,
+            attributes: Attributes {
+                attribute: attributes,
+            },
+        }))
+    }
+}
+
+/// Aut-nums, organisations and contacts collected from the dumps
+#[derive(Debug, Default)]
+pub struct RipeDump {
+    pub aut_nums: Vec<AutNum>,
+    pub organisations: HashMap<String, Organisation>,
+    pub persons: HashMap<String, Person>,
+}
+
+impl RipeDump {
+    /// Keeps the object if it's an aut-num, organisation, person or role
+    pub fn add(&mut self, obj: &RipeObject) {
+        let Some(key) = obj.get_attr(&obj.object_type) else {
+            return;
+        };
+        match obj.object_type.as_str() {
+            "aut-num" => {
+                if let Some(asn) = key.strip_prefix("AS").and_then(|x| x.parse().ok()) {
+                    self.aut_nums.push(parse_aut_num(obj, asn));
+                }
+            }
+            "organisation" => {
+                let org = parse_organisation(obj, key);
+                self.organisations.insert(key.to_string(), org);
+            }
+            "person" | "role" => {
+                if let Some(nic_hdl) = obj.get_attr("nic-hdl") {
+                    let person = parse_person(obj, nic_hdl);
+                    self.persons.insert(nic_hdl.to_string(), person);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// WHOIS data of the aut-num the way [`super::RipeClient::get_as_whois_data`] puts it
+    /// together, references missing from the dumps are skipped
+    pub fn as_whois_data(&self, aut_num: &AutNum) -> AsWhoisData {
+        let organisation = aut_num
+            .org
+            .as_ref()
+            .and_then(|x| self.organisations.get(x))
+            .cloned();
+        let contacts = contact_refs(aut_num, organisation.as_ref())
+            .into_iter()
+            .filter_map(|x| self.persons.get(x))
+            .cloned()
+            .collect();
+        AsWhoisData {
+            aut_num: aut_num.clone(),
+            organisation,
+            contacts,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn objects_are_split_on_empty_lines_and_continuations_joined() {
+        let dump = "\
+% This is the RIPE Database dump.
+
+aut-num:        AS5550
+as-name:        TASK-AS # academic network
+descr:          Gdansk
++
+                University of Technology
+org:            ORG-TUoG1-RIPE
+admin-c:        TASK1-RIPE
+source:         RIPE # Filtered
+
+role:           TASK NOC
+nic-hdl:        TASK1-RIPE
+";
+        let objects: Vec<RipeObject> = RpslReader::new(dump.as_bytes())
+            .collect::<std::io::Result<_>>()
+            .unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects[0].object_type, "aut-num");
+        assert_eq!(objects[0].get_attr("as-name"), Some("TASK-AS"));
+        assert_eq!(
+            objects[0].get_attr("descr"),
+            Some("Gdansk University of Technology")
+        );
+
+        let mut collected = RipeDump::default();
+        objects.iter().for_each(|x| collected.add(x));
+        let data = collected.as_whois_data(&collected.aut_nums[0]);
+        assert_eq!(data.aut_num.asn, 5550);
+        assert!(data.organisation.is_none());
+        assert_eq!(data.contacts.len(), 1);
+        assert_eq!(data.contacts[0].name, "TASK NOC");
+    }
+}
@@ -7,6 +7,8 @@ use std::fmt::Display;
 pub enum Error {
     /// HTTP request failed
     Request(reqwest::Error),
+    /// Failed to read a local file, e.g. the RDAP bootstrap registry
+    Io(std::io::Error),
     /// Failed to parse API response
     Parse(String),
     /// Object not found in WHOIS database
@@ -19,6 +21,7 @@ impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Error::Request(e) => write!(f, "WHOIS request error: {e}"),
+            Error::Io(e) => write!(f, "WHOIS file error: {e}"),
             Error::Parse(msg) => write!(f, "WHOIS parse error: {msg}"),
             Error::NotFound(obj) => write!(f, "WHOIS object not found: {obj}"),
             Error::RateLimited => write!(f, "WHOIS API rate limit exceeded"),
@@ -30,6 +33,7 @@ impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
             Error::Request(e) => Some(e),
+            Error::Io(e) => Some(e),
             _ => None,
         }
     }
@@ -41,5 +45,11 @@ impl From<reqwest::Error> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
 /// Result type for WHOIS operations.
 pub type Result<T> = std::result::Result<T, Error>;
@@ -1,12 +1,14 @@
 //! WHOIS API client for fetching AS, Organisation, and Person data.
 //!
 //! This module provides access to Regional Internet Registry (RIR) databases
-//! via REST APIs. Currently supports RIPE NCC (Europe, Middle East, Central Asia).
+//! via REST APIs. RIPE NCC (Europe, Middle East, Central Asia) is queried through its own
+//! REST API and its database can also be read in bulk from its split dumps, see [`dump`].
+//! All five RIRs are covered over RDAP, see [`rdap`].
 //!
 //! # Example
 //!
 //! ```ignore
-//! use asdb_builder::whois::RipeClient;
+//! use asdb_builder::whois::{Bootstrap, RdapClient, RipeClient};
 //!
 //! let client = RipeClient::new();
 //!
@@ -16,12 +18,20 @@
 //!
 //! // Get complete WHOIS data including org and contacts
 //! let data = client.get_as_whois_data(5550).await?;
+//!
+//! // Networks of the other RIRs, routed by the IANA bootstrap registry
+//! let rdap = RdapClient::new(Bootstrap::from_file("asn.json")?);
+//! let data = rdap.get_as_whois_data(13335).await?;
 //! ```
 
+pub mod dump;
 pub mod error;
 pub mod models;
+pub mod rdap;
 pub mod ripe;
 
+pub use dump::{RipeDump, RpslReader};
 pub use error::{Error, Result};
 pub use models::{AsWhoisData, AutNum, Organisation, Person};
+pub use rdap::{Bootstrap, RdapClient};
 pub use ripe::RipeClient;
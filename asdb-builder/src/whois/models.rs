@@ -88,6 +88,10 @@ pub struct Organisation {
     pub fax: Option<String>,
     /// Email address
     pub email: Option<String>,
+    /// Admin contact references
+    pub admin_c: Vec<String>,
+    /// Technical contact references
+    pub tech_c: Vec<String>,
     /// Abuse contact reference
     pub abuse_c: Option<String>,
 }
@@ -117,6 +121,45 @@ pub struct AsWhoisData {
     pub contacts: Vec<Person>,
 }
 
+impl From<AsWhoisData> for asdb_models::WhoIsAsn {
+    /// Stored form of the data, `fetched_at` is left for the caller to set
+    fn from(data: AsWhoisData) -> Self {
+        Self {
+            as_name: data.aut_num.as_name,
+            descr: data.aut_num.descr,
+            org_id: data.aut_num.org,
+            admin_c: data.aut_num.admin_c,
+            tech_c: data.aut_num.tech_c,
+            abuse_c: data.aut_num.abuse_c,
+            country: data.aut_num.country,
+            organisation: data.organisation.map(|o| asdb_models::WhoIsOrg {
+                org_id: o.org_id,
+                org_name: o.org_name,
+                org_type: o.org_type,
+                address: o.address,
+                country: o.country,
+                phone: o.phone,
+                email: o.email,
+                admin_c: o.admin_c,
+                tech_c: o.tech_c,
+                abuse_c: o.abuse_c,
+            }),
+            contacts: data
+                .contacts
+                .into_iter()
+                .map(|p| asdb_models::WhoIsPerson {
+                    nic_hdl: p.nic_hdl,
+                    name: p.name,
+                    address: p.address,
+                    phone: p.phone,
+                    email: p.email,
+                })
+                .collect(),
+            fetched_at: None,
+        }
+    }
+}
+
 impl RipeObject {
     /// Get the first value for an attribute by name.
     pub fn get_attr(&self, name: &str) -> Option<&str> {
@@ -0,0 +1,415 @@
+//! RDAP client for the autonomous system data of all five RIRs.
+//!
+//! The RDAP server of an ASN is looked up in the IANA bootstrap registry,
+//! https://data.iana.org/rdap/asn.json, read from a local copy. Entities of the autnum response
+//! are mapped by their roles: the registrant becomes the organisation and the administrative,
+//! technical and abuse entities the contacts, their vCards giving names, addresses, phones and
+//! emails. ARIN nests the contacts under the registrant, those become contacts of the
+//! organisation the same way as `admin-c` of a RIPE organisation.
+//! Protocol documentation: https://www.rfc-editor.org/rfc/rfc9083
+
+use std::{collections::BTreeMap, path::Path};
+
+use asdb_models::InternetRegistry;
+use reqwest::{Client, StatusCode, header::ACCEPT};
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::error::{Error, Result};
+use super::models::*;
+use super::ripe::contact_refs;
+
+const RDAP_CONTENT_TYPE: &str = "application/rdap+json";
+
+/// ASN ranges of the RDAP servers, from the IANA bootstrap registry
+#[derive(Debug, Clone, Default)]
+pub struct Bootstrap {
+    /// first and last asn of the range along with the base url of its server, sorted
+    ranges: Vec<(u32, u32, String)>,
+}
+
+/// `{"services": [[["1-1876", ...], ["https://rdap.arin.net/registry/", ...]], ...]}`
+#[derive(Debug, Deserialize)]
+struct BootstrapFile {
+    services: Vec<(Vec<String>, Vec<String>)>,
+}
+
+impl Bootstrap {
+    /// Reads the local copy of https://data.iana.org/rdap/asn.json
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        let file: BootstrapFile =
+            serde_json::from_str(json).map_err(|e| Error::Parse(e.to_string()))?;
+        let mut ranges = vec![];
+        for (asns, urls) in file.services {
+            // servers are listed with both schemes, https preferred
+            let Some(url) = urls
+                .iter()
+                .find(|x| x.starts_with("https://"))
+                .or(urls.first())
+            else {
+                continue;
+            };
+            let url = format!("{}/", url.trim_end_matches('/'));
+            for range in asns {
+                let (first, last) = range.split_once('-').unwrap_or((&range, &range));
+                let (Ok(first), Ok(last)) = (first.parse(), last.parse()) else {
+                    return Err(Error::Parse(format!("invalid asn range {range}")));
+                };
+                ranges.push((first, last, url.clone()));
+            }
+        }
+        ranges.sort();
+        Ok(Self { ranges })
+    }
+
+    /// Base url of the RDAP server responsible for the asn
+    pub fn server(&self, asn: u32) -> Option<&str> {
+        let i = self.ranges.partition_point(|(first, _, _)| *first <= asn);
+        let (_, last, url) = self.ranges.get(i.checked_sub(1)?)?;
+        (asn <= *last).then_some(url.as_str())
+    }
+
+    /// RIR running the RDAP server responsible for the asn, told by the host of the server
+    pub fn registry(&self, asn: u32) -> Option<InternetRegistry> {
+        let host = self.server(asn)?.split('/').nth(2)?;
+        ["ripe", "arin", "apnic", "lacnic", "afrinic"]
+            .into_iter()
+            .find(|x| host.split('.').any(|part| part == *x))
+            .map(InternetRegistry::from)
+    }
+}
+
+/// RDAP autnum object, only the members which end up in [`AsWhoisData`]
+#[derive(Debug, Deserialize)]
+struct RdapAutnum {
+    name: Option<String>,
+    country: Option<String>,
+    #[serde(default)]
+    remarks: Vec<Remark>,
+    #[serde(default)]
+    entities: Vec<Entity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Remark {
+    title: Option<String>,
+    #[serde(default)]
+    description: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Entity {
+    #[serde(default)]
+    handle: String,
+    #[serde(default)]
+    roles: Vec<String>,
+    #[serde(rename = "vcardArray")]
+    vcard_array: Option<Value>,
+    #[serde(default)]
+    entities: Vec<Entity>,
+}
+
+/// Contact details of a jCard (RFC 7095), `["vcard", [[name, params, type, value], ...]]`
+#[derive(Debug, Default)]
+struct VCard {
+    name: Option<String>,
+    kind: Option<String>,
+    address: Vec<String>,
+    phone: Option<String>,
+    fax: Option<String>,
+    email: Option<String>,
+}
+
+impl Entity {
+    fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|x| x.eq_ignore_ascii_case(role))
+    }
+
+    /// Handles of the child entities with the role
+    fn handles(entities: &[Entity], role: &str) -> Vec<String> {
+        entities
+            .iter()
+            .filter(|x| x.has_role(role) && !x.handle.is_empty())
+            .map(|x| x.handle.clone())
+            .collect()
+    }
+
+    fn vcard(&self) -> VCard {
+        let Some(properties) = self
+            .vcard_array
+            .as_ref()
+            .and_then(|x| x.get(1))
+            .and_then(Value::as_array)
+        else {
+            return VCard::default();
+        };
+        let mut vcard = VCard::default();
+        for property in properties {
+            let (Some(name), Some(params), Some(value)) =
+                (property.get(0), property.get(1), property.get(3))
+            else {
+                continue;
+            };
+            let types = params.get("type").map(strings).unwrap_or_default();
+            match name.as_str().unwrap_or_default() {
+                "fn" => vcard.name = value.as_str().map(String::from),
+                "kind" => vcard.kind = value.as_str().map(String::from),
+                "adr" => {
+                    vcard.address = match params.get("label").and_then(Value::as_str) {
+                        Some(label) => label.lines().map(str::trim).map(String::from).collect(),
+                        None => strings(value),
+                    };
+                    vcard.address.retain(|x| !x.is_empty());
+                }
+                "tel" => {
+                    let number = value
+                        .as_str()
+                        .map(|x| x.trim_start_matches("tel:").to_string());
+                    if types.iter().any(|x| x == "fax") {
+                        vcard.fax = vcard.fax.or(number);
+                    } else {
+                        vcard.phone = vcard.phone.or(number);
+                    }
+                }
+                "email" => vcard.email = vcard.email.or(value.as_str().map(String::from)),
+                _ => {}
+            }
+        }
+        vcard
+    }
+
+    fn person(&self) -> Person {
+        let vcard = self.vcard();
+        Person {
+            nic_hdl: self.handle.clone(),
+            name: vcard.name.unwrap_or_default(),
+            address: vcard.address,
+            phone: vcard.phone,
+            fax: vcard.fax,
+            email: vcard.email,
+        }
+    }
+}
+
+/// Strings of a jCard value, structured values are flattened
+fn strings(value: &Value) -> Vec<String> {
+    match value {
+        Value::String(x) => vec![x.clone()],
+        Value::Array(x) => x.iter().flat_map(strings).collect(),
+        _ => vec![],
+    }
+}
+
+/// Client for the RDAP servers of the RIRs.
+#[derive(Clone)]
+pub struct RdapClient {
+    client: Client,
+    bootstrap: Bootstrap,
+}
+
+impl std::fmt::Debug for RdapClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RdapClient").finish()
+    }
+}
+
+impl RdapClient {
+    /// Creates a new RDAP client routing the requests by the bootstrap registry.
+    pub fn new(bootstrap: Bootstrap) -> Self {
+        Self::with_client(Client::new(), bootstrap)
+    }
+
+    /// Creates a new RDAP client with a custom reqwest client.
+    pub fn with_client(client: Client, bootstrap: Bootstrap) -> Self {
+        Self { client, bootstrap }
+    }
+
+    /// RIR responsible for the asn according to the bootstrap registry
+    pub fn registry(&self, asn: u32) -> Option<InternetRegistry> {
+        self.bootstrap.registry(asn)
+    }
+
+    /// Fetches the autnum object from the RDAP server of the asn and maps it to complete WHOIS
+    /// data including organisation and contacts.
+    ///
+    /// # Arguments
+    /// * `asn` - AS number (without "AS" prefix)
+    pub async fn get_as_whois_data(&self, asn: u32) -> Result<AsWhoisData> {
+        let server = self
+            .bootstrap
+            .server(asn)
+            .ok_or_else(|| Error::NotFound(format!("AS{asn}")))?;
+        let response = self
+            .client
+            .get(format!("{server}autnum/{asn}"))
+            .header(ACCEPT, RDAP_CONTENT_TYPE)
+            .send()
+            .await?;
+        match response.status() {
+            StatusCode::NOT_FOUND => return Err(Error::NotFound(format!("AS{asn}"))),
+            StatusCode::TOO_MANY_REQUESTS => return Err(Error::RateLimited),
+            _ => {}
+        }
+        let autnum: RdapAutnum = response.error_for_status()?.json().await?;
+        Ok(as_whois_data(autnum, asn))
+    }
+}
+
+/// WHOIS data of the autnum the way [`super::RipeClient::get_as_whois_data`] puts it together
+fn as_whois_data(autnum: RdapAutnum, asn: u32) -> AsWhoisData {
+    let registrant = autnum.entities.iter().find(|x| x.has_role("registrant"));
+    let organisation = registrant.map(|x| {
+        let vcard = x.vcard();
+        Organisation {
+            org_id: x.handle.clone(),
+            org_name: vcard.name.unwrap_or_default(),
+            org_type: vcard.kind,
+            address: vcard.address,
+            country: autnum.country.clone(),
+            phone: vcard.phone,
+            fax: vcard.fax,
+            email: vcard.email,
+            admin_c: Entity::handles(&x.entities, "administrative"),
+            tech_c: Entity::handles(&x.entities, "technical"),
+            abuse_c: Entity::handles(&x.entities, "abuse").into_iter().next(),
+        }
+    });
+    let aut_num = AutNum {
+        asn,
+        as_name: autnum.name,
+        descr: autnum
+            .remarks
+            .into_iter()
+            .filter(|x| {
+                x.title
+                    .as_ref()
+                    .is_none_or(|t| t.eq_ignore_ascii_case("description"))
+            })
+            .flat_map(|x| x.description)
+            .collect(),
+        org: registrant.map(|x| x.handle.clone()),
+        admin_c: Entity::handles(&autnum.entities, "administrative"),
+        tech_c: Entity::handles(&autnum.entities, "technical"),
+        abuse_c: Entity::handles(&autnum.entities, "abuse")
+            .into_iter()
+            .next(),
+        country: autnum.country,
+    };
+
+    // contacts of the autnum and of the registrant, each entity comes with its vCard
+    let mut persons = BTreeMap::new();
+    let nested = registrant
+        .map(|x| x.entities.as_slice())
+        .unwrap_or_default();
+    for entity in autnum.entities.iter().chain(nested) {
+        if !entity.has_role("registrant") && !entity.handle.is_empty() {
+            persons
+                .entry(entity.handle.as_str())
+                .or_insert_with(|| entity.person());
+        }
+    }
+    let contacts = contact_refs(&aut_num, organisation.as_ref())
+        .into_iter()
+        .filter_map(|x| persons.get(x))
+        .cloned()
+        .collect();
+    AsWhoisData {
+        aut_num,
+        organisation,
+        contacts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOOTSTRAP: &str = r#"{
+        "description": "RDAP bootstrap file for Autonomous System Number allocations",
+        "publication": "2024-01-01T00:00:00Z",
+        "services": [
+            [["1-1876", "1902-2042"], ["https://rdap.arin.net/registry/", "http://rdap.arin.net/registry/"]],
+            [["1877-1901", "5377-6455"], ["https://rdap.db.ripe.net/"]],
+            [["2043"], ["https://rdap.db.ripe.net/"]],
+            [["27648-28671"], ["https://rdap.lacnic.net/rdap"]]
+        ],
+        "version": "1.0"
+    }"#;
+
+    #[test]
+    fn asns_are_routed_by_the_bootstrap_ranges() {
+        let bootstrap = Bootstrap::from_json(BOOTSTRAP).unwrap();
+        assert_eq!(bootstrap.server(1), Some("https://rdap.arin.net/registry/"));
+        assert_eq!(bootstrap.server(2043), Some("https://rdap.db.ripe.net/"));
+        assert_eq!(
+            bootstrap.server(28000),
+            Some("https://rdap.lacnic.net/rdap/")
+        );
+        assert_eq!(bootstrap.server(2044), None);
+        assert_eq!(bootstrap.server(0), None);
+        assert_eq!(bootstrap.registry(1900), Some(InternetRegistry::RIPE));
+        assert_eq!(bootstrap.registry(1950), Some(InternetRegistry::ARIN));
+        assert_eq!(bootstrap.registry(28000), Some(InternetRegistry::LACNIC));
+    }
+
+    #[test]
+    fn entities_and_vcards_are_mapped() {
+        let response = r#"{
+            "objectClassName": "autnum",
+            "handle": "AS13335",
+            "startAutnum": 13335,
+            "endAutnum": 13335,
+            "name": "CLOUDFLARENET",
+            "remarks": [
+                {"title": "Registration Comments", "description": ["All Cloudflare abuse reporting"]},
+                {"description": ["Cloudflare, Inc."]}
+            ],
+            "entities": [{
+                "handle": "CLOUD14",
+                "roles": ["registrant"],
+                "vcardArray": ["vcard", [
+                    ["version", {}, "text", "4.0"],
+                    ["fn", {}, "text", "Cloudflare, Inc."],
+                    ["adr", {"label": "101 Townsend Street\nSan Francisco\nCA\n94107\nUnited States"}, "text", ["", "", "", "", "", "", ""]],
+                    ["kind", {}, "text", "org"]
+                ]],
+                "entities": [{
+                    "handle": "ADMIN2521-ARIN",
+                    "roles": ["administrative", "technical"],
+                    "vcardArray": ["vcard", [
+                        ["fn", {}, "text", "Admin"],
+                        ["adr", {}, "text", ["", "", "101 Townsend Street", "San Francisco", "CA", "94107", "United States"]],
+                        ["tel", {"type": ["work", "voice"]}, "uri", "tel:+1-650-319-8930"],
+                        ["tel", {"type": ["work", "fax"]}, "text", "+1-650-319-8931"],
+                        ["email", {}, "text", "rir@cloudflare.com"]
+                    ]]
+                }, {
+                    "handle": "ABUSE2916-ARIN",
+                    "roles": ["abuse"],
+                    "vcardArray": ["vcard", [["fn", {}, "text", "Abuse"]]]
+                }]
+            }]
+        }"#;
+        let data = as_whois_data(serde_json::from_str(response).unwrap(), 13335);
+
+        assert_eq!(data.aut_num.as_name.as_deref(), Some("CLOUDFLARENET"));
+        assert_eq!(data.aut_num.descr, vec!["Cloudflare, Inc."]);
+        assert_eq!(data.aut_num.org.as_deref(), Some("CLOUD14"));
+        let org = data.organisation.unwrap();
+        assert_eq!(org.org_name, "Cloudflare, Inc.");
+        assert_eq!(org.address.len(), 5);
+        assert_eq!(org.admin_c, vec!["ADMIN2521-ARIN"]);
+        assert_eq!(org.abuse_c.as_deref(), Some("ABUSE2916-ARIN"));
+        assert_eq!(data.contacts.len(), 2);
+        let admin = &data.contacts[1];
+        assert_eq!(admin.nic_hdl, "ADMIN2521-ARIN");
+        assert_eq!(admin.address[0], "101 Townsend Street");
+        assert_eq!(admin.phone.as_deref(), Some("+1-650-319-8930"));
+        assert_eq!(admin.fax.as_deref(), Some("+1-650-319-8931"));
+        assert_eq!(admin.email.as_deref(), Some("rir@cloudflare.com"));
+    }
+}
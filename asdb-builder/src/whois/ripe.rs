@@ -66,7 +66,7 @@ impl RipeClient {
             .find(|o| o.object_type == "aut-num")
             .ok_or_else(|| Error::NotFound(format!("AS{asn}")))?;
 
-        Ok(self.parse_aut_num(&obj, asn))
+        Ok(parse_aut_num(&obj, asn))
     }
 
     /// Fetches Organisation information by org ID.
@@ -93,7 +93,7 @@ impl RipeClient {
             .find(|o| o.object_type == "organisation")
             .ok_or_else(|| Error::NotFound(org_id.to_string()))?;
 
-        Ok(self.parse_organisation(&obj, org_id))
+        Ok(parse_organisation(&obj, org_id))
     }
 
     /// Fetches Person or Role information by NIC handle.
@@ -120,10 +120,10 @@ impl RipeClient {
             .find(|o| o.object_type == "person" || o.object_type == "role")
             .ok_or_else(|| Error::NotFound(nic_hdl.to_string()))?;
 
-        Ok(self.parse_person(&obj, nic_hdl))
+        Ok(parse_person(&obj, nic_hdl))
     }
 
-    /// Fetches complete WHOIS data for an AS including organisation and contacts.
+    /// Fetches complete WHOIS data for an AS including organisation and contacts of both.
     ///
     /// # Arguments
     /// * `asn` - AS number (without "AS" prefix)
@@ -137,19 +137,9 @@ impl RipeClient {
             None
         };
 
-        // Collect unique contact references
-        let mut contact_refs: Vec<&str> = Vec::new();
-        contact_refs.extend(aut_num.admin_c.iter().map(|s| s.as_str()));
-        contact_refs.extend(aut_num.tech_c.iter().map(|s| s.as_str()));
-        if let Some(ref abuse) = aut_num.abuse_c {
-            contact_refs.push(abuse);
-        }
-        contact_refs.sort();
-        contact_refs.dedup();
-
         // Fetch contact details (ignore errors for individual contacts)
         let mut contacts = Vec::new();
-        for nic_hdl in contact_refs {
+        for nic_hdl in contact_refs(&aut_num, organisation.as_ref()) {
             if let Ok(person) = self.get_person(nic_hdl).await {
                 contacts.push(person);
             }
@@ -172,55 +162,108 @@ impl RipeClient {
         }
         Ok(())
     }
+}
 
-    fn parse_aut_num(&self, obj: &RipeObject, asn: u32) -> AutNum {
-        AutNum {
-            asn,
-            as_name: obj.get_attr("as-name").map(String::from),
-            descr: obj.get_attrs("descr").into_iter().map(String::from).collect(),
-            org: obj.get_attr("org").map(String::from),
-            admin_c: obj.get_attrs("admin-c").into_iter().map(String::from).collect(),
-            tech_c: obj.get_attrs("tech-c").into_iter().map(String::from).collect(),
-            abuse_c: obj.get_attr("abuse-c").map(String::from),
-            country: obj.get_attr("country").map(String::from),
-        }
+impl Default for RipeClient {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    fn parse_organisation(&self, obj: &RipeObject, org_id: &str) -> Organisation {
-        Organisation {
-            org_id: org_id.to_string(),
-            org_name: obj.get_attr("org-name").unwrap_or("").to_string(),
-            org_type: obj.get_attr("org-type").map(String::from),
-            address: obj.get_attrs("address").into_iter().map(String::from).collect(),
-            country: obj.get_attr("country").map(String::from),
-            phone: obj.get_attr("phone").map(String::from),
-            fax: obj.get_attr("fax-no").map(String::from),
-            email: obj.get_attr("e-mail").map(String::from),
-            abuse_c: obj.get_attr("abuse-c").map(String::from),
+/// Unique NIC handles the aut-num and its organisation reference as contacts, sorted
+pub(super) fn contact_refs<'a>(
+    aut_num: &'a AutNum,
+    organisation: Option<&'a Organisation>,
+) -> Vec<&'a str> {
+    let mut contact_refs: Vec<&str> = Vec::new();
+    contact_refs.extend(aut_num.admin_c.iter().map(|s| s.as_str()));
+    contact_refs.extend(aut_num.tech_c.iter().map(|s| s.as_str()));
+    if let Some(ref abuse) = aut_num.abuse_c {
+        contact_refs.push(abuse);
+    }
+    if let Some(org) = organisation {
+        contact_refs.extend(org.admin_c.iter().map(|s| s.as_str()));
+        contact_refs.extend(org.tech_c.iter().map(|s| s.as_str()));
+        if let Some(ref abuse) = org.abuse_c {
+            contact_refs.push(abuse);
         }
     }
+    contact_refs.sort();
+    contact_refs.dedup();
+    contact_refs
+}
 
-    fn parse_person(&self, obj: &RipeObject, nic_hdl: &str) -> Person {
-        let name = obj
-            .get_attr("person")
-            .or_else(|| obj.get_attr("role"))
-            .unwrap_or("")
-            .to_string();
-
-        Person {
-            nic_hdl: nic_hdl.to_string(),
-            name,
-            address: obj.get_attrs("address").into_iter().map(String::from).collect(),
-            phone: obj.get_attr("phone").map(String::from),
-            fax: obj.get_attr("fax-no").map(String::from),
-            email: obj.get_attr("e-mail").map(String::from),
-        }
+pub(super) fn parse_aut_num(obj: &RipeObject, asn: u32) -> AutNum {
+    AutNum {
+        asn,
+        as_name: obj.get_attr("as-name").map(String::from),
+        descr: obj
+            .get_attrs("descr")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        org: obj.get_attr("org").map(String::from),
+        admin_c: obj
+            .get_attrs("admin-c")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        tech_c: obj
+            .get_attrs("tech-c")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        abuse_c: obj.get_attr("abuse-c").map(String::from),
+        country: obj.get_attr("country").map(String::from),
     }
 }
 
-impl Default for RipeClient {
-    fn default() -> Self {
-        Self::new()
+pub(super) fn parse_organisation(obj: &RipeObject, org_id: &str) -> Organisation {
+    Organisation {
+        org_id: org_id.to_string(),
+        org_name: obj.get_attr("org-name").unwrap_or("").to_string(),
+        org_type: obj.get_attr("org-type").map(String::from),
+        address: obj
+            .get_attrs("address")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        country: obj.get_attr("country").map(String::from),
+        phone: obj.get_attr("phone").map(String::from),
+        fax: obj.get_attr("fax-no").map(String::from),
+        email: obj.get_attr("e-mail").map(String::from),
+        admin_c: obj
+            .get_attrs("admin-c")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        tech_c: obj
+            .get_attrs("tech-c")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        abuse_c: obj.get_attr("abuse-c").map(String::from),
+    }
+}
+
+pub(super) fn parse_person(obj: &RipeObject, nic_hdl: &str) -> Person {
+    let name = obj
+        .get_attr("person")
+        .or_else(|| obj.get_attr("role"))
+        .unwrap_or("")
+        .to_string();
+
+    Person {
+        nic_hdl: nic_hdl.to_string(),
+        name,
+        address: obj
+            .get_attrs("address")
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        phone: obj.get_attr("phone").map(String::from),
+        fax: obj.get_attr("fax-no").map(String::from),
+        email: obj.get_attr("e-mail").map(String::from),
     }
 }
 
@@ -261,7 +304,11 @@ mod tests {
         let client = RipeClient::new();
         let result = client.get_as_whois_data(TEST_ASN).await;
 
-        assert!(result.is_ok(), "Failed to get WHOIS data: {:?}", result.err());
+        assert!(
+            result.is_ok(),
+            "Failed to get WHOIS data: {:?}",
+            result.err()
+        );
         let data = result.unwrap();
         println!("AS{}: {:?}", data.aut_num.asn, data.aut_num.as_name);
         if let Some(ref org) = data.organisation {
@@ -12,6 +12,13 @@ pub struct As {
     pub whois_data: Option<WhoIsAsn>,
     pub stanford_asdb: Vec<StanfordASdbCategory>,
     pub user_data: Option<UserData>,
+    /// ASRank coordinates as a GeoJSON point, kept in sync by the storage for geo queries
+    #[serde(default)]
+    pub location: Option<GeoPoint>,
+    #[serde(default)]
+    pub peeringdb_data: Option<PeeringdbNet>,
+    #[serde(default)]
+    pub rir_data: Option<RirDelegation>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
@@ -21,6 +28,95 @@ pub struct UserData {
     pub comment: Option<String>,
     #[serde(default)]
     pub geocoded_addresses: Vec<GeocodedAddress>,
+    /// values of the fields defined in [`AnnotationField`]s, one per field
+    #[serde(default)]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub value: AnnotationValue,
+}
+
+/// Value of an annotation, enum options are kept as text and dates as `YYYY-MM-DD`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AnnotationValue {
+    Text(String),
+    Number(f64),
+    Date(String),
+}
+
+/// Values an annotation field takes
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AnnotationKind {
+    /// any string
+    Text,
+    Number,
+    /// one of the options
+    Enum(Vec<String>),
+    /// `YYYY-MM-DD`
+    Date,
+}
+
+/// Annotation users can set on ases, kept in the `annotation_fields` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnnotationField {
+    pub name: String,
+    pub kind: AnnotationKind,
+    pub description: Option<String>,
+}
+
+/// Condition on the annotation of an AS, comparisons only match values of the same type
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AnnotationOp {
+    Exists,
+    Missing,
+    Eq(AnnotationValue),
+    /// set to a different value
+    Ne(AnnotationValue),
+    Lt(AnnotationValue),
+    Lte(AnnotationValue),
+    Gt(AnnotationValue),
+    Gte(AnnotationValue),
+    /// text containing the string, ignoring case
+    Contains(String),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AnnotationFilter {
+    pub name: String,
+    pub op: AnnotationOp,
+}
+
+/// List of ases made by users, its members have the name in [`UserData::lists`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AsList {
+    pub name: String,
+    pub description: Option<String>,
+    /// css color the list is shown with
+    pub color: Option<String>,
+}
+
+/// List along with the number of its members
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct ListSummary {
+    pub list: AsList,
+    pub count: u64,
+}
+
+/// Change of the user data of an AS, kept in the `user_data_edits` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct UserDataEdit {
+    /// asn followed by the time of the edit in unix nanoseconds
+    pub id: String,
+    pub asn: u32,
+    /// RFC 3339 timestamp
+    pub at: String,
+    /// address of the client which made the edit
+    pub editor: Option<String>,
+    pub old: UserData,
+    pub new: UserData,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
@@ -39,46 +135,266 @@ pub struct GeocodedAddress {
 // Based on ipnetdb data? or merge ipnetdb with asrank?
 // details from whois, currently only for RIPE
 // TODO this too was supposed to not be attached to 1 data source. Needed?
-//#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-//pub struct Organization {
-//    pub name: String,
-//    pub registry: Nic,
-//    pub whois: Option<WhoIsOrg>,
-//    pub georesolved: Option<Coord>,
-//}
-
-// TODO is it still needed? The idea was to make generalized prefix struct not attached to single
-// data source
-//#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-//pub struct Prefix {
-//    pub cidr: IpNetwork,
-//    pub registry: Nic,
-//    pub whois: Option<WhoIsPrefix>,
-//    pub ipnetdb_data: Option<IPNetDBPrefix>,
-//}
-
-/// Person data is available only in whois data from registries so there is no
-/// optional data for it.
-/// currently available only for RIPE
-// TODO uncomment when ready to use
-//#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
-//pub struct Person {
-//    pub person: String,
-//    pub address: String,
-//    pub phone: String,
-//    pub changed: String,
-//    pub source: Nic,
-//    pub nic_hdl: Option<String>,
-//    pub remarks: Option<String>,
-//    pub mnt_by: Option<String>,
-//    pub email: Option<String>,
-//    pub georesolved: Option<Coord>,
-//}
+/// Organisation stored in the `organisations` collection. Merges the ASRank organisation
+/// with the WHOIS `organisation` object of its ases, every AS belongs to at most one.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Organisation {
+    /// ASRank org id when known, WHOIS org id otherwise
+    pub org_id: String,
+    /// ASRank org name, WHOIS org name if the organisation is not known to ASRank
+    pub name: String,
+    pub asrank_org_id: Option<String>,
+    /// e.g. "ORG-TUoG1-RIPE"
+    pub whois_org_id: Option<String>,
+    pub whois: Option<WhoIsOrg>,
+    pub asns: Vec<Asn>,
+}
+
+/// Prefix stored on its own in the `prefixes` collection, merged from all the ases announcing it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Prefix {
+    /// canonical network, host bits are always zeroed
+    pub range: IpNetwork,
+    /// asns originating the prefix, more than one for MOAS prefixes
+    pub origins: Vec<Asn>,
+    pub details: Option<IPNetDBPrefixDetails>,
+}
+
+/// Result of the longest prefix match for a single ip address
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IpLookup {
+    /// most specific prefix covering the address
+    pub prefix: Prefix,
+    /// ases originating the prefix which are present in the database
+    pub ases: Vec<As>,
+}
+
+/// Type of a link between two ases as inferred by CAIDA
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Relationship {
+    ProviderCustomer,
+    PeerPeer,
+}
+
+/// Link between two ases stored in the `relationships` collection, `a` is the provider of `b`
+/// for provider-customer links
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AsRelationship {
+    pub a: Asn,
+    pub b: Asn,
+    pub kind: Relationship,
+    /// how the link was inferred according to as-rel2 files, e.g. "bgp" or "mlp"
+    pub source: Option<String>,
+}
+
+/// Role a neighbor plays for the AS it's linked to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NeighborKind {
+    Provider,
+    Customer,
+    Peer,
+}
+
+impl std::str::FromStr for NeighborKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "provider" | "providers" => Ok(Self::Provider),
+            "customer" | "customers" => Ok(Self::Customer),
+            "peer" | "peers" => Ok(Self::Peer),
+            _ => Err(format!(
+                "unknown neighbor kind {s}, expected one of provider, customer, peer"
+            )),
+        }
+    }
+}
+
+/// AS linked to another one
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AsNeighbor {
+    pub asn: Asn,
+    pub kind: NeighborKind,
+}
+
+/// Copy of an AS record taken right after the import run `run_id` touched it, stored in the
+/// `snapshots` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct AsSnapshot {
+    pub run_id: String,
+    pub asn: Asn,
+    pub data: As,
+}
+
+/// Record of a single import run of a data source, stored in the `datasets` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Dataset {
+    /// the same id the imported ases got snapshotted under
+    pub run_id: String,
+    /// name of the data source, e.g. "asrank"
+    pub source: String,
+    pub files: Vec<DatasetFile>,
+    /// RFC 3339 time the run started at
+    pub started_at: String,
+    /// RFC 3339 time the run ended at, None while it's running or if it crashed
+    pub finished_at: Option<String>,
+    /// count of records imported
+    pub records: u64,
+    pub errors: Vec<String>,
+}
+
+/// Input of an import run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct DatasetFile {
+    /// URL the data was downloaded from or the path of a local file
+    pub source: String,
+    /// path of the file read, None when the data came straight from an API
+    pub path: Option<String>,
+    /// hex encoded SHA-256 of the file
+    pub sha256: Option<String>,
+}
+
+/// Single field which differs between two snapshots of an AS
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FieldChange {
+    /// dotted path of the field, e.g. "asrank_data.degree.peer"
+    pub path: String,
+    /// value rendered as JSON, None when the field is missing
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Source of data kept in its own part of [`As`], which re-imports replace as a whole.
+/// WHOIS data isn't one of them as its contacts are stored separately.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsSource {
+    Asrank,
+    Ipnetdb,
+    StanfordAsdb,
+}
+
+impl AsSource {
+    /// Fields of [`As`] owned by the source
+    pub fn fields(&self) -> &'static [&'static str] {
+        match self {
+            Self::Asrank => &["asrank_data", "location"],
+            Self::Ipnetdb => &["ipnetdb_data"],
+            Self::StanfordAsdb => &["stanford_asdb"],
+        }
+    }
+}
+
+/// Counts of records written by an upsert of one source's data
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UpsertStats {
+    /// ases which were not stored before
+    pub inserted: u64,
+    /// stored ases whose data of the source changed
+    pub updated: u64,
+    /// stored ases which already had the same data
+    pub unchanged: u64,
+}
+
+impl Display for UpsertStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "inserted: {}, updated: {}, unchanged: {}",
+            self.inserted, self.updated, self.unchanged
+        )
+    }
+}
+
+/// Person or role stored once in the `persons` collection, keyed by its NIC handle.
+/// Person data is available only in whois data from registries, currently only for RIPE.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Person {
+    /// e.g. "JD1234-RIPE"
+    pub nic_hdl: String,
+    pub contact: WhoIsPerson,
+    /// ases referencing the handle
+    pub ases: ContactRefs<Asn>,
+    /// WHOIS organisations referencing the handle, by WHOIS org id
+    pub orgs: ContactRefs<String>,
+}
+
+impl Person {
+    /// Whether nothing references the handle anymore
+    pub fn is_unused(&self) -> bool {
+        self.ases.is_empty() && self.orgs.is_empty()
+    }
+}
+
+/// Objects referencing a contact, grouped by the attribute they use for it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContactRefs<T> {
+    pub admin_c: Vec<T>,
+    pub tech_c: Vec<T>,
+    pub abuse_c: Vec<T>,
+}
+
+// derive would require T: Default
+impl<T> Default for ContactRefs<T> {
+    fn default() -> Self {
+        Self {
+            admin_c: vec![],
+            tech_c: vec![],
+            abuse_c: vec![],
+        }
+    }
+}
+
+impl<T: PartialEq + Ord> ContactRefs<T> {
+    pub fn is_empty(&self) -> bool {
+        self.admin_c.is_empty() && self.tech_c.is_empty() && self.abuse_c.is_empty()
+    }
+
+    /// All referencing objects, sorted and without duplicates
+    pub fn all(&self) -> Vec<&T> {
+        let mut all: Vec<_> = self
+            .admin_c
+            .iter()
+            .chain(&self.tech_c)
+            .chain(&self.abuse_c)
+            .collect();
+        all.sort();
+        all.dedup();
+        all
+    }
+
+    pub fn remove(&mut self, x: &T) {
+        for refs in [&mut self.admin_c, &mut self.tech_c, &mut self.abuse_c] {
+            refs.retain(|r| r != x);
+        }
+    }
+
+    pub fn add(&mut self, role: ContactRole, x: T) {
+        let refs = match role {
+            ContactRole::AdminC => &mut self.admin_c,
+            ContactRole::TechC => &mut self.tech_c,
+            ContactRole::AbuseC => &mut self.abuse_c,
+        };
+        if !refs.contains(&x) {
+            refs.push(x);
+            refs.sort();
+        }
+    }
+}
+
+/// Attribute through which an aut-num or organisation references a contact
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContactRole {
+    AdminC,
+    TechC,
+    AbuseC,
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct AsrankAsn {
     pub rank: u32,
     pub organization: Option<String>,
+    #[serde(default)]
+    pub organization_id: Option<String>,
     pub country_iso: String,
     pub country_name: String,
     pub coordinates: Coord,
@@ -152,6 +468,96 @@ pub struct IPNetDBPrefixDetails {
     pub prefix_registry: String,
 }
 
+/// Network of the AS registered in PeeringDB
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PeeringdbNet {
+    /// PeeringDB net id
+    pub id: u32,
+    pub name: String,
+    /// general peering policy, e.g. "Open", "Selective", "Restrictive" or "No"
+    pub policy_general: Option<String>,
+    /// e.g. "100-200Gbps"
+    pub info_traffic: Option<String>,
+    /// e.g. "NSP", "Content" or "Cable/DSL/ISP"
+    pub info_type: Option<String>,
+    pub ixps: Vec<IxpPresence>,
+    pub facilities: Vec<FacilityPresence>,
+}
+
+/// Port of a network at an IXP
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IxpPresence {
+    /// PeeringDB ix id, see [`Ixp`]
+    pub ix_id: u32,
+    pub name: String,
+    /// in Mbit/s
+    pub speed: u32,
+    pub ipaddr4: Option<String>,
+    pub ipaddr6: Option<String>,
+    /// peers with the route servers of the IXP
+    pub is_rs_peer: bool,
+}
+
+/// Facility a network is present at
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FacilityPresence {
+    /// PeeringDB fac id, see [`Facility`]
+    pub fac_id: u32,
+    pub name: String,
+}
+
+/// Record of a resource in the delegated-extended statistics file of its RIR
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RirDelegation {
+    pub registry: InternetRegistry,
+    /// 2 letter country code, None for reserved resources
+    pub country: Option<String>,
+    /// date of the allocation or assignment as `YYYY-MM-DD`, None if the RIR doesn't know it
+    pub date: Option<String>,
+    /// e.g. "allocated", "assigned" or "reserved"
+    pub status: String,
+    /// id of the holder in the RIR, the same for all resources of one holder
+    pub opaque_id: Option<String>,
+}
+
+/// IPv4 or IPv6 block delegated by an RIR, stored in the `rir_blocks` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct RirBlock {
+    /// canonical network, IPv4 delegations not aligned to a CIDR are split into several blocks
+    pub range: IpNetwork,
+    pub delegation: RirDelegation,
+}
+
+/// Internet exchange point from PeeringDB, stored in the `ixps` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Ixp {
+    /// PeeringDB ix id
+    pub id: u32,
+    pub name: String,
+    pub name_long: Option<String>,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub website: Option<String>,
+    /// center of the facilities the IXP is at, None if none of them has coordinates
+    pub coordinates: Option<Coord>,
+    /// count of networks connected to the IXP
+    pub networks: u32,
+}
+
+/// Colocation facility from PeeringDB, stored in the `facilities` collection
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Facility {
+    /// PeeringDB fac id
+    pub id: u32,
+    pub name: String,
+    pub city: Option<String>,
+    pub country: Option<String>,
+    pub website: Option<String>,
+    pub coordinates: Option<Coord>,
+    /// count of networks present at the facility
+    pub networks: u32,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct WhoIsPrefix {
     pub netname: String,
@@ -182,12 +588,39 @@ pub struct WhoIsAsn {
     pub country: Option<String>,
     /// Organisation details (fetched separately)
     pub organisation: Option<WhoIsOrg>,
-    /// Contact persons/roles
+    /// Contact persons/roles of the aut-num and its organisation. Stored in the `persons`
+    /// collection rather than with the AS.
+    #[serde(default)]
     pub contacts: Vec<WhoIsPerson>,
     /// When this data was fetched
     pub fetched_at: Option<String>,
 }
 
+impl WhoIsAsn {
+    /// NIC handles referenced by the aut-num along with the attribute referencing them
+    pub fn contact_handles(&self) -> Vec<(ContactRole, &str)> {
+        contact_handles(&self.admin_c, &self.tech_c, self.abuse_c.as_deref())
+    }
+}
+
+impl WhoIsOrg {
+    /// NIC handles referenced by the organisation along with the attribute referencing them
+    pub fn contact_handles(&self) -> Vec<(ContactRole, &str)> {
+        contact_handles(&self.admin_c, &self.tech_c, self.abuse_c.as_deref())
+    }
+}
+
+fn contact_handles<'a>(
+    admin_c: &'a [String],
+    tech_c: &'a [String],
+    abuse_c: Option<&'a str>,
+) -> Vec<(ContactRole, &'a str)> {
+    let admin_c = admin_c.iter().map(|x| (ContactRole::AdminC, x.as_str()));
+    let tech_c = tech_c.iter().map(|x| (ContactRole::TechC, x.as_str()));
+    let abuse_c = abuse_c.map(|x| (ContactRole::AbuseC, x));
+    admin_c.chain(tech_c).chain(abuse_c).collect()
+}
+
 /// WHOIS Organisation data.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct WhoIsOrg {
@@ -205,6 +638,15 @@ pub struct WhoIsOrg {
     pub phone: Option<String>,
     /// Email address
     pub email: Option<String>,
+    /// Admin contact references
+    #[serde(default)]
+    pub admin_c: Vec<String>,
+    /// Technical contact references
+    #[serde(default)]
+    pub tech_c: Vec<String>,
+    /// Abuse contact reference
+    #[serde(default)]
+    pub abuse_c: Option<String>,
 }
 
 /// WHOIS Person/Role data.
@@ -236,7 +678,7 @@ pub enum InternetRegistry {
 impl From<&str> for InternetRegistry {
     fn from(value: &str) -> Self {
         let value = value.trim();
-        if value.eq_ignore_ascii_case("ripe") {
+        if value.eq_ignore_ascii_case("ripe") || value.eq_ignore_ascii_case("ripencc") {
             return Self::RIPE;
         } else if value.eq_ignore_ascii_case("arin") {
             return Self::ARIN;
@@ -273,6 +715,32 @@ pub struct Bound {
     pub south_west: Coord,
 }
 
+/// GeoJSON point
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct GeoPoint {
+    /// always "Point"
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// [lon, lat], GeoJSON puts longitude first
+    pub coordinates: [f64; 2],
+}
+
+impl From<&Coord> for GeoPoint {
+    fn from(c: &Coord) -> Self {
+        Self {
+            kind: "Point".to_string(),
+            coordinates: [c.lon, c.lat],
+        }
+    }
+}
+
+/// Circle around a point on the map
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Radius {
+    pub center: Coord,
+    pub meters: f64,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
 pub struct AsFilters {
     /// 2 letter country code
@@ -281,6 +749,10 @@ pub struct AsFilters {
     pub exclude_country: bool,
     /// top left and bottom right corners of the geo bound
     pub bounds: Option<Bound>,
+    /// only ases within the distance from a point
+    pub radius: Option<Radius>,
+    /// only ases inside the polygon with these vertices, ignored below 3 vertices
+    pub polygon: Option<Vec<Coord>>,
     /// range of addresses, (min, max)
     pub addresses: Option<(i64, i64)>,
     /// range of allowed ranks, (min, max)
@@ -291,6 +763,137 @@ pub struct AsFilters {
     pub category: Vec<String>,
     /// filter by saved user lists (empty = disabled)
     pub lists: Vec<String>,
+    /// words searched for in names, organisations and WHOIS descriptions, any has to match
+    pub text: Option<String>,
+    /// conditions on annotations, all have to match
+    pub annotations: Vec<AnnotationFilter>,
+    /// RIR which delegated the asn, from its delegated-extended statistics
+    pub registry: Option<InternetRegistry>,
+    /// only asns allocated before the `YYYY-MM-DD` date
+    pub allocated_before: Option<String>,
+    /// only asns allocated after the `YYYY-MM-DD` date
+    pub allocated_after: Option<String>,
+}
+
+/// Value ases are grouped by in [`AsStats`]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsGroup {
+    /// ASRank country code
+    Country,
+    /// stanford asdb layer 1 category, an AS counts in each of its categories
+    Category,
+    /// stanford asdb layer 2 category
+    Subcategory,
+    /// registry from IPNetDB
+    Registry,
+    /// ASRank rank rounded up to a power of 10, e.g. "11-100"
+    RankBucket,
+    /// user list, an AS counts in each of its lists
+    List,
+}
+
+impl std::str::FromStr for StatsGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "country" => Ok(Self::Country),
+            "category" => Ok(Self::Category),
+            "subcategory" => Ok(Self::Subcategory),
+            "registry" => Ok(Self::Registry),
+            "rank" | "rank-bucket" => Ok(Self::RankBucket),
+            "list" => Ok(Self::List),
+            _ => Err(format!(
+                "unknown group {s}, expected one of country, category, subcategory, registry, rank, list"
+            )),
+        }
+    }
+}
+
+/// Count of ases and sums of their ASRank addresses and prefixes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatsTotals {
+    pub ases: u64,
+    pub addresses: u64,
+    pub prefixes: u64,
+}
+
+/// Totals of ases sharing the grouped value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StatsGroupRow {
+    /// None groups ases which don't have the value
+    pub key: Option<String>,
+    pub totals: StatsTotals,
+}
+
+/// Ases matching filters grouped by one value, the biggest groups first
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct AsStats {
+    pub group_by: StatsGroup,
+    pub groups: Vec<StatsGroupRow>,
+    /// totals of all matching ases, groups may overlap for categories and lists
+    pub total: StatsTotals,
+}
+
+/// Value ases are ordered by in [`AsPage`], ties are broken by asn
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AsSortField {
+    #[default]
+    Asn,
+    Rank,
+    Addresses,
+    Prefixes,
+    /// total ASRank degree
+    Degree,
+    /// ASRank country code
+    Country,
+    /// ASRank name
+    Name,
+}
+
+impl std::str::FromStr for AsSortField {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "asn" => Ok(Self::Asn),
+            "rank" => Ok(Self::Rank),
+            "addresses" => Ok(Self::Addresses),
+            "prefixes" => Ok(Self::Prefixes),
+            "degree" => Ok(Self::Degree),
+            "country" => Ok(Self::Country),
+            "name" => Ok(Self::Name),
+            _ => Err(format!(
+                "unknown sort field {s}, expected one of asn, rank, addresses, prefixes, degree, country, name"
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AsSort {
+    pub field: AsSortField,
+    pub descending: bool,
+}
+
+/// One page of ases matching filters in the requested order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AsPage {
+    pub ases: Vec<AsForFrontend>,
+    /// opaque token to pass for the next page, None on the last one
+    pub next: Option<String>,
+}
+
+/// AS found by the text search
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SearchHit {
+    pub asn: u32,
+    /// relevance of the match, higher is better
+    pub score: f64,
+    /// ASRank name, or WHOIS as-name for ases missing from ASRank
+    pub name: Option<String>,
+    /// ASRank organisation, or IPNetDB entity for ases missing from ASRank
+    pub organization: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -0,0 +1,229 @@
+//! Typed annotations users set on ases according to the fields defined in the database.
+//!
+//! Values are kept in [`UserData::annotations`] as `{name, value: {Text|Number|Date: x}}`, so
+//! filters compare the value under the key of its type and a number never matches a date.
+
+use std::cmp::Ordering;
+
+use asdb_models::{
+    Annotation, AnnotationField, AnnotationFilter, AnnotationKind, AnnotationOp, AnnotationValue,
+};
+use mongodb::bson::{Bson, Document, doc};
+
+use crate::{Error, Result};
+
+/// Checks the name of a new field, which is used in mongo paths, and that redefining a field
+/// keeps the type of its values
+pub(crate) fn validate_field(
+    field: &AnnotationField,
+    current: Option<&AnnotationField>,
+) -> Result<()> {
+    let valid_name = !field.name.is_empty()
+        && field
+            .name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid_name {
+        return Err(Error::InvalidAnnotation(format!(
+            "field name {:?} has to be made of letters, digits, - and _",
+            field.name
+        )));
+    }
+    if let AnnotationKind::Enum(options) = &field.kind
+        && options.iter().all(|x| x.trim().is_empty())
+    {
+        return Err(Error::InvalidAnnotation(format!(
+            "enum field {:?} needs options",
+            field.name
+        )));
+    }
+    if let Some(current) = current
+        && std::mem::discriminant(&current.kind) != std::mem::discriminant(&field.kind)
+    {
+        return Err(Error::InvalidAnnotation(format!(
+            "field {:?} already holds {:?} values",
+            field.name, current.kind
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that the value fits the kind of the field
+pub(crate) fn validate_value(field: &AnnotationField, value: &AnnotationValue) -> Result<()> {
+    let valid = match (&field.kind, value) {
+        (AnnotationKind::Text, AnnotationValue::Text(_)) => true,
+        (AnnotationKind::Number, AnnotationValue::Number(x)) => x.is_finite(),
+        (AnnotationKind::Enum(options), AnnotationValue::Text(x)) => options.contains(x),
+        (AnnotationKind::Date, AnnotationValue::Date(x)) => {
+            chrono::NaiveDate::parse_from_str(x, "%Y-%m-%d").is_ok()
+        }
+        _ => false,
+    };
+    if !valid {
+        return Err(Error::InvalidAnnotation(format!(
+            "{value:?} doesn't fit {:?} field {:?}",
+            field.kind, field.name
+        )));
+    }
+    Ok(())
+}
+
+/// Key the value is stored under and the value itself
+fn typed(value: &AnnotationValue) -> (&'static str, Bson) {
+    match value {
+        AnnotationValue::Text(x) => ("value.Text", Bson::String(x.clone())),
+        AnnotationValue::Number(x) => ("value.Number", Bson::Double(*x)),
+        AnnotationValue::Date(x) => ("value.Date", Bson::String(x.clone())),
+    }
+}
+
+/// Escapes characters with a meaning in regular expressions
+fn escape_regex(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Condition on the asns collection, to be combined with the others through `$and`
+pub(crate) fn db_filter(filter: &AnnotationFilter) -> Document {
+    let compare = |op: &str, value: &AnnotationValue| {
+        let (key, value) = typed(value);
+        doc! {"name": &filter.name, key: {op: value}}
+    };
+    let element = match &filter.op {
+        AnnotationOp::Exists => doc! {"name": &filter.name},
+        AnnotationOp::Missing => {
+            return doc! {"user_data.annotations": {"$not": {"$elemMatch": {"name": &filter.name}}}};
+        }
+        AnnotationOp::Eq(x) => compare("$eq", x),
+        AnnotationOp::Ne(x) => {
+            let (key, value) = typed(x);
+            // the value of another type doesn't exist under the key
+            doc! {"name": &filter.name, key: {"$exists": true, "$ne": value}}
+        }
+        AnnotationOp::Lt(x) => compare("$lt", x),
+        AnnotationOp::Lte(x) => compare("$lte", x),
+        AnnotationOp::Gt(x) => compare("$gt", x),
+        AnnotationOp::Gte(x) => compare("$gte", x),
+        AnnotationOp::Contains(x) => doc! {
+            "name": &filter.name,
+            "value.Text": {"$regex": escape_regex(x), "$options": "i"},
+        },
+    };
+    doc! {"user_data.annotations": {"$elemMatch": element}}
+}
+
+/// Order of values of the same type
+fn compare(a: &AnnotationValue, b: &AnnotationValue) -> Option<Ordering> {
+    match (a, b) {
+        (AnnotationValue::Text(a), AnnotationValue::Text(b)) => Some(a.cmp(b)),
+        (AnnotationValue::Number(a), AnnotationValue::Number(b)) => a.partial_cmp(b),
+        (AnnotationValue::Date(a), AnnotationValue::Date(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Checks annotations of an AS against the filter the same way [`db_filter`] does
+pub(crate) fn matches(annotations: &[Annotation], filter: &AnnotationFilter) -> bool {
+    let value = annotations
+        .iter()
+        .find(|x| x.name == filter.name)
+        .map(|x| &x.value);
+    let Some(value) = value else {
+        return matches!(filter.op, AnnotationOp::Missing);
+    };
+    match &filter.op {
+        AnnotationOp::Exists => true,
+        AnnotationOp::Missing => false,
+        AnnotationOp::Eq(x) => compare(value, x) == Some(Ordering::Equal),
+        AnnotationOp::Ne(x) => compare(value, x).is_some_and(|o| o != Ordering::Equal),
+        AnnotationOp::Lt(x) => compare(value, x) == Some(Ordering::Less),
+        AnnotationOp::Lte(x) => compare(value, x).is_some_and(|o| o != Ordering::Greater),
+        AnnotationOp::Gt(x) => compare(value, x) == Some(Ordering::Greater),
+        AnnotationOp::Gte(x) => compare(value, x).is_some_and(|o| o != Ordering::Less),
+        AnnotationOp::Contains(x) => match value {
+            AnnotationValue::Text(text) => text.to_lowercase().contains(&x.to_lowercase()),
+            _ => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(kind: AnnotationKind) -> AnnotationField {
+        AnnotationField {
+            name: "status".to_string(),
+            kind,
+            description: None,
+        }
+    }
+
+    #[test]
+    fn values_have_to_fit_their_field() {
+        let status = field(AnnotationKind::Enum(vec!["contacted".to_string()]));
+        let text = |x: &str| AnnotationValue::Text(x.to_string());
+
+        assert!(validate_value(&status, &text("contacted")).is_ok());
+        assert!(validate_value(&status, &text("ignored")).is_err());
+        assert!(
+            validate_value(
+                &field(AnnotationKind::Date),
+                &AnnotationValue::Date("2024-02-30".to_string())
+            )
+            .is_err()
+        );
+        assert!(validate_value(&field(AnnotationKind::Number), &text("3")).is_err());
+        assert!(
+            validate_field(
+                &field(AnnotationKind::Text),
+                Some(&field(AnnotationKind::Number))
+            )
+            .is_err()
+        );
+        assert!(
+            validate_field(
+                &AnnotationField {
+                    name: "a.b".to_string(),
+                    ..field(AnnotationKind::Text)
+                },
+                None
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn filters_compare_values_of_the_same_type() {
+        let annotations = [Annotation {
+            name: "priority".to_string(),
+            value: AnnotationValue::Number(3.0),
+        }];
+        let filter = |op| AnnotationFilter {
+            name: "priority".to_string(),
+            op,
+        };
+
+        assert!(matches(
+            &annotations,
+            &filter(AnnotationOp::Gte(AnnotationValue::Number(3.0)))
+        ));
+        assert!(!matches(
+            &annotations,
+            &filter(AnnotationOp::Lt(AnnotationValue::Number(3.0)))
+        ));
+        assert!(!matches(
+            &annotations,
+            &filter(AnnotationOp::Ne(AnnotationValue::Text("3".to_string())))
+        ));
+        assert!(!matches(&annotations, &filter(AnnotationOp::Missing)));
+        assert!(matches(&[], &filter(AnnotationOp::Missing)));
+        assert_eq!(escape_regex("a.b"), "a\\.b");
+    }
+}
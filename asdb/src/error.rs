@@ -12,6 +12,39 @@ pub enum Error {
     DuplicatesFound(u64),
     #[error("as not found")]
     AsNotFound,
+    #[error("user data edit not found")]
+    EditNotFound,
+    #[error("list {0:?} not found")]
+    ListNotFound(String),
+    #[error("list {0:?} already exists")]
+    ListExists(String),
+    #[error("list name can't be blank")]
+    BlankListName,
+    #[error("annotation field {0:?} not found")]
+    AnnotationFieldNotFound(String),
+    #[error("invalid annotation: {0}")]
+    InvalidAnnotation(String),
+    #[error("database schema version {0} is newer than the supported one")]
+    UnsupportedSchema(u32),
+    #[error("invalid page token")]
+    InvalidPageToken,
+    #[error("invalid stored document: {0}")]
+    InvalidDocument(String),
+    #[error("invalid dataset name {0:?}, only letters, digits, - and _ are allowed")]
+    InvalidDatasetName(String),
+    #[error("staged import can't replace the current data: {0}")]
+    InvalidStaging(String),
+    #[error("there is no previous generation of the data to roll back to")]
+    NoPreviousGeneration,
+}
+
+/// Whether a single write failed on a unique index
+pub(crate) fn is_duplicate(e: &mongodb::error::Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        mongodb::error::ErrorKind::Write(mongodb::error::WriteFailure::WriteError(w))
+            if w.code == DUPLICATES_CODE_ERROR
+    )
 }
 
 impl From<mongodb::error::Error> for Error {
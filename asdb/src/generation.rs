@@ -0,0 +1,198 @@
+//! Staged imports swapped into place once they're complete.
+//!
+//! A staged import writes the imported collections under `{name}_staging` while the live ones
+//! keep being served. Promoting checks the staged ases first, their count against the live ones,
+//! that each has an asn and that no asn is staged twice, and only then renames the live
+//! collections to `{name}_previous` and the staged ones into their place. Rolling back renames
+//! them the other way around. Collections nothing was staged for stay as they are and lose their
+//! previous generation, so a rollback restores only the ones the last promote replaced.
+//!
+//! Mongo can't swap two collections at once, so each one is missing for the moment between its
+//! two renames. If a rename fails, the collections already swapped are put back and the promote
+//! fails as a whole.
+
+use futures::TryStreamExt;
+use mongodb::{
+    Client, Collection, Database,
+    bson::{Document, doc},
+};
+
+use crate::{Error, Result};
+
+/// Collections an import replaces, the import history in snapshots and datasets is only added to
+pub(crate) const STAGED_COLLECTIONS: [&str; 8] = [
+    "asns",
+    "organisations",
+    "prefixes",
+    "persons",
+    "relationships",
+    "ixps",
+    "facilities",
+    "rir_blocks",
+];
+
+/// Staged ases have to be at least this share of the live ones, so a broken download doesn't
+/// replace the whole database
+const MIN_STAGED_SHARE: f64 = 0.5;
+
+pub(crate) fn staging_name(collection: &str) -> String {
+    format!("{collection}_staging")
+}
+
+/// Name a staged import writes the collection under, None for the ones written in place
+pub fn staging_collection(collection: &str) -> Option<String> {
+    STAGED_COLLECTIONS
+        .contains(&collection)
+        .then(|| staging_name(collection))
+}
+
+pub(crate) fn previous_name(collection: &str) -> String {
+    format!("{collection}_previous")
+}
+
+/// Checks whether `staged` ases can replace the `live` ones
+pub(crate) fn validate(live: u64, staged: u64) -> Result<()> {
+    if staged == 0 {
+        return Err(Error::InvalidStaging("no ases were staged".to_string()));
+    }
+    if (staged as f64) < live as f64 * MIN_STAGED_SHARE {
+        return Err(Error::InvalidStaging(format!(
+            "{staged} staged ases are less than half of the {live} live ones, clear the database \
+             first to replace it anyway"
+        )));
+    }
+    Ok(())
+}
+
+/// Checks that each of the staged ases has an asn and none of them is staged twice
+pub(crate) async fn check_asns(asns: &Collection<Document>) -> Result<()> {
+    // matches missing asns as well
+    let missing = asns.count_documents(doc! {"asn": null}).await?;
+    if missing > 0 {
+        return Err(Error::InvalidStaging(format!(
+            "{missing} staged ases have no asn"
+        )));
+    }
+    let duplicated: Vec<Document> = asns
+        .aggregate([
+            doc! {"$group": {"_id": "$asn", "count": {"$sum": 1}}},
+            doc! {"$match": {"count": {"$gt": 1}}},
+            doc! {"$limit": 1},
+        ])
+        .await?
+        .try_collect()
+        .await?;
+    if let Some(d) = duplicated.first() {
+        return Err(Error::InvalidStaging(format!(
+            "AS{} is staged more than once",
+            d.get("_id").cloned().unwrap_or_default()
+        )));
+    }
+    Ok(())
+}
+
+/// Aggregations on the live collections which copy the data users and WHOIS lookups added
+/// onto the staged documents. Data of ases and organisations missing from the import is dropped
+/// with them, as they couldn't be shown without the imported data.
+pub(crate) fn carry_over_pipelines() -> [(&'static str, Vec<Document>); 2] {
+    [
+        (
+            "asns",
+            vec![
+                doc! {"$match": {"$or": [
+                    {"user_data": {"$ne": null}},
+                    {"whois_data": {"$ne": null}},
+                ]}},
+                doc! {"$project": {
+                    "_id": 0,
+                    "asn": 1,
+                    "user_data": 1,
+                    "whois_data": 1,
+                }},
+                doc! {"$merge": {
+                    "into": staging_name("asns"),
+                    "on": "asn",
+                    "whenMatched": [{"$set": {
+                        "user_data": "$$new.user_data",
+                        "whois_data": "$$new.whois_data",
+                    }}],
+                    "whenNotMatched": "discard",
+                }},
+            ],
+        ),
+        (
+            "organisations",
+            vec![
+                doc! {"$match": {"whois_org_id": {"$ne": null}}},
+                doc! {"$project": {"_id": 0, "org_id": 1, "whois_org_id": 1, "whois": 1}},
+                doc! {"$merge": {
+                    "into": staging_name("organisations"),
+                    "on": "org_id",
+                    "whenMatched": "merge",
+                    "whenNotMatched": "discard",
+                }},
+            ],
+        ),
+    ]
+}
+
+pub(crate) async fn exists(db: &Database, collection: &str) -> Result<bool> {
+    let names = db
+        .list_collection_names()
+        .filter(doc! {"name": collection})
+        .await?;
+    Ok(!names.is_empty())
+}
+
+/// Renames a collection, replacing the target if there is one
+pub(crate) async fn rename(client: &Client, database: &str, from: &str, to: &str) -> Result<()> {
+    client
+        .database("admin")
+        .run_command(doc! {
+            "renameCollection": format!("{database}.{from}"),
+            "to": format!("{database}.{to}"),
+            "dropTarget": true,
+        })
+        .await?;
+    Ok(())
+}
+
+/// Puts the staged collection in place of the live one, which becomes the previous generation.
+/// The live one is left in place if the staged one can't be moved.
+pub(crate) async fn swap(client: &Client, database: &str, collection: &str) -> Result<()> {
+    let previous = previous_name(collection);
+    let had_live = exists(&client.database(database), collection).await?;
+    if had_live {
+        rename(client, database, collection, &previous).await?;
+    }
+    if let Err(e) = rename(client, database, &staging_name(collection), collection).await {
+        if had_live {
+            rename(client, database, &previous, collection).await?;
+        }
+        return Err(e);
+    }
+    Ok(())
+}
+
+/// Moves the collection back to staging and its previous generation, if there is one, in place
+pub(crate) async fn unswap(client: &Client, database: &str, collection: &str) -> Result<()> {
+    let previous = previous_name(collection);
+    rename(client, database, collection, &staging_name(collection)).await?;
+    if exists(&client.database(database), &previous).await? {
+        rename(client, database, &previous, collection).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staged_ases_replace_only_most_of_the_live_ones() {
+        assert!(validate(0, 1).is_ok());
+        assert!(validate(100, 50).is_ok());
+        assert!(validate(100, 49).is_err());
+        assert!(validate(0, 0).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Helpers for the geo filters.
+//!
+//! Mongo queries run against the GeoJSON `location` of each AS on a sphere. Map bounds are
+//! turned into polygons whose edges along the parallels are split into short segments, so the
+//! great circle arcs between vertices stay close to the lines of latitude seen on the map.
+
+use asdb_models::{As, Bound, Coord, GeoPoint, Radius};
+use mongodb::bson::{Document, doc};
+
+/// Radius used by mongo to convert distances to radians for `$centerSphere`
+const EARTH_RADIUS_M: f64 = 6_378_100.0;
+/// Longest segment of a parallel in degrees
+const MAX_SEGMENT_DEG: f64 = 1.0;
+/// Poles can't be polygon vertices, all points along them would be the same
+const MAX_LAT: f64 = 89.999;
+
+/// Returns copy of the record with `location` set from the ASRank coordinates
+pub(crate) fn located(a: &As) -> As {
+    As {
+        location: a
+            .asrank_data
+            .as_ref()
+            .map(|x| GeoPoint::from(&x.coordinates)),
+        ..a.clone()
+    }
+}
+
+/// Wraps longitude into [-180, 180)
+fn wrap_lon(lon: f64) -> f64 {
+    (lon + 180.0).rem_euclid(360.0) - 180.0
+}
+
+/// Width of the bounds in degrees of longitude, bounds crossing the antimeridian may come
+/// either unwrapped (east > 180) or with east < west
+fn bounds_width(bounds: &Bound) -> f64 {
+    let width = bounds.north_east.lon - bounds.south_west.lon;
+    if width < 0.0 { width + 360.0 } else { width }
+}
+
+/// Counter clockwise ring around the bounds. None when the bounds are not a proper polygon,
+/// i.e. they span all longitudes or have no area.
+pub(crate) fn bounds_ring(bounds: &Bound) -> Option<Vec<[f64; 2]>> {
+    let width = bounds_width(bounds);
+    let south = bounds.south_west.lat.clamp(-MAX_LAT, MAX_LAT);
+    let north = bounds.north_east.lat.clamp(-MAX_LAT, MAX_LAT);
+    if width <= 0.0 || width >= 360.0 || north <= south {
+        return None;
+    }
+    let west = bounds.south_west.lon;
+    let steps = (width / MAX_SEGMENT_DEG).ceil() as usize;
+    let parallel = |lat: f64| {
+        (0..=steps).map(move |i| [wrap_lon(west + width * i as f64 / steps as f64), lat])
+    };
+    let mut ring: Vec<[f64; 2]> = parallel(south).collect();
+    ring.extend(parallel(north).collect::<Vec<_>>().into_iter().rev());
+    ring.push(ring[0]);
+    Some(ring)
+}
+
+/// Closed counter clockwise ring through the vertices. None below 3 vertices.
+pub(crate) fn polygon_ring(vertices: &[Coord]) -> Option<Vec<[f64; 2]>> {
+    let mut ring: Vec<[f64; 2]> = vertices.iter().map(|c| [c.lon, c.lat]).collect();
+    if ring.first() == ring.last() {
+        ring.pop();
+    }
+    if ring.len() < 3 {
+        return None;
+    }
+    // shoelace on the coordinates as drawn, before wrapping them
+    let area: f64 = ring
+        .iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    if area < 0.0 {
+        ring.reverse();
+    }
+    for p in ring.iter_mut() {
+        p[0] = wrap_lon(p[0]);
+    }
+    ring.push(ring[0]);
+    Some(ring)
+}
+
+/// `$geoWithin` the polygon. Strict winding makes the interior always the left side of the
+/// ring, so polygons bigger than a hemisphere still mean what was drawn.
+pub(crate) fn within_ring(ring: &[[f64; 2]]) -> Document {
+    doc! {"$geoWithin": {"$geometry": {
+        "type": "Polygon",
+        "coordinates": [ring.iter().map(|p| vec![p[0], p[1]]).collect::<Vec<_>>()],
+        "crs": {
+            "type": "name",
+            "properties": {"name": "urn:x-mongodb:crs:strictwinding:EPSG:4326"},
+        },
+    }}}
+}
+
+/// `$geoWithin` the circle
+pub(crate) fn within_radius(radius: &Radius) -> Document {
+    doc! {"$geoWithin": {"$centerSphere": [
+        [radius.center.lon, radius.center.lat],
+        radius.meters / EARTH_RADIUS_M,
+    ]}}
+}
+
+/// Same check as the lat/lon part of [`bounds_ring`] without the spherical edges
+pub(crate) fn bounds_contain(bounds: &Bound, c: &Coord) -> bool {
+    if !(c.lat <= bounds.north_east.lat && c.lat > bounds.south_west.lat) {
+        return false;
+    }
+    let width = bounds_width(bounds);
+    width >= 360.0 || {
+        let offset = (c.lon - bounds.south_west.lon).rem_euclid(360.0);
+        offset > 0.0 && offset <= width
+    }
+}
+
+/// Great circle distance in meters
+pub(crate) fn distance(a: &Coord, b: &Coord) -> f64 {
+    let (lat1, lat2) = (a.lat.to_radians(), b.lat.to_radians());
+    let dlat = lat2 - lat1;
+    let dlon = (b.lon - a.lon).to_radians();
+    let h = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * h.sqrt().asin()
+}
+
+/// Planar point in polygon test on the ring from [`polygon_ring`], good enough for shapes
+/// drawn on the map which don't cross the antimeridian
+pub(crate) fn ring_contains(ring: &[[f64; 2]], c: &Coord) -> bool {
+    let (x, y) = (c.lon, c.lat);
+    let mut inside = false;
+    for (a, b) in ring.iter().zip(ring.iter().skip(1)) {
+        if (a[1] > y) != (b[1] > y) && x < (b[0] - a[0]) * (y - a[1]) / (b[1] - a[1]) + a[0] {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coord(lat: f64, lon: f64) -> Coord {
+        Coord { lat, lon }
+    }
+
+    fn bound(south_west: Coord, north_east: Coord) -> Bound {
+        Bound {
+            north_east,
+            south_west,
+        }
+    }
+
+    #[test]
+    fn bounds_ring_is_closed_and_split_along_parallels() {
+        let ring = bounds_ring(&bound(coord(49.0, 14.0), coord(55.0, 24.0))).unwrap();
+        assert_eq!(ring.first(), ring.last());
+        // 10 degrees wide gives 11 points on each parallel plus the closing one
+        assert_eq!(ring.len(), 23);
+        assert_eq!(ring[0], [14.0, 49.0]);
+        assert_eq!(ring[10], [24.0, 49.0]);
+        assert_eq!(ring[11], [24.0, 55.0]);
+    }
+
+    #[test]
+    fn bounds_crossing_antimeridian_are_wrapped() {
+        for b in [
+            bound(coord(-50.0, 170.0), coord(-30.0, 190.0)),
+            bound(coord(-50.0, 170.0), coord(-30.0, -170.0)),
+        ] {
+            let ring = bounds_ring(&b).unwrap();
+            assert!(ring.iter().all(|p| (-180.0..180.0).contains(&p[0])));
+            assert_eq!(ring[10], [-180.0, -50.0]);
+            assert!(bounds_contain(&b, &coord(-40.0, 179.0)));
+            assert!(bounds_contain(&b, &coord(-40.0, -175.0)));
+            assert!(!bounds_contain(&b, &coord(-40.0, 0.0)));
+        }
+    }
+
+    #[test]
+    fn whole_world_bounds_are_not_a_polygon() {
+        let b = bound(coord(-80.0, -200.0), coord(80.0, 200.0));
+        assert!(bounds_ring(&b).is_none());
+        assert!(bounds_contain(&b, &coord(10.0, 123.0)));
+        assert!(bounds_ring(&bound(coord(0.0, 0.0), coord(0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn polygon_ring_is_counter_clockwise() {
+        let clockwise = [
+            coord(0.0, 0.0),
+            coord(10.0, 0.0),
+            coord(10.0, 10.0),
+            coord(0.0, 10.0),
+        ];
+        let ring = polygon_ring(&clockwise).unwrap();
+        assert_eq!(
+            ring,
+            [
+                [10.0, 0.0],
+                [10.0, 10.0],
+                [0.0, 10.0],
+                [0.0, 0.0],
+                [10.0, 0.0]
+            ]
+        );
+        assert!(ring_contains(&ring, &coord(5.0, 5.0)));
+        assert!(!ring_contains(&ring, &coord(15.0, 5.0)));
+        assert!(polygon_ring(&clockwise[..2]).is_none());
+    }
+
+    #[test]
+    fn distance_between_cities() {
+        let gdansk = coord(54.35, 18.65);
+        let warsaw = coord(52.23, 21.01);
+        let d = distance(&gdansk, &warsaw);
+        assert!((280_000.0..290_000.0).contains(&d), "{d}");
+    }
+}
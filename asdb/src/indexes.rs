@@ -0,0 +1,227 @@
+//! Indexes of all collections, kept in sync by [`crate::Asdb::prepare_database`].
+//!
+//! Every field [`crate::Asdb`] filters or sorts ases by has an index, the sortable ones are
+//! compound with `asn` so keyset pages come straight out of the index. The indexes are named
+//! with [`NAME_PREFIX`] and the ones with it which are no longer listed here are dropped, so
+//! changing a definition replaces the old one. Indexes created by hand are left alone.
+
+use std::collections::BTreeSet;
+
+use mongodb::{
+    Database, IndexModel,
+    bson::{Document, doc},
+    options::IndexOptions,
+};
+use tracing::info;
+
+use crate::{Result, search};
+
+/// (collection, keys, unique)
+fn indexes() -> Vec<(&'static str, Document, bool)> {
+    vec![
+        ("asns", doc! {"asn": 1}, true),
+        ("asns", doc! {"location": "2dsphere"}, false),
+        ("asns", doc! {"asrank_data.rank": 1, "asn": 1}, false),
+        ("asns", doc! {"asrank_data.addresses": 1, "asn": 1}, false),
+        ("asns", doc! {"asrank_data.prefixes": 1, "asn": 1}, false),
+        (
+            "asns",
+            doc! {"asrank_data.degree.total": 1, "asn": 1},
+            false,
+        ),
+        ("asns", doc! {"asrank_data.country_iso": 1, "asn": 1}, false),
+        ("asns", doc! {"asrank_data.name": 1, "asn": 1}, false),
+        ("asns", doc! {"asrank_data.organization": 1}, false),
+        // bounds crossing the antimeridian are filtered by the raw coordinates
+        (
+            "asns",
+            doc! {"asrank_data.coordinates.lat": 1, "asrank_data.coordinates.lon": 1},
+            false,
+        ),
+        ("asns", doc! {"stanford_asdb.layer1": 1}, false),
+        ("asns", doc! {"user_data.lists": 1}, false),
+        ("asns", doc! {"user_data.annotations.name": 1}, false),
+        ("asns", doc! {"rir_data.registry": 1}, false),
+        ("asns", doc! {"rir_data.date": 1}, false),
+        ("prefixes", doc! {"range": 1}, true),
+        ("organisations", doc! {"org_id": 1}, true),
+        ("organisations", doc! {"asns": 1}, false),
+        ("organisations", doc! {"asrank_org_id": 1}, false),
+        ("organisations", doc! {"whois_org_id": 1}, false),
+        ("persons", doc! {"nic_hdl": 1}, true),
+        ("persons", doc! {"ases.admin_c": 1}, false),
+        ("persons", doc! {"ases.tech_c": 1}, false),
+        ("persons", doc! {"ases.abuse_c": 1}, false),
+        ("persons", doc! {"orgs.admin_c": 1}, false),
+        ("persons", doc! {"orgs.tech_c": 1}, false),
+        ("persons", doc! {"orgs.abuse_c": 1}, false),
+        ("snapshots", doc! {"run_id": 1, "asn": 1}, true),
+        ("datasets", doc! {"run_id": 1}, true),
+        ("datasets", doc! {"started_at": -1, "run_id": -1}, false),
+        ("user_data_edits", doc! {"id": 1}, true),
+        ("user_data_edits", doc! {"asn": 1, "at": -1}, false),
+        ("lists", doc! {"name": 1}, true),
+        ("annotation_fields", doc! {"name": 1}, true),
+        ("relationships", doc! {"a": 1, "b": 1}, true),
+        ("relationships", doc! {"b": 1}, false),
+        ("ixps", doc! {"id": 1}, true),
+        ("facilities", doc! {"id": 1}, true),
+        ("rir_blocks", doc! {"range": 1}, true),
+    ]
+}
+
+/// Prefix of the names of the indexes kept in sync here
+const NAME_PREFIX: &str = "asdb_";
+
+/// Indexes created before the sync, under the names mongo gives them. Mongo refuses to create
+/// an index under another name while one with the same keys exists.
+const UNPREFIXED_NAMES: [(&str, &str); 1] = [("asns", "asn_1")];
+
+fn prefixed(name: &str) -> String {
+    format!("{NAME_PREFIX}{name}")
+}
+
+/// Name mongo gives an index with these keys, e.g. `asrank_data.rank_1_asn_1`
+fn default_name(keys: &Document) -> String {
+    keys.iter()
+        .map(|(k, v)| {
+            // Display would quote strings
+            let v = v.as_str().map_or_else(|| v.to_string(), str::to_string);
+            format!("{k}_{v}")
+        })
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// Creates missing indexes and drops the ones created here which are not listed anymore
+pub(crate) async fn sync(db: &Database) -> Result<()> {
+    sync_renamed(db, |c| Some(c.to_string())).await
+}
+
+/// [`sync`] of the collections `rename` gives a name for, under that name
+pub(crate) async fn sync_renamed(
+    db: &Database,
+    rename: impl Fn(&str) -> Option<String>,
+) -> Result<()> {
+    let mut models: Vec<(&str, IndexModel)> = indexes()
+        .into_iter()
+        .map(|(collection, keys, unique)| {
+            let options = IndexOptions::builder()
+                .name(prefixed(&default_name(&keys)))
+                .unique(unique)
+                .build();
+            let index = IndexModel::builder().keys(keys).options(options).build();
+            (collection, index)
+        })
+        .collect();
+    let text_index = IndexModel::builder()
+        .keys(search::index_keys())
+        .options(
+            IndexOptions::builder()
+                .name(prefixed(search::INDEX_NAME))
+                .weights(search::index_weights())
+                // names are in many languages, stemming english words only would not help
+                .default_language("none".to_string())
+                .build(),
+        )
+        .build();
+    models.push(("asns", text_index));
+
+    let collections: BTreeSet<&str> = models.iter().map(|(c, _)| *c).collect();
+    for name in collections {
+        let Some(renamed) = rename(name) else {
+            continue;
+        };
+        let collection = db.collection::<Document>(&renamed);
+        let expected: Vec<IndexModel> = models
+            .iter()
+            .filter(|(c, _)| *c == name)
+            .map(|(_, x)| x.clone())
+            .collect();
+        let expected_names: BTreeSet<String> = expected
+            .iter()
+            .filter_map(|x| x.options.as_ref()?.name.clone())
+            .collect();
+        // listing fails for collections which don't exist yet
+        let existing = collection.list_index_names().await.unwrap_or_default();
+        let is_stale = |x: &String| {
+            if x.starts_with(NAME_PREFIX) {
+                !expected_names.contains(x)
+            } else {
+                UNPREFIXED_NAMES.contains(&(name, x.as_str()))
+            }
+        };
+        for stale in existing.iter().filter(|x| is_stale(x)) {
+            info!("dropping index {stale} of {renamed} which is no longer used");
+            collection.drop_index(stale).await?;
+        }
+        collection.create_indexes(expected).await?;
+    }
+    Ok(())
+}
+
+/// Stages of the winning plan in a `queryPlanner` explain output, outermost first
+#[cfg(test)]
+pub(crate) fn plan_stages(explain: &Document) -> Vec<String> {
+    fn collect(plan: &Document, stages: &mut Vec<String>) {
+        if let Ok(stage) = plan.get_str("stage") {
+            stages.push(stage.to_string());
+        }
+        for child in ["inputStage", "queryPlan"] {
+            if let Ok(x) = plan.get_document(child) {
+                collect(x, stages);
+            }
+        }
+        if let Ok(inputs) = plan.get_array("inputStages") {
+            for x in inputs.iter().filter_map(mongodb::bson::Bson::as_document) {
+                collect(x, stages);
+            }
+        }
+    }
+    let mut stages = vec![];
+    if let Ok(plan) = explain
+        .get_document("queryPlanner")
+        .and_then(|x| x.get_document("winningPlan"))
+    {
+        collect(plan, &mut stages);
+    }
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_names_are_unique() {
+        assert_eq!(
+            default_name(&doc! {"asrank_data.rank": 1, "asn": 1}),
+            "asrank_data.rank_1_asn_1"
+        );
+        assert_eq!(
+            default_name(&doc! {"location": "2dsphere"}),
+            "location_2dsphere"
+        );
+        let indexes = indexes();
+        let names: BTreeSet<(&str, String)> = indexes
+            .iter()
+            .map(|(c, keys, _)| (*c, default_name(keys)))
+            .collect();
+        assert_eq!(names.len(), indexes.len());
+    }
+
+    #[test]
+    fn plan_stages_walk_nested_plans() {
+        let explain = doc! {"queryPlanner": {"winningPlan": {
+            "stage": "FETCH",
+            "inputStage": {"stage": "OR", "inputStages": [
+                {"stage": "IXSCAN"},
+                {"stage": "FETCH", "inputStage": {"stage": "IXSCAN"}},
+            ]},
+        }}};
+        assert_eq!(
+            plan_stages(&explain),
+            ["FETCH", "OR", "IXSCAN", "FETCH", "IXSCAN"]
+        );
+    }
+}
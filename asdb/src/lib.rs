@@ -1,28 +1,66 @@
-use futures::stream::TryStreamExt;
+use std::collections::BTreeMap;
+
+use futures::{
+    Stream,
+    stream::{StreamExt, TryStreamExt},
+};
 use itertools::Itertools;
 use mongodb::{
-    Client, IndexModel,
+    Client, Collection,
     bson::{Bson, Document, doc},
-    options::{ClientOptions, IndexOptions},
+    options::ClientOptions,
+    results::UpdateResult,
 };
 
 use asdb_models::{
-    As, AsFilters, AsForFrontend, AsForFrontendFromDB, GeocodedAddress, IPNetDBAsn,
-    StanfordASdbCategory, UserData,
+    Annotation, AnnotationField, AnnotationValue, As, AsFilters, AsForFrontend,
+    AsForFrontendFromDB, AsList, AsNeighbor, AsPage, AsRelationship, AsSnapshot, AsSort, AsSource,
+    AsStats, Dataset, Facility, GeocodedAddress, IPNetDBAsn, IPNetDBPrefix, InternetRegistry, Ixp,
+    NeighborKind, Organisation, PeeringdbNet, Person, Prefix, RirBlock, RirDelegation, SearchHit,
+    StanfordASdbCategory, StatsGroup, UpsertStats, UserData, UserDataEdit, WhoIsAsn,
 };
 pub use error::{Error, Result};
+pub use generation::staging_collection;
+use ipnetwork::IpNetwork;
+pub use memory::InMemoryAsdb;
+pub use namespace::{DEFAULT_DATASET, dataset_database};
+pub use storage::Storage;
 use tracing::debug;
 
+mod annotations;
 mod error;
+mod generation;
+mod geo;
+mod indexes;
+mod lists;
+mod memory;
+pub mod migrations;
+mod namespace;
+mod page;
+mod prefix;
+mod relationships;
+mod search;
+mod snapshot;
+mod stats;
+mod storage;
+
+/// Updates sent at once by [`Storage::upsert_ases`]
+const UPSERT_CONCURRENCY: usize = 64;
 
 pub struct Asdb {
     client: Client,
     database: String,
+    /// writes the imported collections under their staging names, see [`Storage::staging`]
+    staging: bool,
 }
 
 impl std::fmt::Debug for Asdb {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Asdb {{ database: {} }}", self.database)
+        write!(
+            f,
+            "Asdb {{ database: {}, staging: {} }}",
+            self.database, self.staging
+        )
     }
 }
 
@@ -34,101 +72,114 @@ impl Asdb {
         let s = Asdb {
             client,
             database: database.to_owned(),
+            staging: false,
         };
         Self::prepare_database(&s).await?;
         Ok(s)
     }
 
-    async fn _ping(&self) -> Result<()> {
-        self.client
-            .database(&self.database)
-            .run_command(doc! {"ping": 1})
-            .await?;
-        Ok(())
+    /// Opens `dataset` of the `base` database, see [`dataset_database`]
+    pub async fn new_dataset(conn_str: &str, base: &str, dataset: &str) -> Result<Self> {
+        Self::new(conn_str, &dataset_database(base, dataset)?).await
     }
 
-    pub async fn clear_database(&self) -> Result<()> {
-        struct T {}
-        for c in ["asns", "organisations", "prefixes", "persons"] {
-            self.client
-                .database(&self.database)
-                .collection::<T>(c)
-                .drop()
-                .await?;
+    /// Names of the datasets of the `base` database, the default one first
+    pub async fn dataset_names(conn_str: &str, base: &str) -> Result<Vec<String>> {
+        let client = Client::with_options(ClientOptions::parse(conn_str).await?)?;
+        let mut names: Vec<String> = client
+            .list_database_names()
+            .await?
+            .iter()
+            .filter(|x| *x != base)
+            .filter_map(|x| namespace::database_dataset(base, x))
+            .sorted()
+            .collect();
+        names.insert(0, DEFAULT_DATASET.to_string());
+        Ok(names)
+    }
+
+    fn collection<T: Send + Sync>(&self, name: &str) -> Collection<T> {
+        let db = self.client.database(&self.database);
+        match generation::staging_collection(name).filter(|_| self.staging) {
+            Some(staged) => db.collection(&staged),
+            None => db.collection(name),
         }
-        Ok(())
     }
 
-    #[tracing::instrument]
-    pub async fn prepare_database(&self) -> Result<()> {
-        struct T {}
-        let collection = self.client.database(&self.database).collection::<T>("asns");
-        let index_options = IndexOptions::builder().unique(true).build();
-        let index = IndexModel::builder()
-            .keys(doc! {"asn": 1})
-            .options(index_options)
-            .build();
-        collection.create_index(index).await?;
+    /// Sets an empty user data on the matching ases which have none, so its fields can be
+    /// updated separately
+    async fn ensure_user_data(&self, filter: Document) -> Result<()> {
+        let mut filter = filter;
+        filter.insert("user_data", Bson::Null);
+        self.collection::<As>("asns")
+            .update_many(
+                filter,
+                doc! {"$set": {"user_data": mongodb::bson::to_bson(&UserData::default())
+                .expect("UserData should always be serializable to bson")}},
+            )
+            .await?;
         Ok(())
     }
 
-    // TODO GIS
-    // dodac funkcje do obslugi samych detali z whoisa
-    // rozszerzyc obecne zgodnie z tym czeog moze potrzebowac backend/frontend
-
-    #[tracing::instrument]
-    pub async fn get_ases_page(&self, limit: i64, skip: u64) -> Result<(Vec<AsForFrontend>, u64)> {
-        let collection = self
-            .client
+    async fn _ping(&self) -> Result<()> {
+        self.client
             .database(&self.database)
-            .collection::<AsForFrontendFromDB>("asns");
-        let res = collection
-            .find(doc! {})
-            .skip(skip)
-            .limit(limit)
-            .projection(doc! {
-                "asn": 1,
-                "asrank_data.name": 1,
-                "asrank_data.rank": 1,
-                "asrank_data.country_iso": 1,
-                "asrank_data.prefixes": 1,
-                "asrank_data.addresses": 1,
-                "asrank_data.coordinates": 1,
-                "asrank_data.organization": 1,
-            })
-            .sort(doc! {"asn": 1})
+            .run_command(doc! {"ping": 1})
             .await?;
-        let count = collection.count_documents(doc! {}).await?;
-        let ases: Vec<AsForFrontend> = res.map_ok(AsForFrontend::from).try_collect().await?;
-
-        Ok((ases, count))
+        Ok(())
     }
 
-    #[tracing::instrument]
-    pub async fn get_ases(&self, asns: &[u32]) -> Result<(Vec<As>, u64)> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
-        // let opts = FindOptions::builder().build();
+    /// Fields needed to build [`AsForFrontend`]
+    fn frontend_projection() -> Document {
+        doc! {
+            "asn": 1,
+            "asrank_data.name": 1,
+            "asrank_data.rank": 1,
+            "asrank_data.country_iso": 1,
+            "asrank_data.prefixes": 1,
+            "asrank_data.addresses": 1,
+            "asrank_data.coordinates": 1,
+            "asrank_data.organization": 1,
+        }
+    }
 
-        let res = collection.find(doc! {"asn": doc! { "$in": asns}}).await?;
-        let count = collection.count_documents(doc! {}).await?;
-        let ases: Vec<As> = res.try_collect().await?;
-        Ok((ases, count))
+    /// Filter of the ases the frontend lists, the ones without asrank data can't be shown there
+    fn listed_filter(filters: &AsFilters) -> Document {
+        let mut db_filter = Self::create_db_filter(filters);
+        db_filter.insert("asrank_data", doc! {"$ne": null});
+        db_filter
     }
 
     fn create_db_filter(filters: &AsFilters) -> Document {
         let mut db_filter = doc! {};
+        // several $geoWithin or annotation conditions on the same field have to go through $and
+        let mut and_filters = vec![];
         if let Some(bounds) = &filters.bounds {
-            db_filter.insert(
-                "asrank_data.coordinates.lat",
-                doc! {"$lte": bounds.north_east.lat, "$gt": bounds.south_west.lat},
-            );
-            db_filter.insert(
-                "asrank_data.coordinates.lon",
-                doc! {"$lte": bounds.north_east.lon, "$gt": bounds.south_west.lon},
-            );
+            if let Some(ring) = geo::bounds_ring(bounds) {
+                and_filters.push(doc! {"location": geo::within_ring(&ring)});
+            } else {
+                // spans all longitudes or has no area, nothing to index on anyway
+                db_filter.insert(
+                    "asrank_data.coordinates.lat",
+                    doc! {"$lte": bounds.north_east.lat, "$gt": bounds.south_west.lat},
+                );
+                if bounds.north_east.lon - bounds.south_west.lon < 360.0 {
+                    db_filter.insert(
+                        "asrank_data.coordinates.lon",
+                        doc! {"$lte": bounds.north_east.lon, "$gt": bounds.south_west.lon},
+                    );
+                }
+            }
+        }
+        if let Some(radius) = &filters.radius {
+            and_filters.push(doc! {"location": geo::within_radius(radius)});
+        }
+        if let Some(ring) = filters.polygon.as_deref().and_then(geo::polygon_ring) {
+            and_filters.push(doc! {"location": geo::within_ring(&ring)});
+        }
+        and_filters.extend(filters.annotations.iter().map(annotations::db_filter));
+        if !and_filters.is_empty() {
+            db_filter.insert("$and", and_filters);
         }
         if let Some(x) = &filters.country_iso {
             db_filter.insert(
@@ -161,87 +212,381 @@ impl Asdb {
         if !filters.lists.is_empty() {
             db_filter.insert("user_data.lists", doc! { "$in": filters.lists.as_slice() });
         }
+        if let Some(text) = filters.text.as_deref().filter(|x| !x.trim().is_empty()) {
+            db_filter.insert("$text", doc! {"$search": text});
+        }
+        if let Some(registry) = &filters.registry {
+            db_filter.insert(
+                "rir_data.registry",
+                mongodb::bson::to_bson(registry)
+                    .expect("InternetRegistry should always be serializable to bson"),
+            );
+        }
+        let mut date = doc! {};
+        if let Some(x) = &filters.allocated_before {
+            date.insert("$lt", x);
+        }
+        if let Some(x) = &filters.allocated_after {
+            date.insert("$gt", x);
+        }
+        if !date.is_empty() {
+            db_filter.insert("rir_data.date", date);
+        }
         db_filter
     }
+}
 
-    pub async fn count_ases_filtered(&self, filters: &AsFilters) -> Result<u64> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
-        let db_filter = Self::create_db_filter(filters);
+impl Storage for Asdb {
+    async fn clear_database(&self) -> Result<()> {
+        struct T {}
+        for c in [
+            "asns",
+            "organisations",
+            "prefixes",
+            "persons",
+            "snapshots",
+            "datasets",
+            "user_data_edits",
+            "lists",
+            "annotation_fields",
+            "relationships",
+            "ixps",
+            "facilities",
+            "rir_blocks",
+            migrations::META_COLLECTION,
+        ] {
+            // history and schema version are shared with the live data
+            if !self.staging || generation::STAGED_COLLECTIONS.contains(&c) {
+                self.collection::<T>(c).drop().await?;
+            }
+        }
+        Ok(())
+    }
 
-        let res = collection.count_documents(db_filter).await?;
-        Ok(res)
+    #[tracing::instrument]
+    async fn prepare_database(&self) -> Result<()> {
+        let db = self.client.database(&self.database);
+        if self.staging {
+            indexes::sync_renamed(&db, generation::staging_collection).await?;
+        } else {
+            migrations::run(&db).await?;
+            indexes::sync(&db).await?;
+        }
+        Ok(())
     }
 
     #[tracing::instrument]
-    pub async fn get_ases_filtered(&self, filters: &AsFilters) -> Result<Vec<AsForFrontend>> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<AsForFrontendFromDB>("asns");
-        let db_filter = Self::create_db_filter(filters);
+    async fn staging(&self) -> Result<Self> {
+        let s = Asdb {
+            client: self.client.clone(),
+            database: self.database.clone(),
+            staging: true,
+        };
+        // leftovers of an import which never got promoted
+        s.clear_database().await?;
+        s.prepare_database().await?;
+        Ok(s)
+    }
 
-        // TODO add projection ^ and verify if it speeds up the retrieval
-        let res = collection
+    #[tracing::instrument]
+    async fn promote(&self, staging: Self, keep_user_data: bool) -> Result<u64> {
+        let live = self
+            .collection::<Document>("asns")
+            .count_documents(doc! {})
+            .await?;
+        let asns = staging.collection::<Document>("asns");
+        let staged = asns.count_documents(doc! {}).await?;
+        generation::validate(live, staged)?;
+        generation::check_asns(&asns).await?;
+        // decided before anything is renamed, collections nothing was staged for stay in place
+        let mut replaced = vec![];
+        for c in generation::STAGED_COLLECTIONS {
+            if staging
+                .collection::<Document>(c)
+                .estimated_document_count()
+                .await?
+                > 0
+            {
+                replaced.push(c);
+            } else {
+                debug!("nothing was staged for {c}, keeping the current one");
+            }
+        }
+        if keep_user_data {
+            for (c, pipeline) in generation::carry_over_pipelines() {
+                self.collection::<Document>(c).aggregate(pipeline).await?;
+            }
+        }
+
+        // only the collections replaced now can be rolled back to
+        let db = self.client.database(&self.database);
+        for c in generation::STAGED_COLLECTIONS {
+            db.collection::<Document>(&generation::previous_name(c))
+                .drop()
+                .await?;
+        }
+        for (i, c) in replaced.iter().enumerate() {
+            if let Err(e) = generation::swap(&self.client, &self.database, c).await {
+                // the import fails as a whole, the collections already swapped go back to staging
+                for c in replaced[..i].iter().rev() {
+                    generation::unswap(&self.client, &self.database, c).await?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(self
+            .collection::<Document>("asns")
+            .count_documents(doc! {})
+            .await?)
+    }
+
+    #[tracing::instrument]
+    async fn rollback(&self) -> Result<()> {
+        let db = self.client.database(&self.database);
+        let mut rolled_back = false;
+        for c in generation::STAGED_COLLECTIONS {
+            if !generation::exists(&db, &generation::previous_name(c)).await? {
+                continue;
+            }
+            // the replaced generation can be promoted again from staging
+            generation::unswap(&self.client, &self.database, c).await?;
+            rolled_back = true;
+        }
+        if !rolled_back {
+            return Err(Error::NoPreviousGeneration);
+        }
+        Ok(())
+    }
+
+    // TODO GIS
+    // dodac funkcje do obslugi samych detali z whoisa
+    // rozszerzyc obecne zgodnie z tym czeog moze potrzebowac backend/frontend
+
+    #[tracing::instrument]
+    async fn get_ases_page(&self, limit: i64, skip: u64) -> Result<(Vec<AsForFrontend>, u64)> {
+        let ases = self
+            .stream_ases_page(limit, skip)
+            .await?
+            .try_collect()
+            .await?;
+        let count = self
+            .collection::<As>("asns")
+            .count_documents(Self::listed_filter(&AsFilters::default()))
+            .await?;
+        Ok((ases, count))
+    }
+
+    #[tracing::instrument]
+    async fn stream_ases_page(
+        &self,
+        limit: i64,
+        skip: u64,
+    ) -> Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin> {
+        let res = self
+            .collection::<AsForFrontendFromDB>("asns")
+            .find(Self::listed_filter(&AsFilters::default()))
+            .skip(skip)
+            .limit(limit)
+            .projection(Self::frontend_projection())
+            .sort(doc! {"asn": 1})
+            .await?;
+        Ok(res.map_ok(AsForFrontend::from).map_err(Error::from))
+    }
+
+    #[tracing::instrument]
+    async fn get_ases_sorted(
+        &self,
+        filters: &AsFilters,
+        sort: AsSort,
+        limit: i64,
+        after: Option<&str>,
+    ) -> Result<AsPage> {
+        let mut db_filter = Self::listed_filter(filters);
+        if let Some(token) = after {
+            db_filter.extend(page::after_filter(&page::decode(token, sort)?));
+        }
+        let mut projection = Self::frontend_projection();
+        projection.extend(page::projection(sort));
+        let res = self
+            .collection::<Document>("asns")
             .find(db_filter)
-            .projection(doc! {
-                "asn": 1,
-                "asrank_data.name": 1,
-                "asrank_data.rank": 1,
-                "asrank_data.country_iso": 1,
-                "asrank_data.prefixes": 1,
-                "asrank_data.addresses": 1,
-                "asrank_data.coordinates": 1,
-                "asrank_data.organization": 1,
+            .projection(projection)
+            .sort(page::sort_document(sort))
+            .limit(if limit > 0 { limit + 1 } else { 0 })
+            .await?;
+        let docs: Vec<Document> = res.try_collect().await?;
+        let sorted = docs
+            .into_iter()
+            .map(|d| {
+                let asn = d.get("asn").cloned().unwrap_or_default();
+                let key = page::document_key(&d, sort.field).ok_or_else(|| {
+                    Error::InvalidDocument(format!(
+                        "AS{asn} has no valid {:?} to sort by",
+                        sort.field
+                    ))
+                })?;
+                let a: AsForFrontendFromDB = mongodb::bson::from_document(d)
+                    .map_err(|e| Error::InvalidDocument(format!("AS{asn}: {e}")))?;
+                Ok((key, AsForFrontend::from(a)))
             })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(page::build(sorted.into_iter(), sort, limit))
+    }
+
+    #[tracing::instrument]
+    async fn get_ases(&self, asns: &[u32]) -> Result<(Vec<As>, u64)> {
+        let ases = self.stream_ases(asns).await?.try_collect().await?;
+        let count = self
+            .collection::<As>("asns")
+            .count_documents(doc! {})
+            .await?;
+        Ok((ases, count))
+    }
+
+    #[tracing::instrument]
+    async fn stream_ases(
+        &self,
+        asns: &[u32],
+    ) -> Result<impl Stream<Item = Result<As>> + Send + Unpin> {
+        let res = self
+            .collection::<As>("asns")
+            .find(doc! {"asn": doc! { "$in": asns}})
+            .await?;
+        Ok(res.map_err(Error::from))
+    }
+
+    async fn count_ases_filtered(&self, filters: &AsFilters) -> Result<u64> {
+        let collection = self.collection::<As>("asns");
+        let db_filter = Self::listed_filter(filters);
+
+        let res = collection.count_documents(db_filter).await?;
+        Ok(res)
+    }
+
+    #[tracing::instrument]
+    async fn get_ases_filtered(&self, filters: &AsFilters) -> Result<Vec<AsForFrontend>> {
+        let ases = self
+            .stream_ases_filtered(filters)
+            .await?
+            .try_collect()
             .await?;
-        debug!("cursor retrieved, starting collect");
-        // let ases: Vec<AsForFrontendFromDB> = res.try_collect().await?;
-        // res.for_each(|x| {x.unwrap(); future::ready(())}).await;
-        let ases: Vec<AsForFrontend> = res.map_ok(AsForFrontend::from).try_collect().await?;
         debug!("collected entries into Vec<>");
         Ok(ases)
     }
 
     #[tracing::instrument]
-    pub async fn get_as(&self, asn: u32) -> Result<As> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+    async fn stream_ases_filtered(
+        &self,
+        filters: &AsFilters,
+    ) -> Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin> {
+        let res = self
+            .collection::<AsForFrontendFromDB>("asns")
+            .find(Self::listed_filter(filters))
+            .projection(Self::frontend_projection())
+            .await?;
+        debug!("cursor retrieved");
+        Ok(res.map_ok(AsForFrontend::from).map_err(Error::from))
+    }
+
+    #[tracing::instrument]
+    async fn search(&self, query: &str, limit: i64) -> Result<Vec<SearchHit>> {
+        if query.trim().is_empty() {
+            return Ok(vec![]);
+        }
+        let res = self
+            .collection::<search::SearchDoc>("asns")
+            .find(doc! {"$text": {"$search": query}})
+            .projection(search::projection())
+            .sort(doc! {"score": {"$meta": "textScore"}, "asn": 1})
+            .limit(limit)
+            .await?;
+        Ok(res.map_ok(SearchHit::from).try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_stats(&self, filters: &AsFilters, group_by: StatsGroup) -> Result<AsStats> {
+        let collection = self.collection::<As>("asns");
+        let pipeline = stats::pipeline(Self::create_db_filter(filters), group_by);
+        let mut res = collection
+            .aggregate(pipeline)
+            .with_type::<stats::StatsFacet>()
+            .await?;
+        let facet = res
+            .try_next()
+            .await?
+            .expect("$facet should always output one document");
+        Ok(facet.into_stats(group_by))
+    }
+
+    #[tracing::instrument]
+    async fn get_as(&self, asn: u32) -> Result<As> {
+        let collection = self.collection::<As>("asns");
         let res = collection.find_one(doc! {"asn": asn }).await?;
         res.ok_or(Error::AsNotFound)
     }
 
     #[tracing::instrument]
-    pub async fn insert_as(&self, a: &As) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
-        collection.insert_one(a).await?;
+    async fn insert_as(&self, a: &As) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        collection.insert_one(geo::located(a)).await?;
         Ok(())
     }
 
     #[tracing::instrument]
-    pub async fn insert_ases(&self, a: &[As]) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
-        collection.insert_many(a).ordered(true).await?;
+    async fn insert_ases(&self, a: &[As]) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        let located = a.iter().map(geo::located);
+        collection.insert_many(located).ordered(true).await?;
         Ok(())
     }
 
+    #[tracing::instrument(skip(a))]
+    async fn upsert_ases(&self, a: &[As], source: AsSource) -> Result<UpsertStats> {
+        let collection = self.collection::<As>("asns");
+        let updates: Vec<(Document, Document)> = a
+            .iter()
+            .map(|x| {
+                let mut record = mongodb::bson::to_document(&geo::located(x))
+                    .expect("As should always be serializable to bson");
+                record.remove("asn");
+                let set: Document = source
+                    .fields()
+                    .iter()
+                    .map(|f| (f.to_string(), record.remove(f).unwrap_or(Bson::Null)))
+                    .collect();
+                (
+                    doc! {"asn": x.asn},
+                    doc! {"$set": set, "$setOnInsert": record},
+                )
+            })
+            .collect();
+        let results: Vec<UpdateResult> =
+            futures::stream::iter(updates.into_iter().map(|(filter, update)| {
+                collection
+                    .update_one(filter, update)
+                    .upsert(true)
+                    .into_future()
+            }))
+            .buffer_unordered(UPSERT_CONCURRENCY)
+            .try_collect()
+            .await?;
+        let mut stats = UpsertStats::default();
+        for r in results {
+            if r.upserted_id.is_some() {
+                stats.inserted += 1;
+            } else if r.modified_count > 0 {
+                stats.updated += 1;
+            } else {
+                stats.unchanged += 1;
+            }
+        }
+        Ok(stats)
+    }
+
     /// Updates the record for given asn with the provided IPNetDB data
     #[tracing::instrument]
-    pub async fn insert_ipnetdb_asn(&self, asn: u32, a: &IPNetDBAsn) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+    async fn insert_ipnetdb_asn(&self, asn: u32, a: &IPNetDBAsn) -> Result<()> {
+        let collection = self.collection::<As>("asns");
         let update = doc! {
             "$set": {
                 "ipnetdb_data": mongodb::bson::to_bson(a).expect("IPNetDBAsn should always be serializable to bson")
@@ -251,65 +596,127 @@ impl Asdb {
         Ok(())
     }
 
-    /// Updates the record for given asn with the provided categories list from stanford asdb
     #[tracing::instrument]
-    pub async fn insert_stanford_asdb_categories(
-        &self,
-        asn: u32,
-        categories: &[StanfordASdbCategory],
-    ) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+    async fn clear_peeringdb_nets(&self) -> Result<Vec<u32>> {
+        let collection = self.collection::<As>("asns");
+        let filter = doc! {"peeringdb_data": {"$ne": null}};
+        let asns = collection
+            .distinct("asn", filter.clone())
+            .await?
+            .iter()
+            .filter_map(|x| x.as_i64().or(x.as_i32().map(i64::from)))
+            .map(|x| x as u32)
+            .collect();
+        collection
+            .update_many(filter, doc! {"$unset": {"peeringdb_data": ""}})
+            .await?;
+        Ok(asns)
+    }
+
+    #[tracing::instrument(skip(net))]
+    async fn insert_peeringdb_net(&self, asn: u32, net: &PeeringdbNet) -> Result<()> {
+        let collection = self.collection::<As>("asns");
         let update = doc! {
             "$set": {
-                "stanford_asdb": mongodb::bson::to_bson(categories).expect("StandordAsdbCategory should always be serializable to bson")
+                "peeringdb_data": mongodb::bson::to_bson(net).expect("PeeringdbNet should always be serializable to bson")
             }
         };
         collection.update_one(doc! {"asn": asn}, update).await?;
         Ok(())
     }
 
-    /// Updates the record for given ASN with WHOIS data
     #[tracing::instrument]
-    pub async fn update_whois_data(
+    async fn clear_rir_asn_delegations(&self, registries: &[InternetRegistry]) -> Result<Vec<u32>> {
+        let collection = self.collection::<As>("asns");
+        let registries: Vec<Bson> = registries
+            .iter()
+            .map(|x| {
+                mongodb::bson::to_bson(x)
+                    .expect("InternetRegistry should always be serializable to bson")
+            })
+            .collect();
+        let filter = doc! {"rir_data.registry": {"$in": registries}};
+        let asns = collection
+            .distinct("asn", filter.clone())
+            .await?
+            .iter()
+            .filter_map(|x| x.as_i64().or(x.as_i32().map(i64::from)))
+            .map(|x| x as u32)
+            .collect();
+        collection
+            .update_many(filter, doc! {"$unset": {"rir_data": ""}})
+            .await?;
+        Ok(asns)
+    }
+
+    #[tracing::instrument]
+    async fn insert_rir_asn_delegation(
+        &self,
+        first: u32,
+        count: u32,
+        delegation: &RirDelegation,
+    ) -> Result<Vec<u32>> {
+        let collection = self.collection::<As>("asns");
+        let filter = doc! {"asn": {"$gte": first as i64, "$lt": first as i64 + count as i64}};
+        let asns = collection
+            .distinct("asn", filter.clone())
+            .await?
+            .iter()
+            .filter_map(|x| x.as_i64().or(x.as_i32().map(i64::from)))
+            .map(|x| x as u32)
+            .collect();
+        let update = doc! {
+            "$set": {
+                "rir_data": mongodb::bson::to_bson(delegation).expect("RirDelegation should always be serializable to bson")
+            }
+        };
+        collection.update_many(filter, update).await?;
+        Ok(asns)
+    }
+
+    /// Updates the record for given asn with the provided categories list from stanford asdb
+    #[tracing::instrument]
+    async fn insert_stanford_asdb_categories(
         &self,
         asn: u32,
-        whois_data: &asdb_models::WhoIsAsn,
+        categories: &[StanfordASdbCategory],
     ) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+        let collection = self.collection::<As>("asns");
         let update = doc! {
             "$set": {
-                "whois_data": mongodb::bson::to_bson(whois_data).expect("WhoIsAsn should always be serializable to bson")
+                "stanford_asdb": mongodb::bson::to_bson(categories).expect("StandordAsdbCategory should always be serializable to bson")
             }
         };
         collection.update_one(doc! {"asn": asn}, update).await?;
         Ok(())
     }
 
-    /// Gets cached WHOIS data for an ASN if available
+    /// Updates the record for given ASN with WHOIS data
     #[tracing::instrument]
-    pub async fn get_whois_data(&self, asn: u32) -> Result<Option<asdb_models::WhoIsAsn>> {
-        let as_data = self.get_as(asn).await?;
-        Ok(as_data.whois_data)
+    async fn update_whois_data(&self, asn: u32, whois_data: &WhoIsAsn) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        let stored = WhoIsAsn {
+            contacts: vec![],
+            ..whois_data.clone()
+        };
+        let update = doc! {
+            "$set": {
+                "whois_data": mongodb::bson::to_bson(&stored).expect("WhoIsAsn should always be serializable to bson")
+            }
+        };
+        collection.update_one(doc! {"asn": asn}, update).await?;
+        self.update_whois_contacts(asn, whois_data).await
     }
 
     /// Updates user data (lists/comment) for an ASN
     #[tracing::instrument]
-    pub async fn update_user_data(
+    async fn update_user_data(
         &self,
         asn: u32,
         lists: Option<Vec<String>>,
         comment: Option<String>,
     ) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+        let collection = self.collection::<As>("asns");
         let mut set_doc = Document::new();
         if let Some(lists_value) = lists {
             set_doc.insert("user_data.lists", lists_value);
@@ -331,15 +738,12 @@ impl Asdb {
 
     /// Updates geocoding results for an ASN
     #[tracing::instrument]
-    pub async fn update_geocoded_addresses(
+    async fn update_geocoded_addresses(
         &self,
         asn: u32,
         geocoded: Vec<GeocodedAddress>,
     ) -> Result<()> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+        let collection = self.collection::<As>("asns");
         let update = doc! {
             "$set": {
                 "user_data.geocoded_addresses": mongodb::bson::to_bson(&geocoded)
@@ -350,46 +754,534 @@ impl Asdb {
         Ok(())
     }
 
-    /// Gets user data for an ASN (empty if missing)
     #[tracing::instrument]
-    pub async fn get_user_data(&self, asn: u32) -> Result<UserData> {
-        let as_data = self.get_as(asn).await?;
-        Ok(as_data.user_data.unwrap_or_default())
+    async fn set_user_data(&self, asn: u32, user_data: &UserData) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        let update = doc! {
+            "$set": {
+                "user_data": mongodb::bson::to_bson(user_data)
+                    .expect("UserData should always be serializable to bson")
+            }
+        };
+        collection.update_one(doc! {"asn": asn}, update).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(asns))]
+    async fn get_user_data_of(&self, asns: &[u32]) -> Result<Vec<(u32, UserData)>> {
+        let collection = self.collection::<lists::AsUserData>("asns");
+        let res = collection
+            .find(doc! {"asn": {"$in": asns}})
+            .projection(doc! {"_id": 0, "asn": 1, "user_data": 1})
+            .sort(doc! {"asn": 1})
+            .await?;
+        let found: Vec<lists::AsUserData> = res.try_collect().await?;
+        Ok(found
+            .into_iter()
+            .map(|x| (x.asn, x.user_data.unwrap_or_default()))
+            .collect())
+    }
+
+    #[tracing::instrument(skip(edits))]
+    async fn save_user_data_edits(&self, edits: &[UserDataEdit]) -> Result<()> {
+        if edits.is_empty() {
+            return Ok(());
+        }
+        let collection = self.collection::<UserDataEdit>("user_data_edits");
+        collection.insert_many(edits).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_user_data_history(&self, asn: u32) -> Result<Vec<UserDataEdit>> {
+        let collection = self.collection::<UserDataEdit>("user_data_edits");
+        let res = collection
+            .find(doc! {"asn": asn})
+            .sort(doc! {"at": -1, "id": -1})
+            .await?;
+        Ok(res.try_collect().await?)
     }
 
     /// Gets all list names from user data
     #[tracing::instrument]
-    pub async fn get_list_names(&self) -> Result<Vec<String>> {
-        let collection = self
-            .client
-            .database(&self.database)
-            .collection::<As>("asns");
+    async fn get_list_names(&self) -> Result<Vec<String>> {
+        let collection = self.collection::<As>("asns");
         let values = collection.distinct("user_data.lists", doc! {}).await?;
         let mut out = Vec::new();
         for v in values {
-            if let Bson::String(s) = v {
-                if !s.trim().is_empty() {
-                    out.push(s);
-                }
+            if let Bson::String(s) = v
+                && !s.trim().is_empty()
+            {
+                out.push(s);
             }
         }
         out.sort();
         out.dedup();
         Ok(out)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use std::net::{IpAddr, Ipv4Addr};
+    #[tracing::instrument]
+    async fn save_list(&self, list: &AsList) -> Result<()> {
+        let collection = self.collection::<AsList>("lists");
+        collection
+            .replace_one(doc! {"name": &list.name}, list)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
 
-    use asdb_models::{
-        AsrankAsn, AsrankDegree, Coord, IPNetDBIX, IPNetDBPrefix, IPNetDBPrefixDetails,
-        InternetRegistry,
-    };
-    use ipnetwork::IpNetwork;
-    use itertools::Itertools;
-    use test_context::TestContext;
+    #[tracing::instrument]
+    async fn rename_list_metadata(&self, name: &str, new_name: &str) -> Result<()> {
+        let collection = self.collection::<AsList>("lists");
+        // the unique index on names rejects the new one if it's taken
+        let renamed = collection
+            .update_one(doc! {"name": name}, doc! {"$set": {"name": new_name}})
+            .await;
+        let renamed = match renamed {
+            Ok(x) if x.matched_count > 0 => Ok(()),
+            Ok(_) => collection
+                .insert_one(AsList {
+                    name: new_name.to_string(),
+                    ..Default::default()
+                })
+                .await
+                .map(|_| ()),
+            Err(e) => Err(e),
+        };
+        renamed.map_err(|e| {
+            if error::is_duplicate(&e) {
+                Error::ListExists(new_name.to_string())
+            } else {
+                Error::from(e)
+            }
+        })
+    }
+
+    #[tracing::instrument]
+    async fn delete_list_metadata(&self, name: &str) -> Result<()> {
+        let collection = self.collection::<AsList>("lists");
+        collection.delete_one(doc! {"name": name}).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_list_metadata(&self) -> Result<Vec<AsList>> {
+        let collection = self.collection::<AsList>("lists");
+        let res = collection.find(doc! {}).sort(doc! {"name": 1}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn count_list_members(&self) -> Result<BTreeMap<String, u64>> {
+        let collection = self.collection::<As>("asns");
+        let res = collection
+            .aggregate(lists::count_pipeline())
+            .with_type::<lists::ListCount>()
+            .await?;
+        let counts: Vec<lists::ListCount> = res.try_collect().await?;
+        Ok(counts
+            .into_iter()
+            .filter(|x| !x.name.trim().is_empty())
+            .map(|x| (x.name, x.count))
+            .collect())
+    }
+
+    #[tracing::instrument]
+    async fn find_list(&self, name: &str) -> Result<Option<AsList>> {
+        let collection = self.collection::<AsList>("lists");
+        if let Some(list) = collection.find_one(doc! {"name": name}).await? {
+            return Ok(Some(list));
+        }
+        // lists only named in user data have no metadata
+        let named = self
+            .collection::<Document>("asns")
+            .count_documents(doc! {"user_data.lists": name})
+            .limit(1)
+            .await?;
+        Ok((named > 0 && !name.trim().is_empty()).then(|| AsList {
+            name: name.to_string(),
+            ..Default::default()
+        }))
+    }
+
+    #[tracing::instrument]
+    async fn get_list_members(&self, name: &str) -> Result<Vec<u32>> {
+        let collection = self.collection::<lists::AsUserData>("asns");
+        let res = collection
+            .find(doc! {"user_data.lists": name})
+            .projection(doc! {"_id": 0, "asn": 1, "user_data": 1})
+            .sort(doc! {"asn": 1})
+            .await?;
+        let found: Vec<lists::AsUserData> = res.try_collect().await?;
+        Ok(found.into_iter().map(|x| x.asn).collect())
+    }
+
+    #[tracing::instrument(skip(asns))]
+    async fn add_list_members(&self, name: &str, asns: &[u32]) -> Result<()> {
+        self.ensure_user_data(doc! {"asn": {"$in": asns}}).await?;
+        let collection = self.collection::<As>("asns");
+        collection
+            .update_many(
+                doc! {"asn": {"$in": asns}},
+                doc! {"$addToSet": {"user_data.lists": name}},
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(asns))]
+    async fn remove_list_members(&self, name: &str, asns: &[u32]) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        collection
+            .update_many(
+                doc! {"asn": {"$in": asns}, "user_data.lists": name},
+                doc! {"$pull": {"user_data.lists": name}},
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn save_annotation_field(&self, field: &AnnotationField) -> Result<()> {
+        let collection = self.collection::<AnnotationField>("annotation_fields");
+        collection
+            .replace_one(doc! {"name": &field.name}, field)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_annotation_fields(&self) -> Result<Vec<AnnotationField>> {
+        let collection = self.collection::<AnnotationField>("annotation_fields");
+        let res = collection.find(doc! {}).sort(doc! {"name": 1}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn set_annotation(
+        &self,
+        asn: u32,
+        name: &str,
+        value: Option<&AnnotationValue>,
+    ) -> Result<()> {
+        self.ensure_user_data(doc! {"asn": asn}).await?;
+        let collection = self.collection::<As>("asns");
+        collection
+            .update_one(
+                doc! {"asn": asn},
+                doc! {"$pull": {"user_data.annotations": {"name": name}}},
+            )
+            .await?;
+        if let Some(value) = value {
+            let annotation = Annotation {
+                name: name.to_string(),
+                value: value.clone(),
+            };
+            let annotation = mongodb::bson::to_bson(&annotation)
+                .expect("Annotation should always be serializable to bson");
+            collection
+                .update_one(
+                    doc! {"asn": asn},
+                    doc! {"$push": {"user_data.annotations": annotation}},
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(prefixes))]
+    async fn insert_prefixes(&self, asn: u32, prefixes: &[IPNetDBPrefix]) -> Result<()> {
+        let collection = self.collection::<Prefix>("prefixes");
+        let mut announced: BTreeMap<String, Prefix> = BTreeMap::new();
+        for p in prefixes.iter().map(|p| prefix::from_announced(asn, p)) {
+            match announced.get_mut(&p.range.to_string()) {
+                Some(x) => prefix::merge(x, p),
+                None => {
+                    announced.insert(p.range.to_string(), p);
+                }
+            }
+        }
+        let ranges: Vec<&String> = announced.keys().collect();
+        let stored = collection
+            .distinct("range", doc! {"range": {"$in": ranges}})
+            .await?;
+        // the ones already announced by other ases get merged, the rest are written at once
+        let (merged, inserted): (Vec<Prefix>, Vec<Prefix>) = announced
+            .into_values()
+            .partition(|p| stored.contains(&Bson::String(p.range.to_string())));
+        if !inserted.is_empty() {
+            collection.insert_many(inserted).ordered(false).await?;
+        }
+        for p in merged {
+            let mut update = doc! {
+                "$addToSet": {
+                    "origins": { "$each": mongodb::bson::to_bson(&p.origins).expect("origins should always be serializable to bson") }
+                }
+            };
+            if let Some(details) = &p.details {
+                update.insert(
+                    "$set",
+                    doc! { "details": mongodb::bson::to_bson(details).expect("IPNetDBPrefixDetails should always be serializable to bson") },
+                );
+            } else {
+                update.insert("$setOnInsert", doc! { "details": Bson::Null });
+            }
+            collection
+                .update_one(doc! {"range": p.range.to_string()}, update)
+                .upsert(true)
+                .await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(ranges))]
+    async fn get_prefixes(&self, ranges: &[IpNetwork]) -> Result<Vec<Prefix>> {
+        let collection = self.collection::<Prefix>("prefixes");
+        let ranges: Vec<String> = ranges.iter().map(|x| x.to_string()).collect();
+        let res = collection.find(doc! {"range": {"$in": ranges}}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument(skip(blocks))]
+    async fn replace_rir_blocks(&self, blocks: &[RirBlock]) -> Result<()> {
+        let collection = self.collection::<RirBlock>("rir_blocks");
+        collection.delete_many(doc! {}).await?;
+        if !blocks.is_empty() {
+            collection.insert_many(blocks).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_rir_blocks(&self, ranges: &[IpNetwork]) -> Result<Vec<RirBlock>> {
+        let collection = self.collection::<RirBlock>("rir_blocks");
+        let ranges: Vec<String> = ranges.iter().map(|x| x.to_string()).collect();
+        let res = collection.find(doc! {"range": {"$in": ranges}}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument(skip(relationships))]
+    async fn replace_relationships(&self, relationships: &[AsRelationship]) -> Result<()> {
+        let collection = self.collection::<AsRelationship>("relationships");
+        collection.delete_many(doc! {}).await?;
+        if !relationships.is_empty() {
+            collection.insert_many(relationships).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_neighbors(&self, asn: u32, kind: Option<NeighborKind>) -> Result<Vec<AsNeighbor>> {
+        let links: Vec<AsRelationship> = self
+            .collection::<AsRelationship>("relationships")
+            .find(relationships::db_filter(asn, kind))
+            .await?
+            .try_collect()
+            .await?;
+        Ok(relationships::neighbors(&links, asn, kind))
+    }
+
+    #[tracing::instrument(skip(ixps))]
+    async fn replace_ixps(&self, ixps: &[Ixp]) -> Result<()> {
+        let collection = self.collection::<Ixp>("ixps");
+        collection.delete_many(doc! {}).await?;
+        if !ixps.is_empty() {
+            collection.insert_many(ixps).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_ixps(&self) -> Result<Vec<Ixp>> {
+        let collection = self.collection::<Ixp>("ixps");
+        Ok(collection
+            .find(doc! {})
+            .sort(doc! {"id": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    #[tracing::instrument(skip(facilities))]
+    async fn replace_facilities(&self, facilities: &[Facility]) -> Result<()> {
+        let collection = self.collection::<Facility>("facilities");
+        collection.delete_many(doc! {}).await?;
+        if !facilities.is_empty() {
+            collection.insert_many(facilities).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_facilities(&self) -> Result<Vec<Facility>> {
+        let collection = self.collection::<Facility>("facilities");
+        Ok(collection
+            .find(doc! {})
+            .sort(doc! {"id": 1})
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_organisation(&self, org_id: &str) -> Result<Option<Organisation>> {
+        let collection = self.collection::<Organisation>("organisations");
+        let filter = doc! {
+            "$or": [{"org_id": org_id}, {"asrank_org_id": org_id}, {"whois_org_id": org_id}]
+        };
+        Ok(collection.find_one(filter).await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_as_organisation(&self, asn: u32) -> Result<Option<Organisation>> {
+        let collection = self.collection::<Organisation>("organisations");
+        Ok(collection.find_one(doc! {"asns": asn}).await?)
+    }
+
+    #[tracing::instrument]
+    async fn save_organisation(&self, org: &Organisation) -> Result<()> {
+        let collection = self.collection::<Organisation>("organisations");
+        let asns =
+            mongodb::bson::to_bson(&org.asns).expect("asns should always be serializable to bson");
+        let others = doc! {"org_id": {"$ne": &org.org_id}, "asns": {"$in": asns.clone()}};
+        // only the organisations losing asns here can be left without any
+        let touched = collection.distinct("org_id", others.clone()).await?;
+        if !touched.is_empty() {
+            collection
+                .update_many(others, doc! {"$pull": {"asns": {"$in": asns}}})
+                .await?;
+            collection
+                .delete_many(doc! {"org_id": {"$in": touched}, "asns": {"$size": 0}})
+                .await?;
+        }
+        collection
+            .replace_one(doc! {"org_id": &org.org_id}, org)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_persons(&self, nic_hdls: &[String]) -> Result<Vec<Person>> {
+        let collection = self.collection::<Person>("persons");
+        let res = collection
+            .find(doc! {"nic_hdl": {"$in": nic_hdls}})
+            .sort(doc! {"nic_hdl": 1})
+            .await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_as_contacts(&self, asn: u32) -> Result<Vec<Person>> {
+        let collection = self.collection::<Person>("persons");
+        let filter = doc! {
+            "$or": [{"ases.admin_c": asn}, {"ases.tech_c": asn}, {"ases.abuse_c": asn}]
+        };
+        let res = collection.find(filter).sort(doc! {"nic_hdl": 1}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_org_contacts(&self, whois_org_id: &str) -> Result<Vec<Person>> {
+        let collection = self.collection::<Person>("persons");
+        let filter = doc! {
+            "$or": [
+                {"orgs.admin_c": whois_org_id},
+                {"orgs.tech_c": whois_org_id},
+                {"orgs.abuse_c": whois_org_id},
+            ]
+        };
+        let res = collection.find(filter).sort(doc! {"nic_hdl": 1}).await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn save_person(&self, person: &Person) -> Result<()> {
+        let collection = self.collection::<Person>("persons");
+        let filter = doc! {"nic_hdl": &person.nic_hdl};
+        if person.is_unused() {
+            collection.delete_one(filter).await?;
+        } else {
+            collection.replace_one(filter, person).upsert(true).await?;
+        }
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(asns))]
+    async fn snapshot_ases(&self, run_id: &str, asns: &[u32]) -> Result<()> {
+        let collection = self.collection::<As>("asns");
+        let pipeline = [
+            doc! {"$match": {"asn": {"$in": asns}}},
+            doc! {"$project": {
+                "_id": 0,
+                "run_id": {"$literal": run_id},
+                "asn": 1,
+                "data": "$$ROOT",
+            }},
+            doc! {"$unset": "data._id"},
+            doc! {"$merge": {
+                "into": "snapshots",
+                "on": ["run_id", "asn"],
+                "whenMatched": "replace",
+                "whenNotMatched": "insert",
+            }},
+        ];
+        collection.aggregate(pipeline).await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_run_snapshots(&self, run_id: &str) -> Result<Vec<AsSnapshot>> {
+        let collection = self.collection::<AsSnapshot>("snapshots");
+        let res = collection
+            .find(doc! {"run_id": run_id})
+            .sort(doc! {"asn": 1})
+            .await?;
+        Ok(res.try_collect().await?)
+    }
+
+    #[tracing::instrument]
+    async fn get_snapshot(&self, run_id: &str, asn: u32) -> Result<Option<AsSnapshot>> {
+        let collection = self.collection::<AsSnapshot>("snapshots");
+        Ok(collection
+            .find_one(doc! {"run_id": run_id, "asn": asn})
+            .await?)
+    }
+
+    #[tracing::instrument]
+    async fn save_dataset(&self, dataset: &Dataset) -> Result<()> {
+        let collection = self.collection::<Dataset>("datasets");
+        collection
+            .replace_one(doc! {"run_id": &dataset.run_id}, dataset)
+            .upsert(true)
+            .await?;
+        Ok(())
+    }
+
+    #[tracing::instrument]
+    async fn get_datasets(&self) -> Result<Vec<Dataset>> {
+        let collection = self.collection::<Dataset>("datasets");
+        let res = collection
+            .find(doc! {})
+            .sort(doc! {"started_at": -1, "run_id": -1})
+            .await?;
+        Ok(res.try_collect().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use asdb_models::{
+        AnnotationFilter, AnnotationKind, AnnotationOp, AsSortField, AsrankAsn, AsrankDegree,
+        Bound, Coord, IPNetDBIX, IPNetDBPrefix, IPNetDBPrefixDetails, InternetRegistry,
+        IxpPresence, Radius, Relationship, WhoIsOrg, WhoIsPerson,
+    };
+    use ipnetwork::IpNetwork;
+    use itertools::Itertools;
+    use test_context::TestContext;
 
     use super::*;
 
@@ -468,9 +1360,9 @@ mod tests {
         asdb.clear_database().await.unwrap();
         asdb.prepare_database().await.unwrap();
         asdb.insert_as(&tested_as()).await.unwrap();
-        let first_get = asdb.get_ases_page(0, 0).await.unwrap();
+        let first_get = asdb.get_ases(&[tested_as().asn]).await.unwrap();
         let second_insert = asdb.insert_as(&tested_as()).await;
-        let second_get = asdb.get_ases_page(0, 0).await.unwrap();
+        let second_get = asdb.get_ases(&[tested_as().asn]).await.unwrap();
 
         assert!(second_insert.is_err());
         assert_eq!(first_get.0.len(), second_get.0.len());
@@ -486,11 +1378,12 @@ mod tests {
         asdb.clear_database().await.unwrap();
         asdb.prepare_database().await.unwrap();
         asdb.insert_ases(&tested_ases()).await.unwrap();
+        let asns: Vec<u32> = tested_ases().iter().map(|x| x.asn).collect();
 
-        let (first_get, _) = asdb.get_ases_page(0, 0).await.unwrap();
+        let (first_get, _) = asdb.get_ases(&asns).await.unwrap();
 
         let second_insert = asdb.insert_ases(&tested_ases()).await;
-        let (second_get, _) = asdb.get_ases_page(0, 0).await.unwrap();
+        let (second_get, _) = asdb.get_ases(&asns).await.unwrap();
 
         assert!(second_insert.is_err());
         assert_eq!(first_get.len(), second_get.len());
@@ -515,9 +1408,9 @@ mod tests {
         asdb.prepare_database().await.unwrap();
         asdb.prepare_database().await.unwrap();
         asdb.insert_as(&tested_as()).await.unwrap();
-        let (before_prepare, _) = asdb.get_ases_page(0, 0).await.unwrap();
+        let (before_prepare, _) = asdb.get_ases(&[tested_as().asn]).await.unwrap();
         asdb.prepare_database().await.unwrap();
-        let (after_prepare, _) = asdb.get_ases_page(0, 0).await.unwrap();
+        let (after_prepare, _) = asdb.get_ases(&[tested_as().asn]).await.unwrap();
         assert_eq!(before_prepare.len(), after_prepare.len());
         assert_eq!(
             after_prepare.len(),
@@ -526,25 +1419,1030 @@ mod tests {
     }
 
     #[tokio::test(flavor = "multi_thread")]
-    async fn insert_then_get_ipnetdb_as() {
-        let tested_ipnetdb_as = ipnetdb_as;
-        let tested_as = as_with_asrank;
-        assert!(tested_as().ipnetdb_data.is_none());
-        let tested_asn = tested_as().asn;
+    async fn prepare_database_migrates_unversioned_records() {
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        let db = asdb.client.database(&asdb.database);
+        db.collection::<Document>(migrations::META_COLLECTION)
+            .drop()
+            .await
+            .unwrap();
+        db.collection::<Document>("asns")
+            .insert_one(doc! {"asn": 5550, "asrank_data": null, "ipnetdb_data": null})
+            .await
+            .unwrap();
+        assert!(asdb.get_as(5550).await.is_err());
 
+        asdb.prepare_database().await.unwrap();
+
+        assert_eq!(asdb.get_as(5550).await.unwrap().asn, 5550);
+        assert_eq!(
+            migrations::get_version(&db).await.unwrap(),
+            migrations::SCHEMA_VERSION
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn user_data_edits_are_recorded_and_reverted() {
+        let tested_as = as_with_asrank();
         let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
         let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
-        // first insert normal As so it can be later updated with ipnetdb data
-        asdb.insert_as(&tested_as()).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+
+        asdb.edit_user_data(
+            tested_as.asn,
+            Some(vec!["watch".to_string()]),
+            None,
+            Some("10.0.0.1"),
+        )
+        .await
+        .unwrap();
+        asdb.edit_user_data(tested_as.asn, None, Some("note".to_string()), None)
+            .await
+            .unwrap();
 
-        asdb.insert_ipnetdb_asn(tested_asn, &tested_ipnetdb_as())
+        let history = asdb.get_user_data_history(tested_as.asn).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].new.comment.as_deref(), Some("note"));
+        assert_eq!(history[1].editor.as_deref(), Some("10.0.0.1"));
+
+        asdb.revert_user_data(tested_as.asn, &history[0].id, None)
             .await
             .unwrap();
-        let as_ = asdb.get_as(tested_asn).await.unwrap();
-        assert!(as_.ipnetdb_data.is_some());
-        println!("{:#?}", as_);
-        let retrieved_ipnetdb_data = as_.ipnetdb_data.unwrap();
-        assert_eq!(retrieved_ipnetdb_data, tested_ipnetdb_as());
+        let user_data = asdb.get_user_data(tested_as.asn).await.unwrap();
+        assert_eq!(user_data.lists, ["watch"]);
+        assert_eq!(user_data.comment, None);
+        assert_eq!(
+            asdb.get_user_data_history(tested_as.asn)
+                .await
+                .unwrap()
+                .len(),
+            3
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lists_are_filled_renamed_and_counted() {
+        let tested_as = as_with_asrank();
+        let other_as = As {
+            asn: tested_as.asn + 1,
+            ..tested_as.clone()
+        };
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_ases(&[tested_as.clone(), other_as.clone()])
+            .await
+            .unwrap();
+        asdb.create_list(&AsList {
+            name: "watch".to_string(),
+            description: Some("to keep an eye on".to_string()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let added = asdb
+            .add_to_list("watch", &[tested_as.asn, other_as.asn], None)
+            .await
+            .unwrap();
+        assert_eq!(added.len(), 2);
+        asdb.rename_list("watch", "tier1", None).await.unwrap();
+        asdb.remove_from_list("tier1", &[other_as.asn], None)
+            .await
+            .unwrap();
+
+        let lists = asdb.get_lists().await.unwrap();
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].list.name, "tier1");
+        assert_eq!(
+            lists[0].list.description.as_deref(),
+            Some("to keep an eye on")
+        );
+        assert_eq!(lists[0].count, 1);
+        assert_eq!(
+            asdb.get_list_members("tier1").await.unwrap(),
+            [tested_as.asn]
+        );
+        asdb.delete_list("tier1", None).await.unwrap();
+        assert!(asdb.get_lists().await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn lists_are_not_renamed_onto_taken_names() {
+        let tested_as = as_with_asrank();
+        let list = |name: &str| AsList {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.create_list(&list("watch")).await.unwrap();
+        asdb.create_list(&list("tier1")).await.unwrap();
+        asdb.add_to_list("watch", &[tested_as.asn], None)
+            .await
+            .unwrap();
+        asdb.add_list_members("pl", &[tested_as.asn]).await.unwrap();
+
+        assert_eq!(asdb.find_list("pl").await.unwrap(), Some(list("pl")));
+        assert_eq!(asdb.find_list("nordics").await.unwrap(), None);
+        assert!(matches!(
+            asdb.rename_list("watch", "tier1", None).await,
+            Err(Error::ListExists(_))
+        ));
+        // a rename racing the check above still fails on the unique index
+        assert!(matches!(
+            asdb.rename_list_metadata("watch", "tier1").await,
+            Err(Error::ListExists(_))
+        ));
+        assert_eq!(
+            asdb.get_list_members("watch").await.unwrap(),
+            [tested_as.asn]
+        );
+        asdb.rename_list_metadata("pl", "poland").await.unwrap();
+        assert_eq!(
+            asdb.find_list("poland").await.unwrap(),
+            Some(list("poland"))
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn annotations_are_set_and_filtered() {
+        let tested_as = as_with_asrank();
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.define_annotation_field(&AnnotationField {
+            name: "priority".to_string(),
+            kind: AnnotationKind::Number,
+            description: None,
+        })
+        .await
+        .unwrap();
+        let filters = |op| AsFilters {
+            annotations: vec![AnnotationFilter {
+                name: "priority".to_string(),
+                op,
+            }],
+            ..Default::default()
+        };
+
+        asdb.edit_annotation(
+            tested_as.asn,
+            "priority",
+            Some(AnnotationValue::Number(2.0)),
+            None,
+        )
+        .await
+        .unwrap();
+        let user_data = asdb
+            .edit_annotation(
+                tested_as.asn,
+                "priority",
+                Some(AnnotationValue::Number(3.0)),
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(user_data.annotations.len(), 1);
+        let gte = filters(AnnotationOp::Gte(AnnotationValue::Number(3.0)));
+        assert_eq!(asdb.count_ases_filtered(&gte).await.unwrap(), 1);
+        let lt = filters(AnnotationOp::Lt(AnnotationValue::Number(3.0)));
+        assert_eq!(asdb.count_ases_filtered(&lt).await.unwrap(), 0);
+        let missing = filters(AnnotationOp::Missing);
+        assert_eq!(asdb.count_ases_filtered(&missing).await.unwrap(), 0);
+        assert_eq!(
+            asdb.get_annotation_fields().await.unwrap()[0].name,
+            "priority"
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn upsert_reports_counts_and_keeps_other_sources() {
+        let tested_as = as_with_asrank();
+        let new_as = As {
+            asn: tested_as.asn + 1,
+            ..tested_as.clone()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.insert_ipnetdb_asn(tested_as.asn, &ipnetdb_as())
+            .await
+            .unwrap();
+        asdb.update_user_data(tested_as.asn, None, Some("note".to_string()))
+            .await
+            .unwrap();
+
+        let stats = asdb
+            .upsert_ases(&[tested_as.clone(), new_as.clone()], AsSource::Asrank)
+            .await
+            .unwrap();
+        assert_eq!(stats.inserted, 1);
+        assert_eq!(stats.unchanged, 1);
+
+        let mut changed = tested_as.clone();
+        changed.asrank_data.as_mut().unwrap().rank += 1;
+        let stats = asdb
+            .upsert_ases(&[changed], AsSource::Asrank)
+            .await
+            .unwrap();
+        assert_eq!(stats.updated, 1);
+        let stored = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(
+            stored.asrank_data.unwrap().rank,
+            tested_as.asrank_data.unwrap().rank + 1
+        );
+        assert!(stored.ipnetdb_data.is_some());
+        assert_eq!(stored.user_data.unwrap().comment.as_deref(), Some("note"));
+        assert!(asdb.get_as(new_as.asn).await.unwrap().location.is_some());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn filters_use_indexes() {
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&as_with_asrank()).await.unwrap();
+        let db = asdb.client.database(&asdb.database);
+
+        let filters = [
+            AsFilters {
+                country_iso: Some("PL".to_string()),
+                ..Default::default()
+            },
+            AsFilters {
+                country_iso: Some("PL".to_string()),
+                exclude_country: true,
+                ..Default::default()
+            },
+            AsFilters {
+                rank: Some((1, 100)),
+                ..Default::default()
+            },
+            AsFilters {
+                addresses: Some((256, 65536)),
+                ..Default::default()
+            },
+            AsFilters {
+                has_org: Some(true),
+                ..Default::default()
+            },
+            AsFilters {
+                category: vec!["Computer and Information Technology".to_string()],
+                ..Default::default()
+            },
+            AsFilters {
+                lists: vec!["watch".to_string()],
+                ..Default::default()
+            },
+            AsFilters {
+                bounds: Some(Bound {
+                    north_east: Coord {
+                        lat: 55.0,
+                        lon: 180.0,
+                    },
+                    south_west: Coord {
+                        lat: 49.0,
+                        lon: -180.0,
+                    },
+                }),
+                ..Default::default()
+            },
+            AsFilters {
+                radius: Some(Radius {
+                    center: Coord {
+                        lat: 54.37,
+                        lon: 18.56,
+                    },
+                    meters: 10_000.0,
+                }),
+                ..Default::default()
+            },
+            AsFilters {
+                text: Some("gdansk".to_string()),
+                ..Default::default()
+            },
+            AsFilters {
+                annotations: vec![AnnotationFilter {
+                    name: "priority".to_string(),
+                    op: AnnotationOp::Gte(AnnotationValue::Number(3.0)),
+                }],
+                ..Default::default()
+            },
+            AsFilters {
+                registry: Some(InternetRegistry::RIPE),
+                ..Default::default()
+            },
+            AsFilters {
+                allocated_before: Some("2000-01-01".to_string()),
+                ..Default::default()
+            },
+            AsFilters {
+                allocated_after: Some("2000-01-01".to_string()),
+                ..Default::default()
+            },
+        ];
+        for f in filters {
+            let explain = db
+                .run_command(doc! {
+                    "explain": {"find": "asns", "filter": Asdb::create_db_filter(&f)},
+                    "verbosity": "queryPlanner",
+                })
+                .await
+                .unwrap();
+            let stages = indexes::plan_stages(&explain);
+            assert!(!stages.is_empty(), "no plan for {f:?}");
+            assert!(
+                !stages.iter().any(|x| x == "COLLSCAN"),
+                "{f:?} scans the collection: {stages:?}"
+            );
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn index_sync_keeps_indexes_created_by_hand() {
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        let asns = asdb.collection::<Document>("asns");
+        asns.create_index(
+            mongodb::IndexModel::builder()
+                .keys(doc! {"peeringdb_data.id": 1})
+                .build(),
+        )
+        .await
+        .unwrap();
+        // the way the databases released before the index sync have it
+        asns.drop_index("asdb_asn_1").await.unwrap();
+        asns.create_index(
+            mongodb::IndexModel::builder()
+                .keys(doc! {"asn": 1})
+                .options(
+                    mongodb::options::IndexOptions::builder()
+                        .unique(true)
+                        .build(),
+                )
+                .build(),
+        )
+        .await
+        .unwrap();
+
+        asdb.prepare_database().await.unwrap();
+        let names = asns.list_index_names().await.unwrap();
+        assert!(names.iter().any(|x| x == "peeringdb_data.id_1"));
+        assert!(names.iter().any(|x| x == "asdb_asn_1"));
+        assert!(!names.iter().any(|x| x == "asn_1"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sorted_pages_continue_after_token() {
+        let tested_as = as_with_asrank();
+        let ases: Vec<As> = [(5550, 3), (1299, 1), (3356, 1), (174, 2)]
+            .into_iter()
+            .map(|(asn, total)| {
+                let mut a = As {
+                    asn,
+                    ..tested_as.clone()
+                };
+                a.asrank_data.as_mut().unwrap().degree.total = total;
+                a
+            })
+            .collect();
+        let sort = AsSort {
+            field: AsSortField::Degree,
+            descending: false,
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_ases(&ases).await.unwrap();
+
+        let mut asns = vec![];
+        let mut after = None;
+        loop {
+            let page = asdb
+                .get_ases_sorted(&AsFilters::default(), sort, 3, after.as_deref())
+                .await
+                .unwrap();
+            asns.extend(page.ases.iter().map(|x| x.asn));
+            after = page.next;
+            if after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(asns, [1299, 3356, 174, 5550]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn listings_skip_ases_without_asrank_data() {
+        let tested_as = as_with_asrank();
+        let unranked_as = As {
+            asn: 1,
+            ..Default::default()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_ases(&[tested_as.clone(), unranked_as])
+            .await
+            .unwrap();
+
+        let (page, count) = asdb.get_ases_page(1, 0).await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            page.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            [tested_as.asn]
+        );
+        let filters = AsFilters::default();
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+        let filtered = asdb.get_ases_filtered(&filters).await.unwrap();
+        assert_eq!(
+            filtered.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            [tested_as.asn]
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sorted_pages_fail_on_malformed_documents() {
+        let sort = AsSort {
+            field: AsSortField::Rank,
+            descending: false,
+        };
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.collection::<Document>("asns")
+            .insert_one(doc! {"asn": 64512, "asrank_data": {"rank": 1.5}})
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            asdb.get_ases_sorted(&AsFilters::default(), sort, 3, None)
+                .await,
+            Err(Error::InvalidDocument(_))
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stats_group_filtered_ases() {
+        let tested_as = as_with_asrank();
+        let mut top_as = As {
+            asn: 1299,
+            ..tested_as.clone()
+        };
+        top_as.asrank_data.as_mut().unwrap().rank = 2;
+        let unranked_as = As {
+            asn: 64512,
+            ..Default::default()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_ases(&[tested_as.clone(), top_as.clone(), unranked_as])
+            .await
+            .unwrap();
+        asdb.insert_ipnetdb_asn(tested_as.asn, &ipnetdb_as())
+            .await
+            .unwrap();
+
+        let stats = asdb
+            .get_stats(&AsFilters::default(), StatsGroup::RankBucket)
+            .await
+            .unwrap();
+        let groups: Vec<(Option<&str>, u64)> = stats
+            .groups
+            .iter()
+            .map(|x| (x.key.as_deref(), x.totals.ases))
+            .collect();
+        assert_eq!(
+            groups,
+            [(None, 1), (Some("1-10"), 1), (Some("1001-10000"), 1)]
+        );
+        assert_eq!(stats.total.ases, 3);
+        assert_eq!(stats.total.addresses, 2 * 65536);
+
+        let filters = AsFilters {
+            country_iso: Some("PL".to_string()),
+            ..Default::default()
+        };
+        let stats = asdb
+            .get_stats(&filters, StatsGroup::Registry)
+            .await
+            .unwrap();
+        assert_eq!(stats.groups.len(), 2);
+        assert_eq!(stats.groups[1].key.as_deref(), Some("RIPE"));
+        assert_eq!(stats.total.prefixes, 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn insert_then_get_ipnetdb_as() {
+        let tested_ipnetdb_as = ipnetdb_as;
+        let tested_as = as_with_asrank;
+        assert!(tested_as().ipnetdb_data.is_none());
+        let tested_asn = tested_as().asn;
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        // first insert normal As so it can be later updated with ipnetdb data
+        asdb.insert_as(&tested_as()).await.unwrap();
+
+        asdb.insert_ipnetdb_asn(tested_asn, &tested_ipnetdb_as())
+            .await
+            .unwrap();
+        let as_ = asdb.get_as(tested_asn).await.unwrap();
+        assert!(as_.ipnetdb_data.is_some());
+        println!("{:#?}", as_);
+        let retrieved_ipnetdb_data = as_.ipnetdb_data.unwrap();
+        assert_eq!(retrieved_ipnetdb_data, tested_ipnetdb_as());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn insert_prefixes_then_lookup_ip() {
+        let tested_ipnetdb_as = ipnetdb_as;
+        let tested_as = as_with_asrank;
+        let tested_asn = tested_as().asn;
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as()).await.unwrap();
+        asdb.insert_prefixes(tested_asn, &tested_ipnetdb_as().ipv4_prefixes)
+            .await
+            .unwrap();
+        // inserting again must not duplicate prefixes nor origins
+        asdb.insert_prefixes(tested_asn, &tested_ipnetdb_as().ipv4_prefixes)
+            .await
+            .unwrap();
+
+        let found = asdb
+            .lookup_ip(IpAddr::V4(Ipv4Addr::new(153, 19, 64, 251)))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.prefix.range.to_string(), "153.19.0.0/16");
+        assert_eq!(found.prefix.origins.len(), 5);
+        assert!(found.prefix.details.is_some());
+        assert_eq!(found.ases.len(), 1);
+        assert_eq!(found.ases[0].asn, tested_asn);
+
+        let not_found = asdb
+            .lookup_ip(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)))
+            .await
+            .unwrap();
+        assert!(not_found.is_none());
+
+        // new prefix announced twice in a single call
+        let mut announced = tested_ipnetdb_as().ipv4_prefixes[0].clone();
+        announced.range = "192.168.0.0/16".parse().unwrap();
+        asdb.insert_prefixes(tested_asn, &[announced.clone(), announced])
+            .await
+            .unwrap();
+        let found = asdb
+            .lookup_ip(IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.prefix.range.to_string(), "192.168.0.0/16");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn replace_relationships_then_get_neighbors() {
+        let link = |a, b, kind| AsRelationship {
+            a,
+            b,
+            kind,
+            source: Some("bgp".to_string()),
+        };
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.replace_relationships(&[link(1, 2, Relationship::ProviderCustomer)])
+            .await
+            .unwrap();
+        asdb.replace_relationships(&[
+            link(1299, 5550, Relationship::ProviderCustomer),
+            link(3356, 1299, Relationship::PeerPeer),
+            link(174, 1299, Relationship::ProviderCustomer),
+        ])
+        .await
+        .unwrap();
+
+        let customers = asdb
+            .get_neighbors(1299, Some(NeighborKind::Customer))
+            .await
+            .unwrap();
+        assert_eq!(
+            customers,
+            [AsNeighbor {
+                asn: 5550,
+                kind: NeighborKind::Customer
+            }]
+        );
+        let all = asdb.get_neighbors(1299, None).await.unwrap();
+        assert_eq!(all.iter().map(|x| x.asn).collect_vec(), [174, 3356, 5550]);
+        // replaced by the second call
+        assert!(asdb.get_neighbors(1, None).await.unwrap().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn peeringdb_data_ixps_and_facilities_are_stored() {
+        let tested_as = as_with_asrank();
+        let net = PeeringdbNet {
+            id: 1,
+            name: "TASK".to_string(),
+            policy_general: Some("Open".to_string()),
+            info_traffic: None,
+            info_type: Some("Educational/Research".to_string()),
+            ixps: vec![IxpPresence {
+                ix_id: 10,
+                name: "TPIX".to_string(),
+                speed: 10000,
+                ipaddr4: Some("195.149.232.1".to_string()),
+                ipaddr6: None,
+                is_rs_peer: true,
+            }],
+            facilities: vec![],
+        };
+        let ixp = |id, name: &str| Ixp {
+            id,
+            name: name.to_string(),
+            name_long: None,
+            city: Some("Warsaw".to_string()),
+            country: Some("PL".to_string()),
+            website: None,
+            coordinates: Some(Coord {
+                lat: 52.23,
+                lon: 21.01,
+            }),
+            networks: 1,
+        };
+        let facility = Facility {
+            id: 20,
+            name: "Atman WAW-1".to_string(),
+            city: None,
+            country: Some("PL".to_string()),
+            website: None,
+            coordinates: None,
+            networks: 0,
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.insert_peeringdb_net(tested_as.asn, &net)
+            .await
+            .unwrap();
+        asdb.replace_ixps(&[ixp(11, "old")]).await.unwrap();
+        asdb.replace_ixps(&[ixp(12, "EPIX"), ixp(10, "TPIX")])
+            .await
+            .unwrap();
+        asdb.replace_facilities(std::slice::from_ref(&facility))
+            .await
+            .unwrap();
+
+        let stored = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(stored.peeringdb_data, Some(net));
+        let ixps = asdb.get_ixps().await.unwrap();
+        assert_eq!(ixps, [ixp(10, "TPIX"), ixp(12, "EPIX")]);
+        assert_eq!(asdb.get_facilities().await.unwrap(), [facility]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rir_delegations_and_blocks_are_stored() {
+        let tested_as = as_with_asrank();
+        let delegation = RirDelegation {
+            registry: InternetRegistry::RIPE,
+            country: Some("PL".to_string()),
+            date: Some("1998-06-04".to_string()),
+            status: "allocated".to_string(),
+            opaque_id: Some("a1b2c3".to_string()),
+        };
+        let block = |range: &str| RirBlock {
+            range: range.parse().unwrap(),
+            delegation: delegation.clone(),
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        let updated = asdb
+            .insert_rir_asn_delegation(tested_as.asn - 1, 2, &delegation)
+            .await
+            .unwrap();
+        assert_eq!(updated, [tested_as.asn]);
+        asdb.replace_rir_blocks(&[block("10.0.0.0/8")])
+            .await
+            .unwrap();
+        asdb.replace_rir_blocks(&[block("153.19.0.0/16"), block("2001:4c68::/32")])
+            .await
+            .unwrap();
+
+        let stored = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(stored.rir_data, Some(delegation.clone()));
+        let filters = AsFilters {
+            registry: Some(InternetRegistry::RIPE),
+            allocated_after: Some("1998-01-01".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+        let filters = AsFilters {
+            registry: Some(InternetRegistry::ARIN),
+            ..Default::default()
+        };
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 0);
+        let filters = AsFilters {
+            allocated_before: Some("1998-06-04".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 0);
+
+        let lookup = asdb
+            .lookup_rir_block("153.19.64.251".parse().unwrap())
+            .await
+            .unwrap();
+        assert_eq!(lookup, Some(block("153.19.0.0/16")));
+        let lookup = asdb.lookup_rir_block("10.0.0.1".parse().unwrap()).await;
+        assert_eq!(lookup.unwrap(), None);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn asrank_and_whois_organisations_merge() {
+        let mut tested_as = as_with_asrank();
+        tested_as.asrank_data.as_mut().unwrap().organization_id = Some("org1".to_string());
+        let whois_org = WhoIsOrg {
+            org_id: "ORG-TUoG1-RIPE".to_string(),
+            org_name: "TU Gdansk".to_string(),
+            ..Default::default()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.prepare_database().await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.link_whois_organisation(tested_as.asn, &whois_org)
+            .await
+            .unwrap();
+        asdb.update_asrank_organisations(&[tested_as.clone()])
+            .await
+            .unwrap();
+
+        let org = asdb
+            .get_organisation("ORG-TUoG1-RIPE")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(org.org_id, "org1");
+        assert_eq!(org.asns, [tested_as.asn]);
+        assert_eq!(org.whois, Some(whois_org));
+        let as_org = asdb.get_as_organisation(tested_as.asn).await.unwrap();
+        assert_eq!(as_org, Some(org));
+        let count = asdb
+            .client
+            .database(&asdb.database)
+            .collection::<Organisation>("organisations")
+            .count_documents(doc! {})
+            .await
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn geo_filters_use_location() {
+        let tested_as = as_with_asrank();
+        let center = Coord {
+            lat: 54.35,
+            lon: 18.65,
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.prepare_database().await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        assert!(asdb.get_as(tested_as.asn).await.unwrap().location.is_some());
+
+        let mut filters = AsFilters {
+            radius: Some(Radius {
+                center: center.clone(),
+                meters: 10_000.0,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+        filters.radius.as_mut().unwrap().meters = 1_000.0;
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 0);
+
+        filters.radius = None;
+        filters.polygon = Some(vec![
+            Coord {
+                lat: 50.0,
+                lon: 15.0,
+            },
+            Coord {
+                lat: 50.0,
+                lon: 20.0,
+            },
+            Coord {
+                lat: 55.0,
+                lon: 20.0,
+            },
+            Coord {
+                lat: 55.0,
+                lon: 15.0,
+            },
+        ]);
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+        filters.polygon.as_mut().unwrap().reverse();
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn search_uses_text_index() {
+        let tested_as = as_with_asrank();
+        let other_as = As {
+            asn: tested_as.asn + 1,
+            ..tested_as.clone()
+        };
+        let whois = WhoIsAsn {
+            descr: vec!["Gdańsk University of Technology".to_string()],
+            ..Default::default()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.prepare_database().await.unwrap();
+        asdb.insert_ases(&[tested_as.clone(), other_as.clone()])
+            .await
+            .unwrap();
+        asdb.update_whois_data(tested_as.asn, &whois).await.unwrap();
+
+        let hits = asdb.search("test gdansk", 10).await.unwrap();
+        assert_eq!(
+            hits.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            [tested_as.asn, other_as.asn]
+        );
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(hits[0].name.as_deref(), Some("Test Name"));
+        let filters = AsFilters {
+            text: Some("university".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn whois_contacts_point_back_to_ases() {
+        let tested_as = as_with_asrank();
+        let contact = WhoIsPerson {
+            nic_hdl: "JD1-RIPE".to_string(),
+            name: "John Doe".to_string(),
+            ..Default::default()
+        };
+        let whois = WhoIsAsn {
+            admin_c: vec![contact.nic_hdl.clone()],
+            tech_c: vec![contact.nic_hdl.clone()],
+            contacts: vec![contact.clone()],
+            ..Default::default()
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.update_whois_data(tested_as.asn, &whois).await.unwrap();
+
+        let person = asdb.get_person("JD1-RIPE").await.unwrap().unwrap();
+        assert_eq!(person.contact, contact);
+        assert_eq!(person.ases.admin_c, [tested_as.asn]);
+        assert_eq!(person.ases.tech_c, [tested_as.asn]);
+        let managed = asdb.get_managed_ases("JD1-RIPE").await.unwrap();
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].asn, tested_as.asn);
+        let resolved = asdb.get_whois_data(tested_as.asn).await.unwrap().unwrap();
+        assert_eq!(resolved, whois);
+
+        asdb.update_whois_data(tested_as.asn, &WhoIsAsn::default())
+            .await
+            .unwrap();
+        assert!(asdb.get_person("JD1-RIPE").await.unwrap().is_none());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn promoted_staging_keeps_user_data_and_rolls_back() {
+        let tested_as = as_with_asrank();
+        let mut reranked = tested_as.clone();
+        reranked.asrank_data.as_mut().unwrap().rank += 1;
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.update_user_data(tested_as.asn, Some(vec!["watch".to_string()]), None)
+            .await
+            .unwrap();
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_as(&reranked).await.unwrap();
+        // still the current data until it's promoted
+        let current = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(current.asrank_data, tested_as.asrank_data);
+        let count = asdb.promote(staging, true).await.unwrap();
+
+        assert_eq!(count, 1);
+        let promoted = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(promoted.asrank_data, reranked.asrank_data);
+        assert_eq!(promoted.user_data.unwrap().lists, ["watch"]);
+        asdb.rollback().await.unwrap();
+        let rolled_back = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(rolled_back.asrank_data, tested_as.asrank_data);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn promote_drops_user_data_of_ases_missing_from_the_import() {
+        let tested_as = as_with_asrank();
+        let mut dropped = tested_as.clone();
+        dropped.asn += 1;
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.insert_as(&dropped).await.unwrap();
+        asdb.update_user_data(dropped.asn, Some(vec!["watch".to_string()]), None)
+            .await
+            .unwrap();
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_as(&tested_as).await.unwrap();
+        asdb.promote(staging, true).await.unwrap();
+
+        assert!(asdb.get_as(dropped.asn).await.is_err());
+        let (page, count) = asdb.get_ases_page(0, 0).await.unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(
+            page.iter().map(|a| a.asn).collect::<Vec<_>>(),
+            [tested_as.asn]
+        );
+        let filtered = asdb.get_ases_filtered(&AsFilters::default()).await.unwrap();
+        assert_eq!(filtered.len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn staged_ases_without_asn_are_not_promoted() {
+        let tested_as = as_with_asrank();
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_as(&tested_as).await.unwrap();
+        staging
+            .collection::<Document>("asns")
+            .insert_one(doc! {"name": "no asn"})
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            asdb.promote(staging, true).await,
+            Err(Error::InvalidStaging(_))
+        ));
+        assert!(matches!(
+            asdb.rollback().await,
+            Err(Error::NoPreviousGeneration)
+        ));
+        assert_eq!(asdb.get_as(tested_as.asn).await.unwrap(), tested_as);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn rollback_after_partial_promote_keeps_the_collections_it_did_not_replace() {
+        let tested_as = as_with_asrank();
+        let ixp = |id: u32| Ixp {
+            id,
+            name: format!("IX{id}"),
+            name_long: None,
+            city: None,
+            country: None,
+            website: None,
+            coordinates: None,
+            networks: 0,
+        };
+
+        let context = TestContext::new(TESTED_CONN_STR).await.unwrap();
+        let asdb = Asdb::new(TESTED_CONN_STR, &context.db_name).await.unwrap();
+        asdb.insert_as(&tested_as).await.unwrap();
+        asdb.replace_ixps(&[ixp(1)]).await.unwrap();
+        // full import replacing both the ases and the ixps
+        let mut full = tested_as.clone();
+        full.asrank_data.as_mut().unwrap().rank += 1;
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_as(&full).await.unwrap();
+        staging.replace_ixps(&[ixp(2)]).await.unwrap();
+        asdb.promote(staging, true).await.unwrap();
+        // partial import of the ases only
+        let mut partial = full.clone();
+        partial.asrank_data.as_mut().unwrap().rank += 1;
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_as(&partial).await.unwrap();
+        asdb.promote(staging, true).await.unwrap();
+
+        asdb.rollback().await.unwrap();
+        let rolled_back = asdb.get_as(tested_as.asn).await.unwrap();
+        assert_eq!(rolled_back.asrank_data, full.asrank_data);
+        assert_eq!(asdb.get_ixps().await.unwrap(), [ixp(2)]);
+        // the next import doesn't touch the live ixps either
+        asdb.staging().await.unwrap();
+        assert_eq!(asdb.get_ixps().await.unwrap(), [ixp(2)]);
     }
 
     fn ipnetdb_as() -> IPNetDBAsn {
@@ -643,6 +2541,7 @@ mod tests {
             organization: Some(
                 "Technical University of Gdansk, Academic Computer Center TASK".to_string(),
             ),
+            organization_id: None,
             country_iso: String::from("PL"),
             country_name: String::from("Poland"),
             coordinates: Coord {
@@ -0,0 +1,31 @@
+//! Documents read from the asns collection when managing user lists.
+
+use asdb_models::UserData;
+use mongodb::bson::{Document, doc};
+use serde::Deserialize;
+
+/// User data of an AS, the rest of its document isn't fetched
+#[derive(Deserialize)]
+pub(crate) struct AsUserData {
+    pub asn: u32,
+    pub user_data: Option<UserData>,
+}
+
+/// Result of [`count_pipeline`]
+#[derive(Deserialize)]
+pub(crate) struct ListCount {
+    #[serde(rename = "_id")]
+    pub name: String,
+    pub count: u64,
+}
+
+/// Aggregation on asns counting the members of every list
+pub(crate) fn count_pipeline() -> [Document; 4] {
+    [
+        doc! {"$match": {"user_data.lists.0": {"$exists": true}}},
+        // the same list could be saved twice for one AS
+        doc! {"$project": {"lists": {"$setUnion": ["$user_data.lists"]}}},
+        doc! {"$unwind": "$lists"},
+        doc! {"$group": {"_id": "$lists", "count": {"$sum": 1}}},
+    ]
+}
@@ -0,0 +1,1966 @@
+//! In-memory implementation of [`Storage`], mainly for tests.
+
+use std::{collections::BTreeMap, sync::RwLock};
+
+use asdb_models::{
+    Annotation, AnnotationField, AnnotationValue, As, AsFilters, AsForFrontend, AsList, AsNeighbor,
+    AsPage, AsRelationship, AsSnapshot, AsSort, AsSource, AsStats, Dataset, Facility,
+    GeocodedAddress, IPNetDBAsn, IPNetDBPrefix, InternetRegistry, Ixp, NeighborKind, Organisation,
+    PeeringdbNet, Person, Prefix, RirBlock, RirDelegation, SearchHit, StanfordASdbCategory,
+    StatsGroup, UpsertStats, UserData, UserDataEdit, WhoIsAsn,
+};
+use futures::{Stream, stream};
+use ipnetwork::IpNetwork;
+use itertools::Itertools;
+
+use crate::{
+    Error, Result, Storage, annotations, generation, geo, page, prefix, relationships, search,
+    stats,
+};
+
+/// Keeps all ases in a map ordered by asn. Filtering mirrors the semantics of the
+/// MongoDB queries built by [`crate::Asdb`].
+#[derive(Default)]
+pub struct InMemoryAsdb {
+    ases: RwLock<BTreeMap<u32, As>>,
+    prefixes: RwLock<BTreeMap<IpNetwork, Prefix>>,
+    organisations: RwLock<BTreeMap<String, Organisation>>,
+    persons: RwLock<BTreeMap<String, Person>>,
+    /// keyed by (a, b)
+    relationships: RwLock<BTreeMap<(u32, u32), AsRelationship>>,
+    /// keyed by PeeringDB id
+    ixps: RwLock<BTreeMap<u32, Ixp>>,
+    /// keyed by PeeringDB id
+    facilities: RwLock<BTreeMap<u32, Facility>>,
+    rir_blocks: RwLock<BTreeMap<IpNetwork, RirBlock>>,
+    /// keyed by (run id, asn)
+    snapshots: RwLock<BTreeMap<(String, u32), As>>,
+    /// keyed by run id
+    datasets: RwLock<BTreeMap<String, Dataset>>,
+    /// in the order they were made
+    user_data_edits: RwLock<Vec<UserDataEdit>>,
+    /// keyed by name
+    lists: RwLock<BTreeMap<String, AsList>>,
+    /// keyed by name
+    annotation_fields: RwLock<BTreeMap<String, AnnotationField>>,
+    /// data replaced by the last [`Storage::promote`]
+    previous: RwLock<Option<Generation>>,
+}
+
+/// Collections replaced by a staged import
+struct Generation {
+    ases: BTreeMap<u32, As>,
+    prefixes: BTreeMap<IpNetwork, Prefix>,
+    organisations: BTreeMap<String, Organisation>,
+    persons: BTreeMap<String, Person>,
+    relationships: BTreeMap<(u32, u32), AsRelationship>,
+    ixps: BTreeMap<u32, Ixp>,
+    facilities: BTreeMap<u32, Facility>,
+    rir_blocks: BTreeMap<IpNetwork, RirBlock>,
+}
+
+/// Puts `staged` in place of `live` unless it's empty and returns what was there before
+fn replace<K: Clone, V: Clone>(
+    live: &RwLock<BTreeMap<K, V>>,
+    staged: BTreeMap<K, V>,
+) -> BTreeMap<K, V> {
+    let mut live = live.write().unwrap();
+    if staged.is_empty() {
+        live.clone()
+    } else {
+        std::mem::replace(&mut live, staged)
+    }
+}
+
+impl std::fmt::Debug for InMemoryAsdb {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "InMemoryAsdb {{ ases: {} }}",
+            self.ases.read().unwrap().len()
+        )
+    }
+}
+
+impl InMemoryAsdb {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` on the stored record for `asn`, does nothing if there is none
+    fn update(&self, asn: u32, f: impl FnOnce(&mut As)) {
+        if let Some(a) = self.ases.write().unwrap().get_mut(&asn) {
+            f(a);
+        }
+    }
+}
+
+/// Checks single record against the filters the same way `Asdb::create_db_filter` does
+pub(crate) fn matches_filters(a: &As, filters: &AsFilters) -> bool {
+    let asrank = a.asrank_data.as_ref();
+    let coordinates = asrank.map(|x| &x.coordinates);
+    if let Some(bounds) = &filters.bounds
+        && !coordinates.is_some_and(|c| geo::bounds_contain(bounds, c))
+    {
+        return false;
+    }
+    if let Some(radius) = &filters.radius
+        && !coordinates.is_some_and(|c| geo::distance(&radius.center, c) <= radius.meters)
+    {
+        return false;
+    }
+    if let Some(ring) = filters.polygon.as_deref().and_then(geo::polygon_ring)
+        && !coordinates.is_some_and(|c| geo::ring_contains(&ring, c))
+    {
+        return false;
+    }
+    if let Some(x) = &filters.country_iso {
+        let is_country = asrank.is_some_and(|a| &a.country_iso == x);
+        if is_country == filters.exclude_country {
+            return false;
+        }
+    }
+    if let Some((min, max)) = filters.addresses {
+        let Some(addresses) = asrank.map(|x| x.addresses as i64) else {
+            return false;
+        };
+        if addresses < min || addresses > max {
+            return false;
+        }
+    }
+    if let Some((min, max)) = filters.rank {
+        let Some(rank) = asrank.map(|x| x.rank as i64) else {
+            return false;
+        };
+        if rank < min || rank > max {
+            return false;
+        }
+    }
+    if let Some(true) = filters.has_org
+        && asrank.and_then(|x| x.organization.as_ref()).is_none()
+    {
+        return false;
+    }
+    if !filters.category.iter().any(|x| x == "Any")
+        && !filters.category.is_empty()
+        && !filters
+            .category
+            .iter()
+            .all(|c| a.stanford_asdb.iter().any(|x| &x.layer1 == c))
+    {
+        return false;
+    }
+    if let Some(text) = &filters.text
+        && !text.trim().is_empty()
+        && search::score(a, &search::terms(text)) == 0.0
+    {
+        return false;
+    }
+    if !filters.lists.is_empty() {
+        let lists = a.user_data.as_ref().map(|x| x.lists.as_slice());
+        if !lists
+            .unwrap_or_default()
+            .iter()
+            .any(|l| filters.lists.contains(l))
+        {
+            return false;
+        }
+    }
+    let annotations = a
+        .user_data
+        .as_ref()
+        .map(|x| x.annotations.as_slice())
+        .unwrap_or_default();
+    if !filters
+        .annotations
+        .iter()
+        .all(|f| annotations::matches(annotations, f))
+    {
+        return false;
+    }
+    let rir = a.rir_data.as_ref();
+    if let Some(registry) = &filters.registry
+        && rir.is_none_or(|x| &x.registry != registry)
+    {
+        return false;
+    }
+    let date = rir.and_then(|x| x.date.as_deref());
+    if let Some(before) = &filters.allocated_before
+        && date.is_none_or(|x| x >= before.as_str())
+    {
+        return false;
+    }
+    if let Some(after) = &filters.allocated_after
+        && date.is_none_or(|x| x <= after.as_str())
+    {
+        return false;
+    }
+    true
+}
+
+impl Storage for InMemoryAsdb {
+    async fn clear_database(&self) -> Result<()> {
+        self.ases.write().unwrap().clear();
+        self.prefixes.write().unwrap().clear();
+        self.organisations.write().unwrap().clear();
+        self.persons.write().unwrap().clear();
+        self.relationships.write().unwrap().clear();
+        self.ixps.write().unwrap().clear();
+        self.facilities.write().unwrap().clear();
+        self.rir_blocks.write().unwrap().clear();
+        self.snapshots.write().unwrap().clear();
+        self.datasets.write().unwrap().clear();
+        self.user_data_edits.write().unwrap().clear();
+        self.lists.write().unwrap().clear();
+        self.annotation_fields.write().unwrap().clear();
+        Ok(())
+    }
+
+    async fn prepare_database(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn staging(&self) -> Result<Self> {
+        Ok(Self::new())
+    }
+
+    async fn promote(&self, staging: Self, keep_user_data: bool) -> Result<u64> {
+        let mut ases = staging.ases.into_inner().unwrap();
+        generation::validate(self.ases.read().unwrap().len() as u64, ases.len() as u64)?;
+        let mut organisations = staging.organisations.into_inner().unwrap();
+        if keep_user_data {
+            for a in self.ases.read().unwrap().values() {
+                if (a.user_data.is_some() || a.whois_data.is_some())
+                    && let Some(staged) = ases.get_mut(&a.asn)
+                {
+                    staged.user_data = a.user_data.clone();
+                    staged.whois_data = a.whois_data.clone();
+                }
+            }
+            for org in self.organisations.read().unwrap().values() {
+                if org.whois_org_id.is_some()
+                    && let Some(staged) = organisations.get_mut(&org.org_id)
+                {
+                    staged.whois_org_id = org.whois_org_id.clone();
+                    staged.whois = org.whois.clone();
+                }
+            }
+        }
+
+        let previous = Generation {
+            ases: replace(&self.ases, ases),
+            prefixes: replace(&self.prefixes, staging.prefixes.into_inner().unwrap()),
+            organisations: replace(&self.organisations, organisations),
+            persons: replace(&self.persons, staging.persons.into_inner().unwrap()),
+            relationships: replace(
+                &self.relationships,
+                staging.relationships.into_inner().unwrap(),
+            ),
+            ixps: replace(&self.ixps, staging.ixps.into_inner().unwrap()),
+            facilities: replace(&self.facilities, staging.facilities.into_inner().unwrap()),
+            rir_blocks: replace(&self.rir_blocks, staging.rir_blocks.into_inner().unwrap()),
+        };
+        *self.previous.write().unwrap() = Some(previous);
+        self.snapshots
+            .write()
+            .unwrap()
+            .extend(staging.snapshots.into_inner().unwrap());
+        self.datasets
+            .write()
+            .unwrap()
+            .extend(staging.datasets.into_inner().unwrap());
+        Ok(self.ases.read().unwrap().len() as u64)
+    }
+
+    async fn rollback(&self) -> Result<()> {
+        let previous = self
+            .previous
+            .write()
+            .unwrap()
+            .take()
+            .ok_or(Error::NoPreviousGeneration)?;
+        *self.ases.write().unwrap() = previous.ases;
+        *self.prefixes.write().unwrap() = previous.prefixes;
+        *self.organisations.write().unwrap() = previous.organisations;
+        *self.persons.write().unwrap() = previous.persons;
+        *self.relationships.write().unwrap() = previous.relationships;
+        *self.ixps.write().unwrap() = previous.ixps;
+        *self.facilities.write().unwrap() = previous.facilities;
+        *self.rir_blocks.write().unwrap() = previous.rir_blocks;
+        Ok(())
+    }
+
+    async fn get_ases_page(&self, limit: i64, skip: u64) -> Result<(Vec<AsForFrontend>, u64)> {
+        let ases = self.ases.read().unwrap();
+        let limit = if limit <= 0 {
+            usize::MAX
+        } else {
+            limit as usize
+        };
+        let listed = || ases.values().filter(|a| a.asrank_data.is_some());
+        let page = listed()
+            .skip(skip as usize)
+            .take(limit)
+            .cloned()
+            .map(AsForFrontend::from)
+            .collect();
+        Ok((page, listed().count() as u64))
+    }
+
+    async fn stream_ases_page(
+        &self,
+        limit: i64,
+        skip: u64,
+    ) -> Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin> {
+        let (page, _) = self.get_ases_page(limit, skip).await?;
+        Ok(stream::iter(page.into_iter().map(Ok)))
+    }
+
+    async fn get_ases_sorted(
+        &self,
+        filters: &AsFilters,
+        sort: AsSort,
+        limit: i64,
+        after: Option<&str>,
+    ) -> Result<AsPage> {
+        let cursor = after.map(|x| page::decode(x, sort)).transpose()?;
+        let ases = self.ases.read().unwrap();
+        let mut matching: Vec<(page::SortKey, &As)> = ases
+            .values()
+            .filter(|a| a.asrank_data.is_some() && matches_filters(a, filters))
+            .map(|a| (page::key(a, sort.field), a))
+            .filter(|(key, a)| {
+                cursor
+                    .as_ref()
+                    .is_none_or(|c| page::is_after(c, key, a.asn))
+            })
+            .collect();
+        matching.sort_by(|(x, a), (y, b)| page::compare(sort, (x, a.asn), (y, b.asn)));
+        let sorted = matching
+            .into_iter()
+            .map(|(key, a)| (key, AsForFrontend::from(a.clone())));
+        Ok(page::build(sorted, sort, limit))
+    }
+
+    async fn get_ases(&self, asns: &[u32]) -> Result<(Vec<As>, u64)> {
+        let ases = self.ases.read().unwrap();
+        let found = asns.iter().filter_map(|x| ases.get(x)).cloned().collect();
+        Ok((found, ases.len() as u64))
+    }
+
+    async fn stream_ases(
+        &self,
+        asns: &[u32],
+    ) -> Result<impl Stream<Item = Result<As>> + Send + Unpin> {
+        let (found, _) = self.get_ases(asns).await?;
+        Ok(stream::iter(found.into_iter().map(Ok)))
+    }
+
+    async fn count_ases_filtered(&self, filters: &AsFilters) -> Result<u64> {
+        let ases = self.ases.read().unwrap();
+        Ok(ases
+            .values()
+            .filter(|a| a.asrank_data.is_some() && matches_filters(a, filters))
+            .count() as u64)
+    }
+
+    async fn get_ases_filtered(&self, filters: &AsFilters) -> Result<Vec<AsForFrontend>> {
+        let ases = self.ases.read().unwrap();
+        Ok(ases
+            .values()
+            .filter(|a| a.asrank_data.is_some() && matches_filters(a, filters))
+            .cloned()
+            .map(AsForFrontend::from)
+            .collect())
+    }
+
+    async fn stream_ases_filtered(
+        &self,
+        filters: &AsFilters,
+    ) -> Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin> {
+        let ases = self.get_ases_filtered(filters).await?;
+        Ok(stream::iter(ases.into_iter().map(Ok)))
+    }
+
+    async fn search(&self, query: &str, limit: i64) -> Result<Vec<SearchHit>> {
+        let terms = search::terms(query);
+        let ases = self.ases.read().unwrap();
+        let mut hits: Vec<SearchHit> = ases
+            .values()
+            .map(|a| (a, search::score(a, &terms)))
+            .filter(|(_, score)| *score > 0.0)
+            .map(|(a, score)| search::hit(a, score))
+            .collect();
+        // stable, so equal scores stay sorted by asn
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        if limit > 0 {
+            hits.truncate(limit as usize);
+        }
+        Ok(hits)
+    }
+
+    async fn get_stats(&self, filters: &AsFilters, group_by: StatsGroup) -> Result<AsStats> {
+        let ases = self.ases.read().unwrap();
+        Ok(stats::compute(
+            ases.values().filter(|a| matches_filters(a, filters)),
+            group_by,
+        ))
+    }
+
+    async fn get_as(&self, asn: u32) -> Result<As> {
+        self.ases
+            .read()
+            .unwrap()
+            .get(&asn)
+            .cloned()
+            .ok_or(Error::AsNotFound)
+    }
+
+    async fn insert_as(&self, a: &As) -> Result<()> {
+        self.insert_ases(std::slice::from_ref(a)).await
+    }
+
+    /// Unlike the ordered mongo insert this skips over duplicates and inserts the rest
+    async fn insert_ases(&self, a: &[As]) -> Result<()> {
+        let mut ases = self.ases.write().unwrap();
+        let mut duplicates = 0;
+        for x in a {
+            if ases.contains_key(&x.asn) {
+                duplicates += 1;
+                continue;
+            }
+            ases.insert(x.asn, geo::located(x));
+        }
+        if duplicates > 0 {
+            return Err(Error::DuplicatesFound(duplicates));
+        }
+        Ok(())
+    }
+
+    async fn upsert_ases(&self, a: &[As], source: AsSource) -> Result<UpsertStats> {
+        let mut ases = self.ases.write().unwrap();
+        let mut stats = UpsertStats::default();
+        for x in a {
+            let new = geo::located(x);
+            let Some(stored) = ases.get_mut(&x.asn) else {
+                ases.insert(x.asn, new);
+                stats.inserted += 1;
+                continue;
+            };
+            let before = stored.clone();
+            match source {
+                AsSource::Asrank => {
+                    stored.asrank_data = new.asrank_data;
+                    stored.location = new.location;
+                }
+                AsSource::Ipnetdb => stored.ipnetdb_data = new.ipnetdb_data,
+                AsSource::StanfordAsdb => stored.stanford_asdb = new.stanford_asdb,
+            }
+            if *stored == before {
+                stats.unchanged += 1;
+            } else {
+                stats.updated += 1;
+            }
+        }
+        Ok(stats)
+    }
+
+    async fn insert_ipnetdb_asn(&self, asn: u32, a: &IPNetDBAsn) -> Result<()> {
+        self.update(asn, |x| x.ipnetdb_data = Some(a.clone()));
+        Ok(())
+    }
+
+    async fn clear_peeringdb_nets(&self) -> Result<Vec<u32>> {
+        let mut ases = self.ases.write().unwrap();
+        Ok(ases
+            .values_mut()
+            .filter_map(|a| a.peeringdb_data.take().map(|_| a.asn))
+            .collect())
+    }
+
+    async fn insert_peeringdb_net(&self, asn: u32, net: &PeeringdbNet) -> Result<()> {
+        self.update(asn, |x| x.peeringdb_data = Some(net.clone()));
+        Ok(())
+    }
+
+    async fn clear_rir_asn_delegations(&self, registries: &[InternetRegistry]) -> Result<Vec<u32>> {
+        let mut ases = self.ases.write().unwrap();
+        Ok(ases
+            .values_mut()
+            .filter(|a| {
+                a.rir_data
+                    .as_ref()
+                    .is_some_and(|x| registries.contains(&x.registry))
+            })
+            .map(|a| {
+                a.rir_data = None;
+                a.asn
+            })
+            .collect())
+    }
+
+    async fn insert_rir_asn_delegation(
+        &self,
+        first: u32,
+        count: u32,
+        delegation: &RirDelegation,
+    ) -> Result<Vec<u32>> {
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let last = first.saturating_add(count - 1);
+        let mut ases = self.ases.write().unwrap();
+        Ok(ases
+            .range_mut(first..=last)
+            .map(|(asn, a)| {
+                a.rir_data = Some(delegation.clone());
+                *asn
+            })
+            .collect())
+    }
+
+    async fn insert_stanford_asdb_categories(
+        &self,
+        asn: u32,
+        categories: &[StanfordASdbCategory],
+    ) -> Result<()> {
+        self.update(asn, |x| x.stanford_asdb = categories.to_vec());
+        Ok(())
+    }
+
+    async fn update_whois_data(&self, asn: u32, whois_data: &WhoIsAsn) -> Result<()> {
+        let stored = WhoIsAsn {
+            contacts: vec![],
+            ..whois_data.clone()
+        };
+        self.update(asn, |x| x.whois_data = Some(stored));
+        self.update_whois_contacts(asn, whois_data).await
+    }
+
+    async fn update_user_data(
+        &self,
+        asn: u32,
+        lists: Option<Vec<String>>,
+        comment: Option<String>,
+    ) -> Result<()> {
+        if lists.is_none() && comment.is_none() {
+            return Ok(());
+        }
+        self.update(asn, |x| {
+            let user_data = x.user_data.get_or_insert_default();
+            if let Some(lists) = lists {
+                user_data.lists = lists;
+            }
+            if let Some(comment) = comment {
+                let trimmed = comment.trim();
+                user_data.comment = (!trimmed.is_empty()).then(|| trimmed.to_string());
+            }
+        });
+        Ok(())
+    }
+
+    async fn update_geocoded_addresses(
+        &self,
+        asn: u32,
+        geocoded: Vec<GeocodedAddress>,
+    ) -> Result<()> {
+        self.update(asn, |x| {
+            x.user_data.get_or_insert_default().geocoded_addresses = geocoded;
+        });
+        Ok(())
+    }
+
+    async fn set_user_data(&self, asn: u32, user_data: &UserData) -> Result<()> {
+        self.update(asn, |x| x.user_data = Some(user_data.clone()));
+        Ok(())
+    }
+
+    async fn get_user_data_of(&self, asns: &[u32]) -> Result<Vec<(u32, UserData)>> {
+        let ases = self.ases.read().unwrap();
+        let mut found: Vec<(u32, UserData)> = asns
+            .iter()
+            .filter_map(|asn| ases.get(asn))
+            .map(|a| (a.asn, a.user_data.clone().unwrap_or_default()))
+            .collect();
+        found.sort_by_key(|x| x.0);
+        found.dedup_by_key(|x| x.0);
+        Ok(found)
+    }
+
+    async fn save_user_data_edits(&self, edits: &[UserDataEdit]) -> Result<()> {
+        self.user_data_edits
+            .write()
+            .unwrap()
+            .extend(edits.iter().cloned());
+        Ok(())
+    }
+
+    async fn get_user_data_history(&self, asn: u32) -> Result<Vec<UserDataEdit>> {
+        let edits = self.user_data_edits.read().unwrap();
+        Ok(edits
+            .iter()
+            .rev()
+            .filter(|x| x.asn == asn)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_list_names(&self) -> Result<Vec<String>> {
+        let ases = self.ases.read().unwrap();
+        let mut out: Vec<String> = ases
+            .values()
+            .filter_map(|a| a.user_data.as_ref())
+            .flat_map(|u| u.lists.iter())
+            .filter(|s| !s.trim().is_empty())
+            .cloned()
+            .collect();
+        out.sort();
+        out.dedup();
+        Ok(out)
+    }
+
+    async fn save_list(&self, list: &AsList) -> Result<()> {
+        self.lists
+            .write()
+            .unwrap()
+            .insert(list.name.clone(), list.clone());
+        Ok(())
+    }
+
+    async fn rename_list_metadata(&self, name: &str, new_name: &str) -> Result<()> {
+        let mut lists = self.lists.write().unwrap();
+        if lists.contains_key(new_name) {
+            return Err(Error::ListExists(new_name.to_string()));
+        }
+        let list = lists.remove(name).unwrap_or_default();
+        lists.insert(
+            new_name.to_string(),
+            AsList {
+                name: new_name.to_string(),
+                ..list
+            },
+        );
+        Ok(())
+    }
+
+    async fn delete_list_metadata(&self, name: &str) -> Result<()> {
+        self.lists.write().unwrap().remove(name);
+        Ok(())
+    }
+
+    async fn get_list_metadata(&self) -> Result<Vec<AsList>> {
+        Ok(self.lists.read().unwrap().values().cloned().collect())
+    }
+
+    async fn count_list_members(&self) -> Result<BTreeMap<String, u64>> {
+        let ases = self.ases.read().unwrap();
+        let mut counts = BTreeMap::new();
+        for u in ases.values().filter_map(|a| a.user_data.as_ref()) {
+            for name in u.lists.iter().filter(|s| !s.trim().is_empty()).unique() {
+                *counts.entry(name.clone()).or_default() += 1;
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn get_list_members(&self, name: &str) -> Result<Vec<u32>> {
+        let ases = self.ases.read().unwrap();
+        Ok(ases
+            .values()
+            .filter(|a| {
+                a.user_data
+                    .as_ref()
+                    .is_some_and(|u| u.lists.iter().any(|x| x == name))
+            })
+            .map(|a| a.asn)
+            .collect())
+    }
+
+    async fn add_list_members(&self, name: &str, asns: &[u32]) -> Result<()> {
+        for asn in asns {
+            self.update(*asn, |x| {
+                let lists = &mut x.user_data.get_or_insert_default().lists;
+                if !lists.iter().any(|x| x == name) {
+                    lists.push(name.to_string());
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn remove_list_members(&self, name: &str, asns: &[u32]) -> Result<()> {
+        for asn in asns {
+            self.update(*asn, |x| {
+                if let Some(u) = x.user_data.as_mut() {
+                    u.lists.retain(|x| x != name);
+                }
+            });
+        }
+        Ok(())
+    }
+
+    async fn save_annotation_field(&self, field: &AnnotationField) -> Result<()> {
+        self.annotation_fields
+            .write()
+            .unwrap()
+            .insert(field.name.clone(), field.clone());
+        Ok(())
+    }
+
+    async fn get_annotation_fields(&self) -> Result<Vec<AnnotationField>> {
+        Ok(self
+            .annotation_fields
+            .read()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect())
+    }
+
+    async fn set_annotation(
+        &self,
+        asn: u32,
+        name: &str,
+        value: Option<&AnnotationValue>,
+    ) -> Result<()> {
+        self.update(asn, |x| {
+            let annotations = &mut x.user_data.get_or_insert_default().annotations;
+            annotations.retain(|x| x.name != name);
+            if let Some(value) = value {
+                annotations.push(Annotation {
+                    name: name.to_string(),
+                    value: value.clone(),
+                });
+            }
+        });
+        Ok(())
+    }
+
+    async fn insert_prefixes(&self, asn: u32, prefixes: &[IPNetDBPrefix]) -> Result<()> {
+        let mut stored = self.prefixes.write().unwrap();
+        for p in prefixes.iter().map(|p| prefix::from_announced(asn, p)) {
+            match stored.get_mut(&p.range) {
+                Some(s) => prefix::merge(s, p),
+                None => {
+                    stored.insert(p.range, p);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    async fn get_prefixes(&self, ranges: &[IpNetwork]) -> Result<Vec<Prefix>> {
+        let stored = self.prefixes.read().unwrap();
+        Ok(ranges
+            .iter()
+            .filter_map(|r| stored.get(r))
+            .cloned()
+            .collect())
+    }
+
+    async fn replace_rir_blocks(&self, blocks: &[RirBlock]) -> Result<()> {
+        *self.rir_blocks.write().unwrap() = blocks.iter().map(|x| (x.range, x.clone())).collect();
+        Ok(())
+    }
+
+    async fn get_rir_blocks(&self, ranges: &[IpNetwork]) -> Result<Vec<RirBlock>> {
+        let stored = self.rir_blocks.read().unwrap();
+        Ok(ranges
+            .iter()
+            .filter_map(|r| stored.get(r))
+            .cloned()
+            .collect())
+    }
+
+    async fn replace_relationships(&self, relationships: &[AsRelationship]) -> Result<()> {
+        *self.relationships.write().unwrap() = relationships
+            .iter()
+            .map(|x| ((x.a, x.b), x.clone()))
+            .collect();
+        Ok(())
+    }
+
+    async fn get_neighbors(&self, asn: u32, kind: Option<NeighborKind>) -> Result<Vec<AsNeighbor>> {
+        let stored = self.relationships.read().unwrap();
+        Ok(relationships::neighbors(stored.values(), asn, kind))
+    }
+
+    async fn replace_ixps(&self, ixps: &[Ixp]) -> Result<()> {
+        *self.ixps.write().unwrap() = ixps.iter().map(|x| (x.id, x.clone())).collect();
+        Ok(())
+    }
+
+    async fn get_ixps(&self) -> Result<Vec<Ixp>> {
+        Ok(self.ixps.read().unwrap().values().cloned().collect())
+    }
+
+    async fn replace_facilities(&self, facilities: &[Facility]) -> Result<()> {
+        *self.facilities.write().unwrap() = facilities.iter().map(|x| (x.id, x.clone())).collect();
+        Ok(())
+    }
+
+    async fn get_facilities(&self) -> Result<Vec<Facility>> {
+        Ok(self.facilities.read().unwrap().values().cloned().collect())
+    }
+
+    async fn get_organisation(&self, org_id: &str) -> Result<Option<Organisation>> {
+        let organisations = self.organisations.read().unwrap();
+        Ok(organisations
+            .values()
+            .find(|o| {
+                o.org_id == org_id
+                    || o.asrank_org_id.as_deref() == Some(org_id)
+                    || o.whois_org_id.as_deref() == Some(org_id)
+            })
+            .cloned())
+    }
+
+    async fn get_as_organisation(&self, asn: u32) -> Result<Option<Organisation>> {
+        let organisations = self.organisations.read().unwrap();
+        Ok(organisations
+            .values()
+            .find(|o| o.asns.contains(&asn))
+            .cloned())
+    }
+
+    async fn save_organisation(&self, org: &Organisation) -> Result<()> {
+        let mut organisations = self.organisations.write().unwrap();
+        let mut emptied = vec![];
+        for other in organisations.values_mut() {
+            if other.org_id != org.org_id && other.asns.iter().any(|x| org.asns.contains(x)) {
+                other.asns.retain(|x| !org.asns.contains(x));
+                if other.asns.is_empty() {
+                    emptied.push(other.org_id.clone());
+                }
+            }
+        }
+        for org_id in emptied {
+            organisations.remove(&org_id);
+        }
+        organisations.insert(org.org_id.clone(), org.clone());
+        Ok(())
+    }
+
+    async fn get_persons(&self, nic_hdls: &[String]) -> Result<Vec<Person>> {
+        let persons = self.persons.read().unwrap();
+        Ok(persons
+            .values()
+            .filter(|p| nic_hdls.contains(&p.nic_hdl))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_as_contacts(&self, asn: u32) -> Result<Vec<Person>> {
+        let persons = self.persons.read().unwrap();
+        Ok(persons
+            .values()
+            .filter(|p| p.ases.all().contains(&&asn))
+            .cloned()
+            .collect())
+    }
+
+    async fn get_org_contacts(&self, whois_org_id: &str) -> Result<Vec<Person>> {
+        let persons = self.persons.read().unwrap();
+        Ok(persons
+            .values()
+            .filter(|p| p.orgs.all().iter().any(|x| *x == whois_org_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn save_person(&self, person: &Person) -> Result<()> {
+        let mut persons = self.persons.write().unwrap();
+        if person.is_unused() {
+            persons.remove(&person.nic_hdl);
+        } else {
+            persons.insert(person.nic_hdl.clone(), person.clone());
+        }
+        Ok(())
+    }
+
+    async fn snapshot_ases(&self, run_id: &str, asns: &[u32]) -> Result<()> {
+        let ases = self.ases.read().unwrap();
+        let mut snapshots = self.snapshots.write().unwrap();
+        for a in asns.iter().filter_map(|x| ases.get(x)) {
+            snapshots.insert((run_id.to_string(), a.asn), a.clone());
+        }
+        Ok(())
+    }
+
+    async fn get_run_snapshots(&self, run_id: &str) -> Result<Vec<AsSnapshot>> {
+        let snapshots = self.snapshots.read().unwrap();
+        Ok(snapshots
+            .iter()
+            .filter(|((run, _), _)| run == run_id)
+            .map(|((run, asn), a)| AsSnapshot {
+                run_id: run.clone(),
+                asn: *asn,
+                data: a.clone(),
+            })
+            .collect())
+    }
+
+    async fn get_snapshot(&self, run_id: &str, asn: u32) -> Result<Option<AsSnapshot>> {
+        let snapshots = self.snapshots.read().unwrap();
+        Ok(snapshots
+            .get(&(run_id.to_string(), asn))
+            .map(|a| AsSnapshot {
+                run_id: run_id.to_string(),
+                asn,
+                data: a.clone(),
+            }))
+    }
+
+    async fn save_dataset(&self, dataset: &Dataset) -> Result<()> {
+        self.datasets
+            .write()
+            .unwrap()
+            .insert(dataset.run_id.clone(), dataset.clone());
+        Ok(())
+    }
+
+    async fn get_datasets(&self) -> Result<Vec<Dataset>> {
+        let mut datasets: Vec<Dataset> = self.datasets.read().unwrap().values().cloned().collect();
+        datasets.sort_by(|a, b| (&b.started_at, &b.run_id).cmp(&(&a.started_at, &a.run_id)));
+        Ok(datasets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asdb_models::{
+        AnnotationFilter, AnnotationKind, AnnotationOp, AsSortField, AsrankAsn, AsrankDegree,
+        Bound, Coord, IPNetDBPrefixDetails, ListSummary, Radius, Relationship, WhoIsOrg,
+        WhoIsPerson,
+    };
+
+    use super::*;
+
+    fn asrank_as(asn: u32, rank: u32, country_iso: &str, coordinates: Coord) -> As {
+        As {
+            asn,
+            asrank_data: Some(AsrankAsn {
+                rank,
+                organization: None,
+                organization_id: None,
+                country_iso: country_iso.to_string(),
+                country_name: String::new(),
+                coordinates,
+                degree: AsrankDegree {
+                    provider: 0,
+                    peer: 0,
+                    customer: 0,
+                    total: 0,
+                    transit: 0,
+                    sibling: 0,
+                },
+                prefixes: 1,
+                addresses: 256 * rank,
+                name: format!("AS{asn}"),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn sample_ases() -> Vec<As> {
+        vec![
+            asrank_as(
+                5550,
+                10,
+                "PL",
+                Coord {
+                    lat: 54.37,
+                    lon: 18.56,
+                },
+            ),
+            asrank_as(
+                1299,
+                2,
+                "SE",
+                Coord {
+                    lat: 59.33,
+                    lon: 18.06,
+                },
+            ),
+            asrank_as(
+                3356,
+                1,
+                "US",
+                Coord {
+                    lat: 35.93,
+                    lon: -91.88,
+                },
+            ),
+        ]
+    }
+
+    #[tokio::test]
+    async fn inserting_twice_reports_duplicates_and_keeps_new_ones() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()[..2]).await.unwrap();
+
+        let second = asdb.insert_ases(&sample_ases()).await;
+
+        assert!(matches!(second, Err(Error::DuplicatesFound(2))));
+        let (page, count) = asdb.get_ases_page(0, 0).await.unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(
+            page.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            [1299, 3356, 5550]
+        );
+    }
+
+    #[tokio::test]
+    async fn listings_skip_ases_without_asrank_data() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.insert_as(&As {
+            asn: 1000,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let (page, count) = asdb.get_ases_page(2, 1).await.unwrap();
+        assert_eq!(count, 3);
+        assert_eq!(page.iter().map(|x| x.asn).collect::<Vec<_>>(), [3356, 5550]);
+        let filters = AsFilters::default();
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 3);
+        let filtered = asdb.get_ases_filtered(&filters).await.unwrap();
+        assert!(filtered.iter().all(|x| x.asn != 1000));
+    }
+
+    #[tokio::test]
+    async fn filters_by_country_and_exclusion() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let mut filters = AsFilters {
+            country_iso: Some("PL".to_string()),
+            ..Default::default()
+        };
+
+        let included = asdb.get_ases_filtered(&filters).await.unwrap();
+        filters.exclude_country = true;
+        let excluded = asdb.get_ases_filtered(&filters).await.unwrap();
+
+        assert_eq!(included.len(), 1);
+        assert_eq!(included[0].asn, 5550);
+        assert_eq!(excluded.len(), 2);
+        assert!(excluded.iter().all(|x| x.asn != 5550));
+    }
+
+    #[tokio::test]
+    async fn streams_match_collected_results() {
+        use futures::TryStreamExt;
+
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let filters = AsFilters {
+            country_iso: Some("PL".to_string()),
+            exclude_country: true,
+            ..Default::default()
+        };
+
+        let streamed: Vec<u32> = asdb
+            .stream_ases_filtered(&filters)
+            .await
+            .unwrap()
+            .map_ok(|x| x.asn)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(streamed, [1299, 3356]);
+        let page: Vec<u32> = asdb
+            .stream_ases_page(2, 0)
+            .await
+            .unwrap()
+            .map_ok(|x| x.asn)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(page, [1299, 3356]);
+        let full: Vec<As> = asdb
+            .stream_ases(&[3356, 5550])
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(full.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn filters_by_bounds_and_rank() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let filters = AsFilters {
+            bounds: Some(Bound {
+                north_east: Coord {
+                    lat: 70.0,
+                    lon: 30.0,
+                },
+                south_west: Coord {
+                    lat: 40.0,
+                    lon: 0.0,
+                },
+            }),
+            rank: Some((5, 100)),
+            ..Default::default()
+        };
+
+        let ases = asdb.get_ases_filtered(&filters).await.unwrap();
+
+        assert_eq!(ases.len(), 1);
+        assert_eq!(ases[0].asn, 5550);
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn filters_by_radius_and_polygon() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let stockholm = Coord {
+            lat: 59.0,
+            lon: 18.0,
+        };
+        let mut filters = AsFilters {
+            radius: Some(Radius {
+                center: stockholm.clone(),
+                meters: 100_000.0,
+            }),
+            ..Default::default()
+        };
+
+        let ases = asdb.get_ases_filtered(&filters).await.unwrap();
+        assert_eq!(ases.len(), 1);
+        assert_eq!(ases[0].asn, 1299);
+
+        filters.radius = None;
+        filters.polygon = Some(
+            [(50.0, 10.0), (50.0, 25.0), (60.0, 25.0), (60.0, 10.0)]
+                .into_iter()
+                .map(|(lat, lon)| Coord { lat, lon })
+                .collect(),
+        );
+        let mut asns: Vec<u32> = asdb
+            .get_ases_filtered(&filters)
+            .await
+            .unwrap()
+            .iter()
+            .map(|x| x.asn)
+            .collect();
+        asns.sort();
+        assert_eq!(asns, [1299, 5550]);
+    }
+
+    #[tokio::test]
+    async fn search_ranks_ases_matching_more_words_first() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        for (asn, descr) in [
+            (5550, "Gdańsk University of Technology"),
+            (1299, "University of Warsaw"),
+        ] {
+            let whois = WhoIsAsn {
+                descr: vec![descr.to_string()],
+                ..Default::default()
+            };
+            asdb.update_whois_data(asn, &whois).await.unwrap();
+        }
+
+        let hits = asdb.search("university gdansk", 0).await.unwrap();
+        let asns: Vec<u32> = hits.iter().map(|x| x.asn).collect();
+        assert_eq!(asns, [5550, 1299]);
+        assert!(hits[0].score > hits[1].score);
+        assert_eq!(hits[0].name.as_deref(), Some("AS5550"));
+        assert_eq!(asdb.search("university", 1).await.unwrap().len(), 1);
+        assert!(asdb.search("", 0).await.unwrap().is_empty());
+
+        let filters = AsFilters {
+            text: Some("GDANSK".to_string()),
+            ..Default::default()
+        };
+        let ases = asdb.get_ases_filtered(&filters).await.unwrap();
+        assert_eq!(ases.len(), 1);
+        assert_eq!(ases[0].asn, 5550);
+    }
+
+    #[tokio::test]
+    async fn sorted_pages_continue_after_token() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let sort = AsSort {
+            field: AsSortField::Rank,
+            descending: true,
+        };
+
+        let first = asdb
+            .get_ases_sorted(&AsFilters::default(), sort, 2, None)
+            .await
+            .unwrap();
+        let asns: Vec<u32> = first.ases.iter().map(|x| x.asn).collect();
+        assert_eq!(asns, [5550, 1299]);
+        let token = first.next.unwrap();
+
+        let second = asdb
+            .get_ases_sorted(&AsFilters::default(), sort, 2, Some(&token))
+            .await
+            .unwrap();
+        let asns: Vec<u32> = second.ases.iter().map(|x| x.asn).collect();
+        assert_eq!(asns, [3356]);
+        assert!(second.next.is_none());
+
+        let by_name = AsSort {
+            field: AsSortField::Name,
+            descending: false,
+        };
+        assert!(matches!(
+            asdb.get_ases_sorted(&AsFilters::default(), by_name, 2, Some(&token))
+                .await,
+            Err(Error::InvalidPageToken)
+        ));
+        let filters = AsFilters {
+            country_iso: Some("PL".to_string()),
+            exclude_country: true,
+            ..Default::default()
+        };
+        let all = asdb
+            .get_ases_sorted(&filters, by_name, 0, None)
+            .await
+            .unwrap();
+        let asns: Vec<u32> = all.ases.iter().map(|x| x.asn).collect();
+        assert_eq!(asns, [1299, 3356]);
+    }
+
+    #[tokio::test]
+    async fn stats_match_filters() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+
+        let stats = asdb
+            .get_stats(&AsFilters::default(), StatsGroup::RankBucket)
+            .await
+            .unwrap();
+        assert_eq!(stats.groups.len(), 1);
+        assert_eq!(stats.groups[0].key.as_deref(), Some("1-10"));
+        assert_eq!(stats.total.ases, 3);
+        assert_eq!(stats.total.addresses, 256 * 13);
+
+        let filters = AsFilters {
+            country_iso: Some("US".to_string()),
+            exclude_country: true,
+            ..Default::default()
+        };
+        let stats = asdb.get_stats(&filters, StatsGroup::Country).await.unwrap();
+        let countries: Vec<Option<&str>> = stats.groups.iter().map(|x| x.key.as_deref()).collect();
+        assert_eq!(countries, [Some("PL"), Some("SE")]);
+        assert_eq!(stats.total.prefixes, 2);
+    }
+
+    #[tokio::test]
+    async fn user_data_updates_and_list_filter() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+
+        asdb.update_user_data(
+            1299,
+            Some(vec!["tier1".to_string()]),
+            Some("  ".to_string()),
+        )
+        .await
+        .unwrap();
+        asdb.update_user_data(
+            3356,
+            Some(vec!["tier1".to_string(), "us".to_string()]),
+            None,
+        )
+        .await
+        .unwrap();
+        asdb.update_user_data(5550, None, Some(" gdansk ".to_string()))
+            .await
+            .unwrap();
+        let filters = AsFilters {
+            lists: vec!["tier1".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(asdb.get_list_names().await.unwrap(), ["tier1", "us"]);
+        assert_eq!(asdb.count_ases_filtered(&filters).await.unwrap(), 2);
+        assert_eq!(asdb.get_user_data(1299).await.unwrap().comment, None);
+        assert_eq!(
+            asdb.get_user_data(5550).await.unwrap(),
+            UserData {
+                comment: Some("gdansk".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn user_data_edits_are_recorded_and_reverted() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+
+        asdb.edit_user_data(
+            1299,
+            Some(vec!["tier1".to_string()]),
+            None,
+            Some("10.0.0.1"),
+        )
+        .await
+        .unwrap();
+        asdb.edit_user_data(1299, None, Some("note".to_string()), None)
+            .await
+            .unwrap();
+        // no change, no edit
+        asdb.edit_user_data(1299, Some(vec!["tier1".to_string()]), None, None)
+            .await
+            .unwrap();
+
+        let history = asdb.get_user_data_history(1299).await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].new.comment.as_deref(), Some("note"));
+        assert_eq!(history[1].old, UserData::default());
+        assert_eq!(history[1].editor.as_deref(), Some("10.0.0.1"));
+        assert!(asdb.get_user_data_history(3356).await.unwrap().is_empty());
+
+        let reverted = asdb
+            .revert_user_data(1299, &history[1].id, None)
+            .await
+            .unwrap();
+        assert_eq!(reverted, UserData::default());
+        assert_eq!(asdb.get_user_data(1299).await.unwrap(), UserData::default());
+        let history = asdb.get_user_data_history(1299).await.unwrap();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].old.comment.as_deref(), Some("note"));
+        assert!(matches!(
+            asdb.revert_user_data(1299, "1299-0", None).await,
+            Err(Error::EditNotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn lists_are_created_filled_renamed_and_deleted() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.update_user_data(5550, Some(vec!["pl".to_string()]), None)
+            .await
+            .unwrap();
+        let watch = AsList {
+            name: "watch".to_string(),
+            color: Some("#ff0000".to_string()),
+            ..Default::default()
+        };
+
+        asdb.create_list(&watch).await.unwrap();
+        assert!(matches!(
+            asdb.create_list(&watch).await,
+            Err(Error::ListExists(_))
+        ));
+        assert!(matches!(
+            asdb.add_to_list("missing", &[1299], None).await,
+            Err(Error::ListNotFound(_))
+        ));
+        let added = asdb
+            .add_to_list("watch", &[1299, 3356, 5550, 1], Some("10.0.0.1"))
+            .await
+            .unwrap();
+        assert_eq!(added.len(), 3);
+        assert_eq!(
+            asdb.add_to_list("watch", &[1299], None)
+                .await
+                .unwrap()
+                .len(),
+            0
+        );
+        let lists = asdb.get_lists().await.unwrap();
+        assert_eq!(
+            lists,
+            [
+                ListSummary {
+                    list: AsList {
+                        name: "pl".to_string(),
+                        ..Default::default()
+                    },
+                    count: 1,
+                },
+                ListSummary {
+                    list: watch.clone(),
+                    count: 3,
+                },
+            ]
+        );
+
+        asdb.remove_from_list("watch", &[3356], None).await.unwrap();
+        let renamed = asdb.rename_list("watch", "tier1", None).await.unwrap();
+        assert_eq!(renamed.len(), 2);
+        assert_eq!(asdb.get_list_members("tier1").await.unwrap(), [1299, 5550]);
+        assert!(asdb.get_list_members("watch").await.unwrap().is_empty());
+        assert_eq!(
+            asdb.get_user_data(5550).await.unwrap().lists,
+            ["pl", "tier1"]
+        );
+        let lists = asdb.get_lists().await.unwrap();
+        assert_eq!(lists[1].list.color.as_deref(), Some("#ff0000"));
+
+        asdb.delete_list("tier1", None).await.unwrap();
+        assert_eq!(asdb.get_list_names().await.unwrap(), ["pl"]);
+        assert_eq!(asdb.get_lists().await.unwrap().len(), 1);
+        // add, remove, rename and delete
+        assert_eq!(asdb.get_user_data_history(1299).await.unwrap().len(), 3);
+        assert_eq!(asdb.get_user_data_history(3356).await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn annotations_are_validated_recorded_and_filtered() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let status = AnnotationField {
+            name: "status".to_string(),
+            kind: AnnotationKind::Enum(vec!["contacted".to_string(), "done".to_string()]),
+            description: Some("outreach".to_string()),
+        };
+        asdb.define_annotation_field(&status).await.unwrap();
+        asdb.define_annotation_field(&AnnotationField {
+            name: "since".to_string(),
+            kind: AnnotationKind::Date,
+            description: None,
+        })
+        .await
+        .unwrap();
+        let contacted = AnnotationValue::Text("contacted".to_string());
+
+        asdb.edit_annotation(1299, "status", Some(contacted.clone()), None)
+            .await
+            .unwrap();
+        asdb.edit_annotation(
+            3356,
+            "status",
+            Some(AnnotationValue::Text("done".to_string())),
+            None,
+        )
+        .await
+        .unwrap();
+        asdb.edit_annotation(
+            3356,
+            "since",
+            Some(AnnotationValue::Date("2024-03-01".to_string())),
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            asdb.edit_annotation(
+                1299,
+                "status",
+                Some(AnnotationValue::Text("lost".to_string())),
+                None
+            )
+            .await,
+            Err(Error::InvalidAnnotation(_))
+        ));
+        assert!(matches!(
+            asdb.define_annotation_field(&AnnotationField {
+                kind: AnnotationKind::Number,
+                ..status
+            })
+            .await,
+            Err(Error::InvalidAnnotation(_))
+        ));
+        let count = |annotations: Vec<AnnotationFilter>| {
+            let filters = AsFilters {
+                annotations,
+                ..Default::default()
+            };
+            let asdb = &asdb;
+            async move { asdb.count_ases_filtered(&filters).await.unwrap() }
+        };
+        let filter = |name: &str, op| AnnotationFilter {
+            name: name.to_string(),
+            op,
+        };
+
+        assert_eq!(
+            count(vec![filter("status", AnnotationOp::Eq(contacted.clone()))]).await,
+            1
+        );
+        assert_eq!(count(vec![filter("status", AnnotationOp::Exists)]).await, 2);
+        assert_eq!(
+            count(vec![
+                filter("status", AnnotationOp::Ne(contacted)),
+                filter(
+                    "since",
+                    AnnotationOp::Gte(AnnotationValue::Date("2024-01-01".to_string()))
+                ),
+            ])
+            .await,
+            1
+        );
+        assert_eq!(
+            count(vec![filter("status", AnnotationOp::Missing)]).await,
+            sample_ases().len() as u64 - 2
+        );
+
+        asdb.edit_annotation(1299, "status", None, None)
+            .await
+            .unwrap();
+        assert!(
+            asdb.get_user_data(1299)
+                .await
+                .unwrap()
+                .annotations
+                .is_empty()
+        );
+        assert_eq!(asdb.get_user_data_history(1299).await.unwrap().len(), 2);
+    }
+
+    fn announced(range: &str, origins: Vec<u32>) -> IPNetDBPrefix {
+        IPNetDBPrefix {
+            range: range.parse().unwrap(),
+            details: Some(IPNetDBPrefixDetails {
+                allocation: None,
+                allocation_cc: None,
+                allocation_registry: None,
+                prefix_entity: String::new(),
+                prefix_name: range.to_string(),
+                prefix_origins: origins,
+                prefix_registry: String::new(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn lookup_ip_returns_most_specific_prefix() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.insert_prefixes(3356, &[announced("153.19.0.0/16", vec![])])
+            .await
+            .unwrap();
+        asdb.insert_prefixes(5550, &[announced("153.19.64.251/18", vec![])])
+            .await
+            .unwrap();
+        asdb.insert_prefixes(1299, &[announced("153.19.64.0/18", vec![5550])])
+            .await
+            .unwrap();
+
+        let specific = asdb
+            .lookup_ip("153.19.64.251".parse().unwrap())
+            .await
+            .unwrap();
+        let broad = asdb
+            .lookup_ip("153.19.200.1".parse().unwrap())
+            .await
+            .unwrap();
+        let missing = asdb.lookup_ip("10.0.0.1".parse().unwrap()).await.unwrap();
+
+        let specific = specific.unwrap();
+        assert_eq!(specific.prefix.range.to_string(), "153.19.64.0/18");
+        assert_eq!(specific.prefix.origins, [1299, 5550]);
+        assert_eq!(
+            specific.ases.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            [1299, 5550]
+        );
+        assert_eq!(broad.unwrap().prefix.origins, [3356]);
+        assert!(missing.is_none());
+    }
+
+    #[tokio::test]
+    async fn lookup_ip_handles_ipv6() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.insert_prefixes(
+            1299,
+            &[
+                announced("2001:2000::/20", vec![]),
+                announced("2001:2000:3000::/36", vec![]),
+            ],
+        )
+        .await
+        .unwrap();
+
+        let found = asdb
+            .lookup_ip("2001:2000:3000::1".parse().unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(found.prefix.range.to_string(), "2001:2000:3000::/36");
+        assert_eq!(found.ases.len(), 1);
+        assert_eq!(found.ases[0].asn, 1299);
+    }
+
+    #[tokio::test]
+    async fn neighbors_are_filtered_by_their_role() {
+        let asdb = InMemoryAsdb::new();
+        let link = |a, b, kind| AsRelationship {
+            a,
+            b,
+            kind,
+            source: None,
+        };
+        asdb.replace_relationships(&[
+            link(1299, 5550, Relationship::ProviderCustomer),
+            link(3356, 1299, Relationship::PeerPeer),
+            link(3356, 5550, Relationship::ProviderCustomer),
+        ])
+        .await
+        .unwrap();
+
+        let providers = asdb
+            .get_neighbors(5550, Some(NeighborKind::Provider))
+            .await
+            .unwrap();
+        assert_eq!(providers.iter().map(|x| x.asn).collect_vec(), [1299, 3356]);
+        let peers = asdb
+            .get_neighbors(1299, Some(NeighborKind::Peer))
+            .await
+            .unwrap();
+        assert_eq!(
+            peers,
+            [AsNeighbor {
+                asn: 3356,
+                kind: NeighborKind::Peer
+            }]
+        );
+        assert!(asdb.get_neighbors(174, None).await.unwrap().is_empty());
+    }
+
+    fn with_org(mut a: As, org_id: &str, name: &str) -> As {
+        let asrank = a.asrank_data.as_mut().unwrap();
+        asrank.organization_id = Some(org_id.to_string());
+        asrank.organization = Some(name.to_string());
+        a
+    }
+
+    fn whois_org(org_id: &str, org_name: &str) -> WhoIsOrg {
+        WhoIsOrg {
+            org_id: org_id.to_string(),
+            org_name: org_name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn asrank_organisations_group_ases() {
+        let asdb = InMemoryAsdb::new();
+        let [a, b, c] = sample_ases().try_into().unwrap();
+        let ases = [
+            with_org(a, "org1", "First"),
+            with_org(b, "org1", "First"),
+            with_org(c, "org2", "Second"),
+        ];
+        asdb.insert_ases(&ases).await.unwrap();
+
+        asdb.update_asrank_organisations(&ases).await.unwrap();
+        // importing again must not duplicate anything
+        asdb.update_asrank_organisations(&ases).await.unwrap();
+
+        let org = asdb.get_as_organisation(1299).await.unwrap().unwrap();
+        assert_eq!(org.org_id, "org1");
+        assert_eq!(org.name, "First");
+        assert_eq!(org.asns, [1299, 5550]);
+        let org_ases = asdb.get_organisation_ases("org1").await.unwrap();
+        assert_eq!(org_ases.len(), 2);
+        assert_eq!(asdb.get_organisation_ases("org2").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn whois_organisation_merges_with_asrank_one() {
+        let asdb = InMemoryAsdb::new();
+        let ases = [with_org(sample_ases().remove(0), "org1", "Gdansk Tech")];
+        asdb.insert_ases(&ases).await.unwrap();
+        asdb.update_asrank_organisations(&ases).await.unwrap();
+
+        asdb.link_whois_organisation(5550, &whois_org("ORG-TUoG1-RIPE", "TU Gdansk"))
+            .await
+            .unwrap();
+
+        let by_whois_id = asdb.get_organisation("ORG-TUoG1-RIPE").await.unwrap();
+        let by_asrank_id = asdb.get_organisation("org1").await.unwrap();
+        assert_eq!(by_whois_id, by_asrank_id);
+        let org = by_whois_id.unwrap();
+        assert_eq!(org.org_id, "org1");
+        assert_eq!(org.name, "Gdansk Tech");
+        assert_eq!(org.whois.unwrap().org_name, "TU Gdansk");
+        assert_eq!(org.asns, [5550]);
+    }
+
+    #[tokio::test]
+    async fn asrank_organisation_adopts_whois_only_one() {
+        let asdb = InMemoryAsdb::new();
+        let ases = [with_org(sample_ases().remove(0), "org1", "Gdansk Tech")];
+        asdb.insert_ases(&ases).await.unwrap();
+        asdb.link_whois_organisation(5550, &whois_org("ORG-TUoG1-RIPE", "TU Gdansk"))
+            .await
+            .unwrap();
+        assert_eq!(
+            asdb.get_as_organisation(5550)
+                .await
+                .unwrap()
+                .unwrap()
+                .org_id,
+            "ORG-TUoG1-RIPE"
+        );
+
+        asdb.update_asrank_organisations(&ases).await.unwrap();
+
+        let org = asdb.get_as_organisation(5550).await.unwrap().unwrap();
+        assert_eq!(org.org_id, "org1");
+        assert_eq!(org.whois_org_id.as_deref(), Some("ORG-TUoG1-RIPE"));
+        assert!(org.whois.is_some());
+        // the whois only organisation lost its only AS so it's gone
+        assert_eq!(asdb.organisations.read().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn as_moves_to_new_organisation() {
+        let asdb = InMemoryAsdb::new();
+        let [a, b, _] = sample_ases().try_into().unwrap();
+        let before = [
+            with_org(a.clone(), "org1", "First"),
+            with_org(b, "org1", "First"),
+        ];
+        asdb.insert_ases(&before).await.unwrap();
+        asdb.update_asrank_organisations(&before).await.unwrap();
+
+        asdb.update_asrank_organisations(&[with_org(a, "org2", "Second")])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            asdb.get_organisation("org1").await.unwrap().unwrap().asns,
+            [1299]
+        );
+        assert_eq!(
+            asdb.get_as_organisation(5550)
+                .await
+                .unwrap()
+                .unwrap()
+                .org_id,
+            "org2"
+        );
+    }
+
+    fn person(nic_hdl: &str, name: &str) -> WhoIsPerson {
+        WhoIsPerson {
+            nic_hdl: nic_hdl.to_string(),
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn whois_with_contacts(admin_c: &[&str], org_abuse_c: &str) -> WhoIsAsn {
+        let mut contacts: Vec<_> = admin_c.iter().map(|x| person(x, "Admin")).collect();
+        contacts.push(person(org_abuse_c, "Abuse"));
+        WhoIsAsn {
+            admin_c: admin_c.iter().map(|x| x.to_string()).collect(),
+            organisation: Some(WhoIsOrg {
+                abuse_c: Some(org_abuse_c.to_string()),
+                ..whois_org("ORG-TUoG1-RIPE", "TU Gdansk")
+            }),
+            contacts,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn whois_contacts_are_stored_once() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let whois = whois_with_contacts(&["JD1-RIPE"], "AR1-RIPE");
+        asdb.update_whois_data(5550, &whois).await.unwrap();
+        asdb.update_whois_data(1299, &whois).await.unwrap();
+
+        assert_eq!(asdb.persons.read().unwrap().len(), 2);
+        let stored = asdb.get_as(5550).await.unwrap().whois_data.unwrap();
+        assert!(stored.contacts.is_empty());
+        let resolved = asdb.get_whois_data(5550).await.unwrap().unwrap();
+        assert_eq!(resolved.contacts.len(), 2);
+
+        let admin = asdb.get_person("JD1-RIPE").await.unwrap().unwrap();
+        assert_eq!(admin.contact.name, "Admin");
+        assert_eq!(admin.ases.admin_c, [1299, 5550]);
+        assert!(admin.orgs.is_empty());
+        let abuse = asdb.get_person("AR1-RIPE").await.unwrap().unwrap();
+        assert_eq!(abuse.orgs.abuse_c, ["ORG-TUoG1-RIPE"]);
+        assert!(abuse.ases.is_empty());
+
+        let managed = asdb.get_managed_ases("JD1-RIPE").await.unwrap();
+        let asns: Vec<_> = managed.iter().map(|a| a.asn).collect();
+        assert_eq!(asns, [1299, 5550]);
+        assert_eq!(asdb.get_as_contacts(5550).await.unwrap(), [admin]);
+        assert_eq!(
+            asdb.get_org_contacts("ORG-TUoG1-RIPE").await.unwrap(),
+            [abuse]
+        );
+    }
+
+    #[tokio::test]
+    async fn stale_contact_references_are_dropped() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        let whois = whois_with_contacts(&["JD1-RIPE"], "AR1-RIPE");
+        asdb.update_whois_data(5550, &whois).await.unwrap();
+        asdb.update_whois_data(1299, &whois).await.unwrap();
+
+        let changed = whois_with_contacts(&["NEW1-RIPE"], "AR2-RIPE");
+        asdb.update_whois_data(5550, &changed).await.unwrap();
+
+        let managed = asdb.get_managed_ases("JD1-RIPE").await.unwrap();
+        assert_eq!(managed.len(), 1);
+        assert_eq!(managed[0].asn, 1299);
+        assert_eq!(asdb.get_managed_ases("NEW1-RIPE").await.unwrap().len(), 1);
+        // the organisation switched its abuse contact so nothing uses the old one
+        assert!(asdb.get_person("AR1-RIPE").await.unwrap().is_none());
+
+        asdb.update_whois_data(1299, &changed).await.unwrap();
+        assert!(asdb.get_person("JD1-RIPE").await.unwrap().is_none());
+        assert_eq!(asdb.get_managed_ases("NEW1-RIPE").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn upsert_replaces_only_source_data() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.update_user_data(5550, Some(vec!["watch".to_string()]), None)
+            .await
+            .unwrap();
+        asdb.insert_stanford_asdb_categories(
+            5550,
+            &[StanfordASdbCategory {
+                layer1: "ISP".to_string(),
+                layer2: String::new(),
+            }],
+        )
+        .await
+        .unwrap();
+
+        let mut reloaded = sample_ases();
+        reloaded[0].asrank_data.as_mut().unwrap().rank = 12;
+        reloaded.push(asrank_as(
+            174,
+            3,
+            "US",
+            Coord {
+                lat: 38.9,
+                lon: -77.0,
+            },
+        ));
+        let stats = asdb.upsert_ases(&reloaded, AsSource::Asrank).await.unwrap();
+
+        assert_eq!(
+            stats,
+            UpsertStats {
+                inserted: 1,
+                updated: 1,
+                unchanged: 2,
+            }
+        );
+        let stored = asdb.get_as(5550).await.unwrap();
+        assert_eq!(stored.asrank_data.unwrap().rank, 12);
+        assert_eq!(stored.user_data.unwrap().lists, ["watch"]);
+        assert_eq!(stored.stanford_asdb.len(), 1);
+        assert!(asdb.get_as(174).await.unwrap().location.is_some());
+    }
+
+    #[tokio::test]
+    async fn snapshots_keep_changes_between_runs() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.snapshot_ases("run1", &[5550, 1299]).await.unwrap();
+
+        let mut asrank = asdb.get_as(5550).await.unwrap().asrank_data.unwrap();
+        asrank.rank = 12;
+        let updated = As {
+            asn: 5550,
+            asrank_data: Some(asrank),
+            ..Default::default()
+        };
+        asdb.upsert_ases(&[updated], AsSource::Asrank)
+            .await
+            .unwrap();
+        asdb.snapshot_ases("run2", &[5550, 1299, 3356])
+            .await
+            .unwrap();
+
+        let changed = asdb.get_changed_ases("run1", "run2").await.unwrap();
+        assert_eq!(changed, [3356, 5550]);
+        let diff = asdb.diff_as_snapshots(5550, "run1", "run2").await.unwrap();
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].path, "asrank_data.rank");
+        assert_eq!(diff[0].old.as_deref(), Some("10"));
+        assert_eq!(diff[0].new.as_deref(), Some("12"));
+        // the snapshot doesn't follow later edits
+        let first = asdb.get_snapshot("run1", 5550).await.unwrap().unwrap();
+        assert_eq!(first.data.asrank_data.unwrap().rank, 10);
+    }
+
+    #[tokio::test]
+    async fn missing_as_is_not_found() {
+        let asdb = InMemoryAsdb::new();
+
+        assert!(matches!(asdb.get_as(1).await, Err(Error::AsNotFound)));
+        assert!(asdb.get_user_data(1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn promoted_staging_keeps_user_data_and_rolls_back() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.update_user_data(1299, Some(vec!["tier1".to_string()]), None)
+            .await
+            .unwrap();
+        let mut reranked = sample_ases();
+        reranked[1].asrank_data.as_mut().unwrap().rank = 3;
+
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_ases(&reranked[..1]).await.unwrap();
+        assert!(matches!(
+            asdb.promote(staging, true).await,
+            Err(Error::InvalidStaging(_))
+        ));
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_ases(&reranked[..2]).await.unwrap();
+        // still the current data until it's promoted
+        assert_eq!(
+            asdb.get_as(1299).await.unwrap().asrank_data.unwrap().rank,
+            2
+        );
+        let count = asdb.promote(staging, true).await.unwrap();
+
+        assert_eq!(count, 2);
+        let promoted = asdb.get_as(1299).await.unwrap();
+        assert_eq!(promoted.asrank_data.unwrap().rank, 3);
+        assert_eq!(promoted.user_data.unwrap().lists, ["tier1"]);
+        assert!(asdb.get_as(3356).await.is_err());
+        asdb.rollback().await.unwrap();
+        assert_eq!(
+            asdb.get_as(1299).await.unwrap().asrank_data.unwrap().rank,
+            2
+        );
+        assert!(asdb.get_as(3356).await.is_ok());
+        assert!(matches!(
+            asdb.rollback().await,
+            Err(Error::NoPreviousGeneration)
+        ));
+    }
+
+    #[tokio::test]
+    async fn promote_drops_user_data_of_ases_missing_from_the_import() {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(&sample_ases()).await.unwrap();
+        asdb.update_user_data(3356, Some(vec!["tier1".to_string()]), None)
+            .await
+            .unwrap();
+        let staging = asdb.staging().await.unwrap();
+        staging.insert_ases(&sample_ases()[..2]).await.unwrap();
+        asdb.promote(staging, true).await.unwrap();
+
+        assert!(asdb.get_as(3356).await.is_err());
+        let (page, count) = asdb.get_ases_page(0, 0).await.unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(page.iter().map(|a| a.asn).collect::<Vec<_>>(), [1299, 5550]);
+        let filtered = asdb.get_ases_filtered(&AsFilters::default()).await.unwrap();
+        assert_eq!(filtered.len(), 2);
+    }
+}
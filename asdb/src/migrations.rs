@@ -0,0 +1,219 @@
+//! Schema versions of the stored documents and migrations between them.
+//!
+//! The version is kept in the `meta` collection. Migrations work on raw bson documents so the
+//! same code upgrades a live database in [`crate::Asdb::prepare_database`] and rows of an old
+//! export while they're imported. Databases and exports from before versioning are version 0.
+
+use futures::stream::TryStreamExt;
+use mongodb::{
+    Database,
+    bson::{Bson, Document, doc},
+    options::ReplaceOptions,
+};
+use tracing::info;
+
+use crate::{Error, Result};
+
+/// Collection holding the schema version document
+pub const META_COLLECTION: &str = "meta";
+/// `_id` of the schema version document in [`META_COLLECTION`]
+pub const SCHEMA_VERSION_ID: &str = "schema_version";
+/// Version of documents written by this code
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+struct Migration {
+    /// version of the documents after applying it
+    version: u32,
+    description: &'static str,
+    collection: &'static str,
+    apply: fn(&mut Document),
+}
+
+/// All migrations ordered by version, each has to be a no-op on documents already in shape
+const MIGRATIONS: [Migration; 3] = [
+    Migration {
+        version: 1,
+        description: "add whois, user data and stanford asdb categories to ases",
+        collection: "asns",
+        apply: |d| {
+            set_missing(d, "whois_data", Bson::Null);
+            set_missing(d, "user_data", Bson::Null);
+            set_missing(d, "stanford_asdb", Bson::Array(vec![]));
+        },
+    },
+    Migration {
+        version: 2,
+        description: "add geocoded addresses to user data",
+        collection: "asns",
+        apply: |d| {
+            if let Ok(user_data) = d.get_document_mut("user_data") {
+                set_missing(user_data, "lists", Bson::Array(vec![]));
+                set_missing(user_data, "geocoded_addresses", Bson::Array(vec![]));
+            }
+        },
+    },
+    Migration {
+        version: 3,
+        description: "add GeoJSON location of ases",
+        collection: "asns",
+        apply: |d| {
+            let location = d
+                .get_document("asrank_data")
+                .and_then(|x| x.get_document("coordinates"))
+                .ok()
+                .and_then(|c| Some((c.get_f64("lon").ok()?, c.get_f64("lat").ok()?)))
+                .map_or(Bson::Null, |(lon, lat)| {
+                    doc! {"type": "Point", "coordinates": [lon, lat]}.into()
+                });
+            if !matches!(d.get("location"), Some(Bson::Document(_))) {
+                d.insert("location", location);
+            }
+        },
+    },
+];
+
+fn set_missing(d: &mut Document, key: &str, value: Bson) {
+    if !d.contains_key(key) {
+        d.insert(key, value);
+    }
+}
+
+/// Brings a document of `collection` written with schema `from_version` up to
+/// [`SCHEMA_VERSION`]. Returns whether it changed.
+pub fn migrate_document(collection: &str, d: &mut Document, from_version: u32) -> bool {
+    let before = d.clone();
+    for m in MIGRATIONS
+        .iter()
+        .filter(|m| m.version > from_version && m.collection == collection)
+    {
+        (m.apply)(d);
+    }
+    *d != before
+}
+
+/// Schema version stored in the version document, see [`SCHEMA_VERSION_ID`]
+pub fn version_from_document(d: &Document) -> Option<u32> {
+    if d.get_str("_id").ok()? != SCHEMA_VERSION_ID {
+        return None;
+    }
+    match d.get("version")? {
+        Bson::Int32(x) => u32::try_from(*x).ok(),
+        Bson::Int64(x) => u32::try_from(*x).ok(),
+        _ => None,
+    }
+}
+
+/// Version document for [`META_COLLECTION`]
+pub fn version_document(version: u32) -> Document {
+    doc! {"_id": SCHEMA_VERSION_ID, "version": version}
+}
+
+pub(crate) async fn get_version(db: &Database) -> Result<u32> {
+    let stored = db
+        .collection::<Document>(META_COLLECTION)
+        .find_one(doc! {"_id": SCHEMA_VERSION_ID})
+        .await?;
+    Ok(stored.as_ref().and_then(version_from_document).unwrap_or(0))
+}
+
+pub(crate) async fn set_version(db: &Database, version: u32) -> Result<()> {
+    db.collection::<Document>(META_COLLECTION)
+        .replace_one(doc! {"_id": SCHEMA_VERSION_ID}, version_document(version))
+        .with_options(ReplaceOptions::builder().upsert(true).build())
+        .await?;
+    Ok(())
+}
+
+/// Applies migrations newer than the stored version one by one, recording the version after
+/// each so an interrupted run continues where it stopped.
+pub(crate) async fn run(db: &Database) -> Result<()> {
+    let version = get_version(db).await?;
+    if version > SCHEMA_VERSION {
+        return Err(Error::UnsupportedSchema(version));
+    }
+    for m in MIGRATIONS.iter().filter(|m| m.version > version) {
+        info!(
+            "migrating database to version {}: {}",
+            m.version, m.description
+        );
+        let collection = db.collection::<Document>(m.collection);
+        let mut cursor = collection.find(doc! {}).await?;
+        while let Some(mut d) = cursor.try_next().await? {
+            let before = d.clone();
+            (m.apply)(&mut d);
+            if d != before {
+                collection
+                    .replace_one(doc! {"_id": d.get("_id").cloned()}, d)
+                    .await?;
+            }
+        }
+        set_version(db, m.version).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use asdb_models::As;
+
+    use super::*;
+
+    #[test]
+    fn migrations_are_ordered() {
+        for (i, m) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(m.version, i as u32 + 1);
+        }
+    }
+
+    #[test]
+    fn unversioned_as_deserializes_after_migration() {
+        let mut d = doc! {
+            "asn": 5550,
+            "asrank_data": null,
+            "ipnetdb_data": null,
+        };
+        assert!(mongodb::bson::from_document::<As>(d.clone()).is_err());
+
+        assert!(migrate_document("asns", &mut d, 0));
+        let a: As = mongodb::bson::from_document(d.clone()).unwrap();
+        assert_eq!(a.asn, 5550);
+        assert!(!migrate_document("asns", &mut d, 0));
+    }
+
+    #[test]
+    fn user_data_gets_geocoded_addresses() {
+        let mut d = doc! {"user_data": {"lists": ["watch"], "comment": null}};
+        assert!(migrate_document("asns", &mut d, 1));
+        assert_eq!(
+            d.get_document("user_data")
+                .unwrap()
+                .get_array("geocoded_addresses")
+                .unwrap(),
+            &Vec::<Bson>::new()
+        );
+        assert!(!migrate_document("persons", &mut d, 0));
+    }
+
+    #[test]
+    fn location_is_built_from_asrank_coordinates() {
+        let mut d = doc! {"asrank_data": {"coordinates": {"lat": 54.37, "lon": 18.56}}};
+        migrate_document("asns", &mut d, 2);
+        assert_eq!(
+            d.get_document("location").unwrap(),
+            &doc! {"type": "Point", "coordinates": [18.56, 54.37]}
+        );
+
+        let mut d = doc! {"asrank_data": null};
+        migrate_document("asns", &mut d, 2);
+        assert_eq!(d.get("location"), Some(&Bson::Null));
+    }
+
+    #[test]
+    fn version_document_round_trips() {
+        assert_eq!(version_from_document(&version_document(3)), Some(3));
+        assert_eq!(
+            version_from_document(&doc! {"_id": "other", "version": 3}),
+            None
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! Named datasets kept side by side in one deployment.
+//!
+//! Every dataset is a database of its own next to the configured one, named `{base}-{dataset}`,
+//! so all collections and indexes stay the same for each of them. The configured database is
+//! the [`DEFAULT_DATASET`].
+
+use crate::{Error, Result};
+
+/// Dataset stored in the configured database itself
+pub const DEFAULT_DATASET: &str = "default";
+
+/// Longest database name mongo accepts
+const MAX_DATABASE_NAME: usize = 63;
+
+/// Name of the database holding `dataset` of the `base` database
+pub fn dataset_database(base: &str, dataset: &str) -> Result<String> {
+    if dataset == DEFAULT_DATASET {
+        return Ok(base.to_string());
+    }
+    let database = format!("{base}-{dataset}");
+    let valid = !dataset.is_empty()
+        && dataset
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        && database.len() <= MAX_DATABASE_NAME;
+    if !valid {
+        return Err(Error::InvalidDatasetName(dataset.to_string()));
+    }
+    Ok(database)
+}
+
+/// Reverse of [`dataset_database`], None for databases of other deployments
+pub(crate) fn database_dataset(base: &str, database: &str) -> Option<String> {
+    if database == base {
+        return Some(DEFAULT_DATASET.to_string());
+    }
+    let dataset = database.strip_prefix(base)?.strip_prefix('-')?;
+    (dataset != DEFAULT_DATASET && dataset_database(base, dataset).is_ok())
+        .then(|| dataset.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn datasets_map_to_databases_and_back() {
+        assert_eq!(dataset_database("asmap", DEFAULT_DATASET).unwrap(), "asmap");
+        assert_eq!(
+            dataset_database("asmap", "2024-Q1").unwrap(),
+            "asmap-2024-Q1"
+        );
+        assert!(dataset_database("asmap", "").is_err());
+        assert!(dataset_database("asmap", "a.b").is_err());
+        assert!(dataset_database("asmap", &"x".repeat(60)).is_err());
+
+        assert_eq!(
+            database_dataset("asmap", "asmap").as_deref(),
+            Some(DEFAULT_DATASET)
+        );
+        assert_eq!(
+            database_dataset("asmap", "asmap-2024-Q1").as_deref(),
+            Some("2024-Q1")
+        );
+        assert_eq!(database_dataset("asmap", "asmapper"), None);
+        assert_eq!(database_dataset("asmap", "asmap-default"), None);
+        assert_eq!(database_dataset("asmap", "admin"), None);
+    }
+}
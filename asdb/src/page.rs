@@ -0,0 +1,232 @@
+//! Helpers for the keyset pagination of filtered ases.
+//!
+//! A page starts right after the sort key and asn of the last AS of the previous one instead of
+//! skipping over the earlier pages, so every page costs about the same. The position is handed
+//! to clients as a token, the hex encoded bson of [`Cursor`], which they pass back unchanged.
+
+use std::cmp::Ordering;
+
+use asdb_models::{As, AsForFrontend, AsPage, AsSort, AsSortField};
+use mongodb::bson::{Bson, Document, doc};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// Value of the sort field of an AS
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(untagged)]
+pub(crate) enum SortKey {
+    Int(i64),
+    Str(String),
+}
+
+impl From<SortKey> for Bson {
+    fn from(value: SortKey) -> Self {
+        match value {
+            SortKey::Int(x) => Bson::Int64(x),
+            SortKey::Str(x) => Bson::String(x),
+        }
+    }
+}
+
+/// Position after which the next page starts
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub(crate) struct Cursor {
+    sort: AsSort,
+    key: SortKey,
+    asn: u32,
+}
+
+fn path(field: AsSortField) -> &'static str {
+    match field {
+        AsSortField::Asn => "asn",
+        AsSortField::Rank => "asrank_data.rank",
+        AsSortField::Addresses => "asrank_data.addresses",
+        AsSortField::Prefixes => "asrank_data.prefixes",
+        AsSortField::Degree => "asrank_data.degree.total",
+        AsSortField::Country => "asrank_data.country_iso",
+        AsSortField::Name => "asrank_data.name",
+    }
+}
+
+/// Fields to project on top of the ones for [`AsForFrontend`] to read the sort key
+pub(crate) fn projection(sort: AsSort) -> Document {
+    doc! {path(sort.field): 1}
+}
+
+pub(crate) fn sort_document(sort: AsSort) -> Document {
+    let direction = if sort.descending { -1 } else { 1 };
+    let mut d = doc! {path(sort.field): direction};
+    d.insert("asn", direction);
+    d
+}
+
+/// Filter for documents after the cursor in its sort order
+pub(crate) fn after_filter(cursor: &Cursor) -> Document {
+    let op = if cursor.sort.descending { "$lt" } else { "$gt" };
+    if cursor.sort.field == AsSortField::Asn {
+        return doc! {"asn": {op: cursor.asn}};
+    }
+    let path = path(cursor.sort.field);
+    let key = Bson::from(cursor.key.clone());
+    doc! {"$or": [
+        {path: {op: key.clone()}},
+        {path: key, "asn": {op: cursor.asn}},
+    ]}
+}
+
+/// Sort key of a document projected with [`projection`]
+pub(crate) fn document_key(d: &Document, field: AsSortField) -> Option<SortKey> {
+    let mut parts = path(field).split('.');
+    let mut value = d.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_document()?.get(part)?;
+    }
+    match value {
+        Bson::Int32(x) => Some(SortKey::Int(*x as i64)),
+        Bson::Int64(x) => Some(SortKey::Int(*x)),
+        Bson::String(x) => Some(SortKey::Str(x.clone())),
+        _ => None,
+    }
+}
+
+/// Sort key of a record with ASRank data
+pub(crate) fn key(a: &As, field: AsSortField) -> SortKey {
+    let asrank = a
+        .asrank_data
+        .as_ref()
+        .expect("only ases with asrank data are paged");
+    match field {
+        AsSortField::Asn => SortKey::Int(a.asn as i64),
+        AsSortField::Rank => SortKey::Int(asrank.rank as i64),
+        AsSortField::Addresses => SortKey::Int(asrank.addresses as i64),
+        AsSortField::Prefixes => SortKey::Int(asrank.prefixes as i64),
+        AsSortField::Degree => SortKey::Int(asrank.degree.total as i64),
+        AsSortField::Country => SortKey::Str(asrank.country_iso.clone()),
+        AsSortField::Name => SortKey::Str(asrank.name.clone()),
+    }
+}
+
+/// Order of two (key, asn) positions, the same as mongo sorting by [`sort_document`]
+pub(crate) fn compare(sort: AsSort, a: (&SortKey, u32), b: (&SortKey, u32)) -> Ordering {
+    let ordering = a.cmp(&b);
+    if sort.descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+pub(crate) fn is_after(cursor: &Cursor, key: &SortKey, asn: u32) -> bool {
+    compare(cursor.sort, (&cursor.key, cursor.asn), (key, asn)) == Ordering::Less
+}
+
+/// Builds the page out of sorted ases following the previous page. Takes one more than `limit`
+/// to know whether there is a next page. `limit` of 0 means no limit.
+pub(crate) fn build(
+    sorted: impl Iterator<Item = (SortKey, AsForFrontend)>,
+    sort: AsSort,
+    limit: i64,
+) -> AsPage {
+    let limit = if limit <= 0 {
+        usize::MAX
+    } else {
+        limit as usize
+    };
+    let mut ases = vec![];
+    let mut last = None;
+    for (key, a) in sorted {
+        if ases.len() == limit {
+            let (key, asn): (SortKey, u32) = last.expect("limit is at least 1");
+            return AsPage {
+                ases,
+                next: Some(encode(&Cursor { sort, key, asn })),
+            };
+        }
+        last = Some((key, a.asn));
+        ases.push(a);
+    }
+    AsPage { ases, next: None }
+}
+
+fn encode(cursor: &Cursor) -> String {
+    let bytes =
+        mongodb::bson::to_vec(cursor).expect("Cursor should always be serializable to bson");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Reads the token of [`AsPage::next`], it has to come from a page with the same sort
+pub(crate) fn decode(token: &str, sort: AsSort) -> Result<Cursor> {
+    if !token.len().is_multiple_of(2) || !token.is_ascii() {
+        return Err(Error::InvalidPageToken);
+    }
+    let bytes = (0..token.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&token[i..i + 2], 16))
+        .collect::<std::result::Result<Vec<u8>, _>>()
+        .map_err(|_| Error::InvalidPageToken)?;
+    let cursor: Cursor = mongodb::bson::from_slice(&bytes).map_err(|_| Error::InvalidPageToken)?;
+    if cursor.sort != sort {
+        return Err(Error::InvalidPageToken);
+    }
+    Ok(cursor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_round_trips_only_for_the_same_sort() {
+        let sort = AsSort {
+            field: AsSortField::Name,
+            descending: true,
+        };
+        let cursor = Cursor {
+            sort,
+            key: SortKey::Str("TASK".to_string()),
+            asn: 8501,
+        };
+
+        let token = encode(&cursor);
+        assert_eq!(decode(&token, sort).unwrap(), cursor);
+        assert!(decode(&token, AsSort::default()).is_err());
+        assert!(decode("zz", sort).is_err());
+        assert!(decode(&token[1..], sort).is_err());
+    }
+
+    #[test]
+    fn descending_cursor_continues_with_smaller_keys() {
+        let sort = AsSort {
+            field: AsSortField::Rank,
+            descending: true,
+        };
+        let cursor = Cursor {
+            sort,
+            key: SortKey::Int(10),
+            asn: 5550,
+        };
+
+        assert!(is_after(&cursor, &SortKey::Int(9), 6000));
+        assert!(is_after(&cursor, &SortKey::Int(10), 5549));
+        assert!(!is_after(&cursor, &SortKey::Int(10), 5550));
+        assert!(!is_after(&cursor, &SortKey::Int(11), 1));
+        assert_eq!(
+            after_filter(&cursor),
+            doc! {"$or": [
+                {"asrank_data.rank": {"$lt": 10_i64}},
+                {"asrank_data.rank": 10_i64, "asn": {"$lt": 5550}},
+            ]}
+        );
+    }
+
+    #[test]
+    fn document_key_follows_nested_path() {
+        let d = doc! {"asn": 1, "asrank_data": {"degree": {"total": 14}}};
+        assert_eq!(
+            document_key(&d, AsSortField::Degree),
+            Some(SortKey::Int(14))
+        );
+        assert_eq!(document_key(&d, AsSortField::Name), None);
+    }
+}
@@ -0,0 +1,78 @@
+//! Helpers for storing prefixes and matching ip addresses against them.
+//!
+//! Prefixes are stored under their canonical network so the longest prefix match can be
+//! done with exact lookups of every network covering the address, at most 33 for IPv4
+//! and 129 for IPv6.
+
+use std::net::IpAddr;
+
+use asdb_models::{IPNetDBPrefix, Prefix};
+use ipnetwork::IpNetwork;
+
+/// Returns the network with host bits zeroed, e.g. `153.19.64.251/16` -> `153.19.0.0/16`
+pub(crate) fn canonical(range: IpNetwork) -> IpNetwork {
+    IpNetwork::new(range.network(), range.prefix())
+        .expect("prefix of an existing network is always valid")
+}
+
+/// Returns every network covering `ip` from the least to the most specific one
+pub(crate) fn covering_networks(ip: IpAddr) -> Vec<IpNetwork> {
+    let max_prefix = match ip {
+        IpAddr::V4(_) => 32,
+        IpAddr::V6(_) => 128,
+    };
+    (0..=max_prefix)
+        .map(|len| canonical(IpNetwork::new(ip, len).expect("prefix length is in range")))
+        .collect()
+}
+
+/// Builds the stored prefix for one announced by `asn`, origins include the ones from details
+pub(crate) fn from_announced(asn: u32, prefix: &IPNetDBPrefix) -> Prefix {
+    let mut origins = vec![asn];
+    if let Some(details) = &prefix.details {
+        origins.extend(&details.prefix_origins);
+    }
+    origins.sort();
+    origins.dedup();
+    Prefix {
+        range: canonical(prefix.range),
+        origins,
+        details: prefix.details.clone(),
+    }
+}
+
+/// Merges newly announced prefix into the stored one. Details are only replaced when present.
+pub(crate) fn merge(stored: &mut Prefix, new: Prefix) {
+    stored.origins.extend(new.origins);
+    stored.origins.sort();
+    stored.origins.dedup();
+    if new.details.is_some() {
+        stored.details = new.details;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonical_zeroes_host_bits() {
+        let range: IpNetwork = "153.19.64.251/16".parse().unwrap();
+        assert_eq!(canonical(range).to_string(), "153.19.0.0/16");
+        let range: IpNetwork = "2001:db8::1/32".parse().unwrap();
+        assert_eq!(canonical(range).to_string(), "2001:db8::/32");
+    }
+
+    #[test]
+    fn covering_networks_go_from_least_to_most_specific() {
+        let v4 = covering_networks("10.1.2.3".parse().unwrap());
+        assert_eq!(v4.len(), 33);
+        assert_eq!(v4[0].to_string(), "0.0.0.0/0");
+        assert_eq!(v4[24].to_string(), "10.1.2.0/24");
+        assert_eq!(v4[32].to_string(), "10.1.2.3/32");
+
+        let v6 = covering_networks("2001:db8::1".parse().unwrap());
+        assert_eq!(v6.len(), 129);
+        assert_eq!(v6[32].to_string(), "2001:db8::/32");
+    }
+}
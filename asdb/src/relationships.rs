@@ -0,0 +1,86 @@
+//! Links between ases from the CAIDA AS relationship dataset.
+//!
+//! Each link is stored once as `{a, b, kind}` with the provider in `a`, so neighbors of an AS
+//! are found on either side of its links and their role depends on the side.
+
+use asdb_models::{AsNeighbor, AsRelationship, NeighborKind, Relationship};
+use mongodb::bson::{Document, doc};
+
+/// Condition on the relationships collection matching the links of `asn` to neighbors of `kind`
+pub(crate) fn db_filter(asn: u32, kind: Option<NeighborKind>) -> Document {
+    match kind {
+        None => doc! {"$or": [{"a": asn}, {"b": asn}]},
+        Some(NeighborKind::Provider) => doc! {"b": asn, "kind": "ProviderCustomer"},
+        Some(NeighborKind::Customer) => doc! {"a": asn, "kind": "ProviderCustomer"},
+        Some(NeighborKind::Peer) => doc! {"$or": [{"a": asn}, {"b": asn}], "kind": "PeerPeer"},
+    }
+}
+
+/// The other AS of the link and the role it plays for `asn`, None if the link isn't one of `asn`
+fn neighbor(link: &AsRelationship, asn: u32) -> Option<AsNeighbor> {
+    let (other, kind) = match link.kind {
+        Relationship::ProviderCustomer if link.a == asn => (link.b, NeighborKind::Customer),
+        Relationship::ProviderCustomer if link.b == asn => (link.a, NeighborKind::Provider),
+        Relationship::PeerPeer if link.a == asn => (link.b, NeighborKind::Peer),
+        Relationship::PeerPeer if link.b == asn => (link.a, NeighborKind::Peer),
+        _ => return None,
+    };
+    Some(AsNeighbor { asn: other, kind })
+}
+
+/// Neighbors of `asn` of the `kind` out of `links`, sorted by asn
+pub(crate) fn neighbors<'a>(
+    links: impl IntoIterator<Item = &'a AsRelationship>,
+    asn: u32,
+    kind: Option<NeighborKind>,
+) -> Vec<AsNeighbor> {
+    let mut neighbors: Vec<AsNeighbor> = links
+        .into_iter()
+        .filter_map(|x| neighbor(x, asn))
+        .filter(|x| kind.is_none_or(|k| x.kind == k))
+        .collect();
+    neighbors.sort_by_key(|x| (x.asn, x.kind));
+    neighbors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(a: u32, b: u32, kind: Relationship) -> AsRelationship {
+        AsRelationship {
+            a,
+            b,
+            kind,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn neighbor_role_depends_on_the_side_of_the_link() {
+        let links = [
+            link(3356, 1299, Relationship::PeerPeer),
+            link(1299, 5550, Relationship::ProviderCustomer),
+            link(174, 1299, Relationship::ProviderCustomer),
+            link(174, 5550, Relationship::ProviderCustomer),
+        ];
+        let neighbor = |asn, kind| AsNeighbor { asn, kind };
+
+        assert_eq!(
+            neighbors(&links, 1299, None),
+            [
+                neighbor(174, NeighborKind::Provider),
+                neighbor(3356, NeighborKind::Peer),
+                neighbor(5550, NeighborKind::Customer),
+            ]
+        );
+        assert_eq!(
+            neighbors(&links, 5550, Some(NeighborKind::Provider)),
+            [
+                neighbor(174, NeighborKind::Provider),
+                neighbor(1299, NeighborKind::Provider),
+            ]
+        );
+        assert!(neighbors(&links, 3356, Some(NeighborKind::Customer)).is_empty());
+    }
+}
@@ -0,0 +1,209 @@
+//! Helpers for the full-text search over names and descriptions of ases.
+//!
+//! Mongo uses a single text index over [`TEXT_FIELDS`]. Terms of a query are matched with OR,
+//! case and diacritics are ignored and the score grows with the weight of the fields matched,
+//! so `university gdansk` ranks ases having both words above the ones having only one.
+
+use asdb_models::{As, SearchHit};
+use mongodb::bson::{Document, doc};
+use serde::Deserialize;
+
+/// Indexed fields along with their weights
+pub(crate) const TEXT_FIELDS: [(&str, i32); 5] = [
+    ("asrank_data.name", 10),
+    ("whois_data.as_name", 10),
+    ("asrank_data.organization", 5),
+    ("ipnetdb_data.entity", 5),
+    ("whois_data.descr", 2),
+];
+/// Name of the text index, mongo allows only one per collection
+pub(crate) const INDEX_NAME: &str = "as_text";
+
+pub(crate) fn index_keys() -> Document {
+    TEXT_FIELDS
+        .iter()
+        .map(|(field, _)| (field.to_string(), "text".into()))
+        .collect()
+}
+
+pub(crate) fn index_weights() -> Document {
+    TEXT_FIELDS
+        .iter()
+        .map(|(field, weight)| (field.to_string(), (*weight).into()))
+        .collect()
+}
+
+/// Projection of the fields needed to build [`SearchHit`], including the text score
+pub(crate) fn projection() -> Document {
+    doc! {
+        "asn": 1,
+        "score": {"$meta": "textScore"},
+        "asrank_data.name": 1,
+        "asrank_data.organization": 1,
+        "whois_data.as_name": 1,
+        "ipnetdb_data.entity": 1,
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SearchDoc {
+    asn: u32,
+    score: f64,
+    asrank_data: Option<SearchDocAsrank>,
+    whois_data: Option<SearchDocWhois>,
+    ipnetdb_data: Option<SearchDocIpnetdb>,
+}
+
+#[derive(Deserialize)]
+struct SearchDocAsrank {
+    name: Option<String>,
+    organization: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchDocWhois {
+    as_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SearchDocIpnetdb {
+    entity: Option<String>,
+}
+
+impl From<SearchDoc> for SearchHit {
+    fn from(value: SearchDoc) -> Self {
+        let (asrank_name, organization) = value
+            .asrank_data
+            .map(|x| (x.name, x.organization))
+            .unwrap_or_default();
+        SearchHit {
+            asn: value.asn,
+            score: value.score,
+            name: asrank_name.or(value.whois_data.and_then(|x| x.as_name)),
+            organization: organization.or(value.ipnetdb_data.and_then(|x| x.entity)),
+        }
+    }
+}
+
+/// Splits the query the way mongo does, into lowercase words without diacritics
+pub(crate) fn terms(text: &str) -> Vec<String> {
+    let mut terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|x| !x.is_empty())
+        .map(|x| x.chars().flat_map(char::to_lowercase).map(fold).collect())
+        .collect();
+    terms.sort();
+    terms.dedup();
+    terms
+}
+
+/// Strips diacritics of the latin letters seen in AS names
+fn fold(c: char) -> char {
+    const FROM: &str = "ąàáâãäåćçčęèéêëěìíîïłńñňòóôõöøřśšßťùúûüůýÿźżž";
+    const TO: &str = "aaaaaaaccceeeeeeiiiilnnnoooooorssstuuuuuyyzzz";
+    FROM.chars()
+        .position(|x| x == c)
+        .and_then(|i| TO.chars().nth(i))
+        .unwrap_or(c)
+}
+
+/// Relevance of the record for the query terms, 0 when nothing matches. Sums the weights of
+/// the fields each term was found in, an approximation of the mongo text score.
+pub(crate) fn score(a: &As, query: &[String]) -> f64 {
+    let asrank = a.asrank_data.as_ref();
+    let whois = a.whois_data.as_ref();
+    let fields: [(Vec<&str>, i32); 5] = [
+        (asrank.map(|x| x.name.as_str()).into_iter().collect(), 10),
+        (
+            whois
+                .and_then(|x| x.as_name.as_deref())
+                .into_iter()
+                .collect(),
+            10,
+        ),
+        (
+            asrank
+                .and_then(|x| x.organization.as_deref())
+                .into_iter()
+                .collect(),
+            5,
+        ),
+        (
+            a.ipnetdb_data
+                .as_ref()
+                .map(|x| x.entity.as_str())
+                .into_iter()
+                .collect(),
+            5,
+        ),
+        (
+            whois
+                .map(|x| x.descr.iter().map(String::as_str).collect())
+                .unwrap_or_default(),
+            2,
+        ),
+    ];
+    fields
+        .iter()
+        .map(|(values, weight)| {
+            let words: Vec<String> = values.iter().flat_map(|x| terms(x)).collect();
+            query.iter().filter(|t| words.contains(t)).count() as f64 * *weight as f64
+        })
+        .sum()
+}
+
+/// Hit for the record found by the in-memory backend
+pub(crate) fn hit(a: &As, score: f64) -> SearchHit {
+    let asrank = a.asrank_data.as_ref();
+    SearchHit {
+        asn: a.asn,
+        score,
+        name: asrank
+            .map(|x| x.name.clone())
+            .or(a.whois_data.as_ref().and_then(|x| x.as_name.clone())),
+        organization: asrank
+            .and_then(|x| x.organization.clone())
+            .or(a.ipnetdb_data.as_ref().map(|x| x.entity.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asdb_models::WhoIsAsn;
+
+    use super::*;
+
+    fn whois_as(as_name: &str, descr: &[&str]) -> As {
+        As {
+            asn: 1,
+            whois_data: Some(WhoIsAsn {
+                as_name: Some(as_name.to_string()),
+                descr: descr.iter().map(|x| x.to_string()).collect(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn terms_are_lowercase_words_without_diacritics() {
+        assert_eq!(
+            terms("Politechnika Gdańska, TASK-NET"),
+            ["gdanska", "net", "politechnika", "task"]
+        );
+        assert!(terms(" - ").is_empty());
+    }
+
+    #[test]
+    fn more_matched_terms_score_higher() {
+        let query = terms("university gdansk");
+        let both = whois_as("TASK", &["Gdansk University of Technology"]);
+        let one = whois_as("UW", &["University of Warsaw"]);
+        let none = whois_as("ATMAN", &["Atman Sp. z o.o."]);
+
+        assert_eq!(score(&both, &query), 4.0);
+        assert_eq!(score(&one, &query), 2.0);
+        assert_eq!(score(&none, &query), 0.0);
+        assert_eq!(score(&whois_as("GDANSK", &[]), &query), 10.0);
+    }
+}
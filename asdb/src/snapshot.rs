@@ -0,0 +1,124 @@
+//! Field level comparison of AS snapshots.
+//!
+//! Records are compared as bson documents, nested documents are descended into while
+//! arrays and scalars are compared as a whole.
+
+use asdb_models::{As, FieldChange};
+use itertools::Itertools;
+use mongodb::bson::{Bson, Document};
+
+/// Returns fields which differ between the records. A missing record counts as an empty one.
+pub(crate) fn diff(old: Option<&As>, new: Option<&As>) -> Vec<FieldChange> {
+    let mut changes = vec![];
+    diff_documents("", &to_document(old), &to_document(new), &mut changes);
+    changes
+}
+
+fn to_document(a: Option<&As>) -> Document {
+    a.map(|a| mongodb::bson::to_document(a).expect("As should always be serializable to bson"))
+        .unwrap_or_default()
+}
+
+fn diff_documents(prefix: &str, old: &Document, new: &Document, out: &mut Vec<FieldChange>) {
+    for key in old.keys().chain(new.keys()).unique() {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match (old.get(key), new.get(key)) {
+            (Some(Bson::Document(o)), Some(Bson::Document(n))) => diff_documents(&path, o, n, out),
+            (o, n) if o != n => out.push(FieldChange {
+                path,
+                old: o.map(render),
+                new: n.map(render),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn render(x: &Bson) -> String {
+    x.clone().into_relaxed_extjson().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use asdb_models::{AsrankAsn, AsrankDegree, Coord, StanfordASdbCategory};
+
+    use super::*;
+
+    fn asrank_as(rank: u32, peer: u32) -> As {
+        As {
+            asn: 5550,
+            asrank_data: Some(AsrankAsn {
+                rank,
+                organization: None,
+                organization_id: None,
+                country_iso: "PL".to_string(),
+                country_name: "Poland".to_string(),
+                coordinates: Coord {
+                    lat: 54.3,
+                    lon: 18.5,
+                },
+                degree: AsrankDegree {
+                    provider: 2,
+                    peer,
+                    customer: 2,
+                    total: 4 + peer,
+                    transit: 13,
+                    sibling: 1,
+                },
+                prefixes: 1,
+                addresses: 65536,
+                name: "TASK".to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_records_have_no_changes() {
+        let a = asrank_as(10, 5);
+        assert!(diff(Some(&a), Some(&a)).is_empty());
+    }
+
+    #[test]
+    fn nested_fields_are_reported_by_path() {
+        let changes = diff(Some(&asrank_as(10, 5)), Some(&asrank_as(12, 6)));
+        let paths: Vec<_> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            [
+                "asrank_data.rank",
+                "asrank_data.degree.peer",
+                "asrank_data.degree.total"
+            ]
+        );
+        assert_eq!(changes[0].old.as_deref(), Some("10"));
+        assert_eq!(changes[0].new.as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn arrays_are_compared_as_a_whole() {
+        let old = asrank_as(10, 5);
+        let mut new = old.clone();
+        new.stanford_asdb = vec![StanfordASdbCategory {
+            layer1: "Computer and Information Technology".to_string(),
+            layer2: "Internet Service Provider (ISP)".to_string(),
+        }];
+        let changes = diff(Some(&old), Some(&new));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "stanford_asdb");
+        assert_eq!(changes[0].old.as_deref(), Some("[]"));
+    }
+
+    #[test]
+    fn missing_record_reports_top_level_fields() {
+        let new = asrank_as(10, 5);
+        let changes = diff(None, Some(&new));
+        assert!(changes.iter().all(|c| c.old.is_none()));
+        assert!(changes.iter().any(|c| c.path == "asn"));
+        assert!(changes.iter().any(|c| c.path == "asrank_data"));
+    }
+}
@@ -0,0 +1,265 @@
+//! Helpers for the grouped statistics of ases.
+//!
+//! Every AS gets an array of group keys, empty or with a null for ases missing the value.
+//! Mongo unwinds the array and groups by the key in one `$facet` next to the totals, the
+//! in-memory backend does the same with [`compute`].
+
+use std::collections::BTreeMap;
+
+use asdb_models::{As, AsStats, InternetRegistry, StatsGroup, StatsGroupRow, StatsTotals};
+use mongodb::bson::{Bson, Document, bson, doc};
+use serde::Deserialize;
+
+/// Upper bounds of the rank buckets, ranks above the last one go to an open ended bucket
+const RANK_BUCKETS: [u32; 4] = [10, 100, 1_000, 10_000];
+
+pub(crate) fn rank_bucket(rank: u32) -> String {
+    let mut lower = 1;
+    for upper in RANK_BUCKETS {
+        if rank <= upper {
+            return format!("{lower}-{upper}");
+        }
+        lower = upper + 1;
+    }
+    format!("{lower}+")
+}
+
+/// Expression evaluating to the array of group keys of a document
+fn keys_expr(group_by: StatsGroup) -> Bson {
+    match group_by {
+        StatsGroup::Country => bson!(["$asrank_data.country_iso"]),
+        StatsGroup::Category => bson!({"$setUnion": [{"$ifNull": ["$stanford_asdb.layer1", []]}]}),
+        StatsGroup::Subcategory => {
+            bson!({"$setUnion": [{"$ifNull": ["$stanford_asdb.layer2", []]}]})
+        }
+        StatsGroup::Registry => bson!([{"$cond": [
+            {"$eq": [{"$type": "$ipnetdb_data.registry"}, "object"]},
+            // LOCAL(String) is stored as a document, its name is enough to group by
+            "LOCAL",
+            "$ipnetdb_data.registry",
+        ]}]),
+        StatsGroup::RankBucket => {
+            let mut branches = vec![doc! {
+                "case": {"$not": [{"$isNumber": "$asrank_data.rank"}]},
+                "then": null,
+            }];
+            let mut lower = 1;
+            for upper in RANK_BUCKETS {
+                branches.push(doc! {
+                    "case": {"$lte": ["$asrank_data.rank", upper]},
+                    "then": format!("{lower}-{upper}"),
+                });
+                lower = upper + 1;
+            }
+            bson!([{"$switch": {"branches": branches, "default": format!("{lower}+")}}])
+        }
+        StatsGroup::List => bson!({"$setUnion": [{"$ifNull": ["$user_data.lists", []]}]}),
+    }
+}
+
+/// Aggregation pipeline producing a single [`StatsFacet`] document
+pub(crate) fn pipeline(filter: Document, group_by: StatsGroup) -> Vec<Document> {
+    let sums = doc! {
+        "ases": {"$sum": 1},
+        "addresses": {"$sum": "$addresses"},
+        "prefixes": {"$sum": "$prefixes"},
+    };
+    let mut group = doc! {"_id": "$key"};
+    group.extend(sums.clone());
+    let mut total = doc! {"_id": null};
+    total.extend(sums);
+    vec![
+        doc! {"$match": filter},
+        doc! {"$project": {
+            "_id": 0,
+            "key": keys_expr(group_by),
+            "addresses": {"$ifNull": ["$asrank_data.addresses", 0]},
+            "prefixes": {"$ifNull": ["$asrank_data.prefixes", 0]},
+        }},
+        doc! {"$facet": {
+            "groups": [
+                {"$unwind": {"path": "$key", "preserveNullAndEmptyArrays": true}},
+                {"$group": group},
+                {"$sort": {"ases": -1, "_id": 1}},
+            ],
+            "total": [{"$group": total}],
+        }},
+    ]
+}
+
+#[derive(Deserialize)]
+pub(crate) struct StatsFacet {
+    groups: Vec<GroupDoc>,
+    total: Vec<GroupDoc>,
+}
+
+#[derive(Deserialize)]
+struct GroupDoc {
+    #[serde(rename = "_id")]
+    key: Option<String>,
+    ases: i64,
+    addresses: i64,
+    prefixes: i64,
+}
+
+impl From<&GroupDoc> for StatsTotals {
+    fn from(value: &GroupDoc) -> Self {
+        Self {
+            ases: value.ases as u64,
+            addresses: value.addresses as u64,
+            prefixes: value.prefixes as u64,
+        }
+    }
+}
+
+impl StatsFacet {
+    pub(crate) fn into_stats(self, group_by: StatsGroup) -> AsStats {
+        AsStats {
+            group_by,
+            groups: self
+                .groups
+                .iter()
+                .map(|x| StatsGroupRow {
+                    key: x.key.clone(),
+                    totals: x.into(),
+                })
+                .collect(),
+            // no group at all when nothing matched
+            total: self.total.first().map(Into::into).unwrap_or_default(),
+        }
+    }
+}
+
+/// Group keys of the record, same as the ones from [`keys_expr`]
+fn keys(a: &As, group_by: StatsGroup) -> Vec<Option<String>> {
+    let mut keys: Vec<Option<String>> = match group_by {
+        StatsGroup::Country => vec![a.asrank_data.as_ref().map(|x| x.country_iso.clone())],
+        StatsGroup::Category => a
+            .stanford_asdb
+            .iter()
+            .map(|x| Some(x.layer1.clone()))
+            .collect(),
+        StatsGroup::Subcategory => a
+            .stanford_asdb
+            .iter()
+            .map(|x| Some(x.layer2.clone()))
+            .collect(),
+        StatsGroup::Registry => vec![a.ipnetdb_data.as_ref().map(|x| {
+            match &x.registry {
+                InternetRegistry::RIPE => "RIPE",
+                InternetRegistry::ARIN => "ARIN",
+                InternetRegistry::APNIC => "APNIC",
+                InternetRegistry::AFRINIC => "AFRINIC",
+                InternetRegistry::LACNIC => "LACNIC",
+                InternetRegistry::LOCAL(_) => "LOCAL",
+                InternetRegistry::EMPTY => "EMPTY",
+            }
+            .to_string()
+        })],
+        StatsGroup::RankBucket => vec![a.asrank_data.as_ref().map(|x| rank_bucket(x.rank))],
+        StatsGroup::List => a
+            .user_data
+            .iter()
+            .flat_map(|x| x.lists.iter().map(|l| Some(l.clone())))
+            .collect(),
+    };
+    keys.sort();
+    keys.dedup();
+    if keys.is_empty() {
+        keys.push(None);
+    }
+    keys
+}
+
+/// Computes the statistics of already filtered ases
+pub(crate) fn compute<'a>(ases: impl Iterator<Item = &'a As>, group_by: StatsGroup) -> AsStats {
+    let mut groups: BTreeMap<Option<String>, StatsTotals> = BTreeMap::new();
+    let mut total = StatsTotals::default();
+    for a in ases {
+        let (addresses, prefixes) = a
+            .asrank_data
+            .as_ref()
+            .map_or((0, 0), |x| (x.addresses as u64, x.prefixes as u64));
+        let add = |t: &mut StatsTotals| {
+            t.ases += 1;
+            t.addresses += addresses;
+            t.prefixes += prefixes;
+        };
+        for key in keys(a, group_by) {
+            add(groups.entry(key).or_default());
+        }
+        add(&mut total);
+    }
+    let mut groups: Vec<StatsGroupRow> = groups
+        .into_iter()
+        .map(|(key, totals)| StatsGroupRow { key, totals })
+        .collect();
+    // stable, so groups of the same size stay sorted by key like in mongo
+    groups.sort_by(|a, b| b.totals.ases.cmp(&a.totals.ases));
+    AsStats {
+        group_by,
+        groups,
+        total,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use asdb_models::{StanfordASdbCategory, UserData};
+
+    use super::*;
+
+    #[test]
+    fn ranks_go_to_power_of_ten_buckets() {
+        assert_eq!(rank_bucket(1), "1-10");
+        assert_eq!(rank_bucket(10), "1-10");
+        assert_eq!(rank_bucket(11), "11-100");
+        assert_eq!(rank_bucket(10_000), "1001-10000");
+        assert_eq!(rank_bucket(123_456), "10001+");
+    }
+
+    #[test]
+    fn ases_count_once_in_each_of_their_groups() {
+        let category = |layer1: &str, layer2: &str| StanfordASdbCategory {
+            layer1: layer1.to_string(),
+            layer2: layer2.to_string(),
+        };
+        let ases = [
+            As {
+                asn: 1,
+                stanford_asdb: vec![category("ISP", "Fiber"), category("ISP", "Cable")],
+                user_data: Some(UserData {
+                    lists: vec!["watch".to_string()],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            As {
+                asn: 2,
+                stanford_asdb: vec![category("Education", ""), category("ISP", "")],
+                ..Default::default()
+            },
+            As {
+                asn: 3,
+                ..Default::default()
+            },
+        ];
+
+        let stats = compute(ases.iter(), StatsGroup::Category);
+        let groups: Vec<(Option<&str>, u64)> = stats
+            .groups
+            .iter()
+            .map(|x| (x.key.as_deref(), x.totals.ases))
+            .collect();
+        assert_eq!(
+            groups,
+            [(Some("ISP"), 2), (None, 1), (Some("Education"), 1)]
+        );
+        assert_eq!(stats.total.ases, 3);
+
+        let lists = compute(ases.iter(), StatsGroup::List);
+        assert_eq!(lists.groups.len(), 2);
+        assert_eq!(lists.groups[0].key, None);
+        assert_eq!(lists.groups[0].totals.ases, 2);
+    }
+}
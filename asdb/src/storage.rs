@@ -0,0 +1,948 @@
+//! Storage backend abstraction over the AS database.
+//!
+//! [`crate::Asdb`] implements it on top of MongoDB and [`crate::InMemoryAsdb`] keeps
+//! everything in process memory, which is handy for tests that shouldn't need a live
+//! database.
+
+use std::{collections::BTreeMap, future::Future, net::IpAddr};
+
+use asdb_models::{
+    AnnotationField, AnnotationValue, As, AsFilters, AsForFrontend, AsList, AsNeighbor, AsPage,
+    AsRelationship, AsSnapshot, AsSort, AsSource, AsStats, Dataset, Facility, FieldChange,
+    GeocodedAddress, IPNetDBAsn, IPNetDBPrefix, InternetRegistry, IpLookup, Ixp, ListSummary,
+    NeighborKind, Organisation, PeeringdbNet, Person, Prefix, RirBlock, RirDelegation, SearchHit,
+    StanfordASdbCategory, StatsGroup, UpsertStats, UserData, UserDataEdit, WhoIsAsn, WhoIsOrg,
+    WhoIsPerson,
+};
+use futures::Stream;
+use ipnetwork::IpNetwork;
+
+use crate::{Error, Result, annotations, prefix, snapshot};
+
+/// Operations every AS database backend has to provide.
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    /// Drops all data held by the backend.
+    fn clear_database(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Creates indexes and anything else the backend needs before use. Must be idempotent.
+    fn prepare_database(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Returns an empty backend next to this one for an import which replaces the ases,
+    /// organisations, prefixes and persons once it's complete, see [`Storage::promote`].
+    /// Snapshots and datasets recorded through it are kept either way.
+    fn staging(&self) -> impl Future<Output = Result<Self>> + Send
+    where
+        Self: Sized;
+
+    /// Validates the data loaded into `staging` and swaps it into place. Collections the
+    /// import left empty are kept as they are. With `keep_user_data` the user and WHOIS data of
+    /// the current ases is copied onto the staged ones. Returns the count of ases now in place,
+    /// the replaced data is kept for [`Storage::rollback`].
+    fn promote(
+        &self,
+        staging: Self,
+        keep_user_data: bool,
+    ) -> impl Future<Output = Result<u64>> + Send
+    where
+        Self: Sized;
+
+    /// Puts the data replaced by the last [`Storage::promote`] back into place.
+    fn rollback(&self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Returns a page of ases sorted by asn along with the total count of ases. Like the other
+    /// listings it skips the ases without asrank data, which can't be shown on the frontend.
+    /// `limit` of 0 means no limit.
+    fn get_ases_page(
+        &self,
+        limit: i64,
+        skip: u64,
+    ) -> impl Future<Output = Result<(Vec<AsForFrontend>, u64)>> + Send;
+
+    /// Same page as [`Storage::get_ases_page`] streamed without collecting it
+    fn stream_ases_page(
+        &self,
+        limit: i64,
+        skip: u64,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin>> + Send;
+
+    /// Returns up to `limit` ases matching the filters in the `sort` order, starting after the
+    /// page `after` is the [`AsPage::next`] token of. Unlike [`Storage::get_ases_page`] it doesn't
+    /// skip over the earlier pages nor count the ases. `limit` of 0 means no limit.
+    fn get_ases_sorted(
+        &self,
+        filters: &AsFilters,
+        sort: AsSort,
+        limit: i64,
+        after: Option<&str>,
+    ) -> impl Future<Output = Result<AsPage>> + Send;
+
+    /// Returns full records for given asns along with the total count of ases.
+    fn get_ases(&self, asns: &[u32]) -> impl Future<Output = Result<(Vec<As>, u64)>> + Send;
+
+    /// Streams full records for given asns without collecting them
+    fn stream_ases(
+        &self,
+        asns: &[u32],
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<As>> + Send + Unpin>> + Send;
+
+    fn count_ases_filtered(&self, filters: &AsFilters) -> impl Future<Output = Result<u64>> + Send;
+
+    fn get_ases_filtered(
+        &self,
+        filters: &AsFilters,
+    ) -> impl Future<Output = Result<Vec<AsForFrontend>>> + Send;
+
+    /// Streams ases matching the filters without collecting them, for results too big to
+    /// keep in memory at once
+    fn stream_ases_filtered(
+        &self,
+        filters: &AsFilters,
+    ) -> impl Future<Output = Result<impl Stream<Item = Result<AsForFrontend>> + Send + Unpin>> + Send;
+
+    /// Finds ases whose names, organisations or WHOIS descriptions contain any of the words in
+    /// `query`, the most relevant first. `limit` of 0 means no limit.
+    fn search(
+        &self,
+        query: &str,
+        limit: i64,
+    ) -> impl Future<Output = Result<Vec<SearchHit>>> + Send;
+
+    /// Counts ases matching the filters and sums their addresses and prefixes, in total and
+    /// per value of `group_by`
+    fn get_stats(
+        &self,
+        filters: &AsFilters,
+        group_by: StatsGroup,
+    ) -> impl Future<Output = Result<AsStats>> + Send;
+
+    fn get_as(&self, asn: u32) -> impl Future<Output = Result<As>> + Send;
+
+    fn insert_as(&self, a: &As) -> impl Future<Output = Result<()>> + Send;
+
+    /// Inserts all ases. Already existing asns are reported with [`crate::Error::DuplicatesFound`].
+    fn insert_ases(&self, a: &[As]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Inserts ases which are not stored yet and replaces the `source` data of the stored ones,
+    /// leaving data of other sources and user data untouched.
+    fn upsert_ases(
+        &self,
+        a: &[As],
+        source: AsSource,
+    ) -> impl Future<Output = Result<UpsertStats>> + Send;
+
+    /// Updates the record for given asn with the provided IPNetDB data
+    fn insert_ipnetdb_asn(
+        &self,
+        asn: u32,
+        a: &IPNetDBAsn,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Removes the PeeringDB networks from all records, so the ones missing from a newer dump
+    /// don't stay. Returns the asns of the updated records
+    fn clear_peeringdb_nets(&self) -> impl Future<Output = Result<Vec<u32>>> + Send;
+
+    /// Updates the record for given asn with its PeeringDB network
+    fn insert_peeringdb_net(
+        &self,
+        asn: u32,
+        net: &PeeringdbNet,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Removes the RIR delegations made by the registries from the records, so the ones
+    /// dropped from newer statistics don't stay. Returns the asns of the updated records
+    fn clear_rir_asn_delegations(
+        &self,
+        registries: &[InternetRegistry],
+    ) -> impl Future<Output = Result<Vec<u32>>> + Send;
+
+    /// Updates the records of asns `first..first + count` with their RIR delegation, returns
+    /// the asns of the updated records
+    fn insert_rir_asn_delegation(
+        &self,
+        first: u32,
+        count: u32,
+        delegation: &RirDelegation,
+    ) -> impl Future<Output = Result<Vec<u32>>> + Send;
+
+    /// Updates the record for given asn with the provided categories list from stanford asdb
+    fn insert_stanford_asdb_categories(
+        &self,
+        asn: u32,
+        categories: &[StanfordASdbCategory],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Updates the record for given ASN with WHOIS data. Contacts are stored with
+    /// [`Storage::update_whois_contacts`] instead of the AS.
+    fn update_whois_data(
+        &self,
+        asn: u32,
+        whois_data: &WhoIsAsn,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets cached WHOIS data for an ASN if available, with contacts from the persons collection
+    fn get_whois_data(&self, asn: u32) -> impl Future<Output = Result<Option<WhoIsAsn>>> + Send {
+        async move {
+            let Some(mut whois) = self.get_as(asn).await?.whois_data else {
+                return Ok(None);
+            };
+            // data cached before contacts got their own collection still has them inline
+            if whois.contacts.is_empty() {
+                let org_handles = whois.organisation.iter().flat_map(|o| o.contact_handles());
+                let mut handles: Vec<String> = whois
+                    .contact_handles()
+                    .into_iter()
+                    .chain(org_handles)
+                    .map(|(_, x)| x.to_string())
+                    .collect();
+                handles.sort();
+                handles.dedup();
+                let persons = self.get_persons(&handles).await?;
+                whois.contacts = persons.into_iter().map(|p| p.contact).collect();
+            }
+            Ok(Some(whois))
+        }
+    }
+
+    /// Updates user data (lists/comment) for an ASN. Blank comment clears it. Isn't recorded in
+    /// the edit history, see [`Storage::edit_user_data`].
+    fn update_user_data(
+        &self,
+        asn: u32,
+        lists: Option<Vec<String>>,
+        comment: Option<String>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Updates geocoding results for an ASN
+    fn update_geocoded_addresses(
+        &self,
+        asn: u32,
+        geocoded: Vec<GeocodedAddress>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets user data for an ASN (empty if missing)
+    fn get_user_data(&self, asn: u32) -> impl Future<Output = Result<UserData>> + Send {
+        async move { Ok(self.get_as(asn).await?.user_data.unwrap_or_default()) }
+    }
+
+    /// Replaces the whole user data of an ASN
+    fn set_user_data(
+        &self,
+        asn: u32,
+        user_data: &UserData,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets user data of those of `asns` which are stored (empty if missing), ordered by asn
+    fn get_user_data_of(
+        &self,
+        asns: &[u32],
+    ) -> impl Future<Output = Result<Vec<(u32, UserData)>>> + Send;
+
+    /// Stores edits in the history of user data changes
+    fn save_user_data_edits(
+        &self,
+        edits: &[UserDataEdit],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets the user data edits of an ASN, the most recent first
+    fn get_user_data_history(
+        &self,
+        asn: u32,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send;
+
+    /// [`Storage::update_user_data`] recorded in the edit history along with the `editor`.
+    /// Returns the updated user data.
+    fn edit_user_data(
+        &self,
+        asn: u32,
+        lists: Option<Vec<String>>,
+        comment: Option<String>,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<UserData>> + Send {
+        async move {
+            let old = self.get_user_data(asn).await?;
+            self.update_user_data(asn, lists, comment).await?;
+            self.record_user_data_edit(asn, old, editor).await
+        }
+    }
+
+    /// [`Storage::update_geocoded_addresses`] recorded in the edit history along with the
+    /// `editor`. Returns the updated user data.
+    fn edit_geocoded_addresses(
+        &self,
+        asn: u32,
+        geocoded: Vec<GeocodedAddress>,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<UserData>> + Send {
+        async move {
+            let old = self.get_user_data(asn).await?;
+            self.update_geocoded_addresses(asn, geocoded).await?;
+            self.record_user_data_edit(asn, old, editor).await
+        }
+    }
+
+    /// Puts back the user data an ASN had before the edit `edit_id`, which is recorded as an
+    /// edit of its own. Returns the restored user data.
+    fn revert_user_data(
+        &self,
+        asn: u32,
+        edit_id: &str,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<UserData>> + Send {
+        async move {
+            let edit = self
+                .get_user_data_history(asn)
+                .await?
+                .into_iter()
+                .find(|x| x.id == edit_id)
+                .ok_or(Error::EditNotFound)?;
+            let old = self.get_user_data(asn).await?;
+            self.set_user_data(asn, &edit.old).await?;
+            self.record_user_data_edit(asn, old, editor).await
+        }
+    }
+
+    /// Records the change from `old` to the current user data, if there is any
+    fn record_user_data_edit(
+        &self,
+        asn: u32,
+        old: UserData,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<UserData>> + Send {
+        async move {
+            let new = self.get_user_data(asn).await?;
+            if new != old {
+                let edit = user_data_edit(asn, old, new.clone(), editor);
+                self.save_user_data_edits(&[edit]).await?;
+            }
+            Ok(new)
+        }
+    }
+
+    /// Records the changes from `old` to the current user data of each of the ases and returns
+    /// the recorded edits
+    fn record_user_data_edits(
+        &self,
+        old: Vec<(u32, UserData)>,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send {
+        async move {
+            let asns: Vec<u32> = old.iter().map(|x| x.0).collect();
+            let new: BTreeMap<u32, UserData> =
+                self.get_user_data_of(&asns).await?.into_iter().collect();
+            let edits: Vec<UserDataEdit> = old
+                .into_iter()
+                .filter_map(|(asn, old)| {
+                    let new = new.get(&asn).filter(|x| **x != old)?;
+                    Some(user_data_edit(asn, old, new.clone(), editor))
+                })
+                .collect();
+            if !edits.is_empty() {
+                self.save_user_data_edits(&edits).await?;
+            }
+            Ok(edits)
+        }
+    }
+
+    /// Gets all non-empty list names from user data, sorted
+    fn get_list_names(&self) -> impl Future<Output = Result<Vec<String>>> + Send;
+
+    /// Stores the metadata of a list, replacing the one with the same name
+    fn save_list(&self, list: &AsList) -> impl Future<Output = Result<()>> + Send;
+
+    /// Moves the metadata of a list to a new name, which has to be free. A list only named in
+    /// user data gets empty metadata under the new name.
+    fn rename_list_metadata(
+        &self,
+        name: &str,
+        new_name: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Removes the metadata of a list, its members are kept
+    fn delete_list_metadata(&self, name: &str) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets the metadata of all lists, sorted by name. Lists only named in user data have none.
+    fn get_list_metadata(&self) -> impl Future<Output = Result<Vec<AsList>>> + Send;
+
+    /// Gets the number of members of every list named in user data
+    fn count_list_members(&self) -> impl Future<Output = Result<BTreeMap<String, u64>>> + Send;
+
+    /// Gets the asns which are members of the list, sorted
+    fn get_list_members(&self, name: &str) -> impl Future<Output = Result<Vec<u32>>> + Send;
+
+    /// Adds the list to the user data of those of `asns` which are stored, without recording
+    /// edits
+    fn add_list_members(&self, name: &str, asns: &[u32])
+    -> impl Future<Output = Result<()>> + Send;
+
+    /// Removes the list from the user data of `asns`, without recording edits
+    fn remove_list_members(
+        &self,
+        name: &str,
+        asns: &[u32],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets every list with its metadata and number of members, sorted by name
+    fn get_lists(&self) -> impl Future<Output = Result<Vec<ListSummary>>> + Send {
+        async move {
+            let mut counts = self.count_list_members().await?;
+            let mut lists: Vec<ListSummary> = self
+                .get_list_metadata()
+                .await?
+                .into_iter()
+                .map(|list| ListSummary {
+                    count: counts.remove(&list.name).unwrap_or_default(),
+                    list,
+                })
+                .collect();
+            lists.extend(counts.into_iter().map(|(name, count)| ListSummary {
+                list: AsList {
+                    name,
+                    ..Default::default()
+                },
+                count,
+            }));
+            lists.sort_by(|a, b| a.list.name.cmp(&b.list.name));
+            Ok(lists)
+        }
+    }
+
+    /// Creates an empty list, the name has to be free
+    fn create_list(&self, list: &AsList) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            if list.name.trim().is_empty() {
+                return Err(Error::BlankListName);
+            }
+            if self.find_list(&list.name).await?.is_some() {
+                return Err(Error::ListExists(list.name.clone()));
+            }
+            self.save_list(list).await
+        }
+    }
+
+    /// Changes the description and color of an existing list
+    fn update_list(&self, list: &AsList) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            if self.find_list(&list.name).await?.is_none() {
+                return Err(Error::ListNotFound(list.name.clone()));
+            }
+            self.save_list(list).await
+        }
+    }
+
+    /// Renames a list in its metadata and in the user data of its members, returns the edits
+    /// of the members
+    fn rename_list(
+        &self,
+        name: &str,
+        new_name: &str,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send {
+        async move {
+            if new_name.trim().is_empty() {
+                return Err(Error::BlankListName);
+            }
+            if self.find_list(name).await?.is_none() {
+                return Err(Error::ListNotFound(name.to_string()));
+            }
+            if self.find_list(new_name).await?.is_some() {
+                return Err(Error::ListExists(new_name.to_string()));
+            }
+            // claims the new name before the members are moved to it
+            self.rename_list_metadata(name, new_name).await?;
+            let members = self.get_list_members(name).await?;
+            let old = self.get_user_data_of(&members).await?;
+            self.add_list_members(new_name, &members).await?;
+            self.remove_list_members(name, &members).await?;
+            self.record_user_data_edits(old, editor).await
+        }
+    }
+
+    /// Removes a list from the user data of its members and deletes its metadata, returns the
+    /// edits of the members
+    fn delete_list(
+        &self,
+        name: &str,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send {
+        async move {
+            if self.find_list(name).await?.is_none() {
+                return Err(Error::ListNotFound(name.to_string()));
+            }
+            let members = self.get_list_members(name).await?;
+            let old = self.get_user_data_of(&members).await?;
+            self.remove_list_members(name, &members).await?;
+            self.delete_list_metadata(name).await?;
+            self.record_user_data_edits(old, editor).await
+        }
+    }
+
+    /// Adds ases to an existing list, returns the edits of those which weren't members
+    fn add_to_list(
+        &self,
+        name: &str,
+        asns: &[u32],
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send {
+        async move {
+            if self.find_list(name).await?.is_none() {
+                return Err(Error::ListNotFound(name.to_string()));
+            }
+            let old = self.get_user_data_of(asns).await?;
+            self.add_list_members(name, asns).await?;
+            self.record_user_data_edits(old, editor).await
+        }
+    }
+
+    /// Removes ases from a list, returns the edits of those which were members
+    fn remove_from_list(
+        &self,
+        name: &str,
+        asns: &[u32],
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<Vec<UserDataEdit>>> + Send {
+        async move {
+            let old = self.get_user_data_of(asns).await?;
+            self.remove_list_members(name, asns).await?;
+            self.record_user_data_edits(old, editor).await
+        }
+    }
+
+    /// Gets a list by name, with empty metadata if it's only named in user data
+    fn find_list(&self, name: &str) -> impl Future<Output = Result<Option<AsList>>> + Send {
+        async move {
+            let lists = self.get_lists().await?;
+            Ok(lists.into_iter().map(|x| x.list).find(|x| x.name == name))
+        }
+    }
+
+    /// Stores the definition of an annotation field, replacing the one with the same name
+    fn save_annotation_field(
+        &self,
+        field: &AnnotationField,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all annotation fields, sorted by name
+    fn get_annotation_fields(&self) -> impl Future<Output = Result<Vec<AnnotationField>>> + Send;
+
+    /// Sets or with None removes an annotation of an AS, without checking it against its field
+    /// or recording the edit
+    fn set_annotation(
+        &self,
+        asn: u32,
+        name: &str,
+        value: Option<&AnnotationValue>,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Adds an annotation field or changes one keeping the type of its values
+    fn define_annotation_field(
+        &self,
+        field: &AnnotationField,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let fields = self.get_annotation_fields().await?;
+            let current = fields.iter().find(|x| x.name == field.name);
+            annotations::validate_field(field, current)?;
+            self.save_annotation_field(field).await
+        }
+    }
+
+    /// Sets or with None removes an annotation of an AS, checked against its field and recorded
+    /// in the edit history. Returns the updated user data.
+    fn edit_annotation(
+        &self,
+        asn: u32,
+        name: &str,
+        value: Option<AnnotationValue>,
+        editor: Option<&str>,
+    ) -> impl Future<Output = Result<UserData>> + Send {
+        async move {
+            let fields = self.get_annotation_fields().await?;
+            let field = fields
+                .iter()
+                .find(|x| x.name == name)
+                .ok_or_else(|| Error::AnnotationFieldNotFound(name.to_string()))?;
+            if let Some(value) = &value {
+                annotations::validate_value(field, value)?;
+            }
+            let old = self.get_user_data(asn).await?;
+            self.set_annotation(asn, name, value.as_ref()).await?;
+            self.record_user_data_edit(asn, old, editor).await
+        }
+    }
+
+    /// Stores prefixes announced by `asn` in the prefixes collection, merging origins and
+    /// details with the already stored ones
+    fn insert_prefixes(
+        &self,
+        asn: u32,
+        prefixes: &[IPNetDBPrefix],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets stored prefixes whose canonical range is exactly one of `ranges`
+    fn get_prefixes(
+        &self,
+        ranges: &[IpNetwork],
+    ) -> impl Future<Output = Result<Vec<Prefix>>> + Send;
+
+    /// Finds the most specific stored prefix covering `ip` along with the ases originating it
+    fn lookup_ip(&self, ip: IpAddr) -> impl Future<Output = Result<Option<IpLookup>>> + Send {
+        async move {
+            let candidates = prefix::covering_networks(ip);
+            let Some(prefix) = self
+                .get_prefixes(&candidates)
+                .await?
+                .into_iter()
+                .max_by_key(|p| p.range.prefix())
+            else {
+                return Ok(None);
+            };
+            let (ases, _) = self.get_ases(&prefix.origins).await?;
+            Ok(Some(IpLookup { prefix, ases }))
+        }
+    }
+
+    /// Replaces all stored RIR blocks with `blocks`
+    fn replace_rir_blocks(&self, blocks: &[RirBlock]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets stored RIR blocks whose range is exactly one of `ranges`
+    fn get_rir_blocks(
+        &self,
+        ranges: &[IpNetwork],
+    ) -> impl Future<Output = Result<Vec<RirBlock>>> + Send;
+
+    /// Finds the most specific RIR block covering `ip`
+    fn lookup_rir_block(
+        &self,
+        ip: IpAddr,
+    ) -> impl Future<Output = Result<Option<RirBlock>>> + Send {
+        async move {
+            let candidates = prefix::covering_networks(ip);
+            Ok(self
+                .get_rir_blocks(&candidates)
+                .await?
+                .into_iter()
+                .max_by_key(|x| x.range.prefix()))
+        }
+    }
+
+    /// Replaces all stored AS relationships with `relationships`
+    fn replace_relationships(
+        &self,
+        relationships: &[AsRelationship],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets ases linked to `asn`, only the ones playing the `kind` role for it if given, sorted
+    /// by asn
+    fn get_neighbors(
+        &self,
+        asn: u32,
+        kind: Option<NeighborKind>,
+    ) -> impl Future<Output = Result<Vec<AsNeighbor>>> + Send;
+
+    /// Replaces all stored IXPs with `ixps`
+    fn replace_ixps(&self, ixps: &[Ixp]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all stored IXPs sorted by their PeeringDB id
+    fn get_ixps(&self) -> impl Future<Output = Result<Vec<Ixp>>> + Send;
+
+    /// Replaces all stored facilities with `facilities`
+    fn replace_facilities(
+        &self,
+        facilities: &[Facility],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all stored facilities sorted by their PeeringDB id
+    fn get_facilities(&self) -> impl Future<Output = Result<Vec<Facility>>> + Send;
+
+    /// Gets organisation by its org id, ASRank org id or WHOIS org id
+    fn get_organisation(
+        &self,
+        org_id: &str,
+    ) -> impl Future<Output = Result<Option<Organisation>>> + Send;
+
+    /// Gets the organisation the AS belongs to
+    fn get_as_organisation(
+        &self,
+        asn: u32,
+    ) -> impl Future<Output = Result<Option<Organisation>>> + Send;
+
+    /// Replaces the stored organisation with the same `org_id`. Its asns are removed from every
+    /// other organisation and those it leaves without any asns are dropped.
+    fn save_organisation(&self, org: &Organisation) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all ases of the organisation, accepts the same ids as [`Storage::get_organisation`]
+    fn get_organisation_ases(&self, org_id: &str) -> impl Future<Output = Result<Vec<As>>> + Send {
+        async move {
+            let Some(org) = self.get_organisation(org_id).await? else {
+                return Ok(vec![]);
+            };
+            Ok(self.get_ases(&org.asns).await?.0)
+        }
+    }
+
+    /// Groups ases by their ASRank organisation and merges the groups into stored organisations.
+    /// WHOIS data of an organisation known so far only from WHOIS is kept.
+    fn update_asrank_organisations(&self, ases: &[As]) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let mut groups: BTreeMap<&str, (&str, Vec<u32>)> = BTreeMap::new();
+            for (a, asrank) in ases
+                .iter()
+                .filter_map(|a| Some((a, a.asrank_data.as_ref()?)))
+            {
+                let Some(org_id) = asrank.organization_id.as_deref() else {
+                    continue;
+                };
+                let name = asrank.organization.as_deref().unwrap_or_default();
+                groups.entry(org_id).or_insert((name, vec![])).1.push(a.asn);
+            }
+            for (org_id, (name, asns)) in groups {
+                let mut org = match self.get_organisation(org_id).await? {
+                    Some(org) => org,
+                    None => {
+                        let mut org = Organisation {
+                            org_id: org_id.to_string(),
+                            ..Default::default()
+                        };
+                        for asn in &asns {
+                            if let Some(known) = self.get_as_organisation(*asn).await?
+                                && known.asrank_org_id.is_none()
+                            {
+                                org.whois_org_id = known.whois_org_id;
+                                org.whois = known.whois;
+                                break;
+                            }
+                        }
+                        org
+                    }
+                };
+                org.name = name.to_string();
+                org.asrank_org_id = Some(org_id.to_string());
+                org.asns.extend(asns);
+                org.asns.sort();
+                org.asns.dedup();
+                self.save_organisation(&org).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Attaches the WHOIS organisation to the organisation of the AS. If the AS has none yet,
+    /// or it's linked to a different WHOIS organisation, it's moved to the one for `whois`.
+    fn link_whois_organisation(
+        &self,
+        asn: u32,
+        whois: &WhoIsOrg,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let whois_id = &whois.org_id;
+            let mut org = match self.get_as_organisation(asn).await? {
+                Some(org) if org.whois_org_id.as_ref().is_none_or(|x| x == whois_id) => org,
+                _ => self
+                    .get_organisation(whois_id)
+                    .await?
+                    .unwrap_or_else(|| Organisation {
+                        org_id: whois_id.clone(),
+                        ..Default::default()
+                    }),
+            };
+            if org.asrank_org_id.is_none() {
+                org.name = whois.org_name.clone();
+            }
+            org.whois_org_id = Some(whois_id.clone());
+            org.whois = Some(whois.clone());
+            if !org.asns.contains(&asn) {
+                org.asns.push(asn);
+                org.asns.sort();
+            }
+            self.save_organisation(&org).await
+        }
+    }
+
+    /// Gets persons and roles stored under any of the NIC handles, sorted by handle
+    fn get_persons(&self, nic_hdls: &[String]) -> impl Future<Output = Result<Vec<Person>>> + Send;
+
+    fn get_person(&self, nic_hdl: &str) -> impl Future<Output = Result<Option<Person>>> + Send {
+        async move {
+            let persons = self.get_persons(&[nic_hdl.to_string()]).await?;
+            Ok(persons.into_iter().next())
+        }
+    }
+
+    /// Gets persons and roles the AS references as admin-c, tech-c or abuse-c
+    fn get_as_contacts(&self, asn: u32) -> impl Future<Output = Result<Vec<Person>>> + Send;
+
+    /// Gets persons and roles the WHOIS organisation references as admin-c, tech-c or abuse-c
+    fn get_org_contacts(
+        &self,
+        whois_org_id: &str,
+    ) -> impl Future<Output = Result<Vec<Person>>> + Send;
+
+    /// Replaces the stored person with the same `nic_hdl`. Persons nothing references anymore
+    /// are removed instead.
+    fn save_person(&self, person: &Person) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all ases referencing the NIC handle as admin-c, tech-c or abuse-c
+    fn get_managed_ases(&self, nic_hdl: &str) -> impl Future<Output = Result<Vec<As>>> + Send {
+        async move {
+            let Some(person) = self.get_person(nic_hdl).await? else {
+                return Ok(vec![]);
+            };
+            let asns: Vec<u32> = person.ases.all().into_iter().copied().collect();
+            Ok(self.get_ases(&asns).await?.0)
+        }
+    }
+
+    /// Stores contacts of the aut-num and of its organisation in the persons collection and
+    /// points them back to the AS and the organisation. References the AS or the organisation
+    /// no longer have are dropped.
+    fn update_whois_contacts(
+        &self,
+        asn: u32,
+        whois: &WhoIsAsn,
+    ) -> impl Future<Output = Result<()>> + Send {
+        async move {
+            let org = whois.organisation.as_ref();
+            let mut persons: BTreeMap<String, Person> = BTreeMap::new();
+            let mut previous = self.get_as_contacts(asn).await?;
+            if let Some(org) = org {
+                previous.extend(self.get_org_contacts(&org.org_id).await?);
+            }
+            for person in previous {
+                let person = persons.entry(person.nic_hdl.clone()).or_insert(person);
+                person.ases.remove(&asn);
+                if let Some(org) = org {
+                    person.orgs.remove(&org.org_id);
+                }
+            }
+
+            let as_handles = whois.contact_handles();
+            let org_handles = org.map(|o| o.contact_handles()).unwrap_or_default();
+            let missing: Vec<String> = as_handles
+                .iter()
+                .chain(&org_handles)
+                .filter(|(_, x)| !persons.contains_key(*x))
+                .map(|(_, x)| x.to_string())
+                .collect();
+            for person in self.get_persons(&missing).await? {
+                persons.insert(person.nic_hdl.clone(), person);
+            }
+            for (_, nic_hdl) in as_handles.iter().chain(&org_handles) {
+                persons
+                    .entry(nic_hdl.to_string())
+                    .or_insert_with(|| Person {
+                        nic_hdl: nic_hdl.to_string(),
+                        contact: WhoIsPerson {
+                            nic_hdl: nic_hdl.to_string(),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    });
+            }
+            const ADDED: &str = "all handles were added above";
+            for (role, nic_hdl) in as_handles {
+                persons.get_mut(nic_hdl).expect(ADDED).ases.add(role, asn);
+            }
+            if let Some(org) = org {
+                for (role, nic_hdl) in org_handles {
+                    let person = persons.get_mut(nic_hdl).expect(ADDED);
+                    person.orgs.add(role, org.org_id.clone());
+                }
+            }
+            for contact in &whois.contacts {
+                if let Some(person) = persons.get_mut(&contact.nic_hdl) {
+                    person.contact = contact.clone();
+                }
+            }
+
+            for person in persons.values() {
+                self.save_person(person).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Stores copies of the current records of `asns` tagged with the import run that touched
+    /// them. Snapshotting the same asn twice in one run replaces the earlier copy.
+    fn snapshot_ases(&self, run_id: &str, asns: &[u32]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets all snapshots taken during the import run, sorted by asn
+    fn get_run_snapshots(
+        &self,
+        run_id: &str,
+    ) -> impl Future<Output = Result<Vec<AsSnapshot>>> + Send;
+
+    fn get_snapshot(
+        &self,
+        run_id: &str,
+        asn: u32,
+    ) -> impl Future<Output = Result<Option<AsSnapshot>>> + Send;
+
+    /// Replaces the stored record of the import run with the same `run_id`
+    fn save_dataset(&self, dataset: &Dataset) -> impl Future<Output = Result<()>> + Send;
+
+    /// Gets records of all import runs, the most recently started first
+    fn get_datasets(&self) -> impl Future<Output = Result<Vec<Dataset>>> + Send;
+
+    /// Field level diff of the AS between its snapshots from two import runs. A run which
+    /// didn't snapshot the AS counts as an empty record.
+    fn diff_as_snapshots(
+        &self,
+        asn: u32,
+        from_run: &str,
+        to_run: &str,
+    ) -> impl Future<Output = Result<Vec<FieldChange>>> + Send {
+        async move {
+            let from = self.get_snapshot(from_run, asn).await?;
+            let to = self.get_snapshot(to_run, asn).await?;
+            Ok(snapshot::diff(
+                from.as_ref().map(|x| &x.data),
+                to.as_ref().map(|x| &x.data),
+            ))
+        }
+    }
+
+    /// Gets asns whose snapshots differ between two import runs, including the ones
+    /// snapshotted in only one of them
+    fn get_changed_ases(
+        &self,
+        from_run: &str,
+        to_run: &str,
+    ) -> impl Future<Output = Result<Vec<u32>>> + Send {
+        async move {
+            let from: BTreeMap<u32, As> = self
+                .get_run_snapshots(from_run)
+                .await?
+                .into_iter()
+                .map(|x| (x.asn, x.data))
+                .collect();
+            let to: BTreeMap<u32, As> = self
+                .get_run_snapshots(to_run)
+                .await?
+                .into_iter()
+                .map(|x| (x.asn, x.data))
+                .collect();
+            let mut changed: Vec<u32> = from
+                .keys()
+                .chain(to.keys())
+                .filter(|asn| from.get(asn) != to.get(asn))
+                .copied()
+                .collect();
+            changed.sort();
+            changed.dedup();
+            Ok(changed)
+        }
+    }
+}
+
+/// Edit of the user data of `asn` made now
+fn user_data_edit(asn: u32, old: UserData, new: UserData, editor: Option<&str>) -> UserDataEdit {
+    let now = chrono::Utc::now();
+    UserDataEdit {
+        id: format!("{asn}-{}", now.timestamp_nanos_opt().unwrap_or_default()),
+        asn,
+        at: now.to_rfc3339(),
+        editor: editor.map(str::to_string),
+        old,
+        new,
+    }
+}
@@ -1,6 +1,7 @@
 use std::fs::File;
 use std::io::{BufRead, BufReader, BufWriter, Write};
 
+use asdb::{Asdb, Storage, migrations};
 use flate2::Compression;
 use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
@@ -10,7 +11,23 @@ use mongodb::bson::Document;
 use mongodb::options::{ClientOptions, InsertManyOptions};
 use serde::{Deserialize, Serialize};
 
-const COLLECTIONS: [&str; 4] = ["asns", "organisations", "prefixes", "persons"];
+// meta goes first so the import knows the schema version before reading any other document
+const COLLECTIONS: [&str; 14] = [
+    migrations::META_COLLECTION,
+    "asns",
+    "organisations",
+    "prefixes",
+    "persons",
+    "snapshots",
+    "datasets",
+    "user_data_edits",
+    "lists",
+    "annotation_fields",
+    "relationships",
+    "ixps",
+    "facilities",
+    "rir_blocks",
+];
 const BATCH_SIZE: usize = 1000;
 
 #[derive(Serialize, Deserialize)]
@@ -52,13 +69,21 @@ pub async fn export_db(conn_str: &str, database: &str, output_path: &str) -> any
     Ok(())
 }
 
+/// Imports into staging collections which replace the current ones once the whole file is read,
+/// the replaced data can be restored with rollback
 pub async fn import_db(conn_str: &str, database: &str, input_path: &str) -> anyhow::Result<()> {
+    let asdb = Asdb::new(conn_str, database).await?;
+    let staging = asdb.staging().await?;
     let client = connect(conn_str, database).await?;
     let db = client.database(database);
 
+    // the rest is replaced on promotion, meta keeps the version the data was migrated to
     for collection_name in COLLECTIONS {
-        let collection = db.collection::<Document>(collection_name);
-        let _ = collection.drop().await;
+        if collection_name != migrations::META_COLLECTION
+            && asdb::staging_collection(collection_name).is_none()
+        {
+            let _ = db.collection::<Document>(collection_name).drop().await;
+        }
     }
 
     let file = File::open(input_path)?;
@@ -67,19 +92,33 @@ pub async fn import_db(conn_str: &str, database: &str, input_path: &str) -> anyh
 
     let mut batch_map: std::collections::HashMap<String, Vec<Document>> =
         std::collections::HashMap::new();
+    // exports made before the schema was versioned have no meta rows
+    let mut version = 0;
 
     for line in reader.lines() {
         let line = line?;
         if line.trim().is_empty() {
             continue;
         }
-        let row: ExportRow = serde_json::from_str(&line)?;
+        let mut row: ExportRow = serde_json::from_str(&line)?;
+        if row.collection == migrations::META_COLLECTION
+            && let Some(v) = migrations::version_from_document(&row.doc)
+        {
+            anyhow::ensure!(
+                v <= migrations::SCHEMA_VERSION,
+                "export has schema version {v}, newer than the supported {}",
+                migrations::SCHEMA_VERSION
+            );
+            version = v;
+            continue;
+        }
+        migrations::migrate_document(&row.collection, &mut row.doc, version);
         let bucket = batch_map.entry(row.collection.clone()).or_default();
         bucket.push(row.doc);
 
         if bucket.len() >= BATCH_SIZE {
             let docs = std::mem::take(bucket);
-            let collection = db.collection::<Document>(&row.collection);
+            let collection = db.collection::<Document>(&target(&row.collection));
             collection
                 .insert_many(docs)
                 .with_options(InsertManyOptions::builder().ordered(false).build())
@@ -91,12 +130,19 @@ pub async fn import_db(conn_str: &str, database: &str, input_path: &str) -> anyh
         if docs.is_empty() {
             continue;
         }
-        let collection = db.collection::<Document>(&collection_name);
+        let collection = db.collection::<Document>(&target(&collection_name));
         collection
             .insert_many(docs)
             .with_options(InsertManyOptions::builder().ordered(false).build())
             .await?;
     }
 
+    // the export replaces user data too
+    asdb.promote(staging, false).await?;
     Ok(())
 }
+
+/// Collection documents exported from `collection` are imported into
+fn target(collection: &str) -> String {
+    asdb::staging_collection(collection).unwrap_or_else(|| collection.to_string())
+}
@@ -2,7 +2,9 @@ use std::io::{BufRead, BufReader};
 use std::net::{IpAddr, Ipv6Addr};
 use std::process::{Command, Stdio};
 
+use asdb::Storage;
 use asdb_builder::AsdbBuilder;
+use asdb_models::{AsFilters, AsSort, AsSortField, NeighborKind, StatsGroup};
 use clap::{Args, Parser, Subcommand};
 
 mod details;
@@ -18,20 +20,36 @@ struct Cli {
     pub command: Commands,
     #[arg(short, long, default_value = "inputs/")]
     pub inputs_path: String,
+    /// dataset of the configured database the command works on
+    #[arg(long, global = true, default_value = asdb::DEFAULT_DATASET)]
+    pub dataset: String,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     /// Resets the database
     ClearDB,
-    /// Performs all of the loading steps in the correct order
+    /// Performs all of the loading steps in the correct order into staging collections which
+    /// replace the current ones once everything is loaded
     LoadAll(LoadAllArgs),
+    /// Restores the data replaced by the last load-all or import-db
+    Rollback,
     /// Load asrank asns.jsonl from file, takes in the path
     LoadAsrank(LoadAsrankAsnsArgs),
     /// Downloads if not found and loads IpnetDB data
     LoadIpnetdb,
     /// Downloads and saves the AS categories data from stanford asdb
     LoadStanfordAsdb,
+    /// Loads CAIDA as-rel or as-rel2 file (.txt or .bz2) replacing the stored AS relationships
+    LoadAsRel(LoadAsRelArgs),
+    /// Lists providers, customers and peers of an AS
+    Neighbors(NeighborsArgs),
+    /// Loads networks, IXPs and facilities from a PeeringDB JSON dump
+    LoadPeeringdb(LoadPeeringdbArgs),
+    /// Loads RIR delegations of asns and IP blocks from delegated-extended statistics files
+    LoadRirStats(LoadRirStatsArgs),
+    /// Fills WHOIS data of the ases from the split dumps of the RIPE database
+    LoadRipeDb(LoadRipeDbArgs),
     /// Generates static stanford asdb categories data structure
     GenerateCategories,
     /// Creates detailed file containing information about chosen asns
@@ -40,8 +58,20 @@ enum Commands {
     Start(StartServerArgs),
     /// Export database to a compressed JSONL file (overwrites output)
     ExportDb(ExportDbArgs),
-    /// Import database from a compressed JSONL file (drops existing collections)
+    /// Import database from a compressed JSONL file (replaces existing collections)
     ImportDb(ImportDbArgs),
+    /// Shows asns which changed between two import runs or field changes of a single one
+    Changes(ChangesArgs),
+    /// Searches ases by words in their names, organisations and WHOIS descriptions
+    Search(SearchArgs),
+    /// Shows which data the database was loaded with and when
+    Status(StatusArgs),
+    /// Prints counts of ases along with their addresses and prefixes, grouped by a value
+    Stats(StatsArgs),
+    /// Lists ases page by page in the given order
+    List(ListArgs),
+    /// Lists datasets of the configured database, each can be chosen with --dataset
+    Datasets,
     // Todo LoadWhois (for range?), LoadIpnetDB, Georesolve(Persons|Orgs|Somethin else?)
 }
 
@@ -59,12 +89,106 @@ struct ImportDbArgs {
     pub input: String,
 }
 
+#[derive(Args)]
+struct SearchArgs {
+    /// words to search for, ases matching more of them are shown first
+    pub query: String,
+    /// maximum number of results, 0 shows all
+    #[arg(short, long, default_value_t = 20)]
+    pub limit: i64,
+}
+
+#[derive(Args)]
+struct StatsArgs {
+    /// one of country, category, subcategory, registry, rank, list
+    pub group_by: StatsGroup,
+    /// count only ases from this 2 letter country code
+    #[arg(short, long)]
+    pub country: Option<String>,
+    /// count only ases on any of these user lists
+    #[arg(short, long)]
+    pub list: Vec<String>,
+}
+
+#[derive(Args)]
+struct StatusArgs {
+    /// show every import run instead of the latest one of each source
+    #[arg(short, long)]
+    pub all: bool,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    /// one of asn, rank, addresses, prefixes, degree, country, name
+    #[arg(short, long, default_value = "asn")]
+    pub sort: AsSortField,
+    /// sort from the largest values
+    #[arg(short, long)]
+    pub desc: bool,
+    /// ases per page, 0 lists all at once
+    #[arg(short, long, default_value_t = 50)]
+    pub limit: i64,
+    /// token printed after the previous page, has to be used with the same sort
+    #[arg(short, long)]
+    pub after: Option<String>,
+    /// list only ases from this 2 letter country code
+    #[arg(short, long)]
+    pub country: Option<String>,
+}
+
+#[derive(Args)]
+struct ChangesArgs {
+    /// id of the earlier import run, printed by the load commands
+    #[arg(short, long)]
+    pub from: String,
+    /// id of the later import run
+    #[arg(short, long)]
+    pub to: String,
+    /// show field changes of this asn instead of the list of changed asns
+    #[arg(short, long)]
+    pub asn: Option<u32>,
+}
+
 #[derive(Args)]
 struct LoadAsrankAsnsArgs {
     #[arg(short, long)]
     pub asns_filename: Option<String>,
 }
 
+#[derive(Args)]
+struct LoadAsRelArgs {
+    /// file in the inputs directory, e.g. 20240101.as-rel2.txt.bz2
+    pub filename: String,
+}
+
+#[derive(Args)]
+struct LoadPeeringdbArgs {
+    /// file in the inputs directory, e.g. peeringdb_2_dump_2024_01_01.json
+    pub filename: String,
+}
+
+#[derive(Args)]
+struct LoadRirStatsArgs {
+    /// files in the inputs directory, e.g. delegated-ripencc-extended-latest
+    #[arg(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Args)]
+struct LoadRipeDbArgs {
+    /// files in the inputs directory, e.g. ripe.db.aut-num.gz ripe.db.organisation.gz ripe.db.role.gz
+    #[arg(required = true)]
+    pub files: Vec<String>,
+}
+
+#[derive(Args)]
+struct NeighborsArgs {
+    pub asn: u32,
+    /// one of provider, customer, peer, lists all of them if not given
+    #[arg(short, long)]
+    pub kind: Option<NeighborKind>,
+}
+
 #[derive(Args)]
 struct LoadAllArgs {
     #[arg(short, long)]
@@ -98,6 +222,10 @@ struct StartServerArgs {
     pub ip: IpAddr,
     #[arg(short, long, value_parser = clap::value_parser!(u16).range(1..), default_value_t = 8080)]
     pub port: u16,
+    /// IANA RDAP bootstrap registry in the inputs directory, e.g. asn.json from
+    /// https://data.iana.org/rdap/asn.json, to fetch WHOIS data of non-RIPE ases over RDAP
+    #[arg(long)]
+    pub rdap_bootstrap: Option<String>,
 }
 
 #[tokio::main]
@@ -107,80 +235,6 @@ async fn main() {
 
     std::fs::create_dir_all(&args.inputs_path).expect("Couldn't create input dir");
     match args.command {
-        Commands::ClearDB => {
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            m.clear_database().await.unwrap();
-        }
-        Commands::LoadAll(a) => {
-            println!("performing complete database load");
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            m.load_asrank_asns(a.asrank_asns_filename).await.unwrap();
-            m.load_stanford_asdb().await.unwrap();
-            m.load_ipnetdb().await.unwrap();
-        }
-        Commands::LoadAsrank(a) => {
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            let result = m.load_asrank_asns(a.asns_filename).await;
-            println!("import result: {result:?}");
-        }
-        Commands::LoadIpnetdb => {
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            m.load_ipnetdb().await.unwrap();
-        }
-        Commands::LoadStanfordAsdb => {
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            m.load_stanford_asdb().await.unwrap();
-        }
-        Commands::GenerateCategories => {
-            //This will also be loaded automatically during loadStanfordAsdb but can be also trigerred manually
-            let m = AsdbBuilder::new(&cfg.mongo_conn_str, &cfg.db_name, &args.inputs_path)
-                .await
-                .unwrap();
-            m.generate_categories().await.unwrap();
-        }
-        Commands::GetDetailed(a) => {
-            use asdb::Asdb;
-            use details::*;
-            println!("generating details for {:?}", a.csv);
-
-            let asns = parse_input_csv(&a.csv);
-
-            let asdb = Asdb::new(&cfg.mongo_conn_str, &cfg.db_name).await.unwrap();
-            let (ases_detailed, count) = asdb.get_ases(&asns).await.unwrap();
-            println!(
-                "retrieved {count} detailed ases for {} asns from input",
-                asns.len()
-            );
-
-            match a.format {
-                GetDetailedFormat::InputList => {
-                    let output_path = if let Some(p) = a.output {
-                        p
-                    } else {
-                        a.csv.strip_suffix(".csv").unwrap().to_string()
-                    };
-                    details::generate_nmap_inputlist(&ases_detailed, &output_path);
-                }
-                GetDetailedFormat::Json => {
-                    let output_path = if let Some(p) = a.output {
-                        p
-                    } else {
-                        format!("{}-detailed.json", a.csv.strip_suffix(".csv").unwrap())
-                    };
-                    generate_json(&ases_detailed, &output_path);
-                }
-            }
-        }
         Commands::Start(_a) => {
             let release_flag = if cfg!(debug_assertions) {
                 ""
@@ -226,6 +280,16 @@ async fn main() {
                 "--config",
                 &config,
             ];
+            // the server runs in asmap/
+            let rdap_bootstrap = _a.rdap_bootstrap.as_ref().map(|x| {
+                std::path::absolute(std::path::Path::new(&args.inputs_path).join(x))
+                    .unwrap()
+                    .display()
+                    .to_string()
+            });
+            if let Some(path) = &rdap_bootstrap {
+                server_args.extend(["--rdap-bootstrap", path]);
+            }
             if !release_flag.is_empty() {
                 server_args.insert(1, release_flag)
             };
@@ -242,14 +306,234 @@ async fn main() {
             server_cmd.wait().unwrap();
         }
         Commands::ExportDb(a) => {
-            export::export_db(&cfg.mongo_conn_str, &cfg.db_name, &a.output)
+            let database = asdb::dataset_database(&cfg.db_name, &args.dataset).unwrap();
+            export::export_db(&cfg.mongo_conn_str, &database, &a.output)
                 .await
                 .unwrap();
         }
         Commands::ImportDb(a) => {
-            export::import_db(&cfg.mongo_conn_str, &cfg.db_name, &a.input)
+            let database = asdb::dataset_database(&cfg.db_name, &args.dataset).unwrap();
+            export::import_db(&cfg.mongo_conn_str, &database, &a.input)
+                .await
+                .unwrap();
+        }
+        Commands::Datasets => {
+            let names = asdb::Asdb::dataset_names(&cfg.mongo_conn_str, &cfg.db_name)
+                .await
+                .unwrap();
+            for name in names {
+                println!("{name}");
+            }
+        }
+        command => {
+            let m = AsdbBuilder::new_dataset(
+                &cfg.mongo_conn_str,
+                &cfg.db_name,
+                &args.dataset,
+                &args.inputs_path,
+            )
+            .await
+            .unwrap();
+            run_with_storage(&m, command).await;
+        }
+    }
+}
+
+/// Runs commands which only need the storage backend, regardless of which one it is
+async fn run_with_storage<S: Storage>(m: &AsdbBuilder<S>, command: Commands) {
+    match command {
+        Commands::ClearDB => {
+            m.clear_database().await.unwrap();
+        }
+        Commands::LoadAll(a) => {
+            println!("performing complete database load into staging");
+            let staging = m.staging().await.unwrap();
+            let run = staging
+                .load_asrank_asns(a.asrank_asns_filename)
+                .await
+                .unwrap();
+            println!("asrank import run: {run}");
+            let run = staging.load_stanford_asdb().await.unwrap();
+            println!("stanford asdb import run: {run}");
+            let run = staging.load_ipnetdb().await.unwrap();
+            println!("ipnetdb import run: {run}");
+            let count = m.promote(staging).await.unwrap();
+            println!("{count} ases are in place, the previous data can be restored with rollback");
+        }
+        Commands::Rollback => {
+            m.rollback().await.unwrap();
+            println!("previous data restored");
+        }
+        Commands::LoadAsrank(a) => {
+            let result = m.load_asrank_asns(a.asns_filename).await;
+            println!("import result: {result:?}");
+        }
+        Commands::LoadIpnetdb => {
+            let run = m.load_ipnetdb().await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::LoadStanfordAsdb => {
+            let run = m.load_stanford_asdb().await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::LoadAsRel(a) => {
+            let run = m.load_caida_relationships(&a.filename).await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::Neighbors(a) => {
+            let neighbors = m.storage().get_neighbors(a.asn, a.kind).await.unwrap();
+            for n in neighbors {
+                println!("AS{}\t{:?}", n.asn, n.kind);
+            }
+        }
+        Commands::LoadPeeringdb(a) => {
+            let run = m.load_peeringdb(&a.filename).await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::LoadRirStats(a) => {
+            let run = m.load_rir_stats(&a.files).await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::LoadRipeDb(a) => {
+            let run = m.load_ripe_db(&a.files).await.unwrap();
+            println!("import run: {run}");
+        }
+        Commands::Changes(a) => {
+            if let Some(asn) = a.asn {
+                let changes = m
+                    .storage()
+                    .diff_as_snapshots(asn, &a.from, &a.to)
+                    .await
+                    .unwrap();
+                for c in changes {
+                    let (old, new) = (c.old.unwrap_or_default(), c.new.unwrap_or_default());
+                    println!("{}: {old} -> {new}", c.path);
+                }
+            } else {
+                let changed = m.storage().get_changed_ases(&a.from, &a.to).await.unwrap();
+                println!("{} asns changed between the runs", changed.len());
+                for asn in changed {
+                    println!("{asn}");
+                }
+            }
+        }
+        Commands::Search(a) => {
+            let hits = m.storage().search(&a.query, a.limit).await.unwrap();
+            for h in hits {
+                println!(
+                    "AS{}\t{:.2}\t{}\t{}",
+                    h.asn,
+                    h.score,
+                    h.name.unwrap_or_default(),
+                    h.organization.unwrap_or_default()
+                );
+            }
+        }
+        Commands::Status(a) => {
+            let mut datasets = m.storage().get_datasets().await.unwrap();
+            if !a.all {
+                let mut seen = std::collections::BTreeSet::new();
+                datasets.retain(|x| seen.insert(x.source.clone()));
+            }
+            if datasets.is_empty() {
+                println!("nothing was imported yet");
+            }
+            for d in datasets {
+                let finished = d.finished_at.as_deref().unwrap_or("unfinished");
+                println!("{} ({})", d.source, d.run_id);
+                println!("  started:  {}", d.started_at);
+                println!("  finished: {finished}");
+                println!("  records:  {}", d.records);
+                for f in d.files {
+                    let hash = f.sha256.as_deref().unwrap_or("-");
+                    println!("  file:     {} sha256 {hash}", f.source);
+                }
+                for e in d.errors {
+                    println!("  error:    {e}");
+                }
+            }
+        }
+        Commands::List(a) => {
+            let filters = AsFilters {
+                country_iso: a.country,
+                ..Default::default()
+            };
+            let sort = AsSort {
+                field: a.sort,
+                descending: a.desc,
+            };
+            let page = m
+                .storage()
+                .get_ases_sorted(&filters, sort, a.limit, a.after.as_deref())
                 .await
                 .unwrap();
+            for x in page.ases {
+                println!(
+                    "AS{}\t{}\t{}\t{}\t{}\t{}",
+                    x.asn, x.rank, x.country_code, x.addresses, x.prefixes, x.name
+                );
+            }
+            if let Some(next) = page.next {
+                println!("next page: --after {next}");
+            }
+        }
+        Commands::Stats(a) => {
+            let filters = AsFilters {
+                country_iso: a.country,
+                lists: a.list,
+                ..Default::default()
+            };
+            let stats = m.storage().get_stats(&filters, a.group_by).await.unwrap();
+            println!("group\tases\taddresses\tprefixes");
+            for g in stats.groups {
+                println!(
+                    "{}\t{}\t{}\t{}",
+                    g.key.as_deref().unwrap_or("-"),
+                    g.totals.ases,
+                    g.totals.addresses,
+                    g.totals.prefixes
+                );
+            }
+            let t = stats.total;
+            println!("total\t{}\t{}\t{}", t.ases, t.addresses, t.prefixes);
+        }
+        Commands::GenerateCategories => {
+            //This will also be loaded automatically during loadStanfordAsdb but can be also trigerred manually
+            m.generate_categories().await.unwrap();
+        }
+        Commands::GetDetailed(a) => {
+            use details::*;
+            println!("generating details for {:?}", a.csv);
+
+            let asns = parse_input_csv(&a.csv);
+
+            let (ases_detailed, count) = m.storage().get_ases(&asns).await.unwrap();
+            println!(
+                "retrieved {count} detailed ases for {} asns from input",
+                asns.len()
+            );
+
+            match a.format {
+                GetDetailedFormat::InputList => {
+                    let output_path = if let Some(p) = a.output {
+                        p
+                    } else {
+                        a.csv.strip_suffix(".csv").unwrap().to_string()
+                    };
+                    details::generate_nmap_inputlist(&ases_detailed, &output_path);
+                }
+                GetDetailedFormat::Json => {
+                    let output_path = if let Some(p) = a.output {
+                        p
+                    } else {
+                        format!("{}-detailed.json", a.csv.strip_suffix(".csv").unwrap())
+                    };
+                    generate_json(&ases_detailed, &output_path);
+                }
+            }
+        }
+        Commands::Start(_) | Commands::ExportDb(_) | Commands::ImportDb(_) | Commands::Datasets => {
+            unreachable!("handled in main as they don't go through the storage backend")
         }
     }
 }
@@ -1,6 +1,7 @@
 import { BrowserRouter, Route, Routes } from "react-router-dom";
 import MapView from "./MapView";
 import DetailsPage from "./DetailsPage";
+import ListsPage from "./ListsPage";
 
 export default function App() {
     return (
@@ -8,6 +9,7 @@ export default function App() {
             <Routes>
                 <Route path="/" element={<MapView />} />
                 <Route path="/details/:id" element={<DetailsPage />} />
+                <Route path="/lists" element={<ListsPage />} />
                 <Route path="*" element={<div className="p-6 text-slate-200">{"404"}</div>} />
             </Routes>
         </BrowserRouter>
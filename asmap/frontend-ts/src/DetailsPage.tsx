@@ -4,25 +4,53 @@ import L from "leaflet";
 import countries from "i18n-iso-countries";
 import en from "i18n-iso-countries/langs/en.json";
 import type {
+    AnnotationField,
     As,
     AsrankAsn,
     Coord,
     GeocodedAddress,
     IPNetDBAsn,
+    PeeringdbNet,
+    RirDelegation,
     StanfordASdbCategory,
     UserData,
+    UserDataEdit,
     WhoIsAsn
 } from "./protocol/types";
 import {
     fetchAsWhoisData,
+    getAnnotationFields,
     getAsDetails,
     getUserData,
+    getUserDataHistory,
+    revertUserData,
     saveGeocoding,
+    setAnnotation,
     updateUserData
 } from "./api/ws";
+import { formatAnnotationValue, parseAnnotationValue } from "./annotations";
+import { registryName } from "./registries";
 
 countries.registerLocale(en);
 
+// short description of what an edit changed
+function describeEdit(edit: UserDataEdit): string {
+    const changes: string[] = [];
+    if (edit.old.lists.join("\n") !== edit.new.lists.join("\n")) {
+        changes.push(`lists: ${edit.new.lists.join(", ") || "none"}`);
+    }
+    if (edit.old.comment !== edit.new.comment) {
+        changes.push(edit.new.comment ? "comment changed" : "comment removed");
+    }
+    if (JSON.stringify(edit.old.annotations ?? []) !== JSON.stringify(edit.new.annotations ?? [])) {
+        changes.push("annotations");
+    }
+    if (JSON.stringify(edit.old.geocoded_addresses) !== JSON.stringify(edit.new.geocoded_addresses)) {
+        changes.push("geocoding");
+    }
+    return changes.join("; ") || "no change";
+}
+
 const MINI_MAP_MARKER_URL = "https://unpkg.com/leaflet@1.9.3/dist/images/marker-icon.png";
 
 interface AddressComponents {
@@ -396,6 +424,9 @@ export default function DetailsPage() {
     const [whoisData, setWhoisData] = useState<WhoIsAsn | null>(null);
     const [userData, setUserData] = useState<UserData | null>(null);
     const [userDataLoading, setUserDataLoading] = useState(true);
+    const [history, setHistory] = useState<UserDataEdit[]>([]);
+    const [annotationFields, setAnnotationFields] = useState<AnnotationField[]>([]);
+    const [annotationDrafts, setAnnotationDrafts] = useState<Record<string, string>>({});
     const [listInput, setListInput] = useState("");
     const [commentDraft, setCommentDraft] = useState("");
     const [saveToast, setSaveToast] = useState<string | null>(null);
@@ -458,8 +489,33 @@ export default function DetailsPage() {
 
     useEffect(() => {
         setCommentDraft(userData?.comment ?? "");
+        const drafts: Record<string, string> = {};
+        for (const annotation of userData?.annotations ?? []) {
+            drafts[annotation.name] = formatAnnotationValue(annotation.value);
+        }
+        setAnnotationDrafts(drafts);
     }, [userData]);
 
+    useEffect(() => {
+        getAnnotationFields()
+            .then(setAnnotationFields)
+            .catch((err) => console.error(err));
+    }, []);
+
+    // every change of the user data adds an edit
+    useEffect(() => {
+        if (!Number.isFinite(asn) || !userData) {
+            setHistory([]);
+            return;
+        }
+        getUserDataHistory(asn)
+            .then(setHistory)
+            .catch((err) => {
+                console.error(err);
+                setHistory([]);
+            });
+    }, [asn, userData]);
+
     useEffect(() => {
         return () => {
             if (saveToastTimeoutRef.current !== null) {
@@ -544,6 +600,35 @@ export default function DetailsPage() {
         setListInput("");
     }, [listInput, persistUserData, userData]);
 
+    const revertEdit = useCallback(
+        async (edit: UserDataEdit) => {
+            try {
+                const restored = await revertUserData(asn, edit.id);
+                setUserData(restored);
+                setGeocodedAddresses(restored.geocoded_addresses);
+            } catch (error) {
+                console.error(error);
+            }
+        },
+        [asn]
+    );
+
+    const saveAnnotation = useCallback(
+        async (field: AnnotationField, clear: boolean) => {
+            const input = annotationDrafts[field.name] ?? "";
+            const value = clear ? null : parseAnnotationValue(field.kind, input);
+            if (!clear && !value) {
+                return;
+            }
+            try {
+                setUserData(await setAnnotation(asn, field.name, value));
+            } catch (error) {
+                console.error(error);
+            }
+        },
+        [annotationDrafts, asn]
+    );
+
     const saveComment = useCallback(() => {
         if (!userData) {
             return;
@@ -612,6 +697,8 @@ export default function DetailsPage() {
 
     const asrank = asDetails.asrank_data as AsrankAsn | null;
     const ipnetdb = asDetails.ipnetdb_data as IPNetDBAsn | null;
+    const peeringdb = asDetails.peeringdb_data as PeeringdbNet | null;
+    const rir = asDetails.rir_data as RirDelegation | null;
     const countryCode = asrank?.country_iso ?? "??";
     const countryName = countries.getName(countryCode, "en") ?? countryCode;
 
@@ -817,6 +904,115 @@ export default function DetailsPage() {
                                 </div>
                             )}
 
+                            {rir && (
+                                <div className="p-6 rounded-2xl bg-slate-900/40 border border-slate-800/60 backdrop-blur-sm shadow-[0_10px_40px_-25px_rgba(0,0,0,0.85)] transition-all duration-300 hover:border-slate-700/70 hover:shadow-[0_18px_60px_-35px_rgba(0,0,0,0.9)]">
+                                    <div className="flex items-start gap-3 mb-5">
+                                        <div className="p-2 bg-amber-500/15 rounded-xl border border-amber-500/20">
+                                            <svg className="w-5 h-5 text-amber-300" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                                                <path strokeLinecap="round" strokeLinejoin="round" strokeWidth="2" d="M9 12h6m-6 4h6m2 5H7a2 2 0 01-2-2V5a2 2 0 012-2h5.586a1 1 0 01.707.293l5.414 5.414a1 1 0 01.293.707V19a2 2 0 01-2 2z" />
+                                            </svg>
+                                        </div>
+                                        <div className="min-w-0">
+                                            <h3 className="text-lg font-semibold text-white tracking-tight">{"Registration"}</h3>
+                                            <p className="text-sm text-slate-400">{"RIR delegated-extended statistics"}</p>
+                                        </div>
+                                    </div>
+
+                                    <div className="grid grid-cols-2 sm:grid-cols-4 gap-3">
+                                        {[
+                                            ["Registry", registryName(rir.registry)],
+                                            ["Country", rir.country],
+                                            ["Allocated", rir.date],
+                                            ["Status", rir.status]
+                                        ].map(([label, value]) => (
+                                            <div key={label} className="p-3 rounded-xl bg-slate-800/35 border border-slate-700/40">
+                                                <p className="text-[11px] text-slate-400 uppercase tracking-wider">{label}</p>
+                                                <p className="text-sm text-slate-100/90">{value ?? "-"}</p>
+                                            </div>
+                                        ))}
+                                    </div>
+                                    {rir.opaque_id && (
+                                        <p className="mt-3 text-xs text-slate-500 font-mono break-all">{`holder id: ${rir.opaque_id}`}</p>
+                                    )}
+                                </div>
+                            )}
+
+                            {peeringdb && (
+                                <div className="p-6 rounded-2xl bg-slate-900/40 border border-slate-800/60 backdrop-blur-sm shadow-[0_10px_40px_-25px_rgba(0,0,0,0.85)] transition-all duration-300 hover:border-slate-700/70 hover:shadow-[0_18px_60px_-35px_rgba(0,0,0,0.9)]">
+                                    <div className="flex items-start gap-3 mb-5">
+                                        <div className="p-2 bg-sky-500/15 rounded-xl border border-sky-500/20">
+                                            <svg className="w-5 h-5 text-sky-300" fill="none" stroke="currentColor" viewBox="0 0 24 24">
+                                                <path strokeLinecap="round" strokeLinejoin="round" strokeWidth="2" d="M8.684 13.342C8.886 12.938 9 12.482 9 12c0-.482-.114-.938-.316-1.342m0 2.684a3 3 0 110-2.684m0 2.684l6.632 3.316m-6.632-6l6.632-3.316m0 0a3 3 0 105.367-2.684 3 3 0 00-5.367 2.684zm0 9.316a3 3 0 105.368 2.684 3 3 0 00-5.368-2.684z" />
+                                            </svg>
+                                        </div>
+                                        <div className="min-w-0">
+                                            <h3 className="text-lg font-semibold text-white tracking-tight">{"Peering"}</h3>
+                                            <a
+                                                href={`https://www.peeringdb.com/net/${peeringdb.id}`}
+                                                target="_blank"
+                                                className="text-sm text-slate-400 hover:text-sky-300"
+                                            >
+                                                {`PeeringDB: ${peeringdb.name}`}
+                                            </a>
+                                        </div>
+                                    </div>
+
+                                    <div className="grid grid-cols-1 sm:grid-cols-3 gap-3 mb-4">
+                                        {[
+                                            ["Policy", peeringdb.policy_general],
+                                            ["Traffic", peeringdb.info_traffic],
+                                            ["Type", peeringdb.info_type]
+                                        ].map(([label, value]) => (
+                                            <div key={label} className="p-3 rounded-xl bg-slate-800/35 border border-slate-700/40">
+                                                <p className="text-[11px] text-slate-400 uppercase tracking-wider">{label}</p>
+                                                <p className="text-sm text-slate-100/90">{value ?? "-"}</p>
+                                            </div>
+                                        ))}
+                                    </div>
+
+                                    {peeringdb.ixps.length > 0 && (
+                                        <div className="mb-4">
+                                            <h4 className="text-xs font-semibold text-slate-300 uppercase tracking-wider mb-2">
+                                                {`IXPs (${peeringdb.ixps.length})`}
+                                            </h4>
+                                            <div className="space-y-1 max-h-60 overflow-y-auto pr-2">
+                                                {peeringdb.ixps.map((ixp, index) => (
+                                                    <div
+                                                        key={`${ixp.ix_id}-${index}`}
+                                                        className="flex items-center justify-between gap-3 px-3 py-2 rounded-lg bg-slate-800/35 border border-slate-700/40 text-xs"
+                                                    >
+                                                        <span className="text-slate-200 truncate">{ixp.name}</span>
+                                                        <span className="text-slate-400 font-mono flex-shrink-0">
+                                                            {[ixp.ipaddr4, ixp.ipaddr6, `${ixp.speed / 1000}G`, ixp.is_rs_peer ? "RS" : null]
+                                                                .filter(Boolean)
+                                                                .join(" · ")}
+                                                        </span>
+                                                    </div>
+                                                ))}
+                                            </div>
+                                        </div>
+                                    )}
+
+                                    {peeringdb.facilities.length > 0 && (
+                                        <div>
+                                            <h4 className="text-xs font-semibold text-slate-300 uppercase tracking-wider mb-2">
+                                                {`Facilities (${peeringdb.facilities.length})`}
+                                            </h4>
+                                            <div className="flex flex-wrap gap-2">
+                                                {peeringdb.facilities.map((fac) => (
+                                                    <span
+                                                        key={fac.fac_id}
+                                                        className="px-2 py-1 rounded-md border border-slate-700/50 bg-slate-950/40 text-xs text-slate-300"
+                                                    >
+                                                        {fac.name}
+                                                    </span>
+                                                ))}
+                                            </div>
+                                        </div>
+                                    )}
+                                </div>
+                            )}
+
                             {asDetails.stanford_asdb.length > 0 && (
                                 <div className="p-6 rounded-2xl bg-slate-900/40 border border-slate-800/60 backdrop-blur-sm shadow-[0_10px_40px_-25px_rgba(0,0,0,0.85)] transition-all duration-300 hover:border-slate-700/70 hover:shadow-[0_18px_60px_-35px_rgba(0,0,0,0.9)]">
                                     <div className="flex items-start gap-3 mb-5">
@@ -1167,6 +1363,84 @@ export default function DetailsPage() {
                                                 {"Save comment"}
                                             </button>
                                         </div>
+
+                                        {annotationFields.length > 0 && (
+                                            <div className="space-y-2">
+                                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider">{"Annotations"}</label>
+                                                {annotationFields.map((field) => {
+                                                    const draft = annotationDrafts[field.name] ?? "";
+                                                    const onDraft = (value: string) =>
+                                                        setAnnotationDrafts((current) => ({ ...current, [field.name]: value }));
+                                                    const inputClass = "flex-1 min-w-0 px-2 py-1.5 bg-slate-950/70 border border-slate-700/50 rounded-lg text-xs text-slate-200";
+                                                    return (
+                                                        <div key={field.name} className="space-y-1" title={field.description ?? undefined}>
+                                                            <span className="text-xs text-slate-400">{field.name}</span>
+                                                            <div className="flex gap-1">
+                                                                {typeof field.kind === "object" ? (
+                                                                    <select value={draft} className={inputClass} onChange={(e) => onDraft(e.target.value)}>
+                                                                        <option value="">{"-"}</option>
+                                                                        {field.kind.Enum.map((option) => (
+                                                                            <option key={option} value={option}>
+                                                                                {option}
+                                                                            </option>
+                                                                        ))}
+                                                                    </select>
+                                                                ) : (
+                                                                    <input
+                                                                        type={field.kind === "Number" ? "number" : field.kind === "Date" ? "date" : "text"}
+                                                                        value={draft}
+                                                                        className={inputClass}
+                                                                        onChange={(e) => onDraft(e.target.value)}
+                                                                    />
+                                                                )}
+                                                                <button
+                                                                    onClick={() => saveAnnotation(field, false)}
+                                                                    className="px-2 py-1 text-xs rounded-lg bg-slate-700/50 text-slate-200 border border-slate-600/40 hover:bg-slate-600/60 transition"
+                                                                >
+                                                                    {"Set"}
+                                                                </button>
+                                                                <button
+                                                                    onClick={() => saveAnnotation(field, true)}
+                                                                    className="px-2 py-1 text-xs rounded-lg text-slate-400 border border-transparent hover:text-red-300 hover:border-red-500/20 transition"
+                                                                    aria-label={`Clear ${field.name}`}
+                                                                >
+                                                                    {"×"}
+                                                                </button>
+                                                            </div>
+                                                        </div>
+                                                    );
+                                                })}
+                                            </div>
+                                        )}
+
+                                        {history.length > 0 && (
+                                            <div>
+                                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-2">{"History"}</label>
+                                                <div className="space-y-2 max-h-60 overflow-y-auto">
+                                                    {history.map((edit) => (
+                                                        <div
+                                                            key={edit.id}
+                                                            className="flex items-start justify-between gap-2 rounded-lg border border-slate-700/50 bg-slate-950/40 px-2 py-1.5 text-xs text-slate-300"
+                                                        >
+                                                            <div className="min-w-0">
+                                                                <div className="truncate">{describeEdit(edit)}</div>
+                                                                <div className="text-slate-500">
+                                                                    {new Date(edit.at).toLocaleString()}
+                                                                    {edit.editor ? ` by ${edit.editor}` : ""}
+                                                                </div>
+                                                            </div>
+                                                            <button
+                                                                onClick={() => revertEdit(edit)}
+                                                                className="px-2 py-1 rounded-md text-slate-400 hover:text-amber-200 hover:bg-amber-500/10 border border-transparent hover:border-amber-500/20 transition"
+                                                                title="Restore the data from before this edit"
+                                                            >
+                                                                {"Revert"}
+                                                            </button>
+                                                        </div>
+                                                    ))}
+                                                </div>
+                                            </div>
+                                        )}
                                     </div>
                                 ) : (
                                     <p className="text-sm text-slate-400">{"No user data loaded"}</p>
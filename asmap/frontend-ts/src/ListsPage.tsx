@@ -0,0 +1,280 @@
+import { useCallback, useEffect, useState } from "react";
+import { Link } from "react-router-dom";
+import type { ListSummary } from "./protocol/types";
+import {
+    addToList,
+    createList,
+    deleteList,
+    getListMembers,
+    getLists,
+    removeFromList,
+    renameList,
+    updateList
+} from "./api/ws";
+
+const DEFAULT_LIST_COLOR = "#f59e0b";
+
+// asns separated by commas, spaces or new lines, with or without the AS prefix
+function parseAsns(input: string): number[] {
+    return input
+        .split(/[\s,;]+/)
+        .map((x) => Number(x.trim().replace(/^as/i, "")))
+        .filter((x) => Number.isInteger(x) && x > 0);
+}
+
+export default function ListsPage() {
+    const [lists, setLists] = useState<ListSummary[]>([]);
+    const [selected, setSelected] = useState<string | null>(null);
+    const [members, setMembers] = useState<number[]>([]);
+    const [newName, setNewName] = useState("");
+    const [renameDraft, setRenameDraft] = useState("");
+    const [descriptionDraft, setDescriptionDraft] = useState("");
+    const [colorDraft, setColorDraft] = useState(DEFAULT_LIST_COLOR);
+    const [asnsDraft, setAsnsDraft] = useState("");
+    const [error, setError] = useState<string | null>(null);
+
+    const selectedList = lists.find((x) => x.list.name === selected) ?? null;
+
+    // every change answers with all lists, the members of the selected one are fetched again
+    const applyLists = useCallback(
+        (next: ListSummary[], name: string | null = selected) => {
+            setLists(next);
+            setError(null);
+            setSelected(name);
+            if (name) {
+                getListMembers(name)
+                    .then(setMembers)
+                    .catch((err) => setError(`${err}`));
+            } else {
+                setMembers([]);
+            }
+        },
+        [selected]
+    );
+
+    const run = useCallback(
+        (change: Promise<ListSummary[]>, name?: string | null) => {
+            change
+                .then((next) => applyLists(next, name === undefined ? selected : name))
+                .catch((err) => setError(`${err}`));
+        },
+        [applyLists, selected]
+    );
+
+    useEffect(() => {
+        getLists()
+            .then(setLists)
+            .catch((err) => setError(`Failed to load lists: ${err}`));
+    }, []);
+
+    useEffect(() => {
+        setRenameDraft(selectedList?.list.name ?? "");
+        setDescriptionDraft(selectedList?.list.description ?? "");
+        setColorDraft(selectedList?.list.color ?? DEFAULT_LIST_COLOR);
+    }, [selectedList?.list.name, selectedList?.list.description, selectedList?.list.color]);
+
+    const select = useCallback(
+        (name: string) => {
+            setSelected(name);
+            setAsnsDraft("");
+            getListMembers(name)
+                .then(setMembers)
+                .catch((err) => setError(`${err}`));
+        },
+        []
+    );
+
+    const onCreate = useCallback(() => {
+        const name = newName.trim();
+        if (!name) {
+            return;
+        }
+        run(createList({ name, description: null, color: null }), name);
+        setNewName("");
+    }, [newName, run]);
+
+    const onSaveMetadata = useCallback(() => {
+        if (!selected) {
+            return;
+        }
+        const description = descriptionDraft.trim();
+        run(updateList({ name: selected, description: description || null, color: colorDraft }));
+    }, [colorDraft, descriptionDraft, run, selected]);
+
+    const onRename = useCallback(() => {
+        const name = renameDraft.trim();
+        if (!selected || !name || name === selected) {
+            return;
+        }
+        run(renameList(selected, name), name);
+    }, [renameDraft, run, selected]);
+
+    const onDelete = useCallback(() => {
+        if (!selected || !window.confirm(`Delete list ${selected} from all its ${members.length} ASes?`)) {
+            return;
+        }
+        run(deleteList(selected), null);
+    }, [members.length, run, selected]);
+
+    const onMembers = useCallback(
+        (add: boolean) => {
+            const asns = parseAsns(asnsDraft);
+            if (!selected || !asns.length) {
+                return;
+            }
+            run(add ? addToList(selected, asns) : removeFromList(selected, asns));
+            setAsnsDraft("");
+        },
+        [asnsDraft, run, selected]
+    );
+
+    return (
+        <div className="min-h-screen bg-gradient-to-br from-slate-950 via-slate-900 to-slate-950 text-slate-100">
+            <main className="max-w-6xl mx-auto px-4 py-8 space-y-6">
+                <div className="flex items-center justify-between">
+                    <h1 className="text-2xl font-semibold tracking-tight">{"Lists"}</h1>
+                    <Link to="/" className="text-sm text-blue-300 hover:text-blue-200">{"Back to map"}</Link>
+                </div>
+
+                {error && (
+                    <p className="p-3 rounded-xl bg-red-950/40 border border-red-800/40 text-sm text-red-300">{error}</p>
+                )}
+
+                <div className="grid grid-cols-1 md:grid-cols-3 gap-6">
+                    <div className="p-6 rounded-2xl bg-slate-900/40 border border-slate-800/60 space-y-4">
+                        <div className="flex gap-2">
+                            <input
+                                type="text"
+                                value={newName}
+                                placeholder="New list name"
+                                className="flex-1 px-3 py-2 bg-slate-950/70 border border-slate-700/50 rounded-lg text-xs text-slate-200 focus:outline-none focus:ring-2 focus:ring-amber-400/40"
+                                onChange={(e) => setNewName(e.target.value)}
+                            />
+                            <button
+                                onClick={onCreate}
+                                className="px-3 py-2 text-xs font-semibold rounded-lg bg-amber-500/20 text-amber-200 border border-amber-500/30 hover:bg-amber-500/30 transition"
+                            >
+                                {"Create"}
+                            </button>
+                        </div>
+                        {lists.length ? (
+                            <div className="space-y-2">
+                                {lists.map(({ list, count }) => (
+                                    <button
+                                        key={list.name}
+                                        onClick={() => select(list.name)}
+                                        className={`w-full flex items-center justify-between gap-2 rounded-lg border px-3 py-2 text-xs text-left transition ${
+                                            list.name === selected
+                                                ? "border-amber-500/40 bg-amber-500/10 text-amber-100"
+                                                : "border-slate-700/50 bg-slate-950/40 text-slate-300 hover:border-slate-600"
+                                        }`}
+                                    >
+                                        <span className="flex items-center gap-2 min-w-0">
+                                            <span
+                                                className="w-2.5 h-2.5 rounded-full flex-shrink-0"
+                                                style={{ background: list.color ?? DEFAULT_LIST_COLOR }}
+                                            />
+                                            <span className="truncate">{list.name}</span>
+                                        </span>
+                                        <span className="text-slate-500">{count}</span>
+                                    </button>
+                                ))}
+                            </div>
+                        ) : (
+                            <p className="text-xs text-slate-500">{"No lists yet"}</p>
+                        )}
+                    </div>
+
+                    {selectedList ? (
+                        <div className="md:col-span-2 p-6 rounded-2xl bg-slate-900/40 border border-slate-800/60 space-y-5">
+                            <div className="flex gap-2">
+                                <input
+                                    type="text"
+                                    value={renameDraft}
+                                    className="flex-1 px-3 py-2 bg-slate-950/70 border border-slate-700/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-amber-400/40"
+                                    onChange={(e) => setRenameDraft(e.target.value)}
+                                />
+                                <button
+                                    onClick={onRename}
+                                    className="px-3 py-2 text-xs font-semibold rounded-lg bg-slate-700/50 text-slate-200 border border-slate-600/40 hover:bg-slate-600/60 transition"
+                                >
+                                    {"Rename"}
+                                </button>
+                                <button
+                                    onClick={onDelete}
+                                    className="px-3 py-2 text-xs font-semibold rounded-lg bg-red-500/15 text-red-300 border border-red-500/30 hover:bg-red-500/25 transition"
+                                >
+                                    {"Delete"}
+                                </button>
+                            </div>
+
+                            <div>
+                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-2">{"Description"}</label>
+                                <textarea
+                                    value={descriptionDraft}
+                                    rows={2}
+                                    className="w-full px-3 py-2 bg-slate-950/70 border border-slate-700/50 rounded-lg text-xs text-slate-200 focus:outline-none focus:ring-2 focus:ring-amber-400/40"
+                                    onChange={(e) => setDescriptionDraft(e.target.value)}
+                                />
+                                <div className="mt-2 flex items-center gap-2">
+                                    <input
+                                        type="color"
+                                        value={colorDraft}
+                                        className="h-8 w-10 rounded bg-transparent"
+                                        onChange={(e) => setColorDraft(e.target.value)}
+                                    />
+                                    <button
+                                        onClick={onSaveMetadata}
+                                        className="flex-1 px-3 py-2 text-xs font-semibold rounded-lg bg-slate-700/50 text-slate-200 border border-slate-600/40 hover:bg-slate-600/60 transition"
+                                    >
+                                        {"Save"}
+                                    </button>
+                                </div>
+                            </div>
+
+                            <div>
+                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-2">
+                                    {`Members (${members.length})`}
+                                </label>
+                                <textarea
+                                    value={asnsDraft}
+                                    rows={3}
+                                    placeholder="ASNs separated by commas or new lines"
+                                    className="w-full px-3 py-2 bg-slate-950/70 border border-slate-700/50 rounded-lg text-xs text-slate-200 focus:outline-none focus:ring-2 focus:ring-amber-400/40"
+                                    onChange={(e) => setAsnsDraft(e.target.value)}
+                                />
+                                <div className="mt-2 flex gap-2">
+                                    <button
+                                        onClick={() => onMembers(true)}
+                                        className="flex-1 px-3 py-2 text-xs font-semibold rounded-lg bg-amber-500/20 text-amber-200 border border-amber-500/30 hover:bg-amber-500/30 transition"
+                                    >
+                                        {"Add"}
+                                    </button>
+                                    <button
+                                        onClick={() => onMembers(false)}
+                                        className="flex-1 px-3 py-2 text-xs font-semibold rounded-lg bg-slate-700/50 text-slate-200 border border-slate-600/40 hover:bg-slate-600/60 transition"
+                                    >
+                                        {"Remove"}
+                                    </button>
+                                </div>
+                                <div className="mt-3 flex flex-wrap gap-2 max-h-80 overflow-y-auto">
+                                    {members.map((asn) => (
+                                        <Link
+                                            key={asn}
+                                            to={`/details/${asn}`}
+                                            className="px-2 py-1 rounded-md border border-slate-700/50 bg-slate-950/40 text-xs text-slate-300 hover:text-blue-200"
+                                        >
+                                            {`AS${asn}`}
+                                        </Link>
+                                    ))}
+                                </div>
+                            </div>
+                        </div>
+                    ) : (
+                        <p className="md:col-span-2 text-sm text-slate-500">{"Select a list to edit it"}</p>
+                    )}
+                </div>
+            </main>
+        </div>
+    );
+}
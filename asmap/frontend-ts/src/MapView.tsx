@@ -3,25 +3,55 @@ import L from "leaflet";
 import "leaflet.markercluster";
 import "leaflet.heat";
 import type {
+    AnnotationField,
     As,
+    AsChange,
     AsFilters,
     AsFiltersHasOrg,
     AsForFrontend,
+    AsStats,
     Bound,
+    Coord,
+    InternetRegistry,
+    StatsGroup,
     UserData,
     WhoIsAsn
 } from "./protocol/types";
 import {
     fetchAsWhoisData,
     getAllAsFiltered,
+    getAnnotationFields,
     getAsDetails,
+    getDatasetNames,
     getListNames,
+    getSelectedDataset,
+    getStats,
     getUserData,
+    selectDataset,
+    subscribeAsChanges,
     updateUserData
 } from "./api/ws";
+import type { AsChangesSubscription } from "./api/ws";
+import {
+    ANNOTATION_OPS,
+    annotationOp,
+    describeAnnotationFilter,
+    parseAnnotationValue
+} from "./annotations";
+import type { AnnotationOpName } from "./annotations";
+import { REGISTRIES, registryName } from "./registries";
 
 const POLAND_LAT = 52.11431;
 const POLAND_LON = 19.423672;
+const STATS_TOP_GROUPS = 10;
+const STATS_GROUPS: [StatsGroup, string][] = [
+    ["Country", "Country"],
+    ["Category", "Category"],
+    ["Subcategory", "Subcategory"],
+    ["Registry", "Registry"],
+    ["RankBucket", "Rank"],
+    ["List", "List"]
+];
 const MARKER_ICON_URL = "https://unpkg.com/leaflet@1.9.3/dist/images/marker-icon.png";
 
 type HeatPoint = [number, number, number?];
@@ -42,11 +72,18 @@ const DEFAULT_FILTERS: AsFilters = {
     country: "PL",
     exclude_country: true,
     bounds: null,
+    radius: null,
+    polygon: null,
     addresses: null,
     rank: null,
     has_org: "Both",
     category: [],
-    lists: []
+    lists: [],
+    text: null,
+    annotations: [],
+    registry: null,
+    allocated_before: null,
+    allocated_after: null
 };
 
 function formatFilters(filters: AsFilters): string {
@@ -54,11 +91,13 @@ function formatFilters(filters: AsFilters): string {
     const boundStr = bounds
         ? `b${bounds.south_west.lat.toFixed(4)}:${bounds.south_west.lon.toFixed(4)}-${bounds.north_east.lat.toFixed(4)}:${bounds.north_east.lon.toFixed(4)}`
         : "";
+    const radiusStr = filters.radius ? `-rad${filters.radius.meters.toFixed(0)}` : "";
+    const polygonStr = filters.polygon ? `-poly${filters.polygon.length}` : "";
     const addresses = filters.addresses ?? [0, 0];
     const rank = filters.rank ?? [0, 0];
     const hasOrg = filters.has_org === "Both" ? "both" : filters.has_org === "Yes" ? "yes" : "no";
 
-    return `c${filters.country ?? ""}-exc${filters.exclude_country}-${boundStr}-a${addresses[0]}-${addresses[1]}-r${rank[0]}-${rank[1]}-org${hasOrg}-ncat${filters.category.length}-nl${filters.lists.length}`;
+    return `c${filters.country ?? ""}-exc${filters.exclude_country}-${boundStr}${radiusStr}${polygonStr}-a${addresses[0]}-${addresses[1]}-r${rank[0]}-${rank[1]}-org${hasOrg}-ncat${filters.category.length}-nl${filters.lists.length}-t${filters.text ?? ""}-nann${filters.annotations.length}-reg${registryName(filters.registry)}-ab${filters.allocated_before ?? ""}-aa${filters.allocated_after ?? ""}`;
 }
 
 function csvEscape(value: string): string {
@@ -233,6 +272,8 @@ export default function MapView() {
     const drawnAsRef = useRef<Map<number, AsForFrontend>>(new Map());
     const detailedAsRef = useRef<Map<number, As>>(new Map());
     const userDataByAsnRef = useRef<Map<number, UserData>>(new Map());
+    const subscriptionRef = useRef<AsChangesSubscription | null>(null);
+    const activeAsnRef = useRef<number | null>(null);
     const whoisLoadingRef = useRef<Set<number>>(new Set());
     const heatLayerRef = useRef<HeatLayer | null>(null);
     const heatmapDataRef = useRef<AsForFrontend[]>([]);
@@ -244,6 +285,13 @@ export default function MapView() {
     const [activeAsn, setActiveAsn] = useState<number | null>(null);
     const [whoisCache, setWhoisCache] = useState<Map<number, string>>(new Map());
     const [listNames, setListNames] = useState<string[]>([]);
+    const [datasetNames, setDatasetNames] = useState<string[]>([]);
+    const [annotationFields, setAnnotationFields] = useState<AnnotationField[]>([]);
+    const [annotationDraft, setAnnotationDraft] = useState<{ name: string; op: AnnotationOpName; input: string }>({
+        name: "",
+        op: "Eq",
+        input: ""
+    });
     const [listInput, setListInput] = useState("");
     const [activeUserData, setActiveUserData] = useState<UserData | null>(null);
     const [userDataLoading, setUserDataLoading] = useState(false);
@@ -252,6 +300,13 @@ export default function MapView() {
     const saveToastTimeoutRef = useRef<number | null>(null);
     const [heatmapEnabled, setHeatmapEnabled] = useState(false);
     const [heatmapLoading, setHeatmapLoading] = useState(false);
+    const [drawMode, setDrawMode] = useState<"none" | "polygon" | "radius">("none");
+    const [draftVertices, setDraftVertices] = useState<Coord[]>([]);
+    const [radiusKm, setRadiusKm] = useState(50);
+    const [statsGroup, setStatsGroup] = useState<StatsGroup>("Country");
+    const [asStats, setAsStats] = useState<AsStats | null>(null);
+    const [statsLoading, setStatsLoading] = useState(false);
+    const shapeLayerRef = useRef<L.LayerGroup | null>(null);
 
     const updateCounts = useCallback(() => {
         setDrawnCount(drawnAsRef.current.size);
@@ -353,11 +408,18 @@ export default function MapView() {
             country: null,
             exclude_country: false,
             bounds: null,
+            radius: null,
+            polygon: null,
             addresses: null,
             rank: null,
             has_org: "Both",
             category: [],
-            lists: []
+            lists: [],
+            text: null,
+            annotations: [],
+            registry: null,
+            allocated_before: null,
+            allocated_after: null
         };
 
         try {
@@ -410,12 +472,82 @@ export default function MapView() {
         getListNames()
             .then((names) => setListNames(names))
             .catch((error) => console.error(error));
+        getDatasetNames()
+            .then((names) => setDatasetNames(names))
+            .catch((error) => console.error(error));
+        getAnnotationFields()
+            .then((fields) => {
+                setAnnotationFields(fields);
+                setAnnotationDraft((current) => ({ ...current, name: fields[0]?.name ?? "" }));
+            })
+            .catch((error) => console.error(error));
+    }, []);
+
+    const addAnnotationFilter = useCallback(() => {
+        const field = annotationFields.find((x) => x.name === annotationDraft.name);
+        if (!field) {
+            return;
+        }
+        const value = parseAnnotationValue(field.kind, annotationDraft.input);
+        const op = annotationOp(annotationDraft.op, value, annotationDraft.input);
+        if (!op) {
+            return;
+        }
+        setFilters((current) => ({ ...current, annotations: [...current.annotations, { name: field.name, op }] }));
+        setAnnotationDraft((current) => ({ ...current, input: "" }));
+    }, [annotationDraft, annotationFields]);
+
+    const removeAnnotationFilter = useCallback((index: number) => {
+        setFilters((current) => ({
+            ...current,
+            annotations: current.annotations.filter((_, i) => i !== index)
+        }));
     }, []);
 
     useEffect(() => {
         setCommentDraft(activeUserData?.comment ?? "");
     }, [activeUserData, activeAsn]);
 
+    useEffect(() => {
+        activeAsnRef.current = activeAsn;
+    }, [activeAsn]);
+
+    useEffect(() => {
+        // edits made by other clients to the drawn ases
+        const applyChange = (asn: number, change: AsChange) => {
+            let userData: UserData | null = null;
+            if ("UserData" in change) {
+                userData = change.UserData;
+                setListNames((current) =>
+                    Array.from(new Set([...current, ...change.UserData.lists])).sort()
+                );
+            } else if ("Geocoding" in change) {
+                const cached = userDataByAsnRef.current.get(asn);
+                if (cached) {
+                    userData = { ...cached, geocoded_addresses: change.Geocoding };
+                }
+            } else if ("Whois" in change) {
+                setWhoisCache((current) => {
+                    const next = new Map(current);
+                    next.set(asn, formatWhoisText(change.Whois));
+                    return next;
+                });
+            }
+            if (userData) {
+                userDataByAsnRef.current.set(asn, userData);
+                if (activeAsnRef.current === asn) {
+                    setActiveUserData(userData);
+                }
+            }
+        };
+        const subscription = subscribeAsChanges([], applyChange);
+        subscriptionRef.current = subscription;
+        return () => {
+            subscription.close();
+            subscriptionRef.current = null;
+        };
+    }, []);
+
     useEffect(() => {
         return () => {
             if (saveToastTimeoutRef.current !== null) {
@@ -528,6 +660,7 @@ export default function MapView() {
             });
 
             updateCounts();
+            subscriptionRef.current?.update(Array.from(drawnAsRef.current.keys()));
         },
         [handlePopupOpen, updateCounts]
     );
@@ -546,11 +679,18 @@ export default function MapView() {
                 north_east: { lat: bounds.getNorthEast().lat, lon: bounds.getNorthEast().lng },
                 south_west: { lat: bounds.getSouthWest().lat, lon: bounds.getSouthWest().lng }
             },
+            radius: null,
+            polygon: null,
             addresses: null,
             rank: null,
             has_org: "Both",
             category: [],
-            lists: []
+            lists: [],
+            text: null,
+            annotations: [],
+            registry: null,
+            allocated_before: null,
+            allocated_after: null
         };
 
         try {
@@ -589,6 +729,34 @@ export default function MapView() {
         }
     }, [drawAses, filters]);
 
+    const loadStats = useCallback(async () => {
+        const map = mapRef.current;
+        if (!map) {
+            return;
+        }
+
+        let requestFilters = { ...filters };
+        if (requestFilters.bounds) {
+            const bounds = map.getBounds();
+            requestFilters = {
+                ...requestFilters,
+                bounds: {
+                    north_east: { lat: bounds.getNorthEast().lat, lon: bounds.getNorthEast().lng },
+                    south_west: { lat: bounds.getSouthWest().lat, lon: bounds.getSouthWest().lng }
+                }
+            };
+        }
+
+        setStatsLoading(true);
+        try {
+            setAsStats(await getStats(requestFilters, statsGroup));
+        } catch (error) {
+            console.error(error);
+        } finally {
+            setStatsLoading(false);
+        }
+    }, [filters, statsGroup]);
+
     const clearMap = useCallback(() => {
         drawnAsRef.current.clear();
         detailedAsRef.current.clear();
@@ -599,6 +767,7 @@ export default function MapView() {
         setWhoisCache(new Map());
         clearHeatmap();
         updateCounts();
+        subscriptionRef.current?.update([]);
     }, [clearHeatmap, updateCounts]);
 
     const downloadCsv = useCallback(
@@ -648,6 +817,75 @@ export default function MapView() {
         [prevFilters]
     );
 
+    useEffect(() => {
+        const map = mapRef.current;
+        if (!map || drawMode === "none") {
+            return;
+        }
+        const onClick = (e: L.LeafletMouseEvent) => {
+            const point = { lat: e.latlng.lat, lon: e.latlng.lng };
+            if (drawMode === "radius") {
+                setFilters((current) => ({ ...current, radius: { center: point, meters: radiusKm * 1000 } }));
+                setDrawMode("none");
+            } else {
+                setDraftVertices((current) => [...current, point]);
+            }
+        };
+        map.on("click", onClick);
+        map.getContainer().style.cursor = "crosshair";
+        return () => {
+            map.off("click", onClick);
+            map.getContainer().style.cursor = "";
+        };
+    }, [drawMode, radiusKm]);
+
+    useEffect(() => {
+        const map = mapRef.current;
+        if (!map) {
+            return;
+        }
+        shapeLayerRef.current?.remove();
+        const group = L.layerGroup();
+        const style = { color: "#3b82f6", weight: 2, fillOpacity: 0.08 };
+        if (filters.radius) {
+            const { center, meters } = filters.radius;
+            L.circle([center.lat, center.lon], { ...style, radius: meters }).addTo(group);
+        }
+        if (draftVertices.length) {
+            L.polyline(draftVertices.map((c) => [c.lat, c.lon] as [number, number]), { ...style, dashArray: "4" }).addTo(group);
+        } else if (filters.polygon) {
+            L.polygon(filters.polygon.map((c) => [c.lat, c.lon] as [number, number]), style).addTo(group);
+        }
+        group.addTo(map);
+        shapeLayerRef.current = group;
+    }, [draftVertices, filters.polygon, filters.radius]);
+
+    const togglePolygonDrawing = useCallback(() => {
+        if (drawMode !== "polygon") {
+            setDraftVertices([]);
+            setDrawMode("polygon");
+            return;
+        }
+        if (draftVertices.length >= 3) {
+            setFilters((current) => ({ ...current, polygon: draftVertices }));
+        }
+        setDraftVertices([]);
+        setDrawMode("none");
+    }, [draftVertices, drawMode]);
+
+    const updateRadiusKm = useCallback((km: number) => {
+        setRadiusKm(km);
+        setFilters((current) =>
+            current.radius ? { ...current, radius: { ...current.radius, meters: km * 1000 } } : current
+        );
+    }, []);
+
+    const clearShapes = useCallback(() => {
+        setDraftVertices([]);
+        setDrawMode("none");
+        setFilters((current) => ({ ...current, radius: null, polygon: null }));
+    }, []);
+
     const toggleBounded = useCallback(() => {
         setFilters((current) => {
             if (current.bounds) {
@@ -853,6 +1091,23 @@ export default function MapView() {
                         <span className="text-sm font-semibold text-slate-300">{"Filters"}</span>
                     </div>
                     <div className="space-y-3">
+                        <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
+                            <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Name or description"}</label>
+                            <input
+                                type="text"
+                                id="searchText"
+                                value={filters.text ?? ""}
+                                placeholder="university gdansk"
+                                className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all placeholder-slate-500"
+                                onChange={(e) =>
+                                    setFilters((current) => ({
+                                        ...current,
+                                        text: e.target.value.trim() ? e.target.value : null
+                                    }))
+                                }
+                            />
+                        </div>
+
                         <div className="flex items-center gap-3 p-3 rounded-xl bg-slate-700/30 border border-slate-600/30 hover:bg-slate-700/40 transition-colors">
                             <input
                                 type="checkbox"
@@ -866,6 +1121,50 @@ export default function MapView() {
                             </label>
                         </div>
 
+                        <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30 space-y-3">
+                            <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider">{"Drawn Area"}</label>
+                            <div className="grid grid-cols-2 gap-2">
+                                <button
+                                    onClick={togglePolygonDrawing}
+                                    className={`w-full px-3 py-2 text-xs font-medium rounded-lg border transition-all duration-200 ${drawMode === "polygon"
+                                        ? "bg-blue-500/20 text-blue-200 border-blue-500/30 hover:bg-blue-500/30"
+                                        : "bg-slate-700/40 text-slate-300 border-slate-600/40 hover:bg-slate-600/50"
+                                        }`}
+                                >
+                                    {drawMode === "polygon" ? `Finish polygon (${draftVertices.length})` : "Draw polygon"}
+                                </button>
+                                <button
+                                    onClick={() => setDrawMode(drawMode === "radius" ? "none" : "radius")}
+                                    className={`w-full px-3 py-2 text-xs font-medium rounded-lg border transition-all duration-200 ${drawMode === "radius"
+                                        ? "bg-blue-500/20 text-blue-200 border-blue-500/30 hover:bg-blue-500/30"
+                                        : "bg-slate-700/40 text-slate-300 border-slate-600/40 hover:bg-slate-600/50"
+                                        }`}
+                                >
+                                    {drawMode === "radius" ? "Click the center" : "Draw circle"}
+                                </button>
+                            </div>
+                            <div>
+                                <label className="block text-xs text-slate-500 mb-1">{"Circle radius (km)"}</label>
+                                <input
+                                    type="number"
+                                    id="radiusKm"
+                                    value={radiusKm}
+                                    min={1}
+                                    max={20000}
+                                    className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all"
+                                    onChange={(e) => updateRadiusKm(Number(e.target.value))}
+                                />
+                            </div>
+                            {(filters.radius || filters.polygon) && (
+                                <button
+                                    onClick={clearShapes}
+                                    className="w-full px-3 py-2 bg-red-600/20 hover:bg-red-600/30 text-red-400 text-xs font-medium rounded-lg border border-red-600/30 transition-all duration-200"
+                                >
+                                    {"Clear drawn area"}
+                                </button>
+                            )}
+                        </div>
+
                         <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
                             <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Address Range"}</label>
                             <div className="grid grid-cols-2 gap-3">
@@ -981,6 +1280,29 @@ export default function MapView() {
                             </div>
                         </div>
 
+                        {datasetNames.length > 1 && (
+                            <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
+                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Dataset"}</label>
+                                <select
+                                    id="dataset"
+                                    name="dataset"
+                                    className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all cursor-pointer"
+                                    value={getSelectedDataset()}
+                                    onChange={(e) => {
+                                        selectDataset(e.target.value);
+                                        // drawn ases, lists and caches all belong to the previous dataset
+                                        window.location.reload();
+                                    }}
+                                >
+                                    {datasetNames.map((name) => (
+                                        <option key={name} value={name}>
+                                            {name}
+                                        </option>
+                                    ))}
+                                </select>
+                            </div>
+                        )}
+
                         <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
                             <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Organization"}</label>
                             <select
@@ -996,6 +1318,61 @@ export default function MapView() {
                             </select>
                         </div>
 
+                        <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
+                            <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Registry"}</label>
+                            <select
+                                id="registry"
+                                name="registry"
+                                className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all cursor-pointer"
+                                value={registryName(filters.registry)}
+                                onChange={(e) =>
+                                    setFilters((current) => ({
+                                        ...current,
+                                        registry: e.target.value ? (e.target.value as InternetRegistry) : null
+                                    }))
+                                }
+                            >
+                                <option value="">{"Any"}</option>
+                                {REGISTRIES.map(([registry, label]) => (
+                                    <option key={label} value={registryName(registry)}>
+                                        {label}
+                                    </option>
+                                ))}
+                            </select>
+                            <div className="grid grid-cols-2 gap-3 mt-3">
+                                <div>
+                                    <label className="block text-xs text-slate-500 mb-1">{"Allocated after"}</label>
+                                    <input
+                                        type="date"
+                                        id="allocatedAfter"
+                                        value={filters.allocated_after ?? ""}
+                                        className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all"
+                                        onChange={(e) =>
+                                            setFilters((current) => ({
+                                                ...current,
+                                                allocated_after: e.target.value || null
+                                            }))
+                                        }
+                                    />
+                                </div>
+                                <div>
+                                    <label className="block text-xs text-slate-500 mb-1">{"Allocated before"}</label>
+                                    <input
+                                        type="date"
+                                        id="allocatedBefore"
+                                        value={filters.allocated_before ?? ""}
+                                        className="w-full px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-sm text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all"
+                                        onChange={(e) =>
+                                            setFilters((current) => ({
+                                                ...current,
+                                                allocated_before: e.target.value || null
+                                            }))
+                                        }
+                                    />
+                                </div>
+                            </div>
+                        </div>
+
                         <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
                             <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Category"}</label>
                             <select
@@ -1018,7 +1395,10 @@ export default function MapView() {
                         </div>
 
                         <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30">
-                            <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider mb-3">{"Lists"}</label>
+                            <div className="flex items-center justify-between mb-3">
+                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider">{"Lists"}</label>
+                                <a href="/lists" target="_blank" className="text-xs text-blue-300 hover:text-blue-200">{"Manage"}</a>
+                            </div>
                             {listNames.length ? (
                                 <select
                                     id="lists"
@@ -1041,6 +1421,68 @@ export default function MapView() {
                                 <p className="text-xs text-slate-500">{"No lists yet"}</p>
                             )}
                         </div>
+
+                        {annotationFields.length > 0 && (
+                            <div className="p-3 rounded-xl bg-slate-700/30 border border-slate-600/30 space-y-2">
+                                <label className="block text-xs font-semibold text-slate-400 uppercase tracking-wider">{"Annotations"}</label>
+                                {filters.annotations.map((filter, i) => (
+                                    <div
+                                        key={`${filter.name}-${i}`}
+                                        className="flex items-center justify-between gap-2 rounded-lg border border-slate-600/40 bg-slate-800/60 px-2 py-1 text-xs text-slate-300"
+                                    >
+                                        <span className="truncate">{describeAnnotationFilter(filter)}</span>
+                                        <button
+                                            onClick={() => removeAnnotationFilter(i)}
+                                            className="text-slate-400 hover:text-red-300"
+                                            aria-label="Remove annotation filter"
+                                        >
+                                            {"×"}
+                                        </button>
+                                    </div>
+                                ))}
+                                <div className="flex gap-1">
+                                    <select
+                                        value={annotationDraft.name}
+                                        className="flex-1 min-w-0 px-2 py-1.5 bg-slate-800/80 border border-slate-600/50 rounded-lg text-xs text-slate-200"
+                                        onChange={(e) => setAnnotationDraft((current) => ({ ...current, name: e.target.value }))}
+                                    >
+                                        {annotationFields.map((field) => (
+                                            <option key={field.name} value={field.name}>
+                                                {field.name}
+                                            </option>
+                                        ))}
+                                    </select>
+                                    <select
+                                        value={annotationDraft.op}
+                                        className="px-2 py-1.5 bg-slate-800/80 border border-slate-600/50 rounded-lg text-xs text-slate-200"
+                                        onChange={(e) =>
+                                            setAnnotationDraft((current) => ({ ...current, op: e.target.value as AnnotationOpName }))
+                                        }
+                                    >
+                                        {ANNOTATION_OPS.map(([op, label]) => (
+                                            <option key={op} value={op}>
+                                                {label}
+                                            </option>
+                                        ))}
+                                    </select>
+                                </div>
+                                <div className="flex gap-1">
+                                    <input
+                                        type="text"
+                                        value={annotationDraft.input}
+                                        placeholder="Value"
+                                        className="flex-1 min-w-0 px-2 py-1.5 bg-slate-800/80 border border-slate-600/50 rounded-lg text-xs text-slate-200"
+                                        onChange={(e) => setAnnotationDraft((current) => ({ ...current, input: e.target.value }))}
+                                    />
+                                    <button
+                                        onClick={addAnnotationFilter}
+                                        className="px-3 py-1.5 text-xs font-semibold rounded-lg bg-blue-500/20 text-blue-200 border border-blue-500/30 hover:bg-blue-500/30 transition"
+                                    >
+                                        {"Add"}
+                                    </button>
+                                </div>
+                            </div>
+                        )}
                     </div>
                 </div>
 
@@ -1142,6 +1584,44 @@ export default function MapView() {
                             <p className="text-xs text-slate-400">{"Detailed"}</p>
                         </div>
                     </div>
+                    <div className="flex gap-2 mt-3">
+                        <select
+                            id="statsGroup"
+                            value={statsGroup}
+                            className="flex-1 px-3 py-2 bg-slate-800/80 border border-slate-600/50 rounded-lg text-xs text-slate-200 focus:outline-none focus:ring-2 focus:ring-blue-500/50 focus:border-blue-500/50 transition-all"
+                            onChange={(e) => setStatsGroup(e.target.value as StatsGroup)}
+                        >
+                            {STATS_GROUPS.map(([value, label]) => (
+                                <option key={value} value={value}>
+                                    {label}
+                                </option>
+                            ))}
+                        </select>
+                        <button
+                            onClick={loadStats}
+                            disabled={statsLoading}
+                            className="px-3 py-2 text-xs font-medium rounded-lg bg-slate-700/40 hover:bg-slate-600/50 text-slate-300 border border-slate-600/40 transition-all duration-200 disabled:opacity-50"
+                        >
+                            {statsLoading ? "Loading..." : "Summarize filtered"}
+                        </button>
+                    </div>
+                    {asStats && (
+                        <div className="mt-3 space-y-1 text-xs text-slate-300">
+                            <div className="flex justify-between font-semibold text-slate-200">
+                                <span>{"All matching"}</span>
+                                <span className="tabular-nums">{`${asStats.total.ases} ASes, ${asStats.total.addresses} addresses`}</span>
+                            </div>
+                            {asStats.groups.slice(0, STATS_TOP_GROUPS).map((group) => (
+                                <div key={group.key ?? ""} className="flex justify-between gap-2">
+                                    <span className="truncate">{group.key ?? "unknown"}</span>
+                                    <span className="tabular-nums text-slate-400">{`${group.totals.ases} / ${group.totals.addresses}`}</span>
+                                </div>
+                            ))}
+                            {asStats.groups.length > STATS_TOP_GROUPS && (
+                                <p className="text-slate-500">{`and ${asStats.groups.length - STATS_TOP_GROUPS} more groups`}</p>
+                            )}
+                        </div>
+                    )}
                 </div>
 
                 {whoisText && (
@@ -0,0 +1,87 @@
+import type { AnnotationFilter, AnnotationKind, AnnotationOp, AnnotationValue } from "./protocol/types";
+
+export type AnnotationOpName = "Exists" | "Missing" | "Eq" | "Ne" | "Lt" | "Lte" | "Gt" | "Gte" | "Contains";
+
+export const ANNOTATION_OPS: [AnnotationOpName, string][] = [
+    ["Eq", "="],
+    ["Ne", "≠"],
+    ["Lt", "<"],
+    ["Lte", "≤"],
+    ["Gt", ">"],
+    ["Gte", "≥"],
+    ["Contains", "contains"],
+    ["Exists", "is set"],
+    ["Missing", "is not set"]
+];
+
+export function kindName(kind: AnnotationKind): string {
+    return typeof kind === "string" ? kind : "Enum";
+}
+
+// value typed in by the user for a field of the kind, null if it doesn't fit
+export function parseAnnotationValue(kind: AnnotationKind, input: string): AnnotationValue | null {
+    const text = input.trim();
+    if (!text) {
+        return null;
+    }
+    if (kind === "Number") {
+        const x = Number(text);
+        return Number.isFinite(x) ? { Number: x } : null;
+    }
+    if (kind === "Date") {
+        return /^\d{4}-\d{2}-\d{2}$/.test(text) ? { Date: text } : null;
+    }
+    if (typeof kind === "object" && !kind.Enum.includes(text)) {
+        return null;
+    }
+    return { Text: text };
+}
+
+export function formatAnnotationValue(value: AnnotationValue): string {
+    if ("Number" in value) {
+        return `${value.Number}`;
+    }
+    if ("Date" in value) {
+        return value.Date;
+    }
+    return value.Text;
+}
+
+export function annotationOp(name: AnnotationOpName, value: AnnotationValue | null, input: string): AnnotationOp | null {
+    if (name === "Exists" || name === "Missing") {
+        return name;
+    }
+    if (name === "Contains") {
+        return input.trim() ? { Contains: input.trim() } : null;
+    }
+    if (!value) {
+        return null;
+    }
+    switch (name) {
+        case "Eq":
+            return { Eq: value };
+        case "Ne":
+            return { Ne: value };
+        case "Lt":
+            return { Lt: value };
+        case "Lte":
+            return { Lte: value };
+        case "Gt":
+            return { Gt: value };
+        case "Gte":
+            return { Gte: value };
+    }
+}
+
+export function describeAnnotationFilter(filter: AnnotationFilter): string {
+    const op = filter.op;
+    if (typeof op === "string") {
+        return `${filter.name} ${op === "Exists" ? "is set" : "is not set"}`;
+    }
+    if ("Contains" in op) {
+        return `${filter.name} contains ${op.Contains}`;
+    }
+    const [name, value] = Object.entries(op)[0] as [AnnotationOpName, AnnotationValue];
+    const symbol = ANNOTATION_OPS.find(([x]) => x === name)?.[1] ?? name;
+    return `${filter.name} ${symbol} ${formatAnnotationValue(value)}`;
+}
@@ -1,17 +1,58 @@
 import type {
+    AnnotationField,
+    AnnotationValue,
     As,
+    AsChange,
     AsFilters,
     AsForFrontend,
+    AsList,
+    AsPage,
+    AsSort,
+    AsStats,
+    Dataset,
     GeocodedAddress,
+    ListSummary,
+    SearchHit,
+    StatsGroup,
     UserData,
+    UserDataEdit,
     WSRequest,
     WSResponse
 } from "../protocol/types";
 import { decodeResponse, encodeRequest, ensureProtocolReady } from "../protocol/wasm";
 
 const API_URL = "ws://[::1]:8080/as";
+// kept across pages so details opened in a new tab come from the same dataset
+const DATASET_STORAGE_KEY = "asmap.dataset";
+const DEFAULT_DATASET = "default";
+const SUBSCRIPTION_RETRY_MS = 5000;
 
-async function sendWsRequest(request: WSRequest): Promise<WSResponse> {
+export function getSelectedDataset(): string {
+    return window.localStorage.getItem(DATASET_STORAGE_KEY) ?? DEFAULT_DATASET;
+}
+
+export function selectDataset(name: string) {
+    if (name === DEFAULT_DATASET) {
+        window.localStorage.removeItem(DATASET_STORAGE_KEY);
+    } else {
+        window.localStorage.setItem(DATASET_STORAGE_KEY, name);
+    }
+}
+
+function inSelectedDataset(request: WSRequest): WSRequest {
+    const dataset = getSelectedDataset();
+    if (dataset === DEFAULT_DATASET || "GetDatasetNames" in request) {
+        return request;
+    }
+    return { InDataset: { dataset, request } };
+}
+
+// `onPartial` gets every response first and returns true for the ones followed by more,
+// the promise resolves with the first response it doesn't accept
+async function sendWsRequest(
+    request: WSRequest,
+    onPartial?: (response: WSResponse) => boolean
+): Promise<WSResponse> {
     await ensureProtocolReady();
 
     return new Promise((resolve, reject) => {
@@ -29,14 +70,18 @@ async function sendWsRequest(request: WSRequest): Promise<WSResponse> {
         const socket = new WebSocket(API_URL);
         socket.binaryType = "arraybuffer";
 
-        timeoutId = window.setTimeout(() => {
-            finish(reject, new Error("WebSocket timeout"));
-            socket.close();
-        }, 8000);
+        const restartTimeout = () => {
+            window.clearTimeout(timeoutId);
+            timeoutId = window.setTimeout(() => {
+                finish(reject, new Error("WebSocket timeout"));
+                socket.close();
+            }, 8000);
+        };
+        restartTimeout();
 
         socket.onopen = () => {
             try {
-                const payload = encodeRequest(request);
+                const payload = encodeRequest(inSelectedDataset(request));
                 socket.send(payload);
             } catch (error) {
                 console.error("[ws] encode failed", request, error);
@@ -69,6 +114,10 @@ async function sendWsRequest(request: WSRequest): Promise<WSResponse> {
                 }
 
                 const response = decodeResponse(bytes);
+                if (onPartial?.(response)) {
+                    restartTimeout();
+                    return;
+                }
                 finish(resolve, response);
             } catch (error) {
                 console.error("[ws] decode failed", request, error);
@@ -78,10 +127,83 @@ async function sendWsRequest(request: WSRequest): Promise<WSResponse> {
     });
 }
 
+export interface AsChangesSubscription {
+    // replaces the subscribed ases
+    update: (asns: number[]) => void;
+    close: () => void;
+}
+
+// keeps a socket open which gets changes of the given ases made by other clients, reconnecting
+// after it drops
+export function subscribeAsChanges(
+    asns: number[],
+    onChange: (asn: number, change: AsChange) => void
+): AsChangesSubscription {
+    let subscribed = asns;
+    let socket: WebSocket | null = null;
+    let closed = false;
+    let retryId = 0;
+
+    const send = () => {
+        if (socket?.readyState === WebSocket.OPEN) {
+            socket.send(encodeRequest(inSelectedDataset({ Subscribe: subscribed })));
+        }
+    };
+
+    const connect = async () => {
+        await ensureProtocolReady();
+        if (closed) {
+            return;
+        }
+        socket = new WebSocket(API_URL);
+        socket.binaryType = "arraybuffer";
+        socket.onopen = send;
+        socket.onclose = () => {
+            if (!closed) {
+                retryId = window.setTimeout(connect, SUBSCRIPTION_RETRY_MS);
+            }
+        };
+        socket.onmessage = (event) => {
+            try {
+                const response = decodeResponse(new Uint8Array(event.data as ArrayBuffer));
+                if ("AsChanged" in response) {
+                    onChange(response.AsChanged.asn, response.AsChanged.change);
+                } else if ("Error" in response) {
+                    console.error("[ws] subscription error", response.Error);
+                }
+            } catch (error) {
+                console.error("[ws] decode failed", error);
+            }
+        };
+    };
+    void connect();
+
+    return {
+        update: (next) => {
+            subscribed = next;
+            send();
+        },
+        close: () => {
+            closed = true;
+            window.clearTimeout(retryId);
+            socket?.close();
+        }
+    };
+}
+
 export async function getAllAsFiltered(filters: AsFilters): Promise<AsForFrontend[]> {
-    const response = await sendWsRequest({ FilteredAS: filters });
-    if ("FilteredAS" in response) {
-        return response.FilteredAS[1];
+    const ases: AsForFrontend[] = [];
+    const response = await sendWsRequest({ FilteredAS: filters }, (partial) => {
+        if ("FilteredASChunk" in partial) {
+            for (const a of partial.FilteredASChunk) {
+                ases.push(a);
+            }
+            return true;
+        }
+        return false;
+    });
+    if ("FilteredASDone" in response) {
+        return ases;
     }
     if ("Error" in response) {
         throw new Error(response.Error);
@@ -158,6 +280,28 @@ export async function saveGeocoding(
     throw new Error("Unexpected response for geocoding save");
 }
 
+export async function getUserDataHistory(asn: number): Promise<UserDataEdit[]> {
+    const response = await sendWsRequest({ GetUserDataHistory: asn });
+    if ("UserDataHistory" in response) {
+        return response.UserDataHistory;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for user data history");
+}
+
+export async function revertUserData(asn: number, editId: string): Promise<UserData> {
+    const response = await sendWsRequest({ RevertUserData: { asn, edit_id: editId } });
+    if ("UserData" in response) {
+        return response.UserData;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for user data revert");
+}
+
 export async function getListNames(): Promise<string[]> {
     const response = await sendWsRequest({ GetListNames: null });
     if ("ListNames" in response) {
@@ -168,3 +312,152 @@ export async function getListNames(): Promise<string[]> {
     }
     throw new Error("Unexpected response for list names");
 }
+
+export async function getAnnotationFields(): Promise<AnnotationField[]> {
+    const response = await sendWsRequest({ GetAnnotationFields: null });
+    if ("AnnotationFields" in response) {
+        return response.AnnotationFields;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for annotation fields");
+}
+
+export async function defineAnnotationField(field: AnnotationField): Promise<AnnotationField[]> {
+    const response = await sendWsRequest({ DefineAnnotationField: field });
+    if ("AnnotationFields" in response) {
+        return response.AnnotationFields;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for annotation field");
+}
+
+export async function setAnnotation(
+    asn: number,
+    name: string,
+    value: AnnotationValue | null
+): Promise<UserData> {
+    const response = await sendWsRequest({ SetAnnotation: { asn, name, value } });
+    if ("UserData" in response) {
+        return response.UserData;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for annotation");
+}
+
+// every change of lists is answered with all of them
+async function sendListsRequest(request: WSRequest): Promise<ListSummary[]> {
+    const response = await sendWsRequest(request);
+    if ("Lists" in response) {
+        return response.Lists;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for lists");
+}
+
+export async function getLists(): Promise<ListSummary[]> {
+    return sendListsRequest({ GetLists: null });
+}
+
+export async function createList(list: AsList): Promise<ListSummary[]> {
+    return sendListsRequest({ CreateList: list });
+}
+
+export async function updateList(list: AsList): Promise<ListSummary[]> {
+    return sendListsRequest({ UpdateList: list });
+}
+
+export async function renameList(name: string, newName: string): Promise<ListSummary[]> {
+    return sendListsRequest({ RenameList: { name, new_name: newName } });
+}
+
+export async function deleteList(name: string): Promise<ListSummary[]> {
+    return sendListsRequest({ DeleteList: name });
+}
+
+export async function addToList(name: string, asns: number[]): Promise<ListSummary[]> {
+    return sendListsRequest({ AddToList: { name, asns } });
+}
+
+export async function removeFromList(name: string, asns: number[]): Promise<ListSummary[]> {
+    return sendListsRequest({ RemoveFromList: { name, asns } });
+}
+
+export async function getListMembers(name: string): Promise<number[]> {
+    const response = await sendWsRequest({ GetListMembers: name });
+    if ("ListMembers" in response) {
+        return response.ListMembers.asns;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for list members");
+}
+
+export async function searchAses(query: string, limit: number): Promise<SearchHit[]> {
+    const response = await sendWsRequest({ Search: { query, limit } });
+    if ("SearchResults" in response) {
+        return response.SearchResults;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for search");
+}
+
+export async function getStats(filters: AsFilters, groupBy: StatsGroup): Promise<AsStats> {
+    const response = await sendWsRequest({ Stats: { filters, group_by: groupBy } });
+    if ("Stats" in response) {
+        return response.Stats;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for stats");
+}
+
+// `after` is the `next` token of the previous page, requested with the same sort
+export async function getAsPage(
+    filters: AsFilters,
+    sort: AsSort,
+    limit: number,
+    after: string | null
+): Promise<AsPage> {
+    const response = await sendWsRequest({ AsPage: { filters, sort, limit, after } });
+    if ("AsPage" in response) {
+        return response.AsPage;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for page of ases");
+}
+
+export async function getDatasets(): Promise<Dataset[]> {
+    const response = await sendWsRequest({ GetDatasets: null });
+    if ("Datasets" in response) {
+        return response.Datasets;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for datasets");
+}
+
+export async function getDatasetNames(): Promise<string[]> {
+    const response = await sendWsRequest({ GetDatasetNames: null });
+    if ("DatasetNames" in response) {
+        return response.DatasetNames;
+    }
+    if ("Error" in response) {
+        throw new Error(response.Error);
+    }
+    throw new Error("Unexpected response for dataset names");
+}
@@ -10,15 +10,70 @@ export interface Bound {
     south_west: Coord;
 }
 
+export interface GeoPoint {
+    type: string;
+    coordinates: [number, number];
+}
+
+export interface Radius {
+    center: Coord;
+    meters: number;
+}
+
 export interface AsFilters {
     country: string | null;
     exclude_country: boolean;
     bounds: Bound | null;
+    radius: Radius | null;
+    polygon: Coord[] | null;
     addresses: [number, number] | null;
     rank: [number, number] | null;
     has_org: AsFiltersHasOrg;
     category: string[];
     lists: string[];
+    text: string | null;
+    // all have to match
+    annotations: AnnotationFilter[];
+    registry: InternetRegistry | null;
+    // YYYY-MM-DD
+    allocated_before: string | null;
+    allocated_after: string | null;
+}
+
+// enum options are kept as text and dates as YYYY-MM-DD
+export type AnnotationValue =
+    | { Text: string }
+    | { Number: number }
+    | { Date: string };
+
+export type AnnotationKind = "Text" | "Number" | { Enum: string[] } | "Date";
+
+export interface AnnotationField {
+    name: string;
+    kind: AnnotationKind;
+    description: string | null;
+}
+
+export interface Annotation {
+    name: string;
+    value: AnnotationValue;
+}
+
+// comparisons only match values of the same type
+export type AnnotationOp =
+    | "Exists"
+    | "Missing"
+    | { Eq: AnnotationValue }
+    | { Ne: AnnotationValue }
+    | { Lt: AnnotationValue }
+    | { Lte: AnnotationValue }
+    | { Gt: AnnotationValue }
+    | { Gte: AnnotationValue }
+    | { Contains: string };
+
+export interface AnnotationFilter {
+    name: string;
+    op: AnnotationOp;
 }
 
 export interface AsForFrontend {
@@ -44,6 +99,7 @@ export interface AsrankDegree {
 export interface AsrankAsn {
     rank: number;
     organization: string | null;
+    organization_id: string | null;
     country_iso: string;
     country_name: string;
     coordinates: Coord;
@@ -99,6 +155,42 @@ export interface IPNetDBAsn {
     ix: IPNetDBIX[];
 }
 
+// port of a network at an IXP
+export interface IxpPresence {
+    ix_id: number;
+    name: string;
+    // Mbit/s
+    speed: number;
+    ipaddr4: string | null;
+    ipaddr6: string | null;
+    is_rs_peer: boolean;
+}
+
+export interface FacilityPresence {
+    fac_id: number;
+    name: string;
+}
+
+export interface PeeringdbNet {
+    id: number;
+    name: string;
+    policy_general: string | null;
+    info_traffic: string | null;
+    info_type: string | null;
+    ixps: IxpPresence[];
+    facilities: FacilityPresence[];
+}
+
+// record of the resource in the delegated-extended statistics of its RIR
+export interface RirDelegation {
+    registry: InternetRegistry;
+    country: string | null;
+    // YYYY-MM-DD
+    date: string | null;
+    status: string;
+    opaque_id: string | null;
+}
+
 export interface StanfordASdbCategory {
     layer1: string;
     layer2: string;
@@ -112,6 +204,9 @@ export interface WhoIsOrg {
     country: string | null;
     phone: string | null;
     email: string | null;
+    admin_c: string[];
+    tech_c: string[];
+    abuse_c: string | null;
 }
 
 export interface WhoIsPerson {
@@ -142,12 +237,103 @@ export interface As {
     whois_data: WhoIsAsn | null;
     stanford_asdb: StanfordASdbCategory[];
     user_data: UserData | null;
+    location: GeoPoint | null;
+    peeringdb_data: PeeringdbNet | null;
+    rir_data: RirDelegation | null;
 }
 
 export interface UserData {
     lists: string[];
     comment: string | null;
     geocoded_addresses: GeocodedAddress[];
+    annotations: Annotation[];
+}
+
+// list of ases made by users, members have its name in UserData.lists
+export interface AsList {
+    name: string;
+    description: string | null;
+    // css color the list is shown with
+    color: string | null;
+}
+
+export interface ListSummary {
+    list: AsList;
+    count: number;
+}
+
+// one change of the user data of an AS
+export interface UserDataEdit {
+    id: string;
+    asn: number;
+    at: string;
+    // address of the client which made the edit
+    editor: string | null;
+    old: UserData;
+    new: UserData;
+}
+
+export type StatsGroup = "Country" | "Category" | "Subcategory" | "Registry" | "RankBucket" | "List";
+
+export interface StatsTotals {
+    ases: number;
+    addresses: number;
+    prefixes: number;
+}
+
+export interface StatsGroupRow {
+    // null groups ases without the value
+    key: string | null;
+    totals: StatsTotals;
+}
+
+export interface AsStats {
+    group_by: StatsGroup;
+    groups: StatsGroupRow[];
+    total: StatsTotals;
+}
+
+export type AsSortField = "Asn" | "Rank" | "Addresses" | "Prefixes" | "Degree" | "Country" | "Name";
+
+export interface AsSort {
+    field: AsSortField;
+    descending: boolean;
+}
+
+export interface AsPage {
+    ases: AsForFrontend[];
+    // opaque token for the next page, null on the last one
+    next: string | null;
+}
+
+export interface DatasetFile {
+    source: string;
+    path: string | null;
+    sha256: string | null;
+}
+
+// one import run of a data source
+export interface Dataset {
+    run_id: string;
+    source: string;
+    files: DatasetFile[];
+    started_at: string;
+    finished_at: string | null;
+    records: number;
+    errors: string[];
+}
+
+// data of an AS changed after it was loaded
+export type AsChange =
+    | { UserData: UserData }
+    | { Whois: WhoIsAsn }
+    | { Geocoding: GeocodedAddress[] };
+
+export interface SearchHit {
+    asn: number;
+    score: number;
+    name: string | null;
+    organization: string | null;
 }
 
 export interface GeocodedAddress {
@@ -166,12 +352,43 @@ export type WSRequest =
     | { UpdateUserData: { asn: number; lists?: string[] | null; comment?: string | null } }
     | { GetUserData: number }
     | { SaveGeocoding: { asn: number; geocoded: GeocodedAddress[] } }
-    | { GetListNames: null };
+    | { GetUserDataHistory: number }
+    | { RevertUserData: { asn: number; edit_id: string } }
+    | { GetListNames: null }
+    | { GetAnnotationFields: null }
+    | { DefineAnnotationField: AnnotationField }
+    | { SetAnnotation: { asn: number; name: string; value: AnnotationValue | null } }
+    | { GetLists: null }
+    | { CreateList: AsList }
+    | { UpdateList: AsList }
+    | { RenameList: { name: string; new_name: string } }
+    | { DeleteList: string }
+    | { AddToList: { name: string; asns: number[] } }
+    | { RemoveFromList: { name: string; asns: number[] } }
+    | { GetListMembers: string }
+    | { Search: { query: string; limit: number } }
+    | { Stats: { filters: AsFilters; group_by: StatsGroup } }
+    | { AsPage: { filters: AsFilters; sort: AsSort; limit: number; after: string | null } }
+    | { GetDatasets: null }
+    | { GetDatasetNames: null }
+    | { Subscribe: number[] }
+    | { InDataset: { dataset: string; request: WSRequest } };
 
 export type WSResponse =
-    | { FilteredAS: [AsFilters, AsForFrontend[]] }
+    | { FilteredASChunk: AsForFrontend[] }
+    | { FilteredASDone: { filters: AsFilters; count: number } }
     | { AsDetails: As }
     | { WhoisData: WhoIsAsn | null }
     | { UserData: UserData }
+    | { UserDataHistory: UserDataEdit[] }
     | { ListNames: string[] }
+    | { Lists: ListSummary[] }
+    | { AnnotationFields: AnnotationField[] }
+    | { ListMembers: { name: string; asns: number[] } }
+    | { SearchResults: SearchHit[] }
+    | { Stats: AsStats }
+    | { AsPage: AsPage }
+    | { Datasets: Dataset[] }
+    | { DatasetNames: string[] }
+    | { AsChanged: { asn: number; change: AsChange } }
     | { Error: string };
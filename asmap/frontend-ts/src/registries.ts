@@ -0,0 +1,16 @@
+import type { InternetRegistry } from "./protocol/types";
+
+export const REGISTRIES: [InternetRegistry, string][] = [
+    ["AFRINIC", "AFRINIC"],
+    ["APNIC", "APNIC"],
+    ["ARIN", "ARIN"],
+    ["LACNIC", "LACNIC"],
+    ["RIPE", "RIPE NCC"]
+];
+
+export function registryName(registry: InternetRegistry | null): string {
+    if (registry === null) {
+        return "";
+    }
+    return typeof registry === "string" ? registry : registry.LOCAL;
+}
@@ -2,7 +2,11 @@ use std::fmt::Display;
 
 use serde::{Deserialize, Serialize};
 
-use asdb_models::{As, Bound, GeocodedAddress, UserData, WhoIsAsn};
+use asdb_models::{
+    AnnotationField, AnnotationFilter, AnnotationValue, As, AsList, AsPage, AsSort, AsStats, Bound,
+    Coord, Dataset, GeocodedAddress, InternetRegistry, ListSummary, Radius, SearchHit, StatsGroup,
+    UserData, UserDataEdit, WhoIsAsn,
+};
 // TODO remove pub and switch references to asdb_models
 pub use asdb_models::AsForFrontend;
 
@@ -10,7 +14,7 @@ type Asn = u32;
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum WSRequest {
-    /// requests all ases that match given filter
+    /// requests all ases that match given filter, answered with chunks and a summary
     FilteredAS(AsFilters),
     /// details for single As
     AsDetails(Asn),
@@ -31,26 +35,126 @@ pub enum WSRequest {
         asn: Asn,
         geocoded: Vec<GeocodedAddress>,
     },
+    /// edits of the user data of an AS, the most recent first
+    GetUserDataHistory(Asn),
+    /// puts back the user data an AS had before the given edit, answered with the restored
+    /// [`WSResponse::UserData`]
+    RevertUserData {
+        asn: Asn,
+        edit_id: String,
+    },
     /// get all list names
     GetListNames,
+    /// definitions of the annotation fields
+    GetAnnotationFields,
+    /// adds or changes an annotation field, answered with [`WSResponse::AnnotationFields`]
+    DefineAnnotationField(AnnotationField),
+    /// sets or with None removes an annotation of an AS, answered with [`WSResponse::UserData`]
+    SetAnnotation {
+        asn: Asn,
+        name: String,
+        value: Option<AnnotationValue>,
+    },
+    /// all lists with their metadata and number of members
+    GetLists,
+    /// creates an empty list, answered with [`WSResponse::Lists`] like the other list changes
+    CreateList(AsList),
+    /// changes the description and color of a list
+    UpdateList(AsList),
+    RenameList {
+        name: String,
+        new_name: String,
+    },
+    /// removes the list from all its members
+    DeleteList(String),
+    AddToList {
+        name: String,
+        asns: Vec<Asn>,
+    },
+    RemoveFromList {
+        name: String,
+        asns: Vec<Asn>,
+    },
+    /// asns in the list
+    GetListMembers(String),
+    /// full-text search over AS names, organisations and WHOIS descriptions
+    Search {
+        query: String,
+        limit: i64,
+    },
+    /// totals of ases matching the filters, grouped by one value
+    Stats {
+        filters: AsFilters,
+        group_by: StatsGroup,
+    },
+    /// page of ases matching the filters in the given order, `after` is the token of the
+    /// previous page
+    AsPage {
+        filters: AsFilters,
+        sort: AsSort,
+        limit: i64,
+        after: Option<String>,
+    },
+    /// records of the import runs, the most recent first
+    GetDatasets,
+    /// names of the datasets the server has loaded, the default one first
+    GetDatasetNames,
+    /// keeps the socket open and pushes [`WSResponse::AsChanged`] for these ases, sending it
+    /// again on the same socket replaces them
+    Subscribe(Vec<Asn>),
+    /// any other request answered from the named dataset instead of the default one
+    InDataset {
+        dataset: String,
+        request: Box<WSRequest>,
+    },
 }
 
 #[derive(Serialize, Deserialize, Debug)]
 pub enum WSResponse {
-    /// returnes vec of ases matching the filters along the original filters requested
-    FilteredAS((AsFilters, Vec<AsForFrontend>)),
+    /// part of the ases matching the filters, sent in several frames
+    FilteredASChunk(Vec<AsForFrontend>),
+    /// last frame after all chunks of ases matching the filters, with the total sent
+    FilteredASDone { filters: Box<AsFilters>, count: u64 },
     /// details for single As
-    AsDetails(As),
+    AsDetails(Box<As>),
     /// WHOIS data for an AS (None if not found or fetch failed)
-    WhoisData(Option<WhoIsAsn>),
+    WhoisData(Option<Box<WhoIsAsn>>),
     /// user data for an AS
     UserData(UserData),
+    /// edits of the user data of an AS, the most recent first
+    UserDataHistory(Vec<UserDataEdit>),
     /// list names available in user data
     ListNames(Vec<String>),
+    /// all lists sorted by name
+    Lists(Vec<ListSummary>),
+    /// annotation fields sorted by name
+    AnnotationFields(Vec<AnnotationField>),
+    /// asns in the list, sorted
+    ListMembers { name: String, asns: Vec<Asn> },
+    /// ases matching the search query, the most relevant first
+    SearchResults(Vec<SearchHit>),
+    /// grouped totals of ases matching the filters
+    Stats(AsStats),
+    /// ases of the requested page along with the token for the next one
+    AsPage(AsPage),
+    /// import runs of the data in the database
+    Datasets(Vec<Dataset>),
+    /// names of the datasets which can be chosen with [`WSRequest::InDataset`]
+    DatasetNames(Vec<String>),
+    /// data of a subscribed AS changed, pushed until the socket is closed
+    AsChanged { asn: Asn, change: AsChange },
     /// Error message
     Error(String),
 }
 
+/// Data of an AS which changed after clients loaded it
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum AsChange {
+    UserData(UserData),
+    Whois(Box<WhoIsAsn>),
+    Geocoding(Vec<GeocodedAddress>),
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum AsFiltersHasOrg {
     Yes,
@@ -76,6 +180,10 @@ pub struct AsFilters {
     pub exclude_country: bool,
     /// top left and bottom right corners of the geo bound
     pub bounds: Option<Bound>,
+    /// circle drawn on the map
+    pub radius: Option<Radius>,
+    /// vertices of a polygon drawn on the map
+    pub polygon: Option<Vec<Coord>>,
     /// range of addresses, (min, max)
     pub addresses: Option<(i64, i64)>,
     /// range of rank, (min, max)
@@ -86,6 +194,16 @@ pub struct AsFilters {
     pub category: Vec<String>,
     /// filter by saved user lists (empty = disabled)
     pub lists: Vec<String>,
+    /// words to search for in names, organisations and WHOIS descriptions
+    pub text: Option<String>,
+    /// conditions on annotations, all have to match
+    pub annotations: Vec<AnnotationFilter>,
+    /// RIR which delegated the asn
+    pub registry: Option<InternetRegistry>,
+    /// `YYYY-MM-DD` date the asn had to be allocated before
+    pub allocated_before: Option<String>,
+    /// `YYYY-MM-DD` date the asn had to be allocated after
+    pub allocated_after: Option<String>,
 }
 
 impl From<AsFilters> for asdb_models::AsFilters {
@@ -99,11 +217,18 @@ impl From<AsFilters> for asdb_models::AsFilters {
             country_iso: value.country,
             exclude_country: value.exclude_country,
             bounds: value.bounds,
+            radius: value.radius,
+            polygon: value.polygon,
             addresses: value.addresses,
             rank: value.rank,
             has_org,
             category: value.category,
             lists: value.lists,
+            text: value.text,
+            annotations: value.annotations,
+            registry: value.registry,
+            allocated_before: value.allocated_before,
+            allocated_after: value.allocated_after,
             // ..Default::default()
         }
     }
@@ -115,11 +240,18 @@ impl Default for AsFilters {
             country: None,
             exclude_country: false,
             bounds: None,
+            radius: None,
+            polygon: None,
             addresses: None,
             rank: None,
             has_org: AsFiltersHasOrg::Both,
             category: vec![],
             lists: vec![],
+            text: None,
+            annotations: vec![],
+            registry: None,
+            allocated_before: None,
+            allocated_after: None,
         }
     }
 }
@@ -134,14 +266,31 @@ impl Display for AsFilters {
         } else {
             String::new()
         };
+        let radius_str = self
+            .radius
+            .as_ref()
+            .map(|r| format!("-rad{:.0}", r.meters))
+            .unwrap_or_default();
+        let polygon_str = self
+            .polygon
+            .as_ref()
+            .map(|p| format!("-poly{}", p.len()))
+            .unwrap_or_default();
         let a = self.addresses.as_ref().unwrap_or(&(0, 0));
         let r = self.rank.as_ref().unwrap_or(&(0, 0));
+        let registry = self
+            .registry
+            .as_ref()
+            .map(|x| format!("{x:?}"))
+            .unwrap_or_default();
         write!(
             f,
-            "c{}-exc{}-{}-a{}-{}-r{}-{}-org{}-ncat{}-nlist{}",
+            "c{}-exc{}-{}{}{}-a{}-{}-r{}-{}-org{}-ncat{}-nlist{}-t{}-nann{}-reg{}-ab{}-aa{}",
             self.country.as_deref().unwrap_or(""),
             self.exclude_country,
             bound_str,
+            radius_str,
+            polygon_str,
             a.0,
             a.1,
             r.0,
@@ -149,6 +298,11 @@ impl Display for AsFilters {
             self.has_org,
             self.category.len(),
             self.lists.len(),
+            self.text.as_deref().unwrap_or(""),
+            self.annotations.len(),
+            registry,
+            self.allocated_before.as_deref().unwrap_or(""),
+            self.allocated_after.as_deref().unwrap_or(""),
         )
     }
 }
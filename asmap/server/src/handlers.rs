@@ -1,4 +1,8 @@
-use std::{net::SocketAddr, num::NonZeroU32};
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    num::NonZeroU32,
+};
 
 use axum::{
     extract::{
@@ -7,22 +11,39 @@ use axum::{
     },
     response::IntoResponse,
 };
+use futures::{Sink, SinkExt, Stream, StreamExt, TryStreamExt, future};
+use governor::DefaultKeyedRateLimiter;
 use nonzero_ext::nonzero;
+use tokio::sync::broadcast::error::RecvError;
 use tracing::{debug, info, trace, warn};
 
+use asdb::Storage;
+use asdb_models::{
+    AnnotationField, AnnotationValue, AsList, AsSort, InternetRegistry, StatsGroup, UserDataEdit,
+};
+
 use crate::state::ServerState;
-use protocol::{AsFilters, WSRequest, WSResponse};
+use protocol::{AsChange, AsFilters, WSRequest, WSResponse};
+
+/// Ases sent in a single frame of the response to [`WSRequest::FilteredAS`]
+const FILTERED_CHUNK_SIZE: usize = 1000;
+/// Most hits sent in response to [`WSRequest::Search`], also when the client asks for all
+const MAX_SEARCH_RESULTS: i64 = 100;
 
-pub async fn as_handler(
+pub async fn as_handler<S: Storage + 'static>(
     ws: WebSocketUpgrade,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
-    State(state): State<ServerState>,
+    State(state): State<ServerState<S>>,
 ) -> impl IntoResponse {
     ws.on_upgrade(move |socket| handle_as_socket(socket, addr, state))
 }
 
 #[tracing::instrument(skip(state, socket))]
-pub async fn handle_as_socket(mut socket: WebSocket, addr: SocketAddr, state: ServerState) {
+pub async fn handle_as_socket<S: Storage>(
+    mut socket: WebSocket,
+    addr: SocketAddr,
+    state: ServerState<S>,
+) {
     loop {
         trace!("handle_as_socket loop start");
         let msg = match socket.recv().await {
@@ -63,14 +84,27 @@ pub async fn handle_as_socket(mut socket: WebSocket, addr: SocketAddr, state: Se
                         break;
                     }
                 };
+                let (req, state) = match in_dataset(req, &state) {
+                    Ok(x) => x,
+                    Err(e) => {
+                        let resp = WSResponse::Error(e);
+                        let _ = socket
+                            .send(Message::Binary(bincode::serialize(&resp).unwrap().into()))
+                            .await;
+                        let _ = socket.send(Message::Close(None)).await;
+                        break;
+                    }
+                };
                 match req {
                     WSRequest::FilteredAS(filters) => {
                         info!(
                             "reveived WSRequest::FilteredAs with filters {filters:?} from {}",
                             addr.ip()
                         );
-                        let resp = filtered_as(filters, addr, &state).await;
-                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        if let Err(e) = filtered_as(filters, addr, &state, &mut socket).await {
+                            warn!("sending filtered ases to {} failed: {e:?}", addr.ip());
+                            break;
+                        }
                         socket.send(Message::Close(None)).await.unwrap();
                         break;
                     }
@@ -138,6 +172,26 @@ pub async fn handle_as_socket(mut socket: WebSocket, addr: SocketAddr, state: Se
                         socket.send(Message::Close(None)).await.unwrap();
                         break;
                     }
+                    WSRequest::GetUserDataHistory(asn) => {
+                        info!(
+                            "received WSRequest::GetUserDataHistory for asn {asn} from {}",
+                            addr.ip()
+                        );
+                        let resp = get_user_data_history(asn, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::RevertUserData { asn, edit_id } => {
+                        info!(
+                            "received WSRequest::RevertUserData for asn {asn} to {edit_id} from {}",
+                            addr.ip()
+                        );
+                        let resp = revert_user_data(asn, &edit_id, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
                     WSRequest::GetListNames => {
                         info!("received WSRequest::GetListNames from {}", addr.ip());
                         let resp = get_list_names(addr, &state).await;
@@ -145,6 +199,175 @@ pub async fn handle_as_socket(mut socket: WebSocket, addr: SocketAddr, state: Se
                         socket.send(Message::Close(None)).await.unwrap();
                         break;
                     }
+                    WSRequest::GetLists => {
+                        info!("received WSRequest::GetLists from {}", addr.ip());
+                        let resp = get_lists(addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::CreateList(list) => {
+                        info!(
+                            "received WSRequest::CreateList {:?} from {}",
+                            list.name,
+                            addr.ip()
+                        );
+                        let resp = create_list(list, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::UpdateList(list) => {
+                        info!(
+                            "received WSRequest::UpdateList {:?} from {}",
+                            list.name,
+                            addr.ip()
+                        );
+                        let resp = update_list(list, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::RenameList { name, new_name } => {
+                        info!(
+                            "received WSRequest::RenameList {name:?} to {new_name:?} from {}",
+                            addr.ip()
+                        );
+                        let resp = rename_list(&name, &new_name, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::DeleteList(name) => {
+                        info!("received WSRequest::DeleteList {name:?} from {}", addr.ip());
+                        let resp = delete_list(&name, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::AddToList { name, asns } => {
+                        info!(
+                            "received WSRequest::AddToList {name:?} for {} ases from {}",
+                            asns.len(),
+                            addr.ip()
+                        );
+                        let resp = add_to_list(&name, &asns, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::RemoveFromList { name, asns } => {
+                        info!(
+                            "received WSRequest::RemoveFromList {name:?} for {} ases from {}",
+                            asns.len(),
+                            addr.ip()
+                        );
+                        let resp = remove_from_list(&name, &asns, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::GetListMembers(name) => {
+                        info!(
+                            "received WSRequest::GetListMembers {name:?} from {}",
+                            addr.ip()
+                        );
+                        let resp = get_list_members(name, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::GetAnnotationFields => {
+                        info!("received WSRequest::GetAnnotationFields from {}", addr.ip());
+                        let resp = get_annotation_fields(addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::DefineAnnotationField(field) => {
+                        info!(
+                            "received WSRequest::DefineAnnotationField {:?} from {}",
+                            field.name,
+                            addr.ip()
+                        );
+                        let resp = define_annotation_field(field, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::SetAnnotation { asn, name, value } => {
+                        info!(
+                            "received WSRequest::SetAnnotation {name:?} for asn {asn} from {}",
+                            addr.ip()
+                        );
+                        let resp = set_annotation(asn, &name, value, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::GetDatasets => {
+                        info!("received WSRequest::GetDatasets from {}", addr.ip());
+                        let resp = get_datasets(addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::GetDatasetNames => {
+                        info!("received WSRequest::GetDatasetNames from {}", addr.ip());
+                        let resp = get_dataset_names(addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::Subscribe(asns) => {
+                        info!(
+                            "received WSRequest::Subscribe for {} ases from {}",
+                            asns.len(),
+                            addr.ip()
+                        );
+                        let (mut out, incoming) = (&mut socket).split();
+                        let incoming = incoming.filter_map(|x| future::ready(x.ok()));
+                        if let Err(e) = subscribe(asns, addr, &state, incoming, &mut out).await {
+                            warn!("sending changes to {} failed: {e:?}", addr.ip());
+                        }
+                        break;
+                    }
+                    WSRequest::InDataset { .. } => unreachable!("unwrapped by in_dataset"),
+                    WSRequest::Search { query, limit } => {
+                        info!(
+                            "received WSRequest::Search for {query:?} from {}",
+                            addr.ip()
+                        );
+                        let resp = search(&query, limit, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::Stats { filters, group_by } => {
+                        info!(
+                            "received WSRequest::Stats by {group_by:?} with filters {filters:?} from {}",
+                            addr.ip()
+                        );
+                        let resp = stats(filters, group_by, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
+                    WSRequest::AsPage {
+                        filters,
+                        sort,
+                        limit,
+                        after,
+                    } => {
+                        info!(
+                            "received WSRequest::AsPage of {limit} sorted by {sort:?} with filters {filters:?} from {}",
+                            addr.ip()
+                        );
+                        let resp = as_page(filters, sort, limit, after, addr, &state).await;
+                        socket.send(Message::Binary(resp.into())).await.unwrap();
+                        socket.send(Message::Close(None)).await.unwrap();
+                        break;
+                    }
                 };
             }
             Message::Close(_x) => {
@@ -164,127 +387,213 @@ pub async fn handle_as_socket(mut socket: WebSocket, addr: SocketAddr, state: Se
     }
 }
 
-/// returns WsResponse containing ases that match certain filters encoded using bincode
-#[tracing::instrument(skip(state))]
-async fn filtered_as(filters: AsFilters, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
-    let ases_count = state
-        .asdb
-        .count_ases_filtered(&asdb_models::AsFilters::from(filters.clone()))
-        .await
-        .unwrap();
-    debug!("ases count for current filters is {ases_count}");
+/// Charges `cost` to the limiter's quota of the client, returns the error to answer with if it
+/// runs out
+fn charge(
+    limiter: &DefaultKeyedRateLimiter<IpAddr>,
+    addr: SocketAddr,
+    cost: NonZeroU32,
+) -> Result<(), WSResponse> {
+    match limiter.check_key_n(&addr.ip(), cost) {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(_)) => Err(WSResponse::Error(
+            "Rate limited. Try again in a moment.".to_string(),
+        )),
+        Err(e) => Err(WSResponse::Error(format!("Rate limit error: {e:?}"))),
+    }
+}
 
-    match state
-        .simple_limiter
-        .check_key_n(&addr.ip(), NonZeroU32::new(ases_count as u32).unwrap())
-    {
-        Ok(Ok(_)) => {}
-        Ok(Err(_)) => {
-            let resp = WSResponse::Error("Rate limited. Try again in a moment.".to_string());
-            return bincode::serialize(&resp).unwrap();
+/// unwraps [`WSRequest::InDataset`] into the inner request and the state of its dataset, other
+/// requests are answered from the default dataset
+fn in_dataset<S: Storage>(
+    req: WSRequest,
+    state: &ServerState<S>,
+) -> Result<(WSRequest, ServerState<S>), String> {
+    let WSRequest::InDataset { dataset, request } = req else {
+        return Ok((req, state.clone()));
+    };
+    if matches!(*request, WSRequest::InDataset { .. }) {
+        return Err("Datasets can't be nested".to_string());
+    }
+    match state.dataset(&dataset) {
+        Some(x) => Ok((*request, x)),
+        None => Err(format!("Unknown dataset {dataset:?}")),
+    }
+}
+
+/// pushes changes of the subscribed ases until the client closes the socket, later
+/// [`WSRequest::Subscribe`] on the same socket replace the subscribed ases
+#[tracing::instrument(skip(asns, state, incoming, out))]
+async fn subscribe<S: Storage, W: Sink<Message> + Unpin>(
+    asns: Vec<u32>,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+    mut incoming: impl Stream<Item = Message> + Unpin,
+    out: &mut W,
+) -> Result<(), W::Error> {
+    let send = |resp: WSResponse| Message::Binary(bincode::serialize(&resp).unwrap().into());
+    let mut events = state.events.subscribe();
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return out.send(send(resp)).await;
+    }
+    let mut asns: HashSet<u32> = asns.into_iter().collect();
+    loop {
+        tokio::select! {
+            // changes made before the client went away are still sent
+            biased;
+            event = events.recv() => match event {
+                Ok(e) if asns.contains(&e.asn) => {
+                    out.send(send(WSResponse::AsChanged {
+                        asn: e.asn,
+                        change: e.change,
+                    }))
+                    .await?;
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(n)) => {
+                    warn!("subscriber {} missed {n} changes", addr.ip());
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            msg = incoming.next() => match msg {
+                Some(Message::Binary(b)) => match bincode::deserialize(&b) {
+                    Ok(WSRequest::Subscribe(x)) => {
+                        debug!("{} subscribed to {} ases", addr.ip(), x.len());
+                        asns = x.into_iter().collect();
+                    }
+                    _ => warn!("unexpected message from subscriber {}", addr.ip()),
+                },
+                Some(Message::Ping(payload)) => out.send(Message::Pong(payload)).await?,
+                Some(Message::Close(_)) | None => return Ok(()),
+                Some(_) => {}
+            },
         }
+    }
+}
+
+/// sends ases that match certain filters in chunks of bincode encoded WsResponses, followed by
+/// a summary, so the whole result never has to be kept in memory
+#[tracing::instrument(skip(state, out))]
+async fn filtered_as<S: Storage, W: Sink<Message> + Unpin>(
+    filters: AsFilters,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+    out: &mut W,
+) -> Result<(), W::Error> {
+    let send = |resp: WSResponse| Message::Binary(bincode::serialize(&resp).unwrap().into());
+    let db_filters = asdb_models::AsFilters::from(filters.clone());
+    let ases_count = match state.asdb.count_ases_filtered(&db_filters).await {
+        Ok(count) => count,
         Err(e) => {
-            let resp = WSResponse::Error(format!("Rate limit error: {e:?}"));
-            return bincode::serialize(&resp).unwrap();
+            let resp = WSResponse::Error(format!("Failed to count ases: {e:?}"));
+            return out.send(send(resp)).await;
         }
+    };
+    debug!("ases count for current filters is {ases_count}");
+    // nothing to send, so nothing to charge for
+    if ases_count == 0 {
+        return out
+            .send(send(WSResponse::FilteredASDone {
+                filters: Box::new(filters),
+                count: 0,
+            }))
+            .await;
     }
 
-    let ases = state
-        .asdb
-        .get_ases_filtered(&asdb_models::AsFilters::from(filters.clone()))
-        .await
-        .unwrap();
-    let resp = WSResponse::FilteredAS((filters.clone(), ases));
-    let serialized = bincode::serialize(&resp).unwrap();
-    debug!("successfuly encoded ases filtered by {filters:?} ");
-    serialized
+    let cost = NonZeroU32::new(ases_count.clamp(1, u32::MAX as u64) as u32).unwrap();
+    if let Err(resp) = charge(&state.simple_limiter, addr, cost) {
+        return out.send(send(resp)).await;
+    }
+
+    let mut chunks = match state.asdb.stream_ases_filtered(&db_filters).await {
+        Ok(ases) => ases.try_chunks(FILTERED_CHUNK_SIZE),
+        Err(e) => {
+            let resp = WSResponse::Error(format!("Failed to load ases: {e:?}"));
+            return out.send(send(resp)).await;
+        }
+    };
+    let mut count = 0;
+    while let Some(chunk) = chunks.next().await {
+        let resp = match chunk {
+            Ok(ases) => {
+                count += ases.len() as u64;
+                WSResponse::FilteredASChunk(ases)
+            }
+            Err(e) => {
+                let resp = WSResponse::Error(format!("Failed to load ases: {:?}", e.1));
+                return out.send(send(resp)).await;
+            }
+        };
+        out.send(send(resp)).await?;
+    }
+    debug!("successfuly sent {count} ases filtered by {filters:?}");
+    out.send(send(WSResponse::FilteredASDone {
+        filters: Box::new(filters),
+        count,
+    }))
+    .await
 }
 
 /// returns WsResponse containing details for single AS encoded using bincode
 #[tracing::instrument(skip(state))]
-async fn as_details(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
-    // separate limiter for detailed request? would be best TODO
-    state
-        .detailed_limiter
-        .check_key_n(&addr.ip(), nonzero!(1u32))
-        .unwrap()
-        .unwrap();
+async fn as_details<S: Storage>(asn: u32, addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.detailed_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
 
     let as_ = state.asdb.get_as(asn).await.unwrap();
-    let resp = WSResponse::AsDetails(as_);
+    let resp = WSResponse::AsDetails(Box::new(as_));
     let serialized = bincode::serialize(&resp).unwrap();
     debug!("successfuly encoded AS{asn} details");
     serialized
 }
 
-/// Fetches WHOIS data from RIPE API, caches it in the database, and returns it
+/// Fetches WHOIS data from RIPE API, or over RDAP for the ases of the other RIRs, caches it in
+/// the database, and returns it
 #[tracing::instrument(skip(state))]
-async fn fetch_whois(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
+async fn fetch_whois<S: Storage>(asn: u32, addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
     // Use detailed limiter for WHOIS requests (rate limited)
-    if let Err(e) = state
-        .detailed_limiter
-        .check_key_n(&addr.ip(), nonzero!(1u32))
-    {
-        warn!(
-            "Rate limit exceeded for WHOIS fetch from {}: {:?}",
-            addr.ip(),
-            e
-        );
-        let resp = WSResponse::Error("Rate limit exceeded".to_string());
+    if let Err(resp) = charge(&state.detailed_limiter, addr, nonzero!(1u32)) {
+        warn!("Rate limit exceeded for WHOIS fetch from {}", addr.ip());
         return bincode::serialize(&resp).unwrap();
     }
 
     // Return cached WHOIS data if present
     if let Ok(Some(cached)) = state.asdb.get_whois_data(asn).await {
-        let resp = WSResponse::WhoisData(Some(cached));
+        let resp = WSResponse::WhoisData(Some(Box::new(cached)));
         return bincode::serialize(&resp).unwrap();
     }
 
-    // Fetch from RIPE API
-    let whois_result = state.whois_client.get_as_whois_data(asn).await;
+    // Fetch from RIPE API, the other RIRs over RDAP
+    let whois_result = match &state.rdap_client {
+        Some(rdap) if rdap.registry(asn) != Some(InternetRegistry::RIPE) => {
+            rdap.get_as_whois_data(asn).await
+        }
+        _ => state.whois_client.get_as_whois_data(asn).await,
+    };
 
     let resp = match whois_result {
         Ok(data) => {
             // Convert to our model and cache
             let whois_data = asdb_models::WhoIsAsn {
-                as_name: data.aut_num.as_name,
-                descr: data.aut_num.descr,
-                org_id: data.aut_num.org,
-                admin_c: data.aut_num.admin_c,
-                tech_c: data.aut_num.tech_c,
-                abuse_c: data.aut_num.abuse_c,
-                country: data.aut_num.country,
-                organisation: data.organisation.map(|o| asdb_models::WhoIsOrg {
-                    org_id: o.org_id,
-                    org_name: o.org_name,
-                    org_type: o.org_type,
-                    address: o.address,
-                    country: o.country,
-                    phone: o.phone,
-                    email: o.email,
-                }),
-                contacts: data
-                    .contacts
-                    .into_iter()
-                    .map(|p| asdb_models::WhoIsPerson {
-                        nic_hdl: p.nic_hdl,
-                        name: p.name,
-                        address: p.address,
-                        phone: p.phone,
-                        email: p.email,
-                    })
-                    .collect(),
                 fetched_at: Some(chrono::Utc::now().to_rfc3339()),
+                ..data.into()
             };
 
-            // Cache in database (ignore errors)
+            // Cache in database along with the contacts (ignore errors)
             if let Err(e) = state.asdb.update_whois_data(asn, &whois_data).await {
                 warn!("Failed to cache WHOIS data for AS{}: {:?}", asn, e);
             } else {
                 debug!("Cached WHOIS data for AS{}", asn);
+                state.notify(asn, AsChange::Whois(Box::new(whois_data.clone())));
+            }
+            if let Some(org) = &whois_data.organisation
+                && let Err(e) = state.asdb.link_whois_organisation(asn, org).await
+            {
+                warn!("Failed to link WHOIS organisation for AS{}: {:?}", asn, e);
             }
 
-            WSResponse::WhoisData(Some(whois_data))
+            WSResponse::WhoisData(Some(Box::new(whois_data)))
         }
         Err(e) => {
             warn!("Failed to fetch WHOIS data for AS{}: {:?}", asn, e);
@@ -299,15 +608,13 @@ async fn fetch_whois(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8>
 
 /// Returns cached WHOIS data from database (without fetching from API)
 #[tracing::instrument(skip(state))]
-async fn get_whois(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
-    state
-        .detailed_limiter
-        .check_key_n(&addr.ip(), nonzero!(1u32))
-        .unwrap()
-        .unwrap();
+async fn get_whois<S: Storage>(asn: u32, addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.detailed_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
 
     let resp = match state.asdb.get_whois_data(asn).await {
-        Ok(whois_data) => WSResponse::WhoisData(whois_data),
+        Ok(whois_data) => WSResponse::WhoisData(whois_data.map(Box::new)),
         Err(e) => {
             warn!("Failed to get cached WHOIS data for AS{}: {:?}", asn, e);
             WSResponse::WhoisData(None)
@@ -320,47 +627,36 @@ async fn get_whois(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
 }
 
 #[tracing::instrument(skip(state))]
-async fn update_user_data(
+async fn update_user_data<S: Storage>(
     asn: u32,
     lists: Option<Vec<String>>,
     comment: Option<String>,
     addr: SocketAddr,
-    state: &ServerState,
+    state: &ServerState<S>,
 ) -> Vec<u8> {
-    match state.simple_limiter.check_key_n(&addr.ip(), nonzero!(1u32)) {
-        Ok(Ok(_)) => {}
-        Ok(Err(_)) => {
-            let resp = WSResponse::Error("Rate limited. Try again in a moment.".to_string());
-            return bincode::serialize(&resp).unwrap();
-        }
-        Err(e) => {
-            let resp = WSResponse::Error(format!("Rate limit error: {e:?}"));
-            return bincode::serialize(&resp).unwrap();
-        }
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
     }
 
-    let resp = match state.asdb.update_user_data(asn, lists, comment).await {
-        Ok(_) => match state.asdb.get_user_data(asn).await {
-            Ok(user_data) => WSResponse::UserData(user_data),
-            Err(e) => WSResponse::Error(format!("Failed to read user data: {e:?}")),
-        },
+    let editor = addr.ip().to_string();
+    let resp = match state
+        .asdb
+        .edit_user_data(asn, lists, comment, Some(&editor))
+        .await
+    {
+        Ok(user_data) => {
+            state.notify(asn, AsChange::UserData(user_data.clone()));
+            WSResponse::UserData(user_data)
+        }
         Err(e) => WSResponse::Error(format!("Failed to update user data: {e:?}")),
     };
     bincode::serialize(&resp).unwrap()
 }
 
 #[tracing::instrument(skip(state))]
-async fn get_user_data(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u8> {
-    match state.simple_limiter.check_key_n(&addr.ip(), nonzero!(1u32)) {
-        Ok(Ok(_)) => {}
-        Ok(Err(_)) => {
-            let resp = WSResponse::Error("Rate limited. Try again in a moment.".to_string());
-            return bincode::serialize(&resp).unwrap();
-        }
-        Err(e) => {
-            let resp = WSResponse::Error(format!("Rate limit error: {e:?}"));
-            return bincode::serialize(&resp).unwrap();
-        }
+async fn get_user_data<S: Storage>(asn: u32, addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
     }
 
     let resp = match state.asdb.get_user_data(asn).await {
@@ -371,41 +667,80 @@ async fn get_user_data(asn: u32, addr: SocketAddr, state: &ServerState) -> Vec<u
 }
 
 #[tracing::instrument(skip(state))]
-async fn save_geocoding(
+async fn save_geocoding<S: Storage>(
     asn: u32,
     geocoded: Vec<asdb_models::GeocodedAddress>,
     addr: SocketAddr,
-    state: &ServerState,
+    state: &ServerState<S>,
 ) -> Vec<u8> {
-    match state.simple_limiter.check_key_n(&addr.ip(), nonzero!(1u32)) {
-        Ok(Ok(_)) => {}
-        Ok(Err(_)) => {
-            let resp = WSResponse::Error("Rate limited. Try again in a moment.".to_string());
-            return bincode::serialize(&resp).unwrap();
-        }
-        Err(e) => {
-            let resp = WSResponse::Error(format!("Rate limit error: {e:?}"));
-            return bincode::serialize(&resp).unwrap();
-        }
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
     }
 
-    let resp = match state.asdb.update_geocoded_addresses(asn, geocoded).await {
-        Ok(_) => match state.asdb.get_user_data(asn).await {
-            Ok(user_data) => WSResponse::UserData(user_data),
-            Err(e) => WSResponse::Error(format!("Failed to read user data: {e:?}")),
-        },
+    let editor = addr.ip().to_string();
+    let resp = match state
+        .asdb
+        .edit_geocoded_addresses(asn, geocoded, Some(&editor))
+        .await
+    {
+        Ok(user_data) => {
+            let geocoded = user_data.geocoded_addresses.clone();
+            state.notify(asn, AsChange::Geocoding(geocoded));
+            WSResponse::UserData(user_data)
+        }
         Err(e) => WSResponse::Error(format!("Failed to save geocoding: {e:?}")),
     };
     bincode::serialize(&resp).unwrap()
 }
 
 #[tracing::instrument(skip(state))]
-async fn get_list_names(addr: SocketAddr, state: &ServerState) -> Vec<u8> {
-    state
-        .simple_limiter
-        .check_key_n(&addr.ip(), nonzero!(1u32))
-        .unwrap()
-        .unwrap();
+async fn get_user_data_history<S: Storage>(
+    asn: u32,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.get_user_data_history(asn).await {
+        Ok(edits) => WSResponse::UserDataHistory(edits),
+        Err(e) => WSResponse::Error(format!("Failed to load user data history: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn revert_user_data<S: Storage>(
+    asn: u32,
+    edit_id: &str,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let resp = match state
+        .asdb
+        .revert_user_data(asn, edit_id, Some(&editor))
+        .await
+    {
+        Ok(user_data) => {
+            state.notify(asn, AsChange::UserData(user_data.clone()));
+            WSResponse::UserData(user_data)
+        }
+        Err(e) => WSResponse::Error(format!("Failed to revert user data: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn get_list_names<S: Storage>(addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
 
     let resp = match state.asdb.get_list_names().await {
         Ok(names) => WSResponse::ListNames(names),
@@ -413,3 +748,803 @@ async fn get_list_names(addr: SocketAddr, state: &ServerState) -> Vec<u8> {
     };
     bincode::serialize(&resp).unwrap()
 }
+
+/// answers a change of lists with all of them, subscribers of the changed members are notified
+async fn lists_changed<S: Storage>(
+    changed: asdb::Result<Vec<UserDataEdit>>,
+    state: &ServerState<S>,
+) -> WSResponse {
+    let edits = match changed {
+        Ok(edits) => edits,
+        Err(e) => return WSResponse::Error(format!("Failed to change lists: {e:?}")),
+    };
+    for edit in edits {
+        state.notify(edit.asn, AsChange::UserData(edit.new));
+    }
+    match state.asdb.get_lists().await {
+        Ok(lists) => WSResponse::Lists(lists),
+        Err(e) => WSResponse::Error(format!("Failed to load lists: {e:?}")),
+    }
+}
+
+#[tracing::instrument(skip(state))]
+async fn get_lists<S: Storage>(addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.get_lists().await {
+        Ok(lists) => WSResponse::Lists(lists),
+        Err(e) => WSResponse::Error(format!("Failed to load lists: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn create_list<S: Storage>(
+    list: AsList,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let created = state.asdb.create_list(&list).await.map(|_| vec![]);
+    let resp = lists_changed(created, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn update_list<S: Storage>(
+    list: AsList,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let updated = state.asdb.update_list(&list).await.map(|_| vec![]);
+    let resp = lists_changed(updated, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn rename_list<S: Storage>(
+    name: &str,
+    new_name: &str,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let renamed = state.asdb.rename_list(name, new_name, Some(&editor)).await;
+    let resp = lists_changed(renamed, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn delete_list<S: Storage>(name: &str, addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let deleted = state.asdb.delete_list(name, Some(&editor)).await;
+    let resp = lists_changed(deleted, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn add_to_list<S: Storage>(
+    name: &str,
+    asns: &[u32],
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let added = state.asdb.add_to_list(name, asns, Some(&editor)).await;
+    let resp = lists_changed(added, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn remove_from_list<S: Storage>(
+    name: &str,
+    asns: &[u32],
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let removed = state.asdb.remove_from_list(name, asns, Some(&editor)).await;
+    let resp = lists_changed(removed, state).await;
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn get_list_members<S: Storage>(
+    name: String,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.get_list_members(&name).await {
+        Ok(asns) => WSResponse::ListMembers { name, asns },
+        Err(e) => WSResponse::Error(format!("Failed to load list members: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn get_annotation_fields<S: Storage>(addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.get_annotation_fields().await {
+        Ok(fields) => WSResponse::AnnotationFields(fields),
+        Err(e) => WSResponse::Error(format!("Failed to load annotation fields: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn define_annotation_field<S: Storage>(
+    field: AnnotationField,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.define_annotation_field(&field).await {
+        Ok(_) => match state.asdb.get_annotation_fields().await {
+            Ok(fields) => WSResponse::AnnotationFields(fields),
+            Err(e) => WSResponse::Error(format!("Failed to load annotation fields: {e:?}")),
+        },
+        Err(e) => WSResponse::Error(format!("Failed to define annotation field: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn set_annotation<S: Storage>(
+    asn: u32,
+    name: &str,
+    value: Option<AnnotationValue>,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let editor = addr.ip().to_string();
+    let resp = match state
+        .asdb
+        .edit_annotation(asn, name, value, Some(&editor))
+        .await
+    {
+        Ok(user_data) => {
+            state.notify(asn, AsChange::UserData(user_data.clone()));
+            WSResponse::UserData(user_data)
+        }
+        Err(e) => WSResponse::Error(format!("Failed to set annotation: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[tracing::instrument(skip(state))]
+async fn get_datasets<S: Storage>(addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = match state.asdb.get_datasets().await {
+        Ok(datasets) => WSResponse::Datasets(datasets),
+        Err(e) => WSResponse::Error(format!("Failed to load datasets: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+/// returns WsResponse with names of the loaded datasets
+#[tracing::instrument(skip(state))]
+async fn get_dataset_names<S: Storage>(addr: SocketAddr, state: &ServerState<S>) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let resp = WSResponse::DatasetNames(state.dataset_names());
+    bincode::serialize(&resp).unwrap()
+}
+
+/// returns WsResponse with ases matching the text query, the most relevant first
+#[tracing::instrument(skip(state))]
+async fn search<S: Storage>(
+    query: &str,
+    limit: i64,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let limit = if limit > 0 {
+        limit.min(MAX_SEARCH_RESULTS)
+    } else {
+        MAX_SEARCH_RESULTS
+    };
+    let resp = match state.asdb.search(query, limit).await {
+        Ok(hits) => WSResponse::SearchResults(hits),
+        Err(e) => WSResponse::Error(format!("Failed to search: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+/// returns WsResponse with totals of ases matching the filters, grouped by `group_by`
+#[tracing::instrument(skip(state))]
+async fn stats<S: Storage>(
+    filters: AsFilters,
+    group_by: StatsGroup,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    if let Err(resp) = charge(&state.simple_limiter, addr, nonzero!(1u32)) {
+        return bincode::serialize(&resp).unwrap();
+    }
+
+    let db_filters = asdb_models::AsFilters::from(filters);
+    let resp = match state.asdb.get_stats(&db_filters, group_by).await {
+        Ok(stats) => WSResponse::Stats(stats),
+        Err(e) => WSResponse::Error(format!("Failed to get stats: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+/// returns WsResponse with a page of ases matching the filters in the `sort` order
+#[tracing::instrument(skip(state))]
+async fn as_page<S: Storage>(
+    filters: AsFilters,
+    sort: AsSort,
+    limit: i64,
+    after: Option<String>,
+    addr: SocketAddr,
+    state: &ServerState<S>,
+) -> Vec<u8> {
+    let db_filters = asdb_models::AsFilters::from(filters);
+    // pages are charged like the filtered ases, by the number of ases they may hold
+    let cost = if limit > 0 {
+        limit as u64
+    } else {
+        state
+            .asdb
+            .count_ases_filtered(&db_filters)
+            .await
+            .unwrap_or(1)
+    };
+    let cost = NonZeroU32::new(cost.clamp(1, u32::MAX as u64) as u32).unwrap();
+    if let Err(resp) = charge(&state.simple_limiter, addr, cost) {
+        return bincode::serialize(&resp).unwrap();
+    }
+    let resp = match state
+        .asdb
+        .get_ases_sorted(&db_filters, sort, limit, after.as_deref())
+        .await
+    {
+        Ok(page) => WSResponse::AsPage(page),
+        Err(e) => WSResponse::Error(format!("Failed to get page of ases: {e:?}")),
+    };
+    bincode::serialize(&resp).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv6Addr};
+
+    use asdb::InMemoryAsdb;
+    use asdb_models::{
+        AnnotationFilter, AnnotationKind, AnnotationOp, As, AsrankAsn, AsrankDegree, Coord,
+    };
+    use governor::{Quota, RateLimiter};
+
+    use super::*;
+
+    const ADDR: SocketAddr = SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), 1234);
+
+    async fn state_with(ases: &[As]) -> ServerState<InMemoryAsdb> {
+        let asdb = InMemoryAsdb::new();
+        asdb.insert_ases(ases).await.unwrap();
+        ServerState::with_storage(asdb)
+    }
+
+    fn asrank_as(asn: u32, country_iso: &str) -> As {
+        As {
+            asn,
+            asrank_data: Some(AsrankAsn {
+                rank: asn,
+                organization: None,
+                organization_id: None,
+                country_iso: country_iso.to_string(),
+                country_name: String::new(),
+                coordinates: Coord { lat: 0.0, lon: 0.0 },
+                degree: AsrankDegree {
+                    provider: 0,
+                    peer: 0,
+                    customer: 0,
+                    total: 0,
+                    transit: 0,
+                    sibling: 0,
+                },
+                prefixes: 1,
+                addresses: 256,
+                name: format!("AS{asn}"),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// runs filtered_as and decodes every frame it sent
+    async fn filtered_as_frames(
+        filters: AsFilters,
+        state: &ServerState<InMemoryAsdb>,
+    ) -> Vec<WSResponse> {
+        let (mut tx, rx) = futures::channel::mpsc::unbounded();
+        filtered_as(filters, ADDR, state, &mut tx).await.unwrap();
+        drop(tx);
+        rx.map(|msg| {
+            let Message::Binary(b) = msg else {
+                panic!("unexpected message {msg:?}");
+            };
+            bincode::deserialize(&b).unwrap()
+        })
+        .collect()
+        .await
+    }
+
+    #[test]
+    fn charge_answers_with_an_error_once_the_quota_runs_out() {
+        let limiter = RateLimiter::keyed(Quota::per_minute(nonzero!(2u32)));
+
+        assert!(charge(&limiter, ADDR, nonzero!(1u32)).is_ok());
+        assert!(matches!(
+            charge(&limiter, ADDR, nonzero!(2u32)),
+            Err(WSResponse::Error(e)) if e.starts_with("Rate limited")
+        ));
+        assert!(matches!(
+            charge(&limiter, ADDR, nonzero!(3u32)),
+            Err(WSResponse::Error(e)) if e.starts_with("Rate limit error")
+        ));
+    }
+
+    #[tokio::test]
+    async fn filtered_as_returns_matching_ases() {
+        let state = state_with(&[asrank_as(5550, "PL"), asrank_as(1299, "SE")]).await;
+        let filters = AsFilters {
+            country: Some("PL".to_string()),
+            ..Default::default()
+        };
+
+        let frames = filtered_as_frames(filters, &state).await;
+
+        let [
+            WSResponse::FilteredASChunk(ases),
+            WSResponse::FilteredASDone { count, .. },
+        ] = frames.as_slice()
+        else {
+            panic!("unexpected response {frames:?}");
+        };
+        assert_eq!(ases.len(), 1);
+        assert_eq!(ases[0].asn, 5550);
+        assert_eq!(*count, 1);
+    }
+
+    #[tokio::test]
+    async fn filtered_as_splits_results_into_chunks() {
+        let ases: Vec<As> = (1..=2500).map(|asn| asrank_as(asn, "PL")).collect();
+        let state = state_with(&ases).await;
+
+        let frames = filtered_as_frames(AsFilters::default(), &state).await;
+
+        let sizes: Vec<usize> = frames
+            .iter()
+            .filter_map(|x| match x {
+                WSResponse::FilteredASChunk(ases) => Some(ases.len()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(sizes, [1000, 1000, 500]);
+        assert!(matches!(
+            frames.last(),
+            Some(WSResponse::FilteredASDone { count: 2500, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn filtered_as_matching_nothing_is_done_at_once() {
+        let state = state_with(&[asrank_as(5550, "PL")]).await;
+        let filters = AsFilters {
+            country: Some("SE".to_string()),
+            ..Default::default()
+        };
+
+        let frames = filtered_as_frames(filters, &state).await;
+
+        assert!(matches!(
+            frames.as_slice(),
+            [WSResponse::FilteredASDone { count: 0, .. }]
+        ));
+    }
+
+    #[tokio::test]
+    async fn search_finds_ases_by_name() {
+        let state = state_with(&[asrank_as(5550, "PL"), asrank_as(1299, "SE")]).await;
+
+        let resp: WSResponse =
+            bincode::deserialize(&search("as1299", 10, ADDR, &state).await).unwrap();
+
+        let WSResponse::SearchResults(hits) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].asn, 1299);
+    }
+
+    #[tokio::test]
+    async fn search_caps_the_hits_sent() {
+        let ases: Vec<As> = (1..=150)
+            .map(|asn| {
+                let mut a = asrank_as(asn, "PL");
+                a.asrank_data.as_mut().unwrap().name = "EXAMPLE-NET".to_string();
+                a
+            })
+            .collect();
+        let state = state_with(&ases).await;
+
+        for limit in [0, -1, 1000] {
+            let resp: WSResponse =
+                bincode::deserialize(&search("example-net", limit, ADDR, &state).await).unwrap();
+            let WSResponse::SearchResults(hits) = resp else {
+                panic!("unexpected response {resp:?}");
+            };
+            assert_eq!(hits.len(), MAX_SEARCH_RESULTS as usize);
+        }
+    }
+
+    #[tokio::test]
+    async fn stats_group_filtered_ases_by_country() {
+        let state = state_with(&[
+            asrank_as(5550, "PL"),
+            asrank_as(8501, "PL"),
+            asrank_as(1299, "SE"),
+        ])
+        .await;
+        let filters = AsFilters {
+            country: Some("SE".to_string()),
+            exclude_country: true,
+            ..Default::default()
+        };
+
+        let resp: WSResponse =
+            bincode::deserialize(&stats(filters, StatsGroup::Country, ADDR, &state).await).unwrap();
+
+        let WSResponse::Stats(stats) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(stats.total.ases, 2);
+        assert_eq!(stats.groups.len(), 1);
+        assert_eq!(stats.groups[0].key.as_deref(), Some("PL"));
+    }
+
+    #[tokio::test]
+    async fn as_page_returns_token_for_next_page() {
+        let state = state_with(&[
+            asrank_as(5550, "PL"),
+            asrank_as(8501, "PL"),
+            asrank_as(1299, "SE"),
+        ])
+        .await;
+        let sort = AsSort {
+            field: asdb_models::AsSortField::Asn,
+            descending: true,
+        };
+
+        let resp: WSResponse =
+            bincode::deserialize(&as_page(AsFilters::default(), sort, 2, None, ADDR, &state).await)
+                .unwrap();
+        let WSResponse::AsPage(first) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        let resp: WSResponse = bincode::deserialize(
+            &as_page(AsFilters::default(), sort, 2, first.next, ADDR, &state).await,
+        )
+        .unwrap();
+        let WSResponse::AsPage(second) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+
+        let asns: Vec<u32> = first
+            .ases
+            .iter()
+            .chain(&second.ases)
+            .map(|x| x.asn)
+            .collect();
+        assert_eq!(asns, [8501, 5550, 1299]);
+        assert!(second.next.is_none());
+    }
+
+    #[tokio::test]
+    async fn get_datasets_returns_import_runs() {
+        let state = state_with(&[]).await;
+        let dataset = asdb_models::Dataset {
+            run_id: "asrank-1".to_string(),
+            source: "asrank".to_string(),
+            records: 2,
+            ..Default::default()
+        };
+        state.asdb.save_dataset(&dataset).await.unwrap();
+
+        let resp: WSResponse = bincode::deserialize(&get_datasets(ADDR, &state).await).unwrap();
+
+        let WSResponse::Datasets(datasets) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(datasets, [dataset]);
+    }
+
+    #[tokio::test]
+    async fn in_dataset_answers_from_the_named_dataset() {
+        let archived = InMemoryAsdb::new();
+        archived
+            .insert_ases(&[asrank_as(8501, "PL")])
+            .await
+            .unwrap();
+        let state = state_with(&[asrank_as(5550, "PL")])
+            .await
+            .with_dataset("2024-Q1", archived);
+
+        let names: WSResponse =
+            bincode::deserialize(&get_dataset_names(ADDR, &state).await).unwrap();
+        let req = WSRequest::InDataset {
+            dataset: "2024-Q1".to_string(),
+            request: Box::new(WSRequest::GetListNames),
+        };
+        let (req, dataset_state) = in_dataset(req, &state).unwrap();
+        let page = |state| {
+            as_page(
+                AsFilters::default(),
+                AsSort::default(),
+                0,
+                None,
+                ADDR,
+                state,
+            )
+        };
+        let resp: WSResponse = bincode::deserialize(&page(&dataset_state).await).unwrap();
+        let default_resp: WSResponse = bincode::deserialize(&page(&state).await).unwrap();
+        let unknown = WSRequest::InDataset {
+            dataset: "2023-Q4".to_string(),
+            request: Box::new(WSRequest::GetListNames),
+        };
+
+        assert!(matches!(names, WSResponse::DatasetNames(n) if n == ["default", "2024-Q1"]));
+        assert!(matches!(req, WSRequest::GetListNames));
+        let asns = |resp| match resp {
+            WSResponse::AsPage(p) => p.ases.iter().map(|x| x.asn).collect::<Vec<_>>(),
+            resp => panic!("unexpected response {resp:?}"),
+        };
+        assert_eq!(asns(resp), [8501]);
+        assert_eq!(asns(default_resp), [5550]);
+        assert!(in_dataset(unknown, &state).is_err());
+    }
+
+    #[tokio::test]
+    async fn subscribers_get_changes_of_their_ases() {
+        let state = state_with(&[asrank_as(5550, "PL"), asrank_as(1299, "SE")]).await;
+        let (in_tx, in_rx) = futures::channel::mpsc::unbounded();
+        let (mut out_tx, out_rx) = futures::channel::mpsc::unbounded();
+        let edits = async {
+            let lists = Some(vec!["watch".to_string()]);
+            update_user_data(1299, lists.clone(), None, ADDR, &state).await;
+            let resubscribe = bincode::serialize(&WSRequest::Subscribe(vec![1299])).unwrap();
+            in_tx
+                .unbounded_send(Message::Binary(resubscribe.into()))
+                .unwrap();
+            // let the subscription pick up the new ases before the next edit
+            tokio::task::yield_now().await;
+            update_user_data(1299, lists, Some("note".to_string()), ADDR, &state).await;
+            update_user_data(5550, None, Some("gdansk".to_string()), ADDR, &state).await;
+            drop(in_tx);
+        };
+
+        let (sent, _) = futures::join!(
+            subscribe(vec![5550], ADDR, &state, in_rx, &mut out_tx),
+            edits
+        );
+        sent.unwrap();
+        drop(out_tx);
+        let changes: Vec<WSResponse> = out_rx
+            .map(|msg| {
+                let Message::Binary(b) = msg else {
+                    panic!("unexpected message {msg:?}");
+                };
+                bincode::deserialize(&b).unwrap()
+            })
+            .collect()
+            .await;
+
+        assert_eq!(changes.len(), 1, "unexpected changes {changes:?}");
+        let WSResponse::AsChanged { asn, change } = &changes[0] else {
+            panic!("unexpected response {:?}", changes[0]);
+        };
+        assert_eq!(*asn, 1299);
+        let AsChange::UserData(user_data) = change else {
+            panic!("unexpected change {change:?}");
+        };
+        assert_eq!(user_data.comment.as_deref(), Some("note"));
+    }
+
+    #[tokio::test]
+    async fn update_user_data_returns_updated_data() {
+        let state = state_with(&[asrank_as(5550, "PL")]).await;
+
+        let resp: WSResponse = bincode::deserialize(
+            &update_user_data(
+                5550,
+                Some(vec!["watch".to_string()]),
+                Some("note".to_string()),
+                ADDR,
+                &state,
+            )
+            .await,
+        )
+        .unwrap();
+        let names: WSResponse = bincode::deserialize(&get_list_names(ADDR, &state).await).unwrap();
+
+        let WSResponse::UserData(user_data) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(user_data.lists, ["watch"]);
+        assert_eq!(user_data.comment.as_deref(), Some("note"));
+        assert!(matches!(names, WSResponse::ListNames(n) if n == ["watch"]));
+    }
+
+    #[tokio::test]
+    async fn user_data_history_can_be_reverted() {
+        let state = state_with(&[asrank_as(5550, "PL")]).await;
+        update_user_data(5550, Some(vec!["watch".to_string()]), None, ADDR, &state).await;
+        update_user_data(5550, None, Some("note".to_string()), ADDR, &state).await;
+
+        let resp: WSResponse =
+            bincode::deserialize(&get_user_data_history(5550, ADDR, &state).await).unwrap();
+        let WSResponse::UserDataHistory(edits) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].editor, Some(ADDR.ip().to_string()));
+        let resp: WSResponse =
+            bincode::deserialize(&revert_user_data(5550, &edits[0].id, ADDR, &state).await)
+                .unwrap();
+        let unknown: WSResponse =
+            bincode::deserialize(&revert_user_data(5550, "5550-0", ADDR, &state).await).unwrap();
+
+        let WSResponse::UserData(user_data) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(user_data.lists, ["watch"]);
+        assert_eq!(user_data.comment, None);
+        assert!(matches!(unknown, WSResponse::Error(_)));
+    }
+
+    #[tokio::test]
+    async fn lists_are_managed_and_members_notified() {
+        let state = state_with(&[asrank_as(5550, "PL"), asrank_as(1299, "SE")]).await;
+        let mut events = state.events.subscribe();
+        let watch = AsList {
+            name: "watch".to_string(),
+            ..Default::default()
+        };
+        let call = |resp: Vec<u8>| bincode::deserialize::<WSResponse>(&resp).unwrap();
+
+        call(create_list(watch.clone(), ADDR, &state).await);
+        let resp = call(add_to_list("watch", &[5550, 1299], ADDR, &state).await);
+        let renamed = call(rename_list("watch", "nordics", ADDR, &state).await);
+        let members = call(get_list_members("nordics".to_string(), ADDR, &state).await);
+        let nordics = AsList {
+            name: "nordics".to_string(),
+            ..watch
+        };
+        let duplicate = call(create_list(nordics, ADDR, &state).await);
+        let missing = call(delete_list("watch", ADDR, &state).await);
+
+        let WSResponse::Lists(lists) = resp else {
+            panic!("unexpected response {resp:?}");
+        };
+        assert_eq!(lists.len(), 1);
+        assert_eq!(lists[0].count, 2);
+        assert!(matches!(renamed, WSResponse::Lists(l) if l[0].list.name == "nordics"));
+        assert!(matches!(
+            members,
+            WSResponse::ListMembers { asns, .. } if asns == [1299, 5550]
+        ));
+        assert!(matches!(duplicate, WSResponse::Error(_)));
+        assert!(matches!(missing, WSResponse::Error(_)));
+        // added and renamed for both ases
+        let mut notified = vec![];
+        while let Ok(event) = events.try_recv() {
+            notified.push(event.asn);
+        }
+        assert_eq!(notified, [1299, 5550, 1299, 5550]);
+    }
+
+    #[tokio::test]
+    async fn annotations_are_checked_against_their_fields() {
+        let state = state_with(&[asrank_as(5550, "PL"), asrank_as(1299, "SE")]).await;
+        let call = |resp: Vec<u8>| bincode::deserialize::<WSResponse>(&resp).unwrap();
+        let priority = AnnotationField {
+            name: "priority".to_string(),
+            kind: AnnotationKind::Number,
+            description: None,
+        };
+
+        let fields = call(define_annotation_field(priority, ADDR, &state).await);
+        let set = call(
+            set_annotation(
+                5550,
+                "priority",
+                Some(AnnotationValue::Number(3.0)),
+                ADDR,
+                &state,
+            )
+            .await,
+        );
+        let wrong_type = call(
+            set_annotation(
+                1299,
+                "priority",
+                Some(AnnotationValue::Text("high".to_string())),
+                ADDR,
+                &state,
+            )
+            .await,
+        );
+        let unknown = call(set_annotation(1299, "status", None, ADDR, &state).await);
+        let filters = AsFilters {
+            annotations: vec![AnnotationFilter {
+                name: "priority".to_string(),
+                op: AnnotationOp::Gte(AnnotationValue::Number(2.0)),
+            }],
+            ..Default::default()
+        };
+        let page = call(as_page(filters, AsSort::default(), 0, None, ADDR, &state).await);
+
+        assert!(matches!(fields, WSResponse::AnnotationFields(f) if f.len() == 1));
+        let WSResponse::UserData(user_data) = set else {
+            panic!("unexpected response {set:?}");
+        };
+        assert_eq!(user_data.annotations.len(), 1);
+        assert!(matches!(wrong_type, WSResponse::Error(_)));
+        assert!(matches!(unknown, WSResponse::Error(_)));
+        let WSResponse::AsPage(page) = page else {
+            panic!("unexpected response {page:?}");
+        };
+        let asns: Vec<u32> = page.ases.iter().map(|x| x.asn).collect();
+        assert_eq!(asns, [5550]);
+    }
+}
@@ -1,4 +1,4 @@
-use axum::{Router, http, routing::get, response::IntoResponse, extract::State};
+use axum::{Router, http, routing::get, response::IntoResponse};
 use clap::Parser;
 use std::{
     net::{IpAddr, Ipv6Addr, SocketAddr},
@@ -14,6 +14,7 @@ use tower_http::{cors::CorsLayer, services::ServeDir, trace::TraceLayer};
 use tracing::{Level, info};
 use tracing_subscriber::fmt::format::FmtSpan;
 
+use asdb_builder::whois::{Bootstrap, RdapClient};
 use handlers::as_handler;
 use state::ServerState;
 
@@ -43,6 +44,11 @@ struct Opt {
     /// set the directory where static files are to be found
     #[clap(long = "static-dir", default_value = "../dist")]
     static_dir: String,
+
+    /// local copy of the IANA RDAP bootstrap registry https://data.iana.org/rdap/asn.json,
+    /// WHOIS data of non-RIPE ases is fetched over RDAP when set
+    #[clap(long = "rdap-bootstrap")]
+    rdap_bootstrap: Option<String>,
 }
 
 #[tokio::main]
@@ -74,7 +80,13 @@ async fn main() {
         "Connecting to the database, target database: {}",
         &cfg.db_name
     );
-    let state = ServerState::new(&cfg.mongo_conn_str, &cfg.db_name).await;
+    let mut state = ServerState::new(&cfg.mongo_conn_str, &cfg.db_name).await;
+    if let Some(path) = &opt.rdap_bootstrap {
+        info!("Routing WHOIS requests of non-RIPE ases over RDAP by {path}");
+        let bootstrap =
+            Bootstrap::from_file(path).expect("Couldn't read the RDAP bootstrap registry");
+        state = state.with_rdap(RdapClient::new(bootstrap));
+    }
     tokio::spawn(governor_cleanup(state.clone()));
     
     // SPA fallback: serve index.html for unknown routes (client-side routing)
@@ -1,30 +1,102 @@
-use std::{net::IpAddr, sync::Arc};
+use std::{collections::BTreeMap, net::IpAddr, sync::Arc};
 
-use asdb::Asdb;
-use asdb_builder::whois::RipeClient;
+use asdb::{Asdb, DEFAULT_DATASET, Storage};
+use asdb_builder::whois::{RdapClient, RipeClient};
 use governor::{DefaultKeyedRateLimiter, Quota, RateLimiter};
 use nonzero_ext::*;
-use tracing::Level;
+use protocol::AsChange;
+use tokio::sync::broadcast;
+use tracing::{Level, info};
 
 // TODO move this to external config
 const SIMPLE_PER_MIN: u32 = 20_0000000;
 const SIMPLE_MAX_BURST: u32 = 250_0000000;
 const DETAILED_PER_SEC: u32 = 2;
+/// changes kept for subscribers which didn't send the previous ones yet
+const EVENTS_CAPACITY: usize = 1024;
 
 type LimiterKey = IpAddr;
 
-#[derive(Clone, Debug)]
-pub struct ServerState {
-    pub asdb: Arc<Asdb>,
+/// Change of an AS made through the server
+#[derive(Debug, Clone)]
+pub struct AsEvent {
+    pub asn: u32,
+    pub change: AsChange,
+}
+
+/// Storage of a dataset along with the changes made to it
+#[derive(Debug)]
+pub struct DatasetState<S> {
+    pub asdb: Arc<S>,
+    pub events: broadcast::Sender<AsEvent>,
+}
+
+impl<S> Clone for DatasetState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            asdb: self.asdb.clone(),
+            events: self.events.clone(),
+        }
+    }
+}
+
+impl<S> DatasetState<S> {
+    fn new(asdb: S) -> Self {
+        Self {
+            asdb: Arc::new(asdb),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ServerState<S: Storage = Asdb> {
+    pub asdb: Arc<S>,
+    /// changes of ases in `asdb`, sent to the subscribed clients
+    pub events: broadcast::Sender<AsEvent>,
+    /// datasets other than the default one in `asdb`, by name
+    pub datasets: Arc<BTreeMap<String, DatasetState<S>>>,
     pub whois_client: Arc<RipeClient>,
+    /// WHOIS data of the ases of the other RIRs, None without a bootstrap registry
+    pub rdap_client: Option<Arc<RdapClient>>,
     pub simple_limiter: Arc<DefaultKeyedRateLimiter<LimiterKey>>,
     pub detailed_limiter: Arc<DefaultKeyedRateLimiter<LimiterKey>>,
 }
 
+// derive would require S: Clone even though only the Arc is cloned
+impl<S: Storage> Clone for ServerState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            asdb: self.asdb.clone(),
+            events: self.events.clone(),
+            datasets: self.datasets.clone(),
+            whois_client: self.whois_client.clone(),
+            rdap_client: self.rdap_client.clone(),
+            simple_limiter: self.simple_limiter.clone(),
+            detailed_limiter: self.detailed_limiter.clone(),
+        }
+    }
+}
+
 impl ServerState {
     #[tracing::instrument(level=Level::DEBUG, skip(conn_str))]
     pub async fn new(conn_str: &str, db: &str) -> Self {
         let asdb = Asdb::new(conn_str, db).await.unwrap();
+        let mut state = Self::with_storage(asdb);
+        // datasets loaded later are picked up after a restart
+        for name in Asdb::dataset_names(conn_str, db).await.unwrap() {
+            if name != DEFAULT_DATASET {
+                info!("opening dataset {name}");
+                let asdb = Asdb::new_dataset(conn_str, db, &name).await.unwrap();
+                state = state.with_dataset(&name, asdb);
+            }
+        }
+        state
+    }
+}
+
+impl<S: Storage> ServerState<S> {
+    pub fn with_storage(asdb: S) -> Self {
         let whois_client = RipeClient::new();
         // or just get rid of nonzero_ext and do NonZeroU32::new(20).unwrap();
         let simple_limiter = Arc::new(RateLimiter::<LimiterKey, _, _, _>::keyed(
@@ -33,11 +105,52 @@ impl ServerState {
         let detailed_limiter = Arc::new(RateLimiter::<LimiterKey, _, _, _>::keyed(
             Quota::per_second(nonzero!(DETAILED_PER_SEC)),
         ));
+        let DatasetState { asdb, events } = DatasetState::new(asdb);
         Self {
-            asdb: Arc::new(asdb),
+            asdb,
+            events,
+            datasets: Arc::new(BTreeMap::new()),
             whois_client: Arc::new(whois_client),
+            rdap_client: None,
             simple_limiter,
             detailed_limiter,
         }
     }
+
+    /// Fetches WHOIS data of the non-RIPE ases over RDAP
+    pub fn with_rdap(mut self, client: RdapClient) -> Self {
+        self.rdap_client = Some(Arc::new(client));
+        self
+    }
+
+    /// Adds a named dataset next to the default one
+    pub fn with_dataset(mut self, name: &str, asdb: S) -> Self {
+        Arc::make_mut(&mut self.datasets).insert(name.to_string(), DatasetState::new(asdb));
+        self
+    }
+
+    /// State answering from the named dataset, sharing the rate limits with this one
+    pub fn dataset(&self, name: &str) -> Option<Self> {
+        if name == DEFAULT_DATASET {
+            return Some(self.clone());
+        }
+        let DatasetState { asdb, events } = self.datasets.get(name)?.clone();
+        Some(Self {
+            asdb,
+            events,
+            ..self.clone()
+        })
+    }
+
+    /// Lets the subscribed clients know about the change, nobody may be listening
+    pub fn notify(&self, asn: u32, change: AsChange) {
+        let _ = self.events.send(AsEvent { asn, change });
+    }
+
+    /// Names of all datasets, the default one first
+    pub fn dataset_names(&self) -> Vec<String> {
+        std::iter::once(DEFAULT_DATASET.to_string())
+            .chain(self.datasets.keys().cloned())
+            .collect()
+    }
 }
@@ -212,12 +212,7 @@ impl Geocoder {
         // Try full normalized address first
         match self.geocode(&normalized).await {
             Ok((coord, display_name)) => {
-                return GeocodedAddress::success(
-                    original.to_string(),
-                    normalized,
-                    coord,
-                    display_name,
-                );
+                GeocodedAddress::success(original.to_string(), normalized, coord, display_name)
             }
             Err(Error::NoResults(_)) => {
                 // Try fallback with city/country only
@@ -18,7 +18,6 @@ use thiserror::Error;
 /// let client = Client::with_options(client_options).unwrap();
 /// client.database(&context.db_name).create_collection("test", None).await.unwrap();
 /// ```
-
 pub struct TestContext {
     pub db_name: String,
     root_conn_str: String,